@@ -0,0 +1,89 @@
+//! Tokenizes a generated multi-megabyte program from a string and from a
+//! reader, reporting the time taken and the most memory held at once.
+//! Run with `cargo bench --bench tokenizer`.
+#![allow(dead_code)]
+
+#[path = "../src/language.rs"]
+mod language;
+#[path = "../src/tokenizer.rs"]
+mod tokenizer;
+#[path = "../src/money.rs"]
+mod money;
+#[path = "../src/failure.rs"]
+mod failure;
+#[path = "../src/numbers.rs"]
+mod numbers;
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::io::{BufReader, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Instant;
+use tokenizer::Tokenizer;
+
+// How many copies of the sample program make up the generated one
+const COPIES: usize = 20_000;
+
+const SAMPLE: &str = "\
+Task area requires width as Whole, height as Whole returns Whole:
+    output width * height  # a rectangle
+
+total is 0
+count is 0
+loop while count < 10:
+    total is total + area(count, [1, 2, 3].length)
+    count is count + 1
+when total > 100 and count is 10:
+    show \"total: {total}\"
+";
+
+struct Counting;
+
+static HELD: AtomicUsize = AtomicUsize::new(0);
+static PEAK: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for Counting {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let held = HELD.fetch_add(layout.size(), Ordering::Relaxed) + layout.size();
+        PEAK.fetch_max(held, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        HELD.fetch_sub(layout.size(), Ordering::Relaxed);
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: Counting = Counting;
+
+fn main() {
+    let path = std::env::temp_dir().join("nair-tokenizer-bench.vern");
+    let mut file = std::fs::File::create(&path).expect("bench file should be writable");
+    for _ in 0..COPIES {
+        file.write_all(SAMPLE.as_bytes()).expect("bench file should be writable");
+    }
+    drop(file);
+    let size = std::fs::metadata(&path).map(|metadata| metadata.len()).unwrap_or(0);
+    println!("{:.1} MB program", size as f64 / 1_000_000.0);
+
+    measure("whole string", || {
+        let source = std::fs::read_to_string(&path).expect("bench file should be readable");
+        Tokenizer::new(&source).count()
+    });
+    measure("streamed from a reader", || {
+        let file = std::fs::File::open(&path).expect("bench file should be readable");
+        Tokenizer::from_reader(BufReader::with_capacity(64 * 1024, file)).count()
+    });
+    let _ = std::fs::remove_file(&path);
+}
+
+fn measure(name: &str, tokenize: impl FnOnce() -> usize) {
+    PEAK.store(HELD.load(Ordering::Relaxed), Ordering::Relaxed);
+    let before = HELD.load(Ordering::Relaxed);
+    let started = Instant::now();
+    let tokens = tokenize();
+    let elapsed = started.elapsed();
+    let peak = PEAK.load(Ordering::Relaxed) - before;
+    println!("{:<24} {:>9} tokens  {:>8.1} ms  {:>8.1} MB peak", name, tokens, elapsed.as_secs_f64() * 1000.0, peak as f64 / 1_000_000.0);
+}
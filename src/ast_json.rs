@@ -0,0 +1,343 @@
+use crate::analyzer::Analyzer;
+use crate::formatter::{self, operator_text};
+use crate::generator::Value;
+use crate::json::Json;
+use crate::parser::{self, FailClause, Node, OrWhen, Parser, SyntaxError};
+use crate::tokenizer::Tokenizer;
+use std::fs::File;
+use std::io::BufReader;
+
+/// Prints the syntax tree of each file, as JSON when `json` is set and as
+/// canonical source otherwise. Fails if any file has errors.
+pub fn parse_files(paths: &[String], json: bool) -> Result<(), String> {
+    if paths.is_empty() {
+        return Err("Usage: nair parse [--json] file...".to_string());
+    }
+    let mut failed = 0;
+    for path in paths {
+        let file = File::open(path).map_err(|e| format!("Error reading '{}': {}", path, e))?;
+        // Read a line at a time, so large generated programs aren't held in memory as text
+        let parsed = Tokenizer::from_reader(BufReader::new(file)).tokenize()
+            .map(|tokens| Parser::new(tokens).parse_with_errors());
+        let report = parse_report(path, &parsed);
+        if report.get("diagnostics").and_then(Json::as_array).is_some_and(|diagnostics| !diagnostics.is_empty()) {
+            failed += 1;
+        }
+        match (json, parsed) {
+            (true, _) => println!("{}", report),
+            (false, Ok((ast, errors))) if errors.is_empty() => print!("{}", formatter::print(&ast)),
+            (false, Ok((_, errors))) => errors.iter().for_each(|error| eprintln!("{}: {}", path, error.message)),
+            (false, Err(error)) => eprintln!("{}: {}", path, error),
+        }
+    }
+    match failed {
+        0 => Ok(()),
+        failed => Err(format!("{} file(s) have errors", failed)),
+    }
+}
+
+/// `{"file", "ast", "diagnostics"}` for one program, given its tree and
+/// syntax errors, or the error that stopped it being read at all. Each
+/// statement carries the line it starts on, and one that didn't parse is an
+/// `Error` node. Syntax errors also give the column and byte offset of the
+/// token they were found at. The tree is only type checked once it parses.
+pub fn parse_report(file: &str, parsed: &Result<(Vec<Node>, Vec<SyntaxError>), String>) -> Json {
+    let mut diagnostics = Vec::new();
+    let ast = match parsed {
+        Ok((ast, errors)) => {
+            diagnostics.extend(errors.iter().map(syntax_error));
+            let mut analyzer = Analyzer::new();
+            if errors.is_empty() {
+                if let Err(error) = analyzer.analyze(ast) {
+                    diagnostics.push(diagnostic("type", analyzer.current_line(), &error));
+                }
+            }
+            statements(ast)
+        },
+        Err(error) => {
+            diagnostics.push(diagnostic("syntax", parser::error_line(error), error));
+            Json::Array(Vec::new())
+        },
+    };
+    Json::object(vec![
+        ("file", Json::text(file)),
+        ("ast", ast),
+        ("diagnostics", Json::Array(diagnostics)),
+    ])
+}
+
+fn syntax_error(error: &SyntaxError) -> Json {
+    Json::object(vec![
+        ("severity", Json::text("error")),
+        ("kind", Json::text("syntax")),
+        ("line", error.token.line.into()),
+        ("column", error.token.start_column().into()),
+        ("offset", error.token.offset.into()),
+        ("message", Json::text(&error.message)),
+    ])
+}
+
+fn diagnostic(kind: &str, line: usize, message: &str) -> Json {
+    Json::object(vec![
+        ("severity", Json::text("error")),
+        ("kind", Json::text(kind)),
+        ("line", line.into()),
+        ("message", Json::text(message)),
+    ])
+}
+
+// Statements, each with the line from the marker before it
+fn statements(nodes: &[Node]) -> Json {
+    let mut statements = Vec::new();
+    let mut line = None;
+    for statement in nodes {
+        match (statement, node(statement)) {
+            (Node::Line(start), _) => line = Some(*start),
+            (_, Json::Object(mut members)) => {
+                if let Some(line) = line.take() {
+                    members.insert(1, ("line".to_string(), line.into()));
+                }
+                statements.push(Json::Object(members));
+            },
+            (_, other) => statements.push(other),
+        }
+    }
+    Json::Array(statements)
+}
+
+fn node(node: &Node) -> Json {
+    let kind = |name: &str, mut members: Vec<(&str, Json)>| {
+        members.insert(0, ("kind", Json::text(name)));
+        Json::object(members)
+    };
+    let optional = |node: &Option<Box<Node>>| node.as_deref().map(self::node).unwrap_or(Json::Null);
+    let list = |nodes: &[Node]| Json::Array(nodes.iter().map(self::node).collect());
+    let text = |name: &Option<String>| name.as_deref().map(Json::text).unwrap_or(Json::Null);
+
+    match node {
+        Node::VariableDecl { name, type_annotation, initializer } => kind("VariableDecl", vec![
+            ("name", Json::text(name)),
+            ("type", optional(type_annotation)),
+            ("value", optional(initializer)),
+        ]),
+        Node::TaskDecl { name, params, return_type, body } => kind("TaskDecl", vec![
+            ("name", Json::text(name)),
+            ("params", list(params)),
+            ("returns", optional(return_type)),
+            ("body", self::node(body)),
+        ]),
+        Node::ObjectDecl { name, base, fields, constructor, methods, properties, shared_fields, shared_methods, behaviors, is_behavior } => {
+            kind(if *is_behavior { "BehaviorDecl" } else { "ObjectDecl" }, vec![
+                ("name", Json::text(name)),
+                ("base", optional(base)),
+                ("behaviors", Json::Array(behaviors.iter().map(|name| Json::text(name)).collect())),
+                ("fields", list(fields)),
+                ("constructor", optional(constructor)),
+                ("methods", list(methods)),
+                ("properties", list(properties)),
+                ("sharedFields", list(shared_fields)),
+                ("sharedMethods", list(shared_methods)),
+            ])
+        },
+        Node::ConstructorDecl { params, labels, body } => kind("ConstructorDecl", vec![
+            ("params", list(params)),
+            ("labels", Json::Array(labels.iter().map(|label| Json::text(label)).collect())),
+            ("body", self::node(body)),
+        ]),
+        Node::PropertyDecl { name, setter_param, return_type, body } => kind("PropertyDecl", vec![
+            ("name", Json::text(name)),
+            ("setterParam", optional(setter_param)),
+            ("returns", optional(return_type)),
+            ("body", self::node(body)),
+        ]),
+        Node::Block(nodes) => kind("Block", vec![("statements", statements(nodes))]),
+        Node::Line(line) => kind("Line", vec![("line", (*line).into())]),
+        Node::Error(message) => kind("Error", vec![("message", Json::text(message))]),
+        Node::ExpressionStmt(value) => kind("ExpressionStmt", vec![("value", self::node(value))]),
+        Node::ReturnStmt(value) => kind("ReturnStmt", vec![("value", self::node(value))]),
+        Node::GuardStmt { condition, value } => kind("GuardStmt", vec![
+            ("condition", self::node(condition)),
+            ("value", self::node(value)),
+        ]),
+        Node::ShowStmt(value) => kind("ShowStmt", vec![("value", self::node(value))]),
+        Node::KeepDoingStmt { body, label } => kind("KeepDoingStmt", vec![("body", self::node(body)), ("label", text(label))]),
+        Node::StopStmt(label) => kind("StopStmt", vec![("label", text(label))]),
+        Node::ContinueStmt(label) => kind("ContinueStmt", vec![("label", text(label))]),
+        Node::WhenStmt { condition, then_branch, or_when, else_branch } => kind("WhenStmt", vec![
+            ("condition", self::node(condition)),
+            ("then", self::node(then_branch)),
+            ("orWhen", Json::Array(or_when.iter().map(|OrWhen { line, condition, body }| Json::object(vec![
+                ("line", (*line).into()),
+                ("condition", self::node(condition)),
+                ("body", self::node(body)),
+            ])).collect())),
+            ("else", optional(else_branch)),
+        ]),
+        Node::LoopStmt { condition, body, label } => kind("LoopStmt", vec![
+            ("condition", self::node(condition)),
+            ("body", self::node(body)),
+            ("label", text(label)),
+        ]),
+        Node::RepeatStmt { count, counter, body, label } => kind("RepeatStmt", vec![
+            ("count", self::node(count)),
+            ("counter", text(counter)),
+            ("body", self::node(body)),
+            ("label", text(label)),
+        ]),
+        Node::MeasureStmt { runs, result, body } => kind("MeasureStmt", vec![
+            ("runs", optional(runs)),
+            ("result", text(result)),
+            ("body", self::node(body)),
+        ]),
+        Node::RaiseStmt { message, error_type } => kind("RaiseStmt", vec![
+            ("message", self::node(message)),
+            ("errorType", optional(error_type)),
+        ]),
+        Node::ExpectStmt { actual, expected, source, .. } => kind("ExpectStmt", vec![
+            ("actual", self::node(actual)),
+            ("expected", optional(expected)),
+            ("source", Json::text(source)),
+        ]),
+        Node::TestDecl { name, body } => kind("TestDecl", vec![
+            ("name", Json::text(name)),
+            ("body", self::node(body)),
+        ]),
+        Node::TryStmt { body, handlers, always } => kind("TryStmt", vec![
+            ("body", self::node(body)),
+            ("handlers", Json::Array(handlers.iter().map(|FailClause { name, kind, body }| Json::object(vec![
+                ("name", text(name)),
+                ("errorType", kind.as_ref().map(self::node).unwrap_or(Json::Null)),
+                ("body", self::node(body)),
+            ])).collect())),
+            ("always", optional(always)),
+        ]),
+        Node::Binary { left, operator, right } => kind("Binary", vec![
+            ("operator", Json::text(operator_text(operator))),
+            ("left", self::node(left)),
+            ("right", self::node(right)),
+        ]),
+        Node::Call { callee, args } => kind("Call", vec![
+            ("callee", self::node(callee)),
+            ("args", list(args)),
+        ]),
+        Node::SafeGet { object, name } => kind("SafeGet", vec![
+            ("object", self::node(object)),
+            ("name", Json::text(name)),
+        ]),
+        Node::Get { object, name } => kind("Get", vec![
+            ("object", self::node(object)),
+            ("name", Json::text(name)),
+        ]),
+        Node::Literal(value) => kind("Literal", vec![("value", literal(value))]),
+        Node::Variable(name) => kind("Variable", vec![("name", Json::text(name))]),
+        Node::Assignment { name, value } => kind("Assignment", vec![
+            ("name", Json::text(name)),
+            ("value", self::node(value)),
+        ]),
+        Node::Change { name, operator, amount } => kind("Change", vec![
+            ("name", Json::text(name)),
+            ("operator", Json::text(operator_text(operator))),
+            ("amount", self::node(amount)),
+        ]),
+        Node::Set { object, name, value } => kind("Set", vec![
+            ("object", self::node(object)),
+            ("name", Json::text(name)),
+            ("value", self::node(value)),
+        ]),
+        Node::New { class_name, args } => kind("New", vec![
+            ("className", Json::text(class_name)),
+            ("args", Json::Array(args.iter().map(|(label, value)| Json::object(vec![
+                ("label", text(label)),
+                ("value", self::node(value)),
+            ])).collect())),
+        ]),
+        Node::Cast { value, target } => kind("Cast", vec![
+            ("value", self::node(value)),
+            ("type", self::node(target)),
+        ]),
+        Node::TypeOf { value } => kind("TypeOf", vec![("value", self::node(value))]),
+        Node::Conditional { value, condition, otherwise } => kind("Conditional", vec![
+            ("value", self::node(value)),
+            ("condition", self::node(condition)),
+            ("otherwise", self::node(otherwise)),
+        ]),
+        Node::Comprehension { value, name, list, condition } => kind("Comprehension", vec![
+            ("value", self::node(value)),
+            ("name", Json::text(name)),
+            ("list", self::node(list)),
+            ("condition", optional(condition)),
+        ]),
+        Node::Index { object, key, keyed } => kind("Index", vec![
+            ("object", self::node(object)),
+            ("key", self::node(key)),
+            ("keyed", (*keyed).into()),
+        ]),
+        Node::Ordinal { number, from_end, digits } => kind("Ordinal", vec![
+            ("number", (*number).into()),
+            ("from_end", (*from_end).into()),
+            ("digits", (*digits).into()),
+        ]),
+        Node::Slice { object, first, last } => kind("Slice", vec![
+            ("object", self::node(object)),
+            ("first", optional(first)),
+            ("last", optional(last)),
+        ]),
+        Node::SetIndex { object, key, keyed, value } => kind("SetIndex", vec![
+            ("object", self::node(object)),
+            ("key", self::node(key)),
+            ("keyed", (*keyed).into()),
+            ("value", self::node(value)),
+        ]),
+        Node::ListOf { parts } => kind("ListOf", vec![
+            ("parts", Json::Array(parts.iter().map(|(all, part)| Json::object(vec![
+                ("all", (*all).into()),
+                ("value", self::node(part)),
+            ])).collect())),
+        ]),
+        Node::FollowedBy { left, right } => kind("FollowedBy", vec![
+            ("left", self::node(left)),
+            ("right", self::node(right)),
+        ]),
+        Node::SameAs { left, right, negated } => kind("SameAs", vec![
+            ("left", self::node(left)),
+            ("right", self::node(right)),
+            ("negated", (*negated).into()),
+        ]),
+        Node::TypeTest { value, target, negated } => kind("TypeTest", vec![
+            ("value", self::node(value)),
+            ("type", self::node(target)),
+            ("negated", (*negated).into()),
+        ]),
+        Node::TypeAnnotation(name) => kind("Type", vec![("name", Json::text(name))]),
+        Node::ListType { element_type } => kind("ListType", vec![("element", self::node(element_type))]),
+        Node::MappingType { key_type, value_type } => kind("MappingType", vec![
+            ("key", self::node(key_type)),
+            ("value", self::node(value_type)),
+        ]),
+        Node::StringInterpolation { parts } => kind("StringInterpolation", vec![("parts", list(parts))]),
+        Node::ArrayLiteral { elements, type_annotation } => kind("ListLiteral", vec![
+            ("elements", list(elements)),
+            ("type", optional(type_annotation)),
+        ]),
+        Node::MappingLiteral { entries } => kind("MappingLiteral", vec![
+            ("entries", Json::Array(entries.iter().map(|(key, typ, value)| Json::object(vec![
+                ("key", Json::text(key)),
+                ("type", typ.as_ref().map(self::node).unwrap_or(Json::Null)),
+                ("value", self::node(value)),
+            ])).collect())),
+        ]),
+    }
+}
+
+fn literal(value: &Value) -> Json {
+    match value {
+        Value::Number(n) => Json::Number(*n),
+        Value::String(text) => Json::text(text),
+        Value::Boolean(b) => (*b).into(),
+        Value::Null => Json::Null,
+        Value::Money(money) => Json::from_money(*money, "money"),
+        Value::Quantity(quantity) => Json::from_quantity(quantity, "quantity"),
+        Value::Date(date) => Json::from_date(*date, "date"),
+        other => Json::text(&other.to_string()),
+    }
+}
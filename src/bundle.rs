@@ -0,0 +1,101 @@
+use crate::cache::Compiled;
+use crate::json::Json;
+use crate::runtime::Runtime;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+use std::sync::Arc;
+
+// Ends every standalone program, after the length of the program before it
+const MARKER: &[u8; 8] = b"NAIRPROG";
+
+/// Compiles a script and writes a copy of this executable with the program
+/// appended, which runs the program instead of taking arguments. The copy
+/// goes to the file after `-o`, or beside the script without its extension.
+pub fn build(runtime: &mut Runtime, args: &[String]) -> Result<(), String> {
+    let (script, output) = match args {
+        [script] => (script, Path::new(script).with_extension("")),
+        [script, flag, output] | [flag, output, script] if flag == "-o" => (script, output.into()),
+        _ => return Err("Usage: nair build script [-o output]".to_string()),
+    };
+    if output == Path::new(script) {
+        return Err(format!("Building '{}' would overwrite it; choose another name with -o", script));
+    }
+
+    let source = std::fs::read_to_string(script).map_err(|e| format!("Error reading '{}': {}", script, e))?;
+    runtime.set_source_name(script);
+    let program = runtime.compile_source(&source)?
+        .to_json()
+        .ok_or_else(|| format!("'{}' holds values that can't be built into a program", script))?;
+    let name = Path::new(script).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    let payload = Json::object(vec![
+        ("name", Json::text(&name)),
+        ("source", Json::text(&source)),
+        ("program", program),
+    ]).to_string();
+
+    let exe = std::env::current_exe().map_err(|e| format!("Can't find the nair executable: {}", e))?;
+    let mut bytes = std::fs::read(&exe).map_err(|e| format!("Error reading '{}': {}", exe.display(), e))?;
+    // Building from a built program copies just the runtime
+    if let Some(start) = payload_start(&bytes) {
+        bytes.truncate(start);
+    }
+    bytes.extend_from_slice(payload.as_bytes());
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(MARKER);
+    std::fs::write(&output, bytes).map_err(|e| format!("Error writing '{}': {}", output.display(), e))?;
+    make_executable(&output)?;
+    println!("Built {}", output.display());
+    Ok(())
+}
+
+/// Runs the program built into this executable, if there is one.
+pub fn run_embedded() -> Option<Result<(), String>> {
+    let mut file = File::open(std::env::current_exe().ok()?).ok()?;
+    let mut trailer = [0u8; 16];
+    let end = file.seek(SeekFrom::End(-(trailer.len() as i64))).ok()?;
+    file.read_exact(&mut trailer).ok()?;
+    if &trailer[8..] != MARKER {
+        return None;
+    }
+    let length = u64::from_le_bytes(trailer[..8].try_into().ok()?);
+    let mut payload = vec![0u8; usize::try_from(length).ok()?];
+    file.seek(SeekFrom::Start(end.checked_sub(length)?)).ok()?;
+    file.read_exact(&mut payload).ok()?;
+
+    Some((|| {
+        let damaged = || "This program's compiled code is damaged".to_string();
+        let payload = String::from_utf8(payload).map_err(|_| damaged())?;
+        let payload = Json::parse(&payload).map_err(|_| damaged())?;
+        let name: Arc<str> = Arc::from(payload.get("name").and_then(Json::as_str).ok_or_else(damaged)?);
+        let source = payload.get("source").and_then(Json::as_str).ok_or_else(damaged)?;
+        let compiled = payload.get("program").and_then(|program| Compiled::from_json(program, &name)).ok_or_else(damaged)?;
+
+        let mut runtime = Runtime::new();
+        runtime.set_debug_output(false);
+        runtime.set_source_name(&name);
+        runtime.run_compiled(source, &compiled)
+    })())
+}
+
+// Where an appended program starts in an executable's bytes
+fn payload_start(bytes: &[u8]) -> Option<usize> {
+    let trailer = bytes.len().checked_sub(16)?;
+    if &bytes[trailer + 8..] != MARKER {
+        return None;
+    }
+    let length = u64::from_le_bytes(bytes[trailer..trailer + 8].try_into().ok()?);
+    trailer.checked_sub(usize::try_from(length).ok()?)
+}
+
+#[cfg(unix)]
+fn make_executable(path: &Path) -> Result<(), String> {
+    use std::os::unix::fs::PermissionsExt;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755))
+        .map_err(|e| format!("Error making '{}' executable: {}", path.display(), e))
+}
+
+#[cfg(not(unix))]
+fn make_executable(_path: &Path) -> Result<(), String> {
+    Ok(())
+}
@@ -0,0 +1,317 @@
+use crate::generator::{Class, Function, LineTable, OpCode, TypedNames, Value};
+use crate::json::Json;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::path::PathBuf;
+use std::sync::Arc;
+
+/// Compiled programs saved on disk, so running an unchanged file again skips
+/// tokenizing, parsing, checking and compiling it. Entries are keyed by the
+/// source text, the tokenizer and number settings and the build of `nair`
+/// that wrote them; anything unreadable is treated as missing.
+pub struct BytecodeCache {
+    directory: PathBuf,
+    version: String,
+}
+
+/// Everything compiling a program produces: its top-level code and the
+/// Tasks and Objects it declares.
+pub struct Compiled {
+    pub script: Arc<Function>,
+    pub functions: Vec<Arc<Function>>,
+    pub classes: Vec<Arc<Class>>,
+    /// What the analyzer warned about, shown again when loaded from the cache.
+    pub warnings: Vec<String>,
+}
+
+/// `$NAIR_CACHE_DIR`, or `nair` in the user's cache directory.
+pub fn default_directory() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    var("NAIR_CACHE_DIR")
+        .or_else(|| var("XDG_CACHE_HOME").map(|directory| directory.join("nair")))
+        .or_else(|| var("HOME").map(|home| home.join(".cache").join("nair")))
+}
+
+impl BytecodeCache {
+    pub fn new(directory: PathBuf) -> Self {
+        // A rebuilt executable may compile differently, so its size and
+        // modification time are part of the version
+        let build = std::env::current_exe()
+            .and_then(std::fs::metadata)
+            .map(|metadata| format!("{}-{:?}", metadata.len(), metadata.modified().ok()))
+            .unwrap_or_default();
+        BytecodeCache { directory, version: format!("{}-{}", env!("CARGO_PKG_VERSION"), build) }
+    }
+
+    /// The compiled form of `source`, if an earlier run saved it. Its line
+    /// tables are attributed to `name`.
+    pub fn load(&self, source: &str, settings: &str, name: &Arc<str>) -> Option<Compiled> {
+        let text = std::fs::read_to_string(self.path(source, settings)).ok()?;
+        let entry = Json::parse(&text).ok()?;
+        if entry.get("length")?.as_f64()? as usize != source.len() {
+            return None;
+        }
+        Compiled::from_json(entry.get("program")?, name)
+    }
+
+    /// Saves a compiled program for later runs. Programs holding values
+    /// that can't be written out are skipped, as are write errors.
+    pub fn store(&self, source: &str, settings: &str, compiled: &Compiled) {
+        if let Some(program) = compiled.to_json() {
+            let entry = Json::object(vec![("length", source.len().into()), ("program", program)]);
+            let _ = std::fs::create_dir_all(&self.directory)
+                .and_then(|_| std::fs::write(self.path(source, settings), entry.to_string()));
+        }
+    }
+
+    fn path(&self, source: &str, settings: &str) -> PathBuf {
+        let mut hasher = DefaultHasher::new();
+        (&self.version, settings, source).hash(&mut hasher);
+        self.directory.join(format!("{:016x}.json", hasher.finish()))
+    }
+}
+
+impl Compiled {
+    /// The program as JSON, or None if it holds values that can't be
+    /// written out.
+    pub fn to_json(&self) -> Option<Json> {
+        Some(Json::object(vec![
+            ("script", function(&self.script)?),
+            ("functions", Json::Array(self.functions.iter().map(|function| self::function(function)).collect::<Option<_>>()?)),
+            ("classes", Json::Array(self.classes.iter().map(|class| self::class(class)).collect::<Option<_>>()?)),
+            ("warnings", Json::Array(self.warnings.iter().map(|warning| Json::String(warning.clone())).collect())),
+        ]))
+    }
+
+    /// Reads back what `to_json` wrote, attributing line tables to `name`.
+    pub fn from_json(json: &Json, name: &Arc<str>) -> Option<Compiled> {
+        let functions = json.get("functions")?.as_array()?.iter().map(|function| read_function(function, name)).collect::<Option<Vec<_>>>()?;
+        let classes = json.get("classes")?.as_array()?.iter().map(|class| read_class(class, name)).collect::<Option<Vec<_>>>()?;
+        let warnings = json.get("warnings")?.as_array()?.iter().map(|warning| warning.as_str().map(str::to_string)).collect::<Option<Vec<_>>>()?;
+        Some(Compiled { script: read_function(json.get("script")?, name)?, functions, classes, warnings })
+    }
+}
+
+pub fn function(function: &Function) -> Option<Json> {
+    Some(Json::object(vec![
+        ("name", Json::text(&function.name)),
+        ("params", typed_names(&function.params)),
+        ("body", Json::Array(function.body.iter().map(opcode).collect::<Option<_>>()?)),
+        ("lines", Json::Array(function.lines.starts().map(|(start, line)| Json::Array(vec![start.into(), line.into()])).collect())),
+    ]))
+}
+
+pub fn read_function(json: &Json, source: &Arc<str>) -> Option<Arc<Function>> {
+    let starts = json.get("lines")?.as_array()?.iter()
+        .map(|pair| Some((pair.as_array()?.first()?.as_f64()? as usize, pair.as_array()?.get(1)?.as_f64()? as usize)))
+        .collect::<Option<Vec<_>>>()?;
+    Some(Arc::new(Function {
+        name: json.get("name")?.as_str()?.to_string(),
+        params: read_typed_names(json.get("params")?)?,
+        body: json.get("body")?.as_array()?.iter().map(read_opcode).collect::<Option<_>>()?,
+        lines: LineTable::from_starts(Arc::clone(source), starts),
+    }))
+}
+
+pub fn class(class: &Class) -> Option<Json> {
+    let table = |functions: &HashMap<String, Arc<Function>>| -> Option<Json> {
+        Some(Json::Object(functions.iter().map(|(name, function)| Some((name.clone(), self::function(function)?))).collect::<Option<_>>()?))
+    };
+    let names = |names: &[String]| Json::Array(names.iter().map(|name| Json::text(name)).collect());
+    Some(Json::object(vec![
+        ("name", Json::text(&class.name)),
+        ("base", class.base.as_deref().map(Json::text).unwrap_or(Json::Null)),
+        ("fields", typed_names(&class.fields)),
+        ("initializer", function(&class.initializer)?),
+        ("methods", table(&class.methods)?),
+        ("getters", table(&class.getters)?),
+        ("setters", table(&class.setters)?),
+        ("constructor", match &class.constructor {
+            Some(constructor) => function(constructor)?,
+            None => Json::Null,
+        }),
+        ("constructorLabels", names(&class.constructor_labels)),
+        ("sharedFields", typed_names(&class.shared_fields)),
+        ("sharedInitializer", function(&class.shared_initializer)?),
+        ("sharedMethods", table(&class.shared_methods)?),
+        ("behaviors", names(&class.behaviors)),
+        ("isBehavior", class.is_behavior.into()),
+    ]))
+}
+
+pub fn read_class(json: &Json, source: &Arc<str>) -> Option<Arc<Class>> {
+    let table = |name: &str| -> Option<HashMap<String, Arc<Function>>> {
+        match json.get(name)? {
+            Json::Object(members) => members.iter().map(|(name, function)| Some((name.clone(), read_function(function, source)?))).collect(),
+            _ => None,
+        }
+    };
+    let names = |name: &str| -> Option<Vec<String>> {
+        json.get(name)?.as_array()?.iter().map(|name| name.as_str().map(str::to_string)).collect()
+    };
+    Some(Arc::new(Class {
+        name: json.get("name")?.as_str()?.to_string(),
+        base: json.get("base")?.as_str().map(str::to_string),
+        fields: read_typed_names(json.get("fields")?)?,
+        initializer: read_function(json.get("initializer")?, source)?,
+        methods: table("methods")?,
+        getters: table("getters")?,
+        setters: table("setters")?,
+        constructor: match json.get("constructor")? {
+            Json::Null => None,
+            constructor => Some(read_function(constructor, source)?),
+        },
+        constructor_labels: names("constructorLabels")?,
+        shared_fields: read_typed_names(json.get("sharedFields")?)?,
+        shared_initializer: read_function(json.get("sharedInitializer")?, source)?,
+        shared_methods: table("sharedMethods")?,
+        behaviors: names("behaviors")?,
+        is_behavior: json.get("isBehavior")?.as_bool()?,
+    }))
+}
+
+fn typed_names(names: &TypedNames) -> Json {
+    Json::Array(names.iter().map(|(name, typ)| Json::Array(vec![
+        Json::text(name),
+        typ.as_deref().map(Json::text).unwrap_or(Json::Null),
+    ])).collect())
+}
+
+fn read_typed_names(json: &Json) -> Option<TypedNames> {
+    json.as_array()?.iter().map(|pair| {
+        let pair = pair.as_array()?;
+        Some((pair.first()?.as_str()?.to_string(), pair.get(1)?.as_str().map(str::to_string)))
+    }).collect()
+}
+
+// Each instruction is its name followed by its operands
+fn opcode(op: &OpCode) -> Option<Json> {
+    let text = |text: &str| Json::text(text);
+    let (name, operands) = match op {
+        OpCode::Push(value) => ("Push", vec![value_json(value)?]),
+        OpCode::LoadVar(name) => ("LoadVar", vec![text(name)]),
+        OpCode::StoreVar(name) => ("StoreVar", vec![text(name)]),
+        OpCode::DeclareVar(name, type_name) => ("DeclareVar", vec![text(name), text(type_name)]),
+        OpCode::Jump(target) => ("Jump", vec![(*target).into()]),
+        OpCode::JumpIfFalse(target) => ("JumpIfFalse", vec![(*target).into()]),
+        OpCode::Call(name, count) => ("Call", vec![text(name), (*count).into()]),
+        OpCode::CallMethod(name, count) => ("CallMethod", vec![text(name), (*count).into()]),
+        OpCode::PushHandler(target) => ("PushHandler", vec![(*target).into()]),
+        OpCode::Expect(line, source) => ("Expect", vec![(*line).into(), text(source)]),
+        OpCode::ExpectEqual(line, source) => ("ExpectEqual", vec![(*line).into(), text(source)]),
+        OpCode::NewObject(name, labels) => ("NewObject", vec![
+            text(name),
+            Json::Array(labels.iter().map(|label| label.as_deref().map(Json::text).unwrap_or(Json::Null)).collect()),
+        ]),
+        OpCode::InitClass(name) => ("InitClass", vec![text(name)]),
+        OpCode::GetProperty(name) => ("GetProperty", vec![text(name)]),
+        OpCode::SetProperty(name) => ("SetProperty", vec![text(name)]),
+        OpCode::BuildList(count) => ("BuildList", vec![(*count).into()]),
+        OpCode::JoinLists(count) => ("JoinLists", vec![(*count).into()]),
+        OpCode::ForEach(target) => ("ForEach", vec![(*target).into()]),
+        OpCode::FromEnd(depth) => ("FromEnd", vec![(*depth).into()]),
+        OpCode::BuildMapping(count) => ("BuildMapping", vec![(*count).into()]),
+        OpCode::Cast(name) => ("Cast", vec![text(name)]),
+        OpCode::IsInstance(name) => ("IsInstance", vec![text(name)]),
+        OpCode::Interpolate(count) => ("Interpolate", vec![(*count).into()]),
+        OpCode::Concat(count) => ("Concat", vec![(*count).into()]),
+        // The rest have no operands, so their debug form is just the name
+        other => return Some(Json::Array(vec![Json::text(&format!("{:?}", other))])),
+    };
+    Some(Json::Array(std::iter::once(Json::text(name)).chain(operands).collect()))
+}
+
+fn read_opcode(json: &Json) -> Option<OpCode> {
+    let items = json.as_array()?;
+    let text = |index: usize| items.get(index).and_then(Json::as_str).map(str::to_string);
+    let number = |index: usize| items.get(index).and_then(Json::as_f64).map(|n| n as usize);
+    Some(match items.first()?.as_str()? {
+        "Push" => OpCode::Push(read_value(items.get(1)?)?),
+        "Pop" => OpCode::Pop,
+        "Dup" => OpCode::Dup,
+        "LoadVar" => OpCode::LoadVar(text(1)?),
+        "StoreVar" => OpCode::StoreVar(text(1)?),
+        "DeclareVar" => OpCode::DeclareVar(text(1)?, text(2)?),
+        "Add" => OpCode::Add,
+        "Subtract" => OpCode::Subtract,
+        "Multiply" => OpCode::Multiply,
+        "Divide" => OpCode::Divide,
+        "Modulo" => OpCode::Modulo,
+        "Power" => OpCode::Power,
+        "Equal" => OpCode::Equal,
+        "NotEqual" => OpCode::NotEqual,
+        "SameAs" => OpCode::SameAs,
+        "Greater" => OpCode::Greater,
+        "GreaterEqual" => OpCode::GreaterEqual,
+        "Less" => OpCode::Less,
+        "LessEqual" => OpCode::LessEqual,
+        "And" => OpCode::And,
+        "Or" => OpCode::Or,
+        "Not" => OpCode::Not,
+        "Jump" => OpCode::Jump(number(1)?),
+        "JumpIfFalse" => OpCode::JumpIfFalse(number(1)?),
+        "Call" => OpCode::Call(text(1)?, number(2)?),
+        "CallMethod" => OpCode::CallMethod(text(1)?, number(2)?),
+        "Return" => OpCode::Return,
+        "PushHandler" => OpCode::PushHandler(number(1)?),
+        "PopHandler" => OpCode::PopHandler,
+        "Raise" => OpCode::Raise,
+        "Expect" => OpCode::Expect(number(1)?, text(2)?),
+        "ExpectEqual" => OpCode::ExpectEqual(number(1)?, text(2)?),
+        "NewObject" => OpCode::NewObject(text(1)?, items.get(2)?.as_array()?.iter().map(|label| label.as_str().map(str::to_string)).collect()),
+        "InitClass" => OpCode::InitClass(text(1)?),
+        "GetProperty" => OpCode::GetProperty(text(1)?),
+        "SetProperty" => OpCode::SetProperty(text(1)?),
+        "BuildList" => OpCode::BuildList(number(1)?),
+        "JoinLists" => OpCode::JoinLists(number(1)?),
+        "ForEach" => OpCode::ForEach(number(1)?),
+        "Append" => OpCode::Append,
+        "GetIndex" => OpCode::GetIndex,
+        "SetIndex" => OpCode::SetIndex,
+        "FromEnd" => OpCode::FromEnd(number(1)?),
+        "Slice" => OpCode::Slice,
+        "BuildMapping" => OpCode::BuildMapping(number(1)?),
+        "Cast" => OpCode::Cast(text(1)?),
+        "IsInstance" => OpCode::IsInstance(text(1)?),
+        "TypeOf" => OpCode::TypeOf,
+        "Interpolate" => OpCode::Interpolate(number(1)?),
+        "Concat" => OpCode::Concat(number(1)?),
+        "Show" => OpCode::Show,
+        _ => return None,
+    })
+}
+
+// Constants are the only values in compiled code: numbers, Money,
+// quantities, dates, text, truths, nothing, object kinds and lists of those
+fn value_json(value: &Value) -> Option<Json> {
+    Some(match value {
+        Value::Number(n) if n.is_finite() => Json::Number(*n),
+        Value::Money(money) => Json::from_money(*money, "money"),
+        Value::Quantity(quantity) if quantity.amount.is_finite() => Json::from_quantity(quantity, "quantity"),
+        Value::Date(date) => Json::from_date(*date, "date"),
+        Value::String(text) => Json::text(text),
+        Value::Boolean(b) => (*b).into(),
+        Value::Null => Json::Null,
+        Value::Class(name) => Json::object(vec![("class", Json::text(name))]),
+        Value::List(items) => Json::object(vec![("list", Json::Array(items.borrow().iter().map(value_json).collect::<Option<_>>()?))]),
+        _ => return None,
+    })
+}
+
+fn read_value(json: &Json) -> Option<Value> {
+    Some(match json {
+        Json::Number(n) => Value::Number(*n),
+        Json::String(text) => Value::String(text.clone()),
+        Json::Bool(b) => Value::Boolean(*b),
+        Json::Null => Value::Null,
+        Json::Object(_) => match (json.get("class"), json.get("list")) {
+            (Some(name), _) => Value::Class(name.as_str()?.to_string()),
+            (_, Some(items)) => Value::list(items.as_array()?.iter().map(read_value).collect::<Option<_>>()?),
+            _ => json.to_money("money").map(Value::Money)
+                    .or_else(|| json.to_quantity("quantity").map(Value::Quantity))
+                    .or_else(|| json.to_date("date").map(Value::Date))?,
+        },
+        Json::Array(_) => return None,
+    })
+}
@@ -0,0 +1,132 @@
+use std::cmp::Ordering;
+
+// A letter a language sorts as one of its own, and the letter it comes
+// straight after
+type Letter = (char, char);
+
+// Each language's name, its code and its own letters. Only languages that
+// sort differently from the default order are here.
+const LANGUAGES: [(&str, &str, &[Letter]); 2] = [
+    ("Spanish", "es", &[('ñ', 'n')]),
+    ("Swedish", "sv", &[('å', 'z'), ('ä', 'å'), ('ö', 'ä')]),
+];
+
+// Letters with accents, each after the letter it is written on, in the
+// order they sort when the letters are otherwise the same
+const ACCENTED: [(char, &str); 12] = [
+    ('a', "áàâäãåāą"),
+    ('c', "çćč"),
+    ('d', "ď"),
+    ('e', "éèêëēęě"),
+    ('i', "íìîïī"),
+    ('l', "ł"),
+    ('n', "ñńň"),
+    ('o', "óòôöõøō"),
+    ('r', "ř"),
+    ('s', "śš"),
+    ('u', "úùûüūů"),
+    ('z', "źżž"),
+];
+
+// Letters written as two, which sort as those two
+const LIGATURES: [(char, &str); 3] = [('ß', "ss"), ('æ', "ae"), ('œ', "oe")];
+
+/// An alphabetical order for Text, which `sort` and `collate` follow.
+/// Capitals and accents only count between words that are otherwise the
+/// same, so `apple`, `Apple` and `Äpfel` sort near each other rather than by
+/// their character codes. This approximates the Unicode Collation Algorithm
+/// for the Latin alphabet rather than following it. The default order has
+/// no letters of its own; a language that does puts them in their place:
+/// Spanish `ñ` after `n`, and Swedish `å`, `ä` and `ö` after `z`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Collation(Option<usize>);
+
+impl Collation {
+    /// The collation for a language, by its English name or its two-letter
+    /// code, in any case: `Spanish`, `spanish` or `es`.
+    pub fn named(name: &str) -> Option<Collation> {
+        LANGUAGES.iter()
+            .position(|(language, code, _)| language.eq_ignore_ascii_case(name) || code.eq_ignore_ascii_case(name))
+            .map(|language| Collation(Some(language)))
+    }
+
+    /// The language's English name, or nothing for the default order.
+    pub fn name(self) -> Option<&'static str> {
+        self.0.map(|language| LANGUAGES[language].0)
+    }
+
+    // The letters the language sorts as its own
+    fn letters(self) -> &'static [Letter] {
+        self.0.map_or(&[], |language| LANGUAGES[language].2)
+    }
+
+    /// The languages there are collations for, as an error lists them.
+    pub fn names() -> String {
+        let names: Vec<&str> = LANGUAGES.iter().map(|(language, ..)| *language).collect();
+        format!("{} or {}", names[..names.len() - 1].join(", "), names[names.len() - 1])
+    }
+
+    /// Which of two Texts comes first: by their letters, then their accents,
+    /// then their capitals (small letters first), and last of all by
+    /// character code, so only equal Texts are equal.
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        let (a_letters, a_accents, a_cases) = self.keys(a);
+        let (b_letters, b_accents, b_cases) = self.keys(b);
+        a_letters.cmp(&b_letters)
+            .then_with(|| a_accents.cmp(&b_accents))
+            .then_with(|| a_cases.cmp(&b_cases))
+            .then_with(|| a.cmp(b))
+    }
+
+    // What each character counts as at each level: the letter, its accent
+    // and whether it is a capital
+    fn keys(self, text: &str) -> (Vec<u32>, Vec<u32>, Vec<bool>) {
+        let (mut letters, mut accents, mut cases) = (Vec::new(), Vec::new(), Vec::new());
+        for c in text.chars() {
+            let small = c.to_lowercase().next().unwrap_or(c);
+            let capital = small != c;
+            match LIGATURES.iter().find(|(ligature, _)| *ligature == small) {
+                Some((_, spelled)) => {
+                    for letter in spelled.chars() {
+                        letters.push(self.weight(letter));
+                        accents.push(0);
+                        cases.push(capital);
+                    }
+                },
+                None => {
+                    let (letter, accent) = self.letter(small);
+                    letters.push(letter);
+                    accents.push(accent);
+                    cases.push(capital);
+                },
+            }
+        }
+        (letters, accents, cases)
+    }
+
+    // The weight of a small letter and which accent it has, if any
+    fn letter(self, small: char) -> (u32, u32) {
+        if self.letters().iter().any(|(own, _)| *own == small) {
+            return (self.weight(small), 0);
+        }
+        for (base, accented) in ACCENTED {
+            if let Some(accent) = accented.chars().position(|letter| letter == small) {
+                return (self.weight(base), accent as u32 + 1);
+            }
+        }
+        (self.weight(small), 0)
+    }
+
+    // Where a small letter sorts. Each character has room after it for the
+    // letters a language puts there.
+    fn weight(self, small: char) -> u32 {
+        let mut weight = 0;
+        let mut letter = small;
+        // Follow `ö` after `ä` after `å` after `z` back to a plain letter
+        while let Some((_, after)) = self.letters().iter().find(|(own, _)| *own == letter) {
+            weight += 1;
+            letter = *after;
+        }
+        letter as u32 * 8 + weight
+    }
+}
@@ -0,0 +1,123 @@
+use crate::theme::Theme;
+use std::fmt;
+use std::path::PathBuf;
+
+// The settings there are, in the order `.config` lists them
+const NAMES: [&str; 5] = ["prompt", "debug", "color", "history", "strict"];
+
+/// The REPL's settings, read when it starts from `~/.vernacularrc` or
+/// `vernacular/config` in the user's config directory, and changed while it
+/// runs with `.config`. Each line of the file sets one, as `name = value`,
+/// and `#` starts a comment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Config {
+    /// What the REPL shows while it waits for a line.
+    pub prompt: String,
+    /// Whether each input's tokens, syntax tree and bytecode are shown.
+    pub debug: bool,
+    /// Whether errors and shown values are colored, on a terminal.
+    pub color: bool,
+    /// How many past inputs `.history` keeps.
+    pub history: usize,
+    /// Whether the analyzer insists on types, as `--strict` does.
+    pub strict: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config { prompt: "> ".to_string(), debug: true, color: true, history: 100, strict: false }
+    }
+}
+
+/// `~/.vernacularrc`, or else `vernacular/config` in `$XDG_CONFIG_HOME`
+/// (by default `~/.config`), whichever there is.
+pub fn default_path() -> Option<PathBuf> {
+    let var = |name: &str| std::env::var_os(name).filter(|value| !value.is_empty()).map(PathBuf::from);
+    let home = var("HOME");
+    let config_home = var("XDG_CONFIG_HOME").or_else(|| home.as_ref().map(|home| home.join(".config")));
+    home.map(|home| home.join(".vernacularrc"))
+        .into_iter()
+        .chain(config_home.map(|directory| directory.join("vernacular").join("config")))
+        .find(|path| path.is_file())
+}
+
+impl Config {
+    /// The settings in the user's config file, or the defaults without one.
+    /// Lines that can't be read are warned about and skipped, so one typo
+    /// doesn't lose the rest.
+    pub fn load(theme: Theme) -> Config {
+        let mut config = Config::default();
+        let Some(path) = default_path() else {
+            return config;
+        };
+        let text = match std::fs::read_to_string(&path) {
+            Ok(text) => text,
+            Err(e) => {
+                eprintln!("{}", theme.warning(&format!("Warning: Error reading '{}': {}", path.display(), e)));
+                return config;
+            },
+        };
+        for (number, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let setting = line.split_once('=')
+                .ok_or_else(|| "expected `name = value`".to_string())
+                .and_then(|(name, value)| config.set(name.trim(), value));
+            if let Err(error) = setting {
+                eprintln!("{}", theme.warning(&format!("Warning: {} line {}: {}", path.display(), number + 1, error)));
+            }
+        }
+        config
+    }
+
+    /// Changes a setting from its value as written, like `on` or `"vern> "`.
+    pub fn set(&mut self, name: &str, value: &str) -> Result<(), String> {
+        let value = value.trim();
+        let switch = || match value {
+            "on" | "true" | "yes" => Ok(true),
+            "off" | "false" | "no" => Ok(false),
+            _ => Err(format!("{} is on or off, not '{}'", name, value)),
+        };
+        match name {
+            "prompt" => self.prompt = unquote(value).to_string(),
+            "debug" => self.debug = switch()?,
+            "color" => self.color = switch()?,
+            "history" => self.history = value.parse().map_err(|_| format!("history is a whole number of inputs, not '{}'", value))?,
+            "strict" => self.strict = switch()?,
+            _ => return Err(format!("There is no setting named '{}'; the settings are {}", name, NAMES.join(", "))),
+        }
+        Ok(())
+    }
+
+    /// A setting as the config file would write it.
+    pub fn line(&self, name: &str) -> Option<String> {
+        let switch = |on: bool| if on { "on" } else { "off" };
+        let value = match name {
+            "prompt" => format!("\"{}\"", self.prompt),
+            "debug" => switch(self.debug).to_string(),
+            "color" => switch(self.color).to_string(),
+            "history" => self.history.to_string(),
+            "strict" => switch(self.strict).to_string(),
+            _ => return None,
+        };
+        Some(format!("{} = {}", name, value))
+    }
+}
+
+// Takes off the quotes that keep a value's spaces, as in `prompt = "> "`
+fn unquote(value: &str) -> &str {
+    ['"', '\'']
+        .iter()
+        .find_map(|quote| value.strip_prefix(*quote).and_then(|rest| rest.strip_suffix(*quote)))
+        .unwrap_or(value)
+}
+
+/// Every setting, a line each, so the text can be pasted into the file.
+impl fmt::Display for Config {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let lines: Vec<String> = NAMES.iter().filter_map(|name| self.line(name)).collect();
+        write!(f, "{}", lines.join("\n"))
+    }
+}
@@ -0,0 +1,85 @@
+use crate::generator::Function;
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+/// How many times each statement line of each source ran. Lines are
+/// registered as code is compiled, so statements that never ran show up
+/// with a count of zero.
+#[derive(Default)]
+pub struct Coverage {
+    files: BTreeMap<String, BTreeMap<usize, u64>>,
+}
+
+impl Coverage {
+    pub fn new() -> Self {
+        Coverage::default()
+    }
+
+    pub fn add_function(&mut self, function: &Function) {
+        let lines = self.files.entry(function.lines.source.to_string()).or_default();
+        // A declaration at the very end compiles to nothing, so has nothing to run
+        for (_, line) in function.lines.starts().filter(|(start, _)| *start < function.body.len()) {
+            lines.entry(line).or_insert(0);
+        }
+    }
+
+    pub fn hit(&mut self, source: &str, line: usize) {
+        if let Some(count) = self.files.get_mut(source).and_then(|lines| lines.get_mut(&line)) {
+            *count += 1;
+        }
+    }
+
+    pub fn merge(&mut self, other: Coverage) {
+        for (source, lines) in other.files {
+            let merged = self.files.entry(source).or_default();
+            for (line, count) in lines {
+                *merged.entry(line).or_insert(0) += count;
+            }
+        }
+    }
+
+    /// One line per file: how many statement lines ran, and which didn't.
+    pub fn summary(&self) -> String {
+        let mut summary = String::new();
+        for (source, lines) in &self.files {
+            let covered = lines.values().filter(|count| **count > 0).count();
+            let percent = if lines.is_empty() { 100.0 } else { covered as f64 * 100.0 / lines.len() as f64 };
+            let _ = write!(summary, "{}: {}/{} lines ({:.1}%)", source, covered, lines.len(), percent);
+            let missed = Self::ranges(lines.iter().filter(|(_, count)| **count == 0).map(|(line, _)| *line));
+            if !missed.is_empty() {
+                let _ = write!(summary, ", not run: {}", missed);
+            }
+            summary.push('\n');
+        }
+        summary
+    }
+
+    /// The same information in LCOV's tracefile format.
+    pub fn lcov(&self) -> String {
+        let mut lcov = String::from("TN:\n");
+        for (source, lines) in &self.files {
+            let _ = writeln!(lcov, "SF:{}", source);
+            for (line, count) in lines {
+                let _ = writeln!(lcov, "DA:{},{}", line, count);
+            }
+            let hit = lines.values().filter(|count| **count > 0).count();
+            let _ = writeln!(lcov, "LF:{}\nLH:{}\nend_of_record", lines.len(), hit);
+        }
+        lcov
+    }
+
+    // 3, 4, 5, 9 reads as "3-5, 9"
+    fn ranges(lines: impl Iterator<Item = usize>) -> String {
+        let mut ranges: Vec<(usize, usize)> = Vec::new();
+        for line in lines {
+            match ranges.last_mut() {
+                Some((_, end)) if *end + 1 == line => *end = line,
+                _ => ranges.push((line, line)),
+            }
+        }
+        ranges.iter()
+            .map(|(start, end)| if start == end { start.to_string() } else { format!("{}-{}", start, end) })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+}
@@ -0,0 +1,251 @@
+use crate::debugger::{Debugger, PauseView};
+use crate::json::{self, Json};
+use crate::runtime::Runtime;
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+// Vernacular runs one program at a time, so there is only ever one thread
+const THREAD_ID: usize = 1;
+
+/// A Debug Adapter Protocol connection over standard input and output.
+/// Clones share the message counter, so the debugger and the program's
+/// output can both send messages.
+#[derive(Clone)]
+pub struct Session {
+    seq: Arc<AtomicUsize>,
+}
+
+// What to do once a request has been answered
+enum Next {
+    Wait,
+    Resume,
+    Stop,
+}
+
+/// Serves one debugging session: waits for an editor to launch a program,
+/// runs it under the debugger, and reports how it ended.
+pub fn serve(mut runtime: Runtime) -> Result<(), String> {
+    let session = Session { seq: Arc::new(AtomicUsize::new(0)) };
+    let mut debugger = Debugger::for_dap(session.clone());
+    let mut program = None;
+
+    while let Some(request) = session.read()? {
+        match command(&request) {
+            "initialize" => {
+                session.respond(&request, Json::object(vec![
+                    ("supportsConfigurationDoneRequest", true.into()),
+                ]))?;
+                session.event("initialized", Json::object(vec![]))?;
+            },
+            "launch" => {
+                let arguments = request.get("arguments");
+                let Some(path) = arguments.and_then(|arguments| arguments.get("program")).and_then(Json::as_str) else {
+                    session.fail(&request, "launch needs a 'program' to run")?;
+                    continue;
+                };
+                if arguments.and_then(|arguments| arguments.get("stopOnEntry")).and_then(Json::as_bool) == Some(true) {
+                    debugger.step_into();
+                }
+                program = Some(canonical(path));
+                session.respond(&request, Json::Null)?;
+            },
+            "configurationDone" => {
+                session.respond(&request, Json::Null)?;
+                let Some(path) = program.take() else {
+                    session.output("stderr", "No program was launched\n")?;
+                    continue;
+                };
+                runtime.set_output(Box::new(OutputEvents(session.clone())));
+                // Standard input carries the protocol
+                runtime.set_input(Box::new(io::empty()));
+                runtime.attach_debugger(std::mem::replace(&mut debugger, Debugger::for_dap(session.clone())));
+                let exit_code = match runtime.run_file(&path) {
+                    Ok(()) => 0,
+                    Err(error) => {
+                        session.output("stderr", &format!("Error: {}\n", error))?;
+                        1
+                    },
+                };
+                session.event("exited", Json::object(vec![("exitCode", exit_code.into())]))?;
+                session.event("terminated", Json::object(vec![]))?;
+            },
+            "disconnect" | "terminate" => {
+                session.respond(&request, Json::Null)?;
+                break;
+            },
+            _ => {
+                // Breakpoints and the like may be set before the program starts
+                let view = PauseView { frames: Vec::new(), stack: Vec::new() };
+                session.answer(&mut debugger, &request, &view)?;
+            },
+        }
+    }
+    Ok(())
+}
+
+impl Session {
+    /// Tells the editor the program stopped, then answers its requests
+    /// until it resumes or stops the program.
+    pub fn paused(&self, debugger: &mut Debugger, reason: &str, view: &PauseView) -> Result<(), String> {
+        self.event("stopped", Json::object(vec![
+            ("reason", Json::text(reason)),
+            ("threadId", THREAD_ID.into()),
+            ("allThreadsStopped", true.into()),
+        ]))?;
+        while let Some(request) = self.read()? {
+            match self.answer(debugger, &request, view)? {
+                Next::Wait => {},
+                Next::Resume => return Ok(()),
+                Next::Stop => return Err("Stopped by the debugger".to_string()),
+            }
+        }
+        Err("The debugger disconnected".to_string())
+    }
+
+    fn answer(&self, debugger: &mut Debugger, request: &Json, view: &PauseView) -> Result<Next, String> {
+        let arguments = request.get("arguments");
+        let argument = |name: &str| arguments.and_then(|arguments| arguments.get(name));
+        let depth = view.frames.len();
+
+        match command(request) {
+            "setBreakpoints" => {
+                let path = argument("source").and_then(|source| source.get("path")).and_then(Json::as_str).unwrap_or("");
+                let lines: Vec<usize> = argument("breakpoints").and_then(Json::as_array).unwrap_or_default().iter()
+                    .filter_map(|breakpoint| breakpoint.get("line")?.as_f64())
+                    .map(|line| line as usize)
+                    .collect();
+                debugger.set_breakpoints(&canonical(path), &lines);
+                let verified = lines.iter()
+                    .map(|&line| Json::object(vec![("verified", true.into()), ("line", line.into())]))
+                    .collect();
+                self.respond(request, Json::object(vec![("breakpoints", Json::Array(verified))]))?;
+            },
+            "threads" => {
+                let thread = Json::object(vec![("id", THREAD_ID.into()), ("name", Json::text("main"))]);
+                self.respond(request, Json::object(vec![("threads", Json::Array(vec![thread]))]))?;
+            },
+            "stackTrace" => {
+                let frames: Vec<Json> = view.frames.iter().enumerate().map(|(id, frame)| Json::object(vec![
+                    ("id", id.into()),
+                    ("name", Json::text(&frame.task)),
+                    ("source", Json::object(vec![("path", Json::text(&frame.source))])),
+                    ("line", frame.line.unwrap_or(0).into()),
+                    ("column", 1.into()),
+                ])).collect();
+                self.respond(request, Json::object(vec![
+                    ("totalFrames", frames.len().into()),
+                    ("stackFrames", Json::Array(frames)),
+                ]))?;
+            },
+            "scopes" => {
+                // Each frame has one scope, numbered one past the frame so that 0 means "none"
+                let frame = argument("frameId").and_then(Json::as_f64).unwrap_or(0.0) as usize;
+                let scope = Json::object(vec![
+                    ("name", Json::text("Locals")),
+                    ("variablesReference", (frame + 1).into()),
+                    ("expensive", false.into()),
+                ]);
+                self.respond(request, Json::object(vec![("scopes", Json::Array(vec![scope]))]))?;
+            },
+            "variables" => {
+                let reference = argument("variablesReference").and_then(Json::as_f64).unwrap_or(0.0) as usize;
+                let variables = reference.checked_sub(1)
+                    .and_then(|frame| view.frames.get(frame))
+                    .map(|frame| frame.variables.iter().map(|(name, value)| Json::object(vec![
+                        ("name", Json::text(name)),
+                        ("value", Json::text(value)),
+                        ("variablesReference", 0.into()),
+                    ])).collect())
+                    .unwrap_or_default();
+                self.respond(request, Json::object(vec![("variables", Json::Array(variables))]))?;
+            },
+            "continue" => {
+                debugger.resume();
+                self.respond(request, Json::object(vec![("allThreadsContinued", true.into())]))?;
+                return Ok(Next::Resume);
+            },
+            "next" => {
+                debugger.step_over(depth);
+                self.respond(request, Json::Null)?;
+                return Ok(Next::Resume);
+            },
+            "stepIn" => {
+                debugger.step_into();
+                self.respond(request, Json::Null)?;
+                return Ok(Next::Resume);
+            },
+            "stepOut" => {
+                debugger.step_over(depth.saturating_sub(1));
+                self.respond(request, Json::Null)?;
+                return Ok(Next::Resume);
+            },
+            "disconnect" | "terminate" => {
+                self.respond(request, Json::Null)?;
+                return Ok(Next::Stop);
+            },
+            other => self.fail(request, &format!("'{}' is not supported", other))?,
+        }
+        Ok(Next::Wait)
+    }
+
+    // The next request, or None once the editor has gone
+    fn read(&self) -> Result<Option<Json>, String> {
+        json::read_message()
+    }
+
+    fn send(&self, kind: &str, mut members: Vec<(&str, Json)>) -> Result<(), String> {
+        let seq = self.seq.fetch_add(1, Ordering::Relaxed) + 1;
+        members.splice(0..0, [("seq", seq.into()), ("type", Json::text(kind))]);
+        json::write_message(&Json::object(members))
+    }
+
+    fn respond(&self, request: &Json, body: Json) -> Result<(), String> {
+        self.reply(request, true, vec![("body", body)])
+    }
+
+    fn fail(&self, request: &Json, message: &str) -> Result<(), String> {
+        self.reply(request, false, vec![("message", Json::text(message))])
+    }
+
+    fn reply(&self, request: &Json, success: bool, mut members: Vec<(&str, Json)>) -> Result<(), String> {
+        let request_seq = request.get("seq").cloned().unwrap_or(Json::Null);
+        members.splice(0..0, [
+            ("request_seq", request_seq),
+            ("success", success.into()),
+            ("command", Json::text(command(request))),
+        ]);
+        self.send("response", members)
+    }
+
+    fn event(&self, name: &str, body: Json) -> Result<(), String> {
+        self.send("event", vec![("event", Json::text(name)), ("body", body)])
+    }
+
+    fn output(&self, category: &str, text: &str) -> Result<(), String> {
+        self.event("output", Json::object(vec![("category", Json::text(category)), ("output", Json::text(text))]))
+    }
+}
+
+// Sends what the program shows to the editor's console
+struct OutputEvents(Session);
+
+impl Write for OutputEvents {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.output("stdout", &String::from_utf8_lossy(buffer)).map_err(io::Error::other)?;
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn command(request: &Json) -> &str {
+    request.get("command").and_then(Json::as_str).unwrap_or("")
+}
+
+// Editors and programs may name the same file differently
+fn canonical(path: &str) -> String {
+    std::fs::canonicalize(path).map(|path| path.display().to_string()).unwrap_or_else(|_| path.to_string())
+}
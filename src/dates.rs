@@ -0,0 +1,122 @@
+use crate::failure::Failure;
+use crate::units::{Quantity, Unit};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MONTHS: [&str; 12] = [
+    "January", "February", "March", "April", "May", "June",
+    "July", "August", "September", "October", "November", "December",
+];
+
+const SECONDS_PER_DAY: u64 = 86400;
+
+/// A day on the calendar, like June 1, 2025, held as the number of days
+/// since January 1, 1970. Adding a duration of whole days moves it along,
+/// and taking one date from another gives the days between them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Date(i64);
+
+impl Date {
+    /// The date with this month name, day and year, if there is one.
+    pub fn new(month: &str, day: f64, year: f64) -> Option<Date> {
+        let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+        if day.fract() != 0.0 || year.fract() != 0.0 || !(1.0..=31.0).contains(&day) || year.abs() > 1e6 {
+            return None;
+        }
+        let date = Date::from_civil(year as i64, month, day as i64);
+        // February 30 would quietly become March 2
+        (date.civil() == (year as i64, month, day as i64)).then_some(date)
+    }
+
+    /// Whether a word is the name of a month, as a date starts with.
+    pub fn is_month(word: &str) -> bool {
+        MONTHS.contains(&word)
+    }
+
+    /// The day it is now, by the clock, in UTC.
+    pub fn today() -> Date {
+        let seconds = SystemTime::now().duration_since(UNIX_EPOCH).map(|since| since.as_secs()).unwrap_or(0);
+        Date((seconds / SECONDS_PER_DAY) as i64)
+    }
+
+    /// The date `today` gives in a deterministic run: January 1, 2000.
+    pub fn fixed() -> Date {
+        Date::from_civil(2000, 1, 1)
+    }
+
+    /// A date written `2025-06-01`, as `iso` writes it.
+    pub fn parse(text: &str) -> Option<Date> {
+        let [year, month, day] = text.splitn(3, '-').collect::<Vec<_>>()[..] else {
+            return None;
+        };
+        let month: usize = month.parse().ok()?;
+        Date::new(MONTHS.get(month.checked_sub(1)?)?, day.parse().ok()?, year.parse().ok()?)
+    }
+
+    /// The date as `2025-06-01`.
+    pub fn iso(self) -> String {
+        let (year, month, day) = self.civil();
+        format!("{:04}-{:02}-{:02}", year, month, day)
+    }
+
+    /// The date a duration after this one (or before it, with `earlier`),
+    /// which must be a whole number of days.
+    pub fn after(self, duration: &Quantity, earlier: bool) -> Result<Date, Failure> {
+        let days = Date::days_in(duration)?;
+        if days.fract() != 0.0 {
+            return Err(Failure::new("TypeError", format!("Type mismatch: dates count whole days, so {} can't be added to or taken from {}", duration, self)));
+        }
+        let days = if earlier { -days } else { days };
+        // Within the million years either side of year 0 that `new` allows
+        let (first, last) = (Date::from_civil(-1_000_000, 1, 1), Date::from_civil(1_000_000, 12, 31));
+        self.0.checked_add(days as i64).map(Date).filter(|date| (first..=last).contains(date))
+            .ok_or_else(|| Failure::new("ArithmeticError", format!("{} {} {} is past the years a date can have, which go from -1000000 to 1000000",
+                self, if earlier { "-" } else { "+" }, duration)))
+    }
+
+    /// How long after the other date this one is, in days.
+    pub fn since(self, other: Date) -> Quantity {
+        let unit = Unit::parse("days").expect("days are a unit");
+        Quantity { amount: (self.0 - other.0) as f64, unit }
+    }
+
+    // How many days a duration is, if it measures time
+    fn days_in(duration: &Quantity) -> Result<f64, Failure> {
+        let days = Unit::parse("days").expect("days are a unit");
+        match duration.unit.compatible(&days) {
+            true => Ok(duration.convert(&days)?.amount),
+            false => Err(Failure::new("TypeError", format!("Type mismatch: only durations can be added to or taken from dates, not {}", duration))),
+        }
+    }
+
+    // Days since 1970 from a year, month and day, after Howard Hinnant's
+    // `days_from_civil`
+    fn from_civil(year: i64, month: i64, day: i64) -> Date {
+        let year = if month <= 2 { year - 1 } else { year };
+        let era = year.div_euclid(400);
+        let year_of_era = year - era * 400;
+        let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day - 1;
+        let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+        Date(era * 146097 + day_of_era - 719468)
+    }
+
+    // The year, month and day, the other way round
+    fn civil(self) -> (i64, i64, i64) {
+        let days = self.0 + 719468;
+        let era = days.div_euclid(146097);
+        let day_of_era = days - era * 146097;
+        let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+        let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+        let shifted = (5 * day_of_year + 2) / 153;
+        let day = day_of_year - (153 * shifted + 2) / 5 + 1;
+        let month = if shifted < 10 { shifted + 3 } else { shifted - 9 };
+        let year = year_of_era + era * 400 + i64::from(month <= 2);
+        (year, month, day)
+    }
+}
+
+impl std::fmt::Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (year, month, day) = self.civil();
+        write!(f, "{} {}, {}", MONTHS[month as usize - 1], day, year)
+    }
+}
@@ -0,0 +1,243 @@
+use crate::dap;
+use std::io::{self, Write};
+use std::sync::Arc;
+
+/// Breakpoints and stepping state for the debugger. The Runtime asks
+/// `pause_reason` at the start of every statement and, when it should stop,
+/// hands a snapshot of the program to `pause`, which talks to whoever is
+/// debugging until they resume the program.
+pub struct Debugger {
+    breakpoints: Vec<Breakpoint>,
+    mode: Mode,
+    started: bool,
+    frontend: Frontend,
+}
+
+struct Breakpoint {
+    file: Option<String>,
+    line: usize,
+}
+
+enum Mode {
+    Continue,
+    StepInto,
+    StepOver(usize),  // pause again at this call depth or shallower
+}
+
+// Who is doing the debugging
+enum Frontend {
+    Console,
+    Dap(dap::Session),
+}
+
+/// The state of a paused program, innermost call first.
+pub struct PauseView {
+    pub frames: Vec<FrameView>,
+    pub stack: Vec<String>,
+}
+
+pub struct FrameView {
+    pub task: String,
+    pub source: Arc<str>,
+    pub line: Option<usize>,
+    pub text: Option<String>,
+    pub variables: Vec<(String, String)>,
+}
+
+enum Command {
+    Step,
+    Next,
+    Continue,
+    Stack,
+    Locals,
+    Where,
+    Break(String),
+    Quit,
+    Help,
+}
+
+const HELP: &str = "\
+step (s)         run to the next statement, entering Tasks
+next (n)         run to the next statement in this Task
+continue (c)     run to the next breakpoint
+stack            show the value stack
+locals           show the variables in scope
+where            show the Tasks in progress
+break [file:]N   pause whenever line N is reached
+quit (q)         stop the program";
+
+impl Debugger {
+    /// A console debugger that pauses before the first statement, or only at breakpoints.
+    pub fn new(pause_at_start: bool) -> Self {
+        Debugger {
+            breakpoints: Vec::new(),
+            mode: if pause_at_start { Mode::StepInto } else { Mode::Continue },
+            started: false,
+            frontend: Frontend::Console,
+        }
+    }
+
+    /// A debugger driven by an editor over the Debug Adapter Protocol.
+    pub fn for_dap(session: dap::Session) -> Self {
+        Debugger {
+            frontend: Frontend::Dap(session),
+            ..Debugger::new(false)
+        }
+    }
+
+    /// Adds a breakpoint written as `line` or `file:line`.
+    pub fn add_breakpoint(&mut self, spec: &str) -> Result<(), String> {
+        let (file, line) = match spec.rsplit_once(':') {
+            Some((file, line)) => (Some(file.to_string()), line),
+            None => (None, spec),
+        };
+        let line = line.trim().parse::<usize>()
+            .map_err(|_| format!("Expected a line number in '{}', e.g. .break script.v:12", spec))?;
+        self.breakpoints.push(Breakpoint { file, line });
+        Ok(())
+    }
+
+    /// Replaces every breakpoint in one file.
+    pub fn set_breakpoints(&mut self, file: &str, lines: &[usize]) {
+        self.breakpoints.retain(|breakpoint| breakpoint.file.as_deref() != Some(file));
+        self.breakpoints.extend(lines.iter().map(|&line| Breakpoint { file: Some(file.to_string()), line }));
+    }
+
+    /// Why the program should stop at this statement, if it should.
+    pub fn pause_reason(&self, source: &str, line: usize, depth: usize) -> Option<&'static str> {
+        if self.breakpoints.iter().any(|breakpoint| breakpoint.matches(source, line)) {
+            return Some("breakpoint");
+        }
+        let stepped = match self.mode {
+            Mode::Continue => false,
+            Mode::StepInto => true,
+            Mode::StepOver(target) => depth <= target,
+        };
+        match (stepped, self.started) {
+            (false, _) => None,
+            (true, false) => Some("entry"),
+            (true, true) => Some("step"),
+        }
+    }
+
+    /// Waits for the person debugging to resume the program. Fails if they
+    /// stop it instead.
+    pub fn pause(&mut self, reason: &str, view: &PauseView) -> Result<(), String> {
+        self.started = true;
+        match &self.frontend {
+            Frontend::Console => self.console_pause(view),
+            Frontend::Dap(session) => session.clone().paused(self, reason, view),
+        }
+    }
+
+    pub fn step_into(&mut self) {
+        self.mode = Mode::StepInto;
+    }
+
+    pub fn step_over(&mut self, depth: usize) {
+        self.mode = Mode::StepOver(depth);
+    }
+
+    pub fn resume(&mut self) {
+        self.mode = Mode::Continue;
+    }
+
+    fn console_pause(&mut self, view: &PauseView) -> Result<(), String> {
+        let depth = view.frames.len();
+        if let Some(frame) = view.frames.first() {
+            println!("Paused at {}:{} in {}", frame.source, frame.line.unwrap_or(0), frame.task);
+            if let (Some(line), Some(text)) = (frame.line, &frame.text) {
+                println!("{:>5} | {}", line, text);
+            }
+        }
+        loop {
+            print!("(debug) ");
+            io::stdout().flush().map_err(|e| format!("Failed to write prompt: {}", e))?;
+            let mut input = String::new();
+            if io::stdin().read_line(&mut input).map_err(|e| format!("Failed to read line: {}", e))? == 0 {
+                // Nobody is left to give commands, so let the program finish
+                println!();
+                self.breakpoints.clear();
+                self.resume();
+                return Ok(());
+            }
+            let command = match Self::parse_command(input.trim()) {
+                Ok(command) => command,
+                Err(error) => {
+                    println!("{}", error);
+                    continue;
+                },
+            };
+            match command {
+                Command::Step => {
+                    self.step_into();
+                    return Ok(());
+                },
+                Command::Next => {
+                    self.step_over(depth);
+                    return Ok(());
+                },
+                Command::Continue => {
+                    self.resume();
+                    return Ok(());
+                },
+                Command::Break(spec) => match self.add_breakpoint(&spec) {
+                    Ok(()) => println!("Breakpoint at {}", spec),
+                    Err(error) => println!("{}", error),
+                },
+                Command::Stack => {
+                    if view.stack.is_empty() {
+                        println!("  (empty)");
+                    }
+                    for (position, value) in view.stack.iter().enumerate().rev() {
+                        println!("  [{}] {}", position, value);
+                    }
+                },
+                Command::Locals => {
+                    let variables = view.frames.first().map(|frame| frame.variables.as_slice()).unwrap_or_default();
+                    if variables.is_empty() {
+                        println!("  (no variables)");
+                    }
+                    for (name, value) in variables {
+                        println!("  {} = {}", name, value);
+                    }
+                },
+                Command::Where => {
+                    for frame in &view.frames {
+                        match frame.line {
+                            Some(line) => println!("  in {} at {}:{}", frame.task, frame.source, line),
+                            None => println!("  in {}", frame.task),
+                        }
+                    }
+                },
+                Command::Quit => return Err("Stopped by the debugger".to_string()),
+                Command::Help => println!("{}", HELP),
+            }
+        }
+    }
+
+    fn parse_command(input: &str) -> Result<Command, String> {
+        let (word, rest) = input.split_once(' ').unwrap_or((input, ""));
+        match word {
+            "step" | "s" => Ok(Command::Step),
+            "next" | "n" => Ok(Command::Next),
+            "continue" | "c" => Ok(Command::Continue),
+            "stack" => Ok(Command::Stack),
+            "locals" => Ok(Command::Locals),
+            "where" => Ok(Command::Where),
+            "break" | "b" => Ok(Command::Break(rest.trim().to_string())),
+            "quit" | "q" => Ok(Command::Quit),
+            "help" | "" => Ok(Command::Help),
+            other => Err(format!("Unknown debugger command '{}'; try 'help'", other)),
+        }
+    }
+}
+
+impl Breakpoint {
+    fn matches(&self, source: &str, line: usize) -> bool {
+        line == self.line && match &self.file {
+            None => true,
+            Some(file) => source == file || source.ends_with(&format!("/{}", file)),
+        }
+    }
+}
@@ -0,0 +1,467 @@
+use crate::json::Json;
+use crate::lowering::{self, Expr, Function, Handler, Kind, Object, Operator, Part, Program, Stmt};
+
+const INDENT: &str = "    ";
+
+// Built into every program; see runtime.js
+const SHIM: &str = include_str!("runtime.js");
+
+// Names a program may use that mean something else in JavaScript
+const RESERVED: [&str; 51] = ["arguments", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+    "default", "delete", "do", "else", "enum", "eval", "export", "extends", "false", "finally", "for", "function",
+    "if", "implements", "import", "in", "instanceof", "interface", "let", "new", "null", "package", "private",
+    "protected", "public", "return", "static", "super", "switch", "this", "throw", "true", "try", "typeof", "var",
+    "void", "while", "with", "yield", "undefined", "NaN", "V"];
+
+/// Prints a program as JavaScript that runs in Node or a browser. Types are
+/// checked here, before the program is written, rather than as it runs.
+pub fn emit_file(path: &str) -> Result<(), String> {
+    let ast = lowering::check_file(path)?;
+    let name = std::path::Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    print!("{}", emit(&lowering::lower(&ast), &name));
+    Ok(())
+}
+
+/// JavaScript for a program: the value shim, then its Objects and Tasks,
+/// then its top-level statements.
+pub fn emit(program: &Program, file: &str) -> String {
+    let mut emitter = JsEmitter { output: String::new() };
+    emitter.line(0, &format!("// Generated by `nair emit-js` from {}", file));
+    emitter.line(0, "\"use strict\";");
+    emitter.line(0, "");
+    emitter.output.push_str(SHIM);
+    if !program.globals.is_empty() {
+        emitter.line(0, "");
+        emitter.line(0, &format!("let {};", names(&program.globals)));
+    }
+    for object in &program.objects {
+        emitter.line(0, "");
+        emitter.object(object);
+    }
+    for task in &program.tasks {
+        emitter.line(0, "");
+        emitter.function(0, &format!("function {}", ident(&task.name)), task);
+    }
+    emitter.line(0, "");
+    emitter.line(0, "V.main(() => {");
+    emitter.statements(&program.statements, 1);
+    emitter.line(0, "});");
+    emitter.output
+}
+
+struct JsEmitter {
+    output: String,
+}
+
+impl JsEmitter {
+    fn line(&mut self, depth: usize, text: &str) {
+        if !text.is_empty() {
+            self.output.push_str(&INDENT.repeat(depth));
+            self.output.push_str(text);
+        }
+        self.output.push('\n');
+    }
+
+    // `header(params) { ... }` with the variables the body assigns declared first
+    fn function(&mut self, depth: usize, header: &str, function: &Function) {
+        self.line(depth, &format!("{}({}) {{", header, names(&function.params)));
+        if !function.locals.is_empty() {
+            self.line(depth + 1, &format!("let {};", names(&function.locals)));
+        }
+        self.statements(&function.body, depth + 1);
+        self.line(depth, "}");
+    }
+
+    fn object(&mut self, object: &Object) {
+        let name = ident(&object.name);
+        let base = object.base.as_ref().map(kind).unwrap_or_else(|| "V.Object".to_string());
+        self.line(0, &format!("class {} extends {} {{", name, base));
+        let mut first = true;
+        let mut member = |emitter: &mut Self| {
+            if !std::mem::take(&mut first) {
+                emitter.line(0, "");
+            }
+        };
+
+        if !object.fields.is_empty() {
+            member(self);
+            self.fields("$fields", &object.fields);
+        }
+        if let Some(build) = &object.build {
+            member(self);
+            self.function(1, "build", build);
+        }
+        for getter in &object.getters {
+            member(self);
+            self.function(1, &format!("get {}", getter.name), getter);
+        }
+        for setter in &object.setters {
+            member(self);
+            self.function(1, &format!("set {}", setter.name), setter);
+        }
+        for method in &object.methods {
+            member(self);
+            self.function(1, &method.name, method);
+        }
+        if !object.shared_fields.is_empty() {
+            member(self);
+            self.fields("static $shared", &object.shared_fields);
+        }
+        for method in &object.shared_methods {
+            member(self);
+            self.function(1, &format!("static {}", method.name), method);
+        }
+        self.line(0, "}");
+
+        if !object.behaviors.is_empty() {
+            let behaviors: Vec<String> = object.behaviors.iter().map(kind).collect();
+            self.line(0, &format!("V.behave({}, {});", name, behaviors.join(", ")));
+        }
+        if name != object.name {
+            self.line(0, &format!("{}.$name = {};", name, Json::text(&object.name)));
+        }
+    }
+
+    fn fields(&mut self, header: &str, fields: &[(String, Expr)]) {
+        self.line(1, &format!("{}() {{", header));
+        for (name, value) in fields {
+            self.line(2, &format!("this.{} = {};", name, expression(value)));
+        }
+        self.line(1, "}");
+    }
+
+    fn statements(&mut self, statements: &[Stmt], depth: usize) {
+        for statement in statements {
+            self.statement(statement, depth);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt, depth: usize) {
+        match statement {
+            Stmt::Assign(name, value) => self.line(depth, &format!("{} = {};", ident(name), expression(value))),
+            Stmt::Set(object, name, value) => self.line(depth, &format!("{} = {};", get(object, name), expression(value))),
+            Stmt::SetItem(object, key, value) => {
+                self.line(depth, &format!("V.setItem({}, {}, {});", expression(object), expression(key), expression(value)));
+            },
+            Stmt::Show(value) => self.line(depth, &format!("V.show({});", expression(value))),
+            Stmt::Return(value) => self.line(depth, &format!("return {};", expression(value))),
+            Stmt::Eval(value) => self.line(depth, &format!("{};", expression(value))),
+            Stmt::Raise(value) => self.line(depth, &format!("throw V.raise({});", expression(value))),
+            Stmt::RaiseAs(error, message) => self.line(depth, &format!("throw V.error({}, {});", kind(error), expression(message))),
+            Stmt::Expect(actual, line, source) => {
+                self.line(depth, &format!("V.expect({}, {}, {});", expression(actual), line, Json::text(source)));
+            },
+            Stmt::ExpectEqual(actual, expected, line, source) => {
+                let (actual, expected) = (expression(actual), expression(expected));
+                self.line(depth, &format!("V.expectEqual({}, {}, {}, {});", actual, expected, line, Json::text(source)));
+            },
+            Stmt::If(branches, otherwise) => {
+                for (index, (condition, body)) in branches.iter().enumerate() {
+                    let header = if index == 0 { "if" } else { "} else if" };
+                    self.line(depth, &format!("{} ({}) {{", header, expression(condition)));
+                    self.statements(body, depth + 1);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.line(depth, "} else {");
+                    self.statements(otherwise, depth + 1);
+                }
+                self.line(depth, "}");
+            },
+            Stmt::While(condition, body) => {
+                self.line(depth, &format!("while ({}) {{", expression(condition)));
+                self.statements(body, depth + 1);
+                self.line(depth, "}");
+            },
+            Stmt::Forever(body) => {
+                self.line(depth, "while (true) {");
+                self.statements(body, depth + 1);
+                self.line(depth, "}");
+            },
+            Stmt::Break(None) => self.line(depth, "break;"),
+            Stmt::Break(Some(label)) => self.line(depth, &format!("break ${};", label)),
+            Stmt::Continue(None) => self.line(depth, "continue;"),
+            Stmt::Continue(Some(label)) => self.line(depth, &format!("continue ${};", label)),
+            Stmt::Named(label, statement) => {
+                self.line(depth, &format!("${}:", label));
+                self.statement(statement, depth);
+            },
+            Stmt::Repeat(count, counter, body) => {
+                self.line(depth, &format!("for (let $next = 1, $count = {}; $next <= $count; $next++) {{", expression(count)));
+                if let Some(counter) = counter {
+                    self.line(depth + 1, &format!("{} = $next;", ident(counter)));
+                }
+                self.statements(body, depth + 1);
+                self.line(depth, "}");
+            },
+            // `continue` still ends a run, so the time is kept in `finally`
+            Stmt::Measure(runs, result, body) => {
+                self.line(depth, "{");
+                self.line(depth + 1, "const $times = [];");
+                self.line(depth + 1, &format!("for (let $next = 1, $count = {}; $next <= $count; $next++) {{", expression(runs)));
+                self.line(depth + 2, "const $started = V.clock();");
+                self.line(depth + 2, "try {");
+                self.statements(body, depth + 3);
+                self.line(depth + 2, "} finally {");
+                self.line(depth + 3, "$times.push(V.clock() - $started);");
+                self.line(depth + 2, "}");
+                self.line(depth + 1, "}");
+                match result {
+                    Some(result) => self.line(depth + 1, &format!("{} = V.timing($times);", ident(result))),
+                    None => self.line(depth + 1, "V.show(V.timingReport($times));"),
+                }
+                self.line(depth, "}");
+            },
+            Stmt::Try { body, handlers, always } => {
+                self.line(depth, "try {");
+                self.statements(body, depth + 1);
+                if !handlers.is_empty() {
+                    self.line(depth, "} catch ($thrown) {");
+                    self.line(depth + 1, "const $error = V.caught($thrown);");
+                    self.handlers(handlers, depth + 1);
+                }
+                if let Some(always) = always {
+                    self.line(depth, "} finally {");
+                    self.statements(always, depth + 1);
+                }
+                self.line(depth, "}");
+            },
+            Stmt::InitShared(name) => self.line(depth, &format!("{}.$shared();", ident(name))),
+        }
+    }
+
+    // Fail clauses in order; the first whose kind matches handles the error
+    fn handlers(&mut self, handlers: &[Handler], depth: usize) {
+        let mut opened = false;
+        for Handler { kind: error, name, body } in handlers {
+            let inner = match error {
+                Some(error) => {
+                    let test = format!("V.isA($error, {})", kind(error));
+                    self.line(depth, &if opened { format!("}} else if ({}) {{", test) } else { format!("if ({}) {{", test) });
+                    opened = true;
+                    depth + 1
+                },
+                None if opened => {
+                    self.line(depth, "} else {");
+                    depth + 1
+                },
+                None => depth,
+            };
+            if let Some(name) = name {
+                self.line(inner, &format!("{} = $error;", ident(name)));
+            }
+            self.statements(body, inner);
+            if error.is_none() {
+                if opened {
+                    self.line(depth, "}");
+                }
+                return;
+            }
+        }
+        // No clause matched: pass the error on
+        self.line(depth, "} else {");
+        self.line(depth + 1, "throw $error;");
+        self.line(depth, "}");
+    }
+}
+
+fn expression(expr: &Expr) -> String {
+    let list = |items: &[Expr]| items.iter().map(expression).collect::<Vec<_>>().join(", ");
+    let not = |negated: &bool| if *negated { "!" } else { "" };
+    match expr {
+        Expr::Number(n) => n.to_string(),
+        Expr::Text(text) => Json::text(text).to_string(),
+        Expr::Truth(b) => b.to_string(),
+        Expr::Nothing => "null".to_string(),
+        Expr::Variable(name) => ident(name),
+        Expr::Me => "this".to_string(),
+        Expr::Kind(error) => kind(error),
+        Expr::Binary(left, operator @ (Operator::Divide | Operator::Modulo), right) if !nonzero(right) || !lowering::native(left, *operator, right) => {
+            let function = if *operator == Operator::Divide { "V.divide" } else { "V.remainder" };
+            format!("{}({}, {})", function, expression(left), expression(right))
+        },
+        // Operands that may turn out not to be numbers, or Text, are checked
+        Expr::Binary(left, operator, right) if !lowering::native(left, *operator, right) => {
+            let (function, symbol) = checked(*operator);
+            format!("{}({}, {}, {})", function, Json::text(symbol), expression(left), expression(right))
+        },
+        Expr::Binary(left, operator, right) => {
+            let precedence = precedence(expr);
+            let text = match operator {
+                Operator::Or => "||",
+                Operator::And => "&&",
+                Operator::Equal => "===",
+                Operator::NotEqual => "!==",
+                Operator::Greater => ">",
+                Operator::GreaterEqual => ">=",
+                Operator::Less => "<",
+                Operator::LessEqual => "<=",
+                Operator::Add => "+",
+                Operator::Subtract => "-",
+                Operator::Multiply => "*",
+                Operator::Divide => "/",
+                Operator::Modulo => "%",
+                Operator::Power => "**",
+            };
+            // `**` groups to the right, and can't follow a bare `-x`
+            let (left_needs, right_needs) = match operator {
+                Operator::Power => (precedence + 2, precedence),
+                _ => (precedence, precedence + 1),
+            };
+            format!("{} {} {}", operand(left, left_needs), text, operand(right, right_needs))
+        },
+        Expr::Negate(value) if !value.numeric() => format!("V.arithmetic(\"-\", 0, {})", expression(value)),
+        Expr::Negate(value) => format!("-{}", operand(value, 16)),
+        Expr::Equal(left, right, negated) => format!("{}V.equal({}, {})", not(negated), expression(left), expression(right)),
+        Expr::SameAs(left, right, negated) => format!("{}V.same({}, {})", not(negated), expression(left), expression(right)),
+        Expr::IsA(value, error, negated) => format!("{}V.isA({}, {})", not(negated), expression(value), kind(error)),
+        Expr::IsType(value, name, negated) => format!("{}V.is({}, {})", not(negated), expression(value), Json::text(name)),
+        Expr::Cast(value, name) => format!("V.as({}, {})", expression(value), Json::text(name)),
+        Expr::TypeOf(value) => format!("V.typeOf({})", expression(value)),
+        Expr::Choose(condition, value, otherwise) => {
+            format!("{} ? {} : {}", operand(condition, 4), operand(value, 3), operand(otherwise, 3))
+        },
+        Expr::Show(args) => format!("V.show({})", list(args)),
+        Expr::Copy(value) => format!("V.copy({})", expression(value)),
+        Expr::Freeze(value) => format!("V.freeze({})", expression(value)),
+        Expr::Round(name, value, places) => match places {
+            Some(places) => format!("V.round(\"{}\", {}, {})", name, expression(value), expression(places)),
+            None => format!("V.round(\"{}\", {})", name, expression(value)),
+        },
+        Expr::ReadNumber(text, whole) => format!("V.readNumber({}, {})", expression(text), whole),
+        Expr::Reflect(name, args) => {
+            let name = match name.as_str() {
+                "properties_of" => "propertiesOf",
+                "has_task" => "hasTask",
+                "get_property" => "getProperty",
+                _ => "setProperty",
+            };
+            format!("V.{}({})", name, list(args))
+        },
+        Expr::Written(args) => format!("V.formatNumber({})", list(args)),
+        Expr::CompareText(args) => format!("V.compareText({})", list(args)),
+        Expr::Normalize(text) => format!("V.normalize({})", expression(text)),
+        Expr::Wrapping(operator, left, right) => format!("V.wrapping(\"{}\", {}, {})", wrapping(*operator), expression(left), expression(right)),
+        Expr::Call(name, args) => format!("{}({})", ident(name), list(args)),
+        Expr::MethodCall(object, name, args) => format!("{}({})", get(object, name), list(args)),
+        Expr::Get(object, name) => get(object, name),
+        Expr::SafeGet(object, name) => format!("({}?.{} ?? null)", operand(object, 18), name),
+        Expr::New(error, args) => format!("new {}({})", kind(error), list(args)),
+        // Fails the way the interpreter would, when it gets there
+        Expr::Fail(error, message) => format!("V.fail({}, {})", kind(error), Json::text(message)),
+        Expr::Template(parts) => {
+            let text: String = parts.iter().map(|part| match part {
+                Part::Text(text) => text.replace('\\', "\\\\").replace('`', "\\`").replace("${", "\\${"),
+                Part::Value(value) => format!("${{V.text({})}}", expression(value)),
+            }).collect();
+            format!("`{}`", text)
+        },
+        Expr::List(items) => format!("[{}]", list(items)),
+        Expr::Item(object, key) => format!("V.item({}, {})", expression(object), expression(key)),
+        Expr::FromEnd(number) => format!("V.fromEnd({})", number),
+        Expr::Slice(object, first, last) => {
+            let bound = |number: &Option<Box<Expr>>| number.as_deref().map(expression).unwrap_or_else(|| "null".to_string());
+            format!("V.slice({}, {}, {})", expression(object), bound(first), bound(last))
+        },
+        Expr::Each { value, name, list, condition } => {
+            let filter = condition.as_ref()
+                .map(|condition| format!(".filter(({}) => {})", ident(name), expression(condition)))
+                .unwrap_or_default();
+            format!("V.items({}){}.map(({}) => {})", expression(list), filter, ident(name), expression(value))
+        },
+        Expr::Joined(parts) => {
+            let parts: Vec<String> = parts.iter().map(|(all, part)| match all {
+                true => format!("...V.items({})", expression(part)),
+                false => expression(part),
+            }).collect();
+            format!("[{}]", parts.join(", "))
+        },
+        Expr::Mapping(entries) => {
+            let entries: Vec<String> = entries.iter()
+                .map(|(key, value)| format!("[{}, {}]", Json::text(key), expression(value)))
+                .collect();
+            format!("new V.Mapping([{}])", entries.join(", "))
+        },
+        Expr::Assign(name, value) => format!("({} = {})", ident(name), expression(value)),
+        Expr::Set(object, name, value) => format!("({} = {})", get(object, name), expression(value)),
+    }
+}
+
+// An operand, parenthesized when it binds more loosely than its position needs
+fn operand(expr: &Expr, needed: u8) -> String {
+    match precedence(expr) < needed {
+        true => format!("({})", expression(expr)),
+        false => expression(expr),
+    }
+}
+
+// How tightly an expression binds in JavaScript, from `||` up to names and calls
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Number(n) if *n < 0.0 => 15,
+        Expr::Choose(..) => 3,
+        Expr::Negate(value) if !value.numeric() => 18,
+        Expr::Negate(_) => 15,
+        Expr::Equal(_, _, true) | Expr::SameAs(_, _, true) | Expr::IsA(_, _, true) | Expr::IsType(_, _, true) => 15,
+        Expr::Binary(left, operator @ (Operator::Divide | Operator::Modulo), right) if !nonzero(right) || !lowering::native(left, *operator, right) => 18,
+        Expr::Binary(left, operator, right) if !lowering::native(left, *operator, right) => 18,
+        Expr::Binary(_, operator, _) => match operator {
+            Operator::Or => 4,
+            Operator::And => 5,
+            Operator::Equal | Operator::NotEqual => 9,
+            Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual => 10,
+            Operator::Add | Operator::Subtract => 12,
+            Operator::Power => 14,
+            _ => 13,
+        },
+        _ => 18,
+    }
+}
+
+fn wrapping(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Add => "+",
+        Operator::Subtract => "-",
+        _ => "*",
+    }
+}
+
+// The runtime's checked helper for an operator, and how it names it
+fn checked(operator: Operator) -> (&'static str, &'static str) {
+    match operator {
+        Operator::Greater => ("V.compare", ">"),
+        Operator::GreaterEqual => ("V.compare", ">="),
+        Operator::Less => ("V.compare", "<"),
+        Operator::LessEqual => ("V.compare", "<="),
+        Operator::Subtract => ("V.arithmetic", "-"),
+        Operator::Multiply => ("V.arithmetic", "*"),
+        Operator::Power => ("V.arithmetic", "^"),
+        _ => ("V.arithmetic", "+"),
+    }
+}
+
+fn nonzero(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(n) if *n != 0.0)
+}
+
+fn get(object: &Expr, name: &str) -> String {
+    format!("{}.{}", operand(object, 18), name)
+}
+
+fn kind(kind: &Kind) -> String {
+    match kind {
+        Kind::BuiltIn(name) => format!("V.{}", name),
+        Kind::Declared(name) => ident(name),
+    }
+}
+
+fn names(names: &[String]) -> String {
+    names.iter().map(|name| ident(name)).collect::<Vec<_>>().join(", ")
+}
+
+// A name that can't be mistaken for one of JavaScript's own
+fn ident(name: &str) -> String {
+    match RESERVED.contains(&name) {
+        true => format!("{}$", name),
+        false => name.to_string(),
+    }
+}
@@ -0,0 +1,602 @@
+use crate::json::Json;
+use crate::lowering::{self, Expr, Function, Handler, Kind, Object, Operator, Part, Program, Stmt};
+
+const INDENT: &str = "    ";
+
+// Built into every program; see runtime.py
+const SHIM: &str = include_str!("runtime.py");
+
+// Names a program may use that mean something else in Python
+const RESERVED: [&str; 41] = ["False", "None", "True", "and", "as", "assert", "async", "await", "break", "class",
+    "continue", "def", "del", "elif", "else", "except", "finally", "for", "from", "global", "if", "import", "in",
+    "is", "lambda", "nonlocal", "not", "or", "pass", "raise", "return", "try", "while", "with", "yield", "V", "math",
+    "main", "self", "cls", "property"];
+
+/// Prints a program as Python 3.10 or later. Types are checked here, before
+/// the program is written, rather than as it runs.
+pub fn emit_file(path: &str) -> Result<(), String> {
+    let ast = lowering::check_file(path)?;
+    let name = std::path::Path::new(path).file_name().map(|name| name.to_string_lossy().into_owned()).unwrap_or_default();
+    print!("{}", emit(&lowering::lower(&ast), &name));
+    Ok(())
+}
+
+/// Python for a program: the value shim, then its Objects and Tasks, then a
+/// `main` holding its top-level statements.
+pub fn emit(program: &Program, file: &str) -> String {
+    let mut emitter = PyEmitter { output: String::new(), me: "self", loops: Vec::new(), label: None };
+    emitter.line(0, &format!("# Generated by `nair emit-py` from {}", file));
+    emitter.output.push_str(SHIM);
+    for object in &program.objects {
+        emitter.line(0, "");
+        emitter.line(0, "");
+        emitter.object(object);
+    }
+    for task in &program.tasks {
+        emitter.line(0, "");
+        emitter.line(0, "");
+        emitter.function(0, &ident(&task.name), &[], task);
+    }
+    emitter.line(0, "");
+    emitter.line(0, "");
+    emitter.line(0, "def main():");
+    if !program.globals.is_empty() {
+        emitter.line(1, &format!("global {}", names(&program.globals)));
+    }
+    emitter.block(&program.statements, 1);
+    emitter.line(0, "");
+    emitter.line(0, "");
+    emitter.line(0, "V.run(main)");
+    emitter.output
+}
+
+struct PyEmitter {
+    output: String,
+    // What `me` is called in the member being written
+    me: &'static str,
+    // The names of the loops around the statement being written, innermost
+    // last, and the name of the loop about to be written
+    loops: Vec<Option<String>>,
+    label: Option<String>,
+}
+
+impl PyEmitter {
+    fn line(&mut self, depth: usize, text: &str) {
+        if !text.is_empty() {
+            self.output.push_str(&INDENT.repeat(depth));
+            self.output.push_str(text);
+        }
+        self.output.push('\n');
+    }
+
+    // `def name(receiver, params):`, with the body
+    fn function(&mut self, depth: usize, name: &str, receiver: &[&str], function: &Function) {
+        let params: Vec<String> = receiver.iter().map(|name| name.to_string())
+            .chain(function.params.iter().map(|param| ident(param)))
+            .collect();
+        self.line(depth, &format!("def {}({}):", name, params.join(", ")));
+        self.block(&function.body, depth + 1);
+    }
+
+    fn object(&mut self, object: &Object) {
+        let name = ident(&object.name);
+        let base = object.base.as_ref().map(kind).unwrap_or_else(|| "V.Object".to_string());
+        self.line(0, &format!("class {}({}):", name, base));
+        let mut first = true;
+        let mut member = |emitter: &mut Self| {
+            if !std::mem::take(&mut first) {
+                emitter.line(0, "");
+            }
+        };
+
+        self.me = "self";
+        if !object.fields.is_empty() {
+            member(self);
+            self.fields("def _fields(self):", &object.fields);
+        }
+        if let Some(build) = &object.build {
+            member(self);
+            self.function(1, "build", &["self"], build);
+        }
+        for getter in &object.getters {
+            member(self);
+            self.line(1, "@property");
+            self.function(1, &ident(&getter.name), &["self"], getter);
+        }
+        for setter in &object.setters {
+            member(self);
+            let name = ident(&setter.name);
+            match object.getters.iter().any(|getter| getter.name == setter.name) {
+                true => {
+                    self.line(1, &format!("@{}.setter", name));
+                    self.function(1, &name, &["self"], setter);
+                },
+                // A property that can be set but not read
+                false => {
+                    self.function(1, &format!("_set_{}", setter.name), &["self"], setter);
+                    self.line(0, "");
+                    self.line(1, &format!("{} = property(None, _set_{})", name, setter.name));
+                },
+            }
+        }
+        for method in &object.methods {
+            member(self);
+            self.function(1, &ident(&method.name), &["self"], method);
+        }
+
+        self.me = "cls";
+        if !object.shared_fields.is_empty() {
+            member(self);
+            self.line(1, "@classmethod");
+            self.fields("def _shared(cls):", &object.shared_fields);
+        }
+        for method in &object.shared_methods {
+            member(self);
+            self.line(1, "@classmethod");
+            self.function(1, &ident(&method.name), &["cls"], method);
+        }
+        self.me = "self";
+        if first {
+            self.line(1, "pass");
+        }
+
+        if !object.behaviors.is_empty() {
+            let behaviors: Vec<String> = object.behaviors.iter().map(kind).collect();
+            self.line(0, "");
+            self.line(0, &format!("V.behave({}, {})", name, behaviors.join(", ")));
+        }
+        if name != object.name {
+            self.line(0, &format!("{}._name = {}", name, Json::text(&object.name)));
+        }
+    }
+
+    fn fields(&mut self, header: &str, fields: &[(String, Expr)]) {
+        self.line(1, header);
+        for (name, value) in fields {
+            let value = self.expression(value);
+            self.line(2, &format!("{}.{} = {}", self.me, ident(name), value));
+        }
+    }
+
+    // An indented block, which Python doesn't allow to be empty
+    // The body of a loop, which catches the V.Jump of a `continue` naming
+    // it from a loop inside it
+    fn loop_block(&mut self, statements: &[Stmt], depth: usize) {
+        let label = self.label.take();
+        let again = label.as_ref().is_some_and(|label| jumps(statements, label, true, false));
+        self.loops.push(label.clone());
+        match (label, again) {
+            (Some(label), true) => {
+                self.line(depth, "try:");
+                self.block(statements, depth + 1);
+                self.line(depth, "except V.Jump as _jump:");
+                self.line(depth + 1, &format!("if _jump.label != {} or not _jump.again:", Json::text(&label)));
+                self.line(depth + 2, "raise");
+            },
+            _ => self.block(statements, depth),
+        }
+        self.loops.pop();
+    }
+
+    // `break` or `continue`, for the innermost loop or the one named
+    fn jump(&mut self, depth: usize, word: &str, label: &Option<String>, again: bool) {
+        match label {
+            Some(label) if self.loops.last() != Some(&Some(label.clone())) => {
+                let again = if again { "True" } else { "False" };
+                self.line(depth, &format!("raise V.Jump({}, {})", Json::text(label), again));
+            },
+            _ => self.line(depth, word),
+        }
+    }
+
+    fn block(&mut self, statements: &[Stmt], depth: usize) {
+        if statements.is_empty() {
+            self.line(depth, "pass");
+        }
+        for statement in statements {
+            self.statement(statement, depth);
+        }
+    }
+
+    fn statement(&mut self, statement: &Stmt, depth: usize) {
+        match statement {
+            Stmt::Assign(name, value) => {
+                let value = self.expression(value);
+                self.line(depth, &format!("{} = {}", ident(name), value));
+            },
+            Stmt::SetItem(object, key, value) => {
+                let (object, key, value) = (self.expression(object), self.expression(key), self.expression(value));
+                self.line(depth, &format!("V.set_item({}, {}, {})", object, key, value));
+            },
+            Stmt::Set(object, name, value) => {
+                let (target, value) = (self.get(object, name), self.expression(value));
+                self.line(depth, &format!("{} = {}", target, value));
+            },
+            Stmt::Show(value) => {
+                let value = self.expression(value);
+                self.line(depth, &format!("V.show({})", value));
+            },
+            Stmt::Return(value) => {
+                let value = self.expression(value);
+                self.line(depth, &format!("return {}", value));
+            },
+            Stmt::Eval(value) => {
+                let value = self.expression(value);
+                self.line(depth, &value);
+            },
+            Stmt::Raise(value) => {
+                let value = self.expression(value);
+                self.line(depth, &format!("raise V.to_error({})", value));
+            },
+            Stmt::RaiseAs(error, message) => {
+                let message = self.expression(message);
+                self.line(depth, &format!("raise V.error({}, {})", kind(error), message));
+            },
+            Stmt::Expect(actual, line, source) => {
+                let actual = self.expression(actual);
+                self.line(depth, &format!("V.expect({}, {}, {})", actual, line, Json::text(source)));
+            },
+            Stmt::ExpectEqual(actual, expected, line, source) => {
+                let (actual, expected) = (self.expression(actual), self.expression(expected));
+                self.line(depth, &format!("V.expect_equal({}, {}, {}, {})", actual, expected, line, Json::text(source)));
+            },
+            Stmt::If(branches, otherwise) => {
+                for (index, (condition, body)) in branches.iter().enumerate() {
+                    let condition = self.expression(condition);
+                    self.line(depth, &format!("{} {}:", if index == 0 { "if" } else { "elif" }, condition));
+                    self.block(body, depth + 1);
+                }
+                if let Some(otherwise) = otherwise {
+                    self.line(depth, "else:");
+                    self.block(otherwise, depth + 1);
+                }
+            },
+            Stmt::While(condition, body) => {
+                let condition = self.expression(condition);
+                self.line(depth, &format!("while {}:", condition));
+                self.loop_block(body, depth + 1);
+            },
+            Stmt::Forever(body) => {
+                self.line(depth, "while True:");
+                self.loop_block(body, depth + 1);
+            },
+            Stmt::Break(label) => self.jump(depth, "break", label, false),
+            Stmt::Continue(label) => self.jump(depth, "continue", label, true),
+            Stmt::Repeat(count, counter, body) => {
+                let counter = counter.as_deref().map(ident).unwrap_or_else(|| "_".to_string());
+                let count = self.expression(count);
+                self.line(depth, &format!("for {} in V.times({}):", counter, count));
+                self.loop_block(body, depth + 1);
+            },
+            // `continue` still ends a run, so the time is kept in `finally`;
+            // the names carry the depth so measurements can nest
+            Stmt::Measure(runs, result, body) => {
+                let runs = self.expression(runs);
+                self.line(depth, &format!("_times{} = []", depth));
+                self.line(depth, &format!("for _ in V.times({}):", runs));
+                self.line(depth + 1, &format!("_started{} = V.clock()", depth));
+                self.line(depth + 1, "try:");
+                self.loop_block(body, depth + 2);
+                self.line(depth + 1, "finally:");
+                self.line(depth + 2, &format!("_times{0}.append(V.clock() - _started{0})", depth));
+                match result {
+                    Some(result) => self.line(depth, &format!("{} = V.timing(_times{})", ident(result), depth)),
+                    None => self.line(depth, &format!("V.show(V.timing_report(_times{}))", depth)),
+                }
+            },
+            // Python can only leave the innermost loop, so leaving a named
+            // one from a loop inside it raises V.Jump
+            Stmt::Named(label, statement) => {
+                if !jumps(std::slice::from_ref(statement), label, false, false) {
+                    self.label = Some(label.clone());
+                    self.statement(statement, depth);
+                    return;
+                }
+                self.line(depth, "try:");
+                self.label = Some(label.clone());
+                self.statement(statement, depth + 1);
+                self.line(depth, "except V.Jump as _jump:");
+                self.line(depth + 1, &format!("if _jump.label != {}:", Json::text(label)));
+                self.line(depth + 2, "raise");
+            },
+            Stmt::Try { body, handlers, always } => {
+                self.line(depth, "try:");
+                self.block(body, depth + 1);
+                if !handlers.is_empty() {
+                    self.line(depth, "except Exception as _thrown:");
+                    self.line(depth + 1, "_error = V.caught(_thrown)");
+                    self.handlers(handlers, depth + 1);
+                }
+                if let Some(always) = always {
+                    self.line(depth, "finally:");
+                    self.block(always, depth + 1);
+                }
+            },
+            Stmt::InitShared(name) => self.line(depth, &format!("{}._shared()", ident(name))),
+        }
+    }
+
+    // Fail clauses in order; the first whose kind matches handles the error
+    fn handlers(&mut self, handlers: &[Handler], depth: usize) {
+        let mut opened = false;
+        for Handler { kind: error, name, body } in handlers {
+            let inner = match error {
+                Some(error) => {
+                    let test = format!("V.is_a(_error, {})", kind(error));
+                    self.line(depth, &format!("{} {}:", if opened { "elif" } else { "if" }, test));
+                    opened = true;
+                    depth + 1
+                },
+                None if opened => {
+                    self.line(depth, "else:");
+                    depth + 1
+                },
+                None => depth,
+            };
+            if let Some(name) = name {
+                self.line(inner, &format!("{} = _error", ident(name)));
+            }
+            match name.is_none() && inner != depth {
+                true => self.block(body, inner),
+                false => body.iter().for_each(|statement| self.statement(statement, inner)),
+            }
+            if error.is_none() {
+                return;
+            }
+        }
+        // No clause matched: pass the error on
+        self.line(depth, "else:");
+        self.line(depth + 1, "raise _error");
+    }
+
+    fn expression(&self, expr: &Expr) -> String {
+        let list = |items: &[Expr]| items.iter().map(|item| self.expression(item)).collect::<Vec<_>>().join(", ");
+        let not = |negated: &bool| if *negated { "not " } else { "" };
+        match expr {
+            Expr::Number(n) => n.to_string(),
+            Expr::Text(text) => Json::text(text).to_string(),
+            Expr::Truth(b) => if *b { "True" } else { "False" }.to_string(),
+            Expr::Nothing => "None".to_string(),
+            Expr::Variable(name) => ident(name),
+            Expr::Me => self.me.to_string(),
+            Expr::Kind(error) => kind(error),
+            // Python raises where dividing by zero gives infinity or NaN
+            Expr::Binary(left, operator @ (Operator::Divide | Operator::Modulo), right) if !nonzero(right) || !lowering::native(left, *operator, right) => {
+                let function = if *operator == Operator::Divide { "V.divide" } else { "V.remainder" };
+                format!("{}({}, {})", function, self.expression(left), self.expression(right))
+            },
+            // Operands that may turn out not to be numbers, or Text, are checked
+            Expr::Binary(left, operator, right) if !lowering::native(left, *operator, right) => {
+                let (function, symbol) = checked(*operator);
+                format!("{}({}, {}, {})", function, Json::text(symbol), self.expression(left), self.expression(right))
+            },
+            // Python's `%` takes the sign of the divisor rather than the dividend
+            Expr::Binary(left, Operator::Modulo, right) => format!("math.fmod({}, {})", self.expression(left), self.expression(right)),
+            Expr::Binary(left, operator @ (Operator::Equal | Operator::NotEqual), right)
+                if matches!(left.as_ref(), Expr::Nothing) || matches!(right.as_ref(), Expr::Nothing) => {
+                let text = if *operator == Operator::Equal { "is" } else { "is not" };
+                format!("{} {} {}", self.operand(left, 6), text, self.operand(right, 6))
+            },
+            Expr::Binary(left, operator, right) => {
+                let precedence = precedence(expr);
+                let text = match operator {
+                    Operator::Or => "or",
+                    Operator::And => "and",
+                    Operator::Equal => "==",
+                    Operator::NotEqual => "!=",
+                    Operator::Greater => ">",
+                    Operator::GreaterEqual => ">=",
+                    Operator::Less => "<",
+                    Operator::LessEqual => "<=",
+                    Operator::Add => "+",
+                    Operator::Subtract => "-",
+                    Operator::Multiply => "*",
+                    Operator::Divide => "/",
+                    Operator::Modulo => "%",
+                    Operator::Power => "**",
+                };
+                // Comparisons would chain, and `**` groups to the right and
+                // binds more tightly than a `-x` before it
+                let (left_needs, right_needs) = match operator {
+                    Operator::Or | Operator::And | Operator::Add | Operator::Subtract | Operator::Multiply
+                        | Operator::Divide | Operator::Modulo => (precedence, precedence + 1),
+                    Operator::Power => (precedence + 1, precedence - 1),
+                    _ => (precedence + 1, precedence + 1),
+                };
+                format!("{} {} {}", self.operand(left, left_needs), text, self.operand(right, right_needs))
+            },
+            Expr::Negate(value) if !value.numeric() => format!("V.arithmetic(\"-\", 0, {})", self.expression(value)),
+            Expr::Negate(value) => format!("-{}", self.operand(value, 13)),
+            Expr::Equal(left, right, negated) => format!("{}V.equal({}, {})", not(negated), self.expression(left), self.expression(right)),
+            Expr::SameAs(left, right, negated) => format!("{}V.same({}, {})", not(negated), self.expression(left), self.expression(right)),
+            Expr::IsA(value, error, negated) => format!("{}V.is_a({}, {})", not(negated), self.expression(value), kind(error)),
+            Expr::IsType(value, name, negated) => format!("{}V.is_type({}, {})", not(negated), self.expression(value), Json::text(name)),
+            Expr::Cast(value, name) => format!("V.cast({}, {})", self.expression(value), Json::text(name)),
+            Expr::TypeOf(value) => format!("V.type_of({})", self.expression(value)),
+            Expr::Choose(condition, value, otherwise) => {
+                format!("{} if {} else {}", self.operand(value, 2), self.operand(condition, 2), self.operand(otherwise, 1))
+            },
+            Expr::Show(args) => format!("V.show({})", list(args)),
+            Expr::Copy(value) => format!("V.copy({})", self.expression(value)),
+            Expr::Freeze(value) => format!("V.freeze({})", self.expression(value)),
+            Expr::Round(name, value, places) => match places {
+                Some(places) => format!("V.round(\"{}\", {}, {})", name, self.expression(value), self.expression(places)),
+                None => format!("V.round(\"{}\", {})", name, self.expression(value)),
+            },
+            Expr::ReadNumber(text, whole) => format!("V.read_number({}, {})", self.expression(text), if *whole { "True" } else { "False" }),
+            Expr::Reflect(name, args) => format!("V.{}({})", name, list(args)),
+            Expr::Written(args) => format!("V.format_number({})", list(args)),
+            Expr::CompareText(args) => format!("V.compare_text({})", list(args)),
+            Expr::Normalize(text) => format!("V.normalize({})", self.expression(text)),
+            Expr::Wrapping(operator, left, right) => {
+                format!("V.wrapping(\"{}\", {}, {})", wrapping(*operator), self.expression(left), self.expression(right))
+            },
+            Expr::Call(name, args) => format!("{}({})", ident(name), list(args)),
+            Expr::MethodCall(object, name, args) => format!("{}({})", self.get(object, name), list(args)),
+            Expr::Get(object, name) => self.get(object, name),
+            Expr::SafeGet(object, name) => format!("V.get_if({}, {})", self.expression(object), Json::text(&ident(name))),
+            Expr::New(error, args) => format!("{}({})", kind(error), list(args)),
+            // Fails the way the interpreter would, when it gets there
+            Expr::Fail(error, message) => format!("V.fail({}, {})", kind(error), Json::text(message)),
+            Expr::Template(parts) => self.template(parts),
+            Expr::List(items) => format!("[{}]", list(items)),
+            Expr::Item(object, key) => format!("V.item({}, {})", self.expression(object), self.expression(key)),
+            Expr::FromEnd(number) => format!("V.from_end({})", number),
+            Expr::Slice(object, first, last) => {
+                let bound = |number: &Option<Box<Expr>>| number.as_deref().map(|number| self.expression(number)).unwrap_or_else(|| "None".to_string());
+                let (first, last) = (bound(first), bound(last));
+                format!("V.slice({}, {}, {})", self.expression(object), first, last)
+            },
+            Expr::Each { value, name, list, condition } => {
+                let filter = condition.as_ref()
+                    .map(|condition| format!(" if {}", self.expression(condition)))
+                    .unwrap_or_default();
+                format!("[{} for {} in V.items({}){}]", self.expression(value), ident(name), self.expression(list), filter)
+            },
+            Expr::Joined(parts) => {
+                let parts: Vec<String> = parts.iter().map(|(all, part)| match all {
+                    true => format!("*V.items({})", self.expression(part)),
+                    false => self.expression(part),
+                }).collect();
+                format!("[{}]", parts.join(", "))
+            },
+            Expr::Mapping(entries) => {
+                let entries: Vec<String> = entries.iter()
+                    .map(|(key, value)| format!("{}: {}", Json::text(key), self.expression(value)))
+                    .collect();
+                format!("V.Mapping({{{}}})", entries.join(", "))
+            },
+            Expr::Assign(name, value) => format!("({} := {})", ident(name), self.expression(value)),
+            Expr::Set(object, name, value) => {
+                format!("V.put({}, {}, {})", self.expression(object), Json::text(&ident(name)), self.expression(value))
+            },
+        }
+    }
+
+    // An f-string, or joined pieces when a value's code can't go inside one
+    fn template(&self, parts: &[Part]) -> String {
+        let values: Vec<Option<String>> = parts.iter().map(|part| match part {
+            Part::Text(_) => None,
+            Part::Value(value) => Some(format!("V.text({})", self.expression(value))),
+        }).collect();
+        let quoted = |text: &str| Json::text(text).to_string();
+        if values.iter().flatten().any(|value| value.contains(['"', '\\', '{', '}'])) {
+            let pieces: Vec<String> = parts.iter().zip(values).map(|(part, value)| match part {
+                Part::Text(text) => quoted(text),
+                Part::Value(_) => value.unwrap_or_default(),
+            }).collect();
+            return pieces.join(" + ");
+        }
+        let text: String = parts.iter().zip(values).map(|(part, value)| match part {
+            Part::Text(text) => {
+                let quoted = quoted(text);
+                quoted[1..quoted.len() - 1].replace('{', "{{").replace('}', "}}")
+            },
+            Part::Value(_) => format!("{{{}}}", value.unwrap_or_default()),
+        }).collect();
+        format!("f\"{}\"", text)
+    }
+
+    // An operand, parenthesized when it binds more loosely than its position needs
+    fn operand(&self, expr: &Expr, needed: u8) -> String {
+        match precedence(expr) < needed {
+            true => format!("({})", self.expression(expr)),
+            false => self.expression(expr),
+        }
+    }
+
+    fn get(&self, object: &Expr, name: &str) -> String {
+        format!("{}.{}", self.operand(object, 15), ident(name))
+    }
+}
+
+// How tightly an expression binds in Python, from `or` up to names and calls
+fn precedence(expr: &Expr) -> u8 {
+    match expr {
+        Expr::Number(n) if *n < 0.0 => 12,
+        Expr::Choose(..) => 1,
+        Expr::Negate(value) if !value.numeric() => 15,
+        Expr::Negate(_) => 12,
+        Expr::Equal(_, _, true) | Expr::SameAs(_, _, true) | Expr::IsA(_, _, true) | Expr::IsType(_, _, true) => 4,
+        Expr::Binary(left, operator @ (Operator::Divide | Operator::Modulo), right) if !nonzero(right) || !lowering::native(left, *operator, right) => 15,
+        Expr::Binary(left, operator, right) if !lowering::native(left, *operator, right) => 15,
+        Expr::Binary(_, operator, _) => match operator {
+            Operator::Or => 2,
+            Operator::And => 3,
+            Operator::Add | Operator::Subtract => 10,
+            Operator::Multiply | Operator::Divide => 11,
+            Operator::Power => 13,
+            Operator::Modulo => 15,
+            _ => 5,
+        },
+        _ => 15,
+    }
+}
+
+fn wrapping(operator: Operator) -> &'static str {
+    match operator {
+        Operator::Add => "+",
+        Operator::Subtract => "-",
+        _ => "*",
+    }
+}
+
+// The runtime's checked helper for an operator, and how it names it
+fn checked(operator: Operator) -> (&'static str, &'static str) {
+    match operator {
+        Operator::Greater => ("V.compare", ">"),
+        Operator::GreaterEqual => ("V.compare", ">="),
+        Operator::Less => ("V.compare", "<"),
+        Operator::LessEqual => ("V.compare", "<="),
+        Operator::Subtract => ("V.arithmetic", "-"),
+        Operator::Multiply => ("V.arithmetic", "*"),
+        Operator::Power => ("V.arithmetic", "^"),
+        _ => ("V.arithmetic", "+"),
+    }
+}
+
+fn nonzero(expr: &Expr) -> bool {
+    matches!(expr, Expr::Number(n) if *n != 0.0)
+}
+
+fn kind(kind: &Kind) -> String {
+    match kind {
+        Kind::BuiltIn(name) => format!("V.{}", name),
+        Kind::Declared(name) => ident(name),
+    }
+}
+
+fn names(names: &[String]) -> String {
+    names.iter().map(|name| ident(name)).collect::<Vec<_>>().join(", ")
+}
+
+// A name that can't be mistaken for one of Python's own
+fn ident(name: &str) -> String {
+    match RESERVED.contains(&name) {
+        true => format!("{}_", name),
+        false => name.to_string(),
+    }
+}
+
+// Whether these statements `stop` (or, when `again`, `continue`) the loop
+// called `label` from inside a loop of their own
+fn jumps(statements: &[Stmt], label: &str, again: bool, nested: bool) -> bool {
+    statements.iter().any(|statement| match statement {
+        Stmt::Break(Some(target)) => nested && !again && target == label,
+        Stmt::Continue(Some(target)) => nested && again && target == label,
+        Stmt::If(branches, otherwise) => {
+            branches.iter().any(|(_, body)| jumps(body, label, again, nested))
+                || otherwise.as_ref().is_some_and(|body| jumps(body, label, again, nested))
+        },
+        Stmt::While(_, body) | Stmt::Forever(body) | Stmt::Repeat(_, _, body) | Stmt::Measure(_, _, body) => {
+            jumps(body, label, again, true)
+        },
+        Stmt::Named(_, statement) => jumps(std::slice::from_ref(statement), label, again, nested),
+        Stmt::Try { body, handlers, always } => {
+            jumps(body, label, again, nested)
+                || handlers.iter().any(|handler| jumps(&handler.body, label, again, nested))
+                || always.as_ref().is_some_and(|always| jumps(always, label, again, nested))
+        },
+        _ => false,
+    })
+}
@@ -0,0 +1,489 @@
+use crate::lowering::{self, Expr, Function, Operator, Stmt};
+use std::collections::HashMap;
+use std::path::Path;
+
+// What the module imports from the "nair" namespace, for arithmetic
+// WebAssembly has no instruction for
+const IMPORTS: [&str; 2] = ["pow", "remainder"];
+
+const F64: u8 = 0x7c;
+const I32: u8 = 0x7f;
+const EMPTY: u8 = 0x40;
+
+/// Compiles the Tasks of a script that only work with numbers into a
+/// WebAssembly module, one exported function each, and writes it to the
+/// file after `-o` or beside the script. Other Tasks are left out, with the
+/// reason why.
+pub fn emit_file(args: &[String]) -> Result<(), String> {
+    let (script, output) = match args {
+        [script] => (script, Path::new(script).with_extension("wasm")),
+        [script, flag, output] | [flag, output, script] if flag == "-o" => (script, output.into()),
+        _ => return Err("Usage: nair emit-wasm script [-o output]".to_string()),
+    };
+    if output == Path::new(script) {
+        return Err(format!("Compiling '{}' would overwrite it; choose another name with -o", script));
+    }
+
+    let program = lowering::lower(&lowering::check_file(script)?);
+    let (module, compiled, left_out) = compile(&program.tasks);
+    if compiled.is_empty() {
+        return Err(format!("'{}' has no Tasks that only work with numbers", script));
+    }
+    std::fs::write(&output, module).map_err(|e| format!("Error writing '{}': {}", output.display(), e))?;
+    println!("Compiled {} to {}", compiled.join(", "), output.display());
+    for (name, reason) in left_out {
+        println!("Left out {}: it {}", name, reason);
+    }
+    Ok(())
+}
+
+/// A module of the Tasks that take and return Whole or Decimal numbers and
+/// only do arithmetic, comparisons, `when`, loops and calls to each other;
+/// the names it exports; and the Tasks left out, with why.
+pub fn compile(tasks: &[Function]) -> (Vec<u8>, Vec<&str>, Vec<(&str, String)>) {
+    let numeric = |name: &str| name == "Whole" || name == "Decimal";
+    let (mut candidates, mut left_out) = (Vec::new(), Vec::new());
+    for task in tasks {
+        match &task.returns {
+            None => left_out.push((task.name.as_str(), "returns nothing".to_string())),
+            Some(returns) if !numeric(returns) || !task.types.iter().all(|name| numeric(name)) => {
+                left_out.push((task.name.as_str(), "takes or returns something other than a number".to_string()));
+            },
+            Some(_) => candidates.push(task),
+        }
+    }
+
+    // Leaving one Task out can leave out those that call it, so compile
+    // until every remaining Task succeeds
+    let bodies = loop {
+        let indices: HashMap<&str, u32> = candidates.iter().enumerate()
+            .map(|(index, task)| (task.name.as_str(), (IMPORTS.len() + index) as u32))
+            .collect();
+        let arities: HashMap<&str, usize> = candidates.iter().map(|task| (task.name.as_str(), task.params.len())).collect();
+        let results: Vec<Result<Vec<u8>, String>> = candidates.iter()
+            .map(|task| FunctionCompiler::new(task, &indices, &arities).compile(task))
+            .collect();
+        match results.iter().position(Result::is_err) {
+            Some(failed) => {
+                let reason = results.into_iter().nth(failed).and_then(Result::err).unwrap_or_default();
+                left_out.push((candidates.remove(failed).name.as_str(), reason));
+            },
+            None => break results.into_iter().flatten().collect::<Vec<_>>(),
+        }
+    };
+    left_out.sort_by_key(|(name, _)| tasks.iter().position(|task| task.name == *name));
+    (module(&candidates, bodies), candidates.iter().map(|task| task.name.as_str()).collect(), left_out)
+}
+
+// The binary module: types, imports, functions, exports and their code
+fn module(tasks: &[&Function], bodies: Vec<Vec<u8>>) -> Vec<u8> {
+    // Type 0 is the imports'; the rest are one per number of parameters
+    let mut arities = vec![2];
+    for task in tasks {
+        if !arities.contains(&task.params.len()) {
+            arities.push(task.params.len());
+        }
+    }
+    let type_of = |arity: usize| arities.iter().position(|known| *known == arity).unwrap_or_default() as u32;
+
+    let mut output = b"\0asm".to_vec();
+    output.extend_from_slice(&1u32.to_le_bytes());
+    section(&mut output, 1, vector(arities.iter().map(|arity| {
+        let mut signature = vec![0x60];
+        signature.extend(vector(std::iter::repeat_n(vec![F64], *arity)));
+        signature.extend(vector([vec![F64]]));
+        signature
+    })));
+    section(&mut output, 2, vector(IMPORTS.iter().map(|import| {
+        let mut entry = name("nair");
+        entry.extend(name(import));
+        entry.push(0x00);
+        unsigned(&mut entry, 0);
+        entry
+    })));
+    section(&mut output, 3, vector(tasks.iter().map(|task| {
+        let mut entry = Vec::new();
+        unsigned(&mut entry, type_of(task.params.len()));
+        entry
+    })));
+    section(&mut output, 7, vector(tasks.iter().enumerate().map(|(index, task)| {
+        let mut entry = name(&task.name);
+        entry.push(0x00);
+        unsigned(&mut entry, (IMPORTS.len() + index) as u32);
+        entry
+    })));
+    section(&mut output, 10, vector(bodies.into_iter().map(|body| {
+        let mut entry = Vec::new();
+        unsigned(&mut entry, body.len() as u32);
+        entry.extend(body);
+        entry
+    })));
+    output
+}
+
+struct FunctionCompiler<'a> {
+    code: Vec<u8>,
+    // Parameters, then the variables the body assigns
+    locals: Vec<&'a str>,
+    indices: &'a HashMap<&'a str, u32>,
+    arities: &'a HashMap<&'a str, usize>,
+    // How many `repeat`s enclose the code being compiled, and the most at
+    // once; each keeps its count and next number in two locals after the
+    // rest and the one that holds a divisor while it is checked for zero
+    repeats: u32,
+    most_repeats: u32,
+    // How many blocks are open, and for each loop around the code, its name
+    // and how many were open once the block that `stop` leaves had started;
+    // `continue` leaves the block two inside that one
+    blocks: u32,
+    exits: Vec<(Option<String>, u32)>,
+    // The name of the loop about to be compiled
+    label: Option<String>,
+}
+
+impl<'a> FunctionCompiler<'a> {
+    fn new(task: &'a Function, indices: &'a HashMap<&'a str, u32>, arities: &'a HashMap<&'a str, usize>) -> Self {
+        let locals = task.params.iter().chain(&task.locals).map(String::as_str).collect();
+        FunctionCompiler { code: Vec::new(), locals, indices, arities, repeats: 0, most_repeats: 0, blocks: 0, exits: Vec::new(), label: None }
+    }
+
+    fn compile(mut self, task: &Function) -> Result<Vec<u8>, String> {
+        self.statements(&task.body)?;
+        let mut body = Vec::new();
+        match task.locals.len() as u32 + 1 + 2 * self.most_repeats {
+            0 => unsigned(&mut body, 0),
+            count => {
+                unsigned(&mut body, 1);
+                unsigned(&mut body, count);
+                body.push(F64);
+            },
+        }
+        // Reaching the end without `output` traps
+        self.code.extend([0x00, 0x0b]);
+        body.extend(self.code);
+        Ok(body)
+    }
+
+    // The body of a loop, inside its outer block and its loop block, in a
+    // block of its own
+    fn loop_body(&mut self, body: &[Stmt]) -> Result<(), String> {
+        self.code.extend([0x02, EMPTY]);
+        self.blocks += 3;
+        self.exits.push((self.label.take(), self.blocks - 2));
+        self.statements(body)?;
+        self.exits.pop();
+        self.blocks -= 3;
+        self.code.push(0x0b);
+        Ok(())
+    }
+
+    // The loop that `stop` or `continue` names, or the innermost one
+    fn exit(&self, label: &Option<String>) -> Result<u32, String> {
+        let exit = match label {
+            Some(label) => self.exits.iter().rev().find(|(name, _)| name.as_ref() == Some(label)),
+            None => self.exits.last(),
+        };
+        exit.map(|(_, exit)| *exit).ok_or_else(|| "stops outside a loop".to_string())
+    }
+
+    fn statements(&mut self, statements: &[Stmt]) -> Result<(), String> {
+        statements.iter().try_for_each(|statement| self.statement(statement))
+    }
+
+    fn statement(&mut self, statement: &Stmt) -> Result<(), String> {
+        match statement {
+            Stmt::Assign(name, value) => {
+                self.number(value)?;
+                let index = self.local(name)?;
+                self.code.push(0x21);
+                unsigned(&mut self.code, index);
+            },
+            Stmt::Return(value) => {
+                self.number(value)?;
+                self.code.push(0x0f);
+            },
+            Stmt::Eval(value) => {
+                self.number(value)?;
+                self.code.push(0x1a);
+            },
+            Stmt::If(branches, otherwise) => {
+                for (condition, body) in branches {
+                    self.condition(condition)?;
+                    self.code.extend([0x04, EMPTY]);
+                    self.blocks += 1;
+                    self.statements(body)?;
+                    self.code.push(0x05);
+                }
+                self.statements(otherwise.as_deref().unwrap_or_default())?;
+                self.code.extend(std::iter::repeat_n(0x0b, branches.len()));
+                self.blocks -= branches.len() as u32;
+            },
+            Stmt::While(condition, body) => {
+                self.code.extend([0x02, EMPTY, 0x03, EMPTY]);
+                self.condition(condition)?;
+                self.code.extend([0x45, 0x0d, 1]);
+                self.loop_body(body)?;
+                self.code.extend([0x0c, 0, 0x0b, 0x0b]);
+            },
+            Stmt::Forever(body) => {
+                self.code.extend([0x02, EMPTY, 0x03, EMPTY]);
+                self.loop_body(body)?;
+                self.code.extend([0x0c, 0, 0x0b, 0x0b]);
+            },
+            Stmt::Break(label) => {
+                let exit = self.exit(label)?;
+                self.code.push(0x0c);
+                unsigned(&mut self.code, self.blocks - exit);
+            },
+            Stmt::Continue(label) => {
+                let exit = self.exit(label)?;
+                self.code.push(0x0c);
+                unsigned(&mut self.code, self.blocks - exit - 2);
+            },
+            Stmt::Named(label, statement) => {
+                self.label = Some(label.clone());
+                self.statement(statement)?;
+            },
+            Stmt::Repeat(count, counter, body) => {
+                let limit = self.locals.len() as u32 + 1 + 2 * self.repeats;
+                let next = limit + 1;
+                self.number(count)?;
+                self.code.push(0x21);
+                unsigned(&mut self.code, limit);
+                self.code.push(0x44);
+                self.code.extend(1f64.to_le_bytes());
+                self.code.push(0x21);
+                unsigned(&mut self.code, next);
+
+                self.code.extend([0x02, EMPTY, 0x03, EMPTY, 0x20]);
+                unsigned(&mut self.code, next);
+                self.code.push(0x20);
+                unsigned(&mut self.code, limit);
+                self.code.extend([0x65, 0x45, 0x0d, 1]);
+                if let Some(counter) = counter {
+                    let index = self.local(counter)?;
+                    self.code.push(0x20);
+                    unsigned(&mut self.code, next);
+                    self.code.push(0x21);
+                    unsigned(&mut self.code, index);
+                }
+                self.repeats += 1;
+                self.most_repeats = self.most_repeats.max(self.repeats);
+                self.loop_body(body)?;
+                self.repeats -= 1;
+                self.code.push(0x20);
+                unsigned(&mut self.code, next);
+                self.code.push(0x44);
+                self.code.extend(1f64.to_le_bytes());
+                self.code.push(0xa0);
+                self.code.push(0x21);
+                unsigned(&mut self.code, next);
+                self.code.extend([0x0c, 0, 0x0b, 0x0b]);
+            },
+            Stmt::Show(_) => return Err("shows output".to_string()),
+            Stmt::Set(..) => return Err("sets fields".to_string()),
+            Stmt::SetItem(..) => return Err("uses collections".to_string()),
+            Stmt::Raise(_) | Stmt::RaiseAs(..) => return Err("raises errors".to_string()),
+            Stmt::Expect(..) | Stmt::ExpectEqual(..) => return Err("checks expectations".to_string()),
+            Stmt::Try { .. } => return Err("handles errors".to_string()),
+            Stmt::Measure(..) => return Err("measures time".to_string()),
+            Stmt::InitShared(_) => return Err("declares an Object".to_string()),
+        }
+        Ok(())
+    }
+
+    // Code leaving a number on the stack
+    fn number(&mut self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Number(n) => {
+                self.code.push(0x44);
+                self.code.extend(n.to_le_bytes());
+            },
+            Expr::Variable(name) => {
+                let index = self.local(name)?;
+                self.code.push(0x20);
+                unsigned(&mut self.code, index);
+            },
+            Expr::Binary(left, operator, right) => {
+                self.number(left)?;
+                self.number(right)?;
+                // Dividing by zero traps, as it fails in the interpreter
+                if matches!(operator, Operator::Divide | Operator::Modulo) && !matches!(right.as_ref(), Expr::Number(n) if *n != 0.0) {
+                    let divisor = self.locals.len() as u32;
+                    self.code.push(0x22);
+                    unsigned(&mut self.code, divisor);
+                    self.code.push(0x20);
+                    unsigned(&mut self.code, divisor);
+                    self.code.push(0x44);
+                    self.code.extend(0f64.to_le_bytes());
+                    self.code.extend([0x61, 0x04, EMPTY, 0x00, 0x0b]);
+                }
+                match operator {
+                    Operator::Add => self.code.push(0xa0),
+                    Operator::Subtract => self.code.push(0xa1),
+                    Operator::Multiply => self.code.push(0xa2),
+                    Operator::Divide => self.code.push(0xa3),
+                    Operator::Power => self.code.extend([0x10, 0]),
+                    Operator::Modulo => self.code.extend([0x10, 1]),
+                    _ => return Err("uses a truth value as a number".to_string()),
+                }
+            },
+            Expr::Negate(value) => {
+                self.number(value)?;
+                self.code.push(0x9a);
+            },
+            Expr::Round(name, value, places) => {
+                self.number(value)?;
+                if let Some(places) = places {
+                    self.scale(places)?;
+                    self.code.push(0xa2);
+                }
+                match name.as_str() {
+                    "round_down" => self.code.push(0x9c),
+                    "round_up" => self.code.push(0x9b),
+                    "round_even" => self.code.push(0x9e),
+                    // Halves round away from zero: the sign of the number on
+                    // the floor of its size plus a half
+                    _ => {
+                        let number = self.locals.len() as u32;
+                        self.code.push(0x22);
+                        unsigned(&mut self.code, number);
+                        self.code.push(0x99);
+                        self.code.push(0x44);
+                        self.code.extend(0.5f64.to_le_bytes());
+                        self.code.extend([0xa0, 0x9c, 0x20]);
+                        unsigned(&mut self.code, number);
+                        self.code.push(0xa6);
+                    },
+                }
+                if let Some(places) = places {
+                    self.scale(places)?;
+                    self.code.push(0xa3);
+                }
+            },
+            Expr::Call(name, args) => {
+                let index = *self.indices.get(name.as_str())
+                    .ok_or_else(|| format!("calls {}, which isn't compiled", name))?;
+                if self.arities.get(name.as_str()) != Some(&args.len()) {
+                    return Err(format!("calls {} with the wrong number of arguments", name));
+                }
+                args.iter().try_for_each(|arg| self.number(arg))?;
+                self.code.push(0x10);
+                unsigned(&mut self.code, index);
+            },
+            Expr::Choose(condition, value, otherwise) => {
+                self.condition(condition)?;
+                self.code.extend([0x04, F64]);
+                self.number(value)?;
+                self.code.push(0x05);
+                self.number(otherwise)?;
+                self.code.push(0x0b);
+            },
+            Expr::Assign(name, value) => {
+                self.number(value)?;
+                let index = self.local(name)?;
+                self.code.push(0x22);
+                unsigned(&mut self.code, index);
+            },
+            Expr::Show(_) => return Err("shows output".to_string()),
+            Expr::Text(_) | Expr::Template(_) => return Err("uses Text".to_string()),
+            Expr::List(_) | Expr::Joined(_) | Expr::Each { .. } | Expr::Item(..) | Expr::FromEnd(_) | Expr::Slice(..) | Expr::Mapping(_) => return Err("uses collections".to_string()),
+            Expr::Truth(_) | Expr::Equal(..) | Expr::SameAs(..) | Expr::IsA(..) | Expr::IsType(..) => {
+                return Err("uses a truth value as a number".to_string());
+            },
+            _ => return Err("uses Objects or values other than numbers".to_string()),
+        }
+        Ok(())
+    }
+
+    // Code leaving 10 to the power of the decimal places on the stack
+    fn scale(&mut self, places: &Expr) -> Result<(), String> {
+        self.code.push(0x44);
+        self.code.extend(10f64.to_le_bytes());
+        self.number(places)?;
+        self.code.extend([0x10, 0]);
+        Ok(())
+    }
+
+    // Code leaving 1 or 0 on the stack, for whether a condition holds
+    fn condition(&mut self, expr: &Expr) -> Result<(), String> {
+        match expr {
+            Expr::Truth(b) => self.code.extend([0x41, *b as u8]),
+            Expr::Binary(left, Operator::And, right) => {
+                self.condition(left)?;
+                self.code.extend([0x04, I32]);
+                self.condition(right)?;
+                self.code.extend([0x05, 0x41, 0, 0x0b]);
+            },
+            Expr::Binary(left, Operator::Or, right) => {
+                self.condition(left)?;
+                self.code.extend([0x04, I32, 0x41, 1, 0x05]);
+                self.condition(right)?;
+                self.code.push(0x0b);
+            },
+            // Numbers are the same as each other when they're equal
+            Expr::Equal(left, right, negated) | Expr::SameAs(left, right, negated) => {
+                self.number(left)?;
+                self.number(right)?;
+                self.code.push(if *negated { 0x62 } else { 0x61 });
+            },
+            Expr::Binary(left, operator, right) => {
+                let opcode = match operator {
+                    Operator::Equal => 0x61,
+                    Operator::NotEqual => 0x62,
+                    Operator::Less => 0x63,
+                    Operator::Greater => 0x64,
+                    Operator::LessEqual => 0x65,
+                    Operator::GreaterEqual => 0x66,
+                    _ => return Err("uses a number as a truth value".to_string()),
+                };
+                self.number(left)?;
+                self.number(right)?;
+                self.code.push(opcode);
+            },
+            _ => return Err("uses truth values other than comparisons".to_string()),
+        }
+        Ok(())
+    }
+
+    fn local(&self, name: &str) -> Result<u32, String> {
+        self.locals.iter().position(|local| *local == name)
+            .map(|index| index as u32)
+            .ok_or_else(|| format!("uses {}, which isn't one of its own variables", name))
+    }
+}
+
+fn section(output: &mut Vec<u8>, id: u8, content: Vec<u8>) {
+    output.push(id);
+    unsigned(output, content.len() as u32);
+    output.extend(content);
+}
+
+// A count followed by the items
+fn vector(items: impl IntoIterator<Item = Vec<u8>>) -> Vec<u8> {
+    let items: Vec<Vec<u8>> = items.into_iter().collect();
+    let mut output = Vec::new();
+    unsigned(&mut output, items.len() as u32);
+    output.extend(items.into_iter().flatten());
+    output
+}
+
+fn name(text: &str) -> Vec<u8> {
+    let mut output = Vec::new();
+    unsigned(&mut output, text.len() as u32);
+    output.extend_from_slice(text.as_bytes());
+    output
+}
+
+// LEB128, as the format writes every count and index
+fn unsigned(output: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            output.push(byte);
+            return;
+        }
+        output.push(byte | 0x80);
+    }
+}
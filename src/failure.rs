@@ -0,0 +1,22 @@
+use std::fmt;
+
+/// Why a running program failed: the built-in error kind it is caught as,
+/// like `TypeError`, and the message that explains it. Each failure is
+/// given its kind where it happens.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Failure {
+    pub kind: &'static str,
+    pub message: String,
+}
+
+impl Failure {
+    pub fn new(kind: &'static str, message: impl Into<String>) -> Failure {
+        Failure { kind, message: message.into() }
+    }
+}
+
+impl fmt::Display for Failure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
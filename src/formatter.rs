@@ -0,0 +1,930 @@
+use crate::generator::Value;
+use crate::parser::{is_ordering, negated, ordinal_suffix, FailClause, Node, OrWhen, Parser, ORDINALS, ROUNDING};
+use crate::test_runner;
+use crate::tokenizer::{TokenType, Tokenizer};
+use std::path::PathBuf;
+
+// Lines longer than this are continued onto the next line with `\`
+const MAX_WIDTH: usize = 100;
+const INDENT: &str = "    ";
+
+/// How `nair fmt` reports.
+#[derive(Default)]
+pub struct FormatOptions {
+    /// List the files that are not formatted instead of rewriting them
+    pub check: bool,
+    /// Print the formatted source instead of rewriting the files
+    pub stdout: bool,
+}
+
+/// Formats every source file in the given files or directories (the current
+/// directory when none are given). With `check`, fails if any would change.
+pub fn format_files(paths: &[String], options: &FormatOptions) -> Result<(), String> {
+    let roots: Vec<PathBuf> = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let mut files = Vec::new();
+    for root in &roots {
+        test_runner::collect_files(root, &mut files)?;
+    }
+    files.sort();
+
+    let (mut unformatted, mut failed) = (0, 0);
+    for file in &files {
+        let result = std::fs::read_to_string(file)
+            .map_err(|e| e.to_string())
+            .and_then(|source| format_source(&source).map(|formatted| (source, formatted)));
+        let (source, formatted) = match result {
+            Ok(result) => result,
+            Err(error) => {
+                eprintln!("{}: {}", file.display(), error);
+                failed += 1;
+                continue;
+            },
+        };
+        if options.stdout {
+            print!("{}", formatted);
+        } else if formatted != source {
+            unformatted += 1;
+            if options.check {
+                println!("{} is not formatted", file.display());
+            } else {
+                std::fs::write(file, &formatted).map_err(|e| format!("Error writing '{}': {}", file.display(), e))?;
+                println!("Formatted {}", file.display());
+            }
+        }
+    }
+
+    match (failed, options.check && unformatted > 0) {
+        (0, false) => Ok(()),
+        (0, true) => Err(format!("{} file(s) need formatting", unformatted)),
+        (failed, _) => Err(format!("{} file(s) could not be formatted", failed)),
+    }
+}
+
+/// Source in the canonical layout: four-space indentation, one statement
+/// per line, one space around operators, at most one blank line in a row,
+/// and long lines continued with `\`. Comments stay with the code after them.
+pub fn format_source(source: &str) -> Result<String, String> {
+    let tokens = Tokenizer::new(source).tokenize()?;
+    let ast = Parser::new(tokens).parse()?;
+
+    let mut formatter = Formatter::new(source.lines().collect());
+    formatter.statements(&ast, 0);
+    formatter.catch_up(0, formatter.lines.len() + 1);
+
+    // What is written must mean the same as what was read
+    let formatted = formatter.output;
+    Tokenizer::new(&formatted).tokenize()
+        .and_then(|tokens| Parser::new(tokens).parse())
+        .map_err(|error| format!("Formatting would break this file ({}); please report it", error))?;
+    Ok(formatted)
+}
+
+/// Source for a syntax tree in the same layout as `format_source`. The tree
+/// keeps no comments or blank lines, and object members come out grouped by
+/// kind. Parsing the result gives back the same tree.
+pub fn print(nodes: &[Node]) -> String {
+    let mut formatter = Formatter::new(Vec::new());
+    formatter.statements(nodes, 0);
+    formatter.output
+}
+
+struct Formatter<'a> {
+    lines: Vec<&'a str>,
+    output: String,
+    // The last source line whose comments have been written
+    seen: usize,
+    at_block_start: bool,
+}
+
+// Where an object's member is declared, found by searching the source
+enum Member<'a> {
+    Behaviors(&'a [String]),
+    Field(&'a Node, bool),
+    Build(&'a Node),
+    Property(&'a Node),
+    Task(&'a Node, bool),
+}
+
+impl<'a> Formatter<'a> {
+    // Without source lines only the tree is written
+    fn new(lines: Vec<&'a str>) -> Self {
+        Formatter { lines, output: String::new(), seen: 0, at_block_start: true }
+    }
+
+    fn statements(&mut self, nodes: &[Node], depth: usize) {
+        let mut line = None;
+        for node in nodes {
+            match node {
+                Node::Line(start) => {
+                    self.catch_up(depth, *start);
+                    line = Some(*start);
+                },
+                Node::Block(statements) => self.statements(statements, depth),
+                statement => self.statement(statement, depth, line.take()),
+            }
+        }
+    }
+
+    // An indented block after a header ending in ':'
+    fn body(&mut self, block: &Node, depth: usize) {
+        self.at_block_start = true;
+        match block {
+            Node::Block(statements) => self.statements(statements, depth),
+            statement => self.statement(statement, depth, None),
+        }
+    }
+
+    fn statement(&mut self, node: &Node, depth: usize, line: Option<usize>) {
+        match node {
+            Node::TaskDecl { .. } => {
+                self.write(depth, &task_header(node, ""), line);
+                if let Node::TaskDecl { body, .. } = node {
+                    self.body(body, depth + 1);
+                }
+            },
+            Node::ObjectDecl { .. } => self.object(node, depth, line),
+            Node::WhenStmt { condition, then_branch, or_when, else_branch } => {
+                self.write(depth, &when_header(condition), line);
+                self.body(then_branch, depth + 1);
+                for OrWhen { line, condition, body } in or_when {
+                    self.catch_up(depth, *line);
+                    self.write(depth, &format!("or when {}:", expression(condition)), Some(*line));
+                    self.body(body, depth + 1);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.write(depth, "or:", None);
+                    self.body(else_branch, depth + 1);
+                }
+            },
+            Node::LoopStmt { condition, body, label } => {
+                self.write(depth, &loop_header(condition, label), line);
+                self.body(body, depth + 1);
+            },
+            Node::RepeatStmt { count, counter, body, label } => {
+                self.write(depth, &repeat_header(count, counter, label), line);
+                self.body(body, depth + 1);
+            },
+            Node::MeasureStmt { runs, result, body } => {
+                self.write(depth, &measure_header(runs, result), line);
+                self.body(body, depth + 1);
+            },
+            Node::KeepDoingStmt { body, label } => {
+                self.write(depth, &format!("keep doing the following{}:", called(label)), line);
+                self.body(body, depth + 1);
+            },
+            Node::TestDecl { name, body } => {
+                let plain = !name.is_empty() && name.split(' ').all(|word| !word.is_empty() && word.chars().all(|c| c.is_alphanumeric() || c == '_'));
+                let name = if plain { name.clone() } else { format!("\"{}\"", name) };
+                self.write(depth, &format!("to test {}:", name), line);
+                self.body(body, depth + 1);
+            },
+            Node::TryStmt { body, handlers, always } => {
+                self.write(depth, "do:", line);
+                self.body(body, depth + 1);
+                for FailClause { name, kind, body } in handlers {
+                    let mut header = "fail".to_string();
+                    if let Some(name) = name {
+                        header.push_str(&format!(" {}", name));
+                    }
+                    if let Some(kind) = kind {
+                        header.push_str(&format!(" as {}", type_name(kind)));
+                    }
+                    self.write(depth, &format!("{}:", header), None);
+                    self.body(body, depth + 1);
+                }
+                if let Some(always) = always {
+                    self.write(depth, "always:", None);
+                    self.body(always, depth + 1);
+                }
+            },
+            Node::Block(statements) => self.statements(statements, depth),
+            simple => self.write(depth, &simple_statement(simple), line),
+        }
+    }
+
+    fn object(&mut self, node: &Node, depth: usize, line: Option<usize>) {
+        let Node::ObjectDecl { name, base, fields, constructor, methods, properties, shared_fields, shared_methods, behaviors, is_behavior } = node else {
+            return;
+        };
+        let header = match (is_behavior, base) {
+            (true, _) => format!("Behavior {}:", name),
+            (false, Some(base)) => format!("Object {} inherits {}:", name, type_name(base)),
+            (false, None) => format!("Object {}:", name),
+        };
+        self.write(depth, &header, line);
+
+        let mut members: Vec<Member> = Vec::new();
+        if !behaviors.is_empty() {
+            members.push(Member::Behaviors(behaviors));
+        }
+        members.extend(fields.iter().map(|field| Member::Field(field, false)));
+        members.extend(shared_fields.iter().map(|field| Member::Field(field, true)));
+        members.extend(constructor.iter().map(|constructor| Member::Build(constructor)));
+        members.extend(properties.iter().map(Member::Property));
+        members.extend(methods.iter().map(|method| Member::Task(method, false)));
+        members.extend(shared_methods.iter().map(|method| Member::Task(method, true)));
+
+        // Members are kept in the order they were written, as far as the source shows it
+        let mut claimed = Vec::new();
+        let mut located: Vec<(Option<usize>, Member)> = members.into_iter()
+            .map(|member| (line.and_then(|line| self.member_line(line, &member, &mut claimed)), member))
+            .collect();
+        located.sort_by_key(|(line, _)| line.unwrap_or(usize::MAX));
+
+        self.at_block_start = true;
+        for (line, member) in located {
+            if let Some(line) = line {
+                self.catch_up(depth + 1, line);
+            }
+            match member {
+                Member::Behaviors(behaviors) => self.write(depth + 1, &format!("behaves like {}", behaviors.join(", ")), line),
+                Member::Field(field, shared) => {
+                    let text = simple_statement(field);
+                    self.write(depth + 1, &if shared { format!("shared {}", text) } else { text }, line);
+                },
+                Member::Build(Node::ConstructorDecl { params, labels, body }) => {
+                    let params: Vec<String> = params.iter().zip(labels).map(|(param, label)| match param {
+                        Node::VariableDecl { name, .. } if name != label => format!("{} {}", label, parameter(param)),
+                        _ => parameter(param),
+                    }).collect();
+                    let header = if params.is_empty() { "build:".to_string() } else { format!("build {}:", params.join(", ")) };
+                    self.write(depth + 1, &header, line);
+                    self.body(body, depth + 2);
+                },
+                Member::Property(Node::PropertyDecl { name, setter_param, return_type, body }) => {
+                    let header = match (setter_param, return_type) {
+                        (Some(param), _) => format!("about {} becomes {}:", name, parameter(param)),
+                        (None, Some(return_type)) => format!("about {} returns {}:", name, type_name(return_type)),
+                        (None, None) => format!("about {}:", name),
+                    };
+                    self.write(depth + 1, &header, line);
+                    self.body(body, depth + 2);
+                },
+                Member::Task(task, shared) => {
+                    self.write(depth + 1, &task_header(task, if shared { "shared " } else { "" }), line);
+                    if let Node::TaskDecl { body, .. } = task {
+                        self.body(body, depth + 2);
+                    }
+                },
+                Member::Build(_) | Member::Property(_) => {},
+            }
+        }
+    }
+
+    // The first unclaimed line of the object's body that declares the member
+    fn member_line(&self, header: usize, member: &Member, claimed: &mut Vec<usize>) -> Option<usize> {
+        let starts = |text: &str, prefix: &str| text.strip_prefix(prefix)
+            .is_some_and(|rest| !rest.starts_with(|c: char| c.is_alphanumeric() || c == '_'));
+        let indent_of = |text: &str| text.len() - text.trim_start().len();
+        let object_indent = indent_of(self.lines.get(header.checked_sub(1)?)?);
+        let mut member_indent = None;
+
+        for (index, text) in self.lines.iter().enumerate().skip(header) {
+            let trimmed = text.trim();
+            if trimmed.is_empty() || trimmed.starts_with('#') {
+                continue;
+            }
+            if indent_of(text) <= object_indent {
+                break;
+            }
+            if *member_indent.get_or_insert(indent_of(text)) != indent_of(text) || claimed.contains(&(index + 1)) {
+                continue;
+            }
+            let found = match member {
+                Member::Behaviors(_) => starts(trimmed, "behaves") || starts(trimmed, "also"),
+                Member::Field(Node::VariableDecl { name, .. }, shared) => {
+                    let text = if *shared { trimmed.strip_prefix("shared ").unwrap_or("") } else { trimmed };
+                    starts(text, name)
+                },
+                Member::Build(_) => starts(trimmed, "build"),
+                Member::Property(Node::PropertyDecl { name, .. }) => starts(trimmed, &format!("about {}", name)),
+                Member::Task(Node::TaskDecl { name, .. }, shared) => {
+                    let text = if *shared { trimmed.strip_prefix("shared ").unwrap_or("") } else { trimmed };
+                    starts(text, &format!("Task {}", name))
+                },
+                _ => false,
+            };
+            if found {
+                claimed.push(index + 1);
+                return Some(index + 1);
+            }
+        }
+        None
+    }
+
+    // Writes the comments and keeps one blank line from the source lines
+    // before `line`
+    fn catch_up(&mut self, depth: usize, line: usize) {
+        let mut blank = false;
+        while self.seen + 1 < line && self.seen < self.lines.len() {
+            let text = self.lines[self.seen];
+            self.seen += 1;
+            if text.trim().is_empty() {
+                blank = true;
+            } else if let Some(comment) = comment(text) {
+                if blank && !self.at_block_start {
+                    self.output.push('\n');
+                }
+                blank = false;
+                self.output.push_str(&format!("{}{}\n", INDENT.repeat(depth), comment));
+                self.at_block_start = false;
+            }
+        }
+        if blank && !self.at_block_start && line <= self.lines.len() {
+            self.output.push('\n');
+        }
+        self.seen = self.seen.max(line.min(self.lines.len()));
+    }
+
+    // One logical line, with the comment that ended its source line
+    fn write(&mut self, depth: usize, text: &str, line: Option<usize>) {
+        let indent = INDENT.repeat(depth);
+        let mut lines = wrap(&indent, text);
+        if let (Some(comment), Some(last)) = (line.and_then(|line| self.lines.get(line - 1)).and_then(|text| comment(text)), lines.last_mut()) {
+            last.push_str(&format!("  {}", comment));
+        }
+        for (i, text) in lines.iter().enumerate() {
+            // Continued lines are indented twice so they don't look like a block
+            let indent = if i == 0 { indent.clone() } else { INDENT.repeat(depth + 2) };
+            self.output.push_str(&format!("{}{}\n", indent, text));
+        }
+        self.at_block_start = false;
+    }
+}
+
+// Splits a long line between words, outside of text literals
+fn wrap(indent: &str, text: &str) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut rest = text;
+    let mut width = MAX_WIDTH.saturating_sub(indent.len());
+    while rest.chars().count() > width {
+        let mut in_string = false;
+        let mut cut = None;
+        for (index, c) in rest.char_indices() {
+            if rest[..index].chars().count() + 2 > width {
+                break;
+            }
+            match c {
+                '"' => in_string = !in_string,
+                ' ' if !in_string && index > 0 => cut = Some(index),
+                _ => {},
+            }
+        }
+        let Some(cut) = cut else {
+            break;
+        };
+        lines.push(format!("{} \\", &rest[..cut]));
+        rest = &rest[cut + 1..];
+        width = MAX_WIDTH.saturating_sub(indent.len() + 2 * INDENT.len());
+    }
+    lines.push(rest.to_string());
+    lines
+}
+
+/// The comment on a source line, if it has one outside of text literals.
+pub fn comment(text: &str) -> Option<&str> {
+    let mut in_string = false;
+    for (index, c) in text.char_indices() {
+        match c {
+            '"' => in_string = !in_string,
+            '#' if !in_string => return Some(text[index..].trim_end()),
+            _ => {},
+        }
+    }
+    None
+}
+
+fn task_header(node: &Node, prefix: &str) -> String {
+    let Node::TaskDecl { name, params, return_type, .. } = node else {
+        return String::new();
+    };
+    let mut header = format!("{}Task {}", prefix, name);
+    if !params.is_empty() {
+        let params: Vec<String> = params.iter().map(parameter).collect();
+        header.push_str(&format!(" requires {}", params.join(", ")));
+    }
+    if let Some(return_type) = return_type {
+        // "requires n as Whole returning Whole" but "greet returns Text"
+        let word = if params.is_empty() { "returns" } else { "returning" };
+        header.push_str(&format!(" {} {}", word, type_name(return_type)));
+    }
+    format!("{}:", header)
+}
+
+fn parameter(node: &Node) -> String {
+    match node {
+        Node::VariableDecl { name, type_annotation: Some(typ), .. } => format!("{} as {}", name, type_name(typ)),
+        Node::VariableDecl { name, .. } => name.clone(),
+        other => expression(other),
+    }
+}
+
+/// The header of a `when`, written with `unless` when it tests that a
+/// condition is false.
+pub fn when_header(condition: &Node) -> String {
+    match negated(condition) {
+        Some(condition) => format!("unless {}:", expression(condition)),
+        None => format!("when {}:", expression(condition)),
+    }
+}
+
+/// The header of a `loop`, written with `until` when it runs while a
+/// condition is false.
+pub fn loop_header(condition: &Node, label: &Option<String>) -> String {
+    match negated(condition) {
+        Some(condition) => format!("loop until {}{}:", expression(condition), called(label)),
+        None => format!("loop while {}{}:", expression(condition), called(label)),
+    }
+}
+
+/// The header of a `repeat`.
+pub fn repeat_header(count: &Node, counter: &Option<String>, label: &Option<String>) -> String {
+    match counter {
+        Some(counter) => format!("repeat {} times, counting with {}{}:", expression(count), counter, called(label)),
+        None => format!("repeat {} times{}:", expression(count), called(label)),
+    }
+}
+
+pub fn measure_header(runs: &Option<Box<Node>>, result: &Option<String>) -> String {
+    let mut header = "measure how long it takes to".to_string();
+    if let Some(runs) = runs {
+        header.push_str(&format!(", {} times", expression(runs)));
+    }
+    if let Some(result) = result {
+        header.push_str(&format!(", calling it {}", result));
+    }
+    header + ":"
+}
+
+// The end of a loop header naming the loop, if it has a name
+fn called(label: &Option<String>) -> String {
+    match label {
+        Some(label) => format!(", called the {} loop", label),
+        None => String::new(),
+    }
+}
+
+/// A statement that fits on one line, in canonical form.
+pub fn simple_statement(node: &Node) -> String {
+    match node {
+        Node::VariableDecl { name, type_annotation, initializer } => {
+            let mut text = match type_annotation {
+                Some(typ) => format!("{} as {}", name, type_name(typ)),
+                None => name.clone(),
+            };
+            match initializer.as_deref() {
+                Some(Node::MappingLiteral { entries }) if !entries.is_empty() => {
+                    let entries: Vec<String> = entries.iter().map(|(key, typ, value)| match typ {
+                        Some(typ) => format!("{} as {} is {}", key, type_name(typ), expression(value)),
+                        None => format!("{} is {}", key, expression(value)),
+                    }).collect();
+                    text.push_str(&format!(" includes {}", entries.join(", ")));
+                },
+                Some(value) => text.push_str(&format!(" is {}", expression(value))),
+                None => {},
+            }
+            text
+        },
+        Node::Assignment { name, value } => format!("{} is {}", name, expression(value)),
+        Node::Change { name, operator, amount } => match operator {
+            TokenType::Plus | TokenType::Minus => {
+                let verb = if *operator == TokenType::Plus { "increase" } else { "decrease" };
+                match amount.as_ref() {
+                    // `increase price by 10%`
+                    Node::Binary { left, operator: TokenType::Multiply, right } if percent(left).is_some()
+                        && matches!(right.as_ref(), Node::Variable(own) if own == name) => format!("{} {} by {}", verb, name, expression(left)),
+                    amount => format!("{} {} by {}", verb, name, expression(amount)),
+                }
+            },
+            _ => format!("double {}", name),
+        },
+        Node::Set { object, name, value } => format!("{} is {}", get(object, name), expression(value)),
+        Node::SetIndex { object, key, keyed, value } => format!("{} is {}", index(object, key, *keyed), expression(value)),
+        Node::ShowStmt(value) => format!("show {}", expression(value)),
+        Node::StopStmt(None) => "stop".to_string(),
+        Node::StopStmt(Some(label)) => format!("stop the {} loop", label),
+        Node::ContinueStmt(None) => "continue".to_string(),
+        Node::ContinueStmt(Some(label)) => format!("continue the {} loop", label),
+        Node::ReturnStmt(value) if matches!(value.as_ref(), Node::Literal(Value::Null)) => "stop here".to_string(),
+        Node::ReturnStmt(value) => format!("output {}", expression(value)),
+        Node::GuardStmt { condition, value } => match value.as_ref() {
+            Node::Literal(Value::Null) => format!("if {}, stop here", expression(condition)),
+            value => format!("if {}, give back {}", expression(condition), expression(value)),
+        },
+        Node::RaiseStmt { message, error_type } => match error_type {
+            Some(kind) => format!("raise {} as {}", operand(message, 1), type_name(kind)),
+            None => format!("raise {}", expression(message)),
+        },
+        Node::ExpectStmt { actual, expected: None, .. } => format!("expect that {}", expression(actual)),
+        Node::ExpectStmt { actual, expected: Some(expected), .. } => format!("expect {} to equal {}", expression(actual), expression(expected)),
+        // A statement like `x is 3` would be read as an assignment
+        Node::ExpressionStmt(value) => match value.as_ref() {
+            Node::Binary { operator: TokenType::Is | TokenType::NotEquals, .. } | Node::TypeTest { .. } | Node::SameAs { .. } | Node::Cast { .. } => {
+                format!("({})", expression(value))
+            },
+            value if same_text(value).is_some() => format!("({})", expression(value)),
+            value => expression(value),
+        },
+        other => expression(other),
+    }
+}
+
+/// Vernacular source for an expression, with only the parentheses it needs.
+pub fn expression(node: &Node) -> String {
+    if let Some(percent) = percent(node) {
+        return percent;
+    }
+    if let Some(compared) = same_text(node) {
+        return compared;
+    }
+    match node {
+        Node::Literal(value) => literal(value),
+        Node::Binary { left, operator: TokenType::Multiply, right } if percent(left).is_some() => {
+            format!("{} of {}", expression(left), operand(right, 7))
+        },
+        Node::Variable(name) => name.clone(),
+        Node::Binary { left, operator: TokenType::Minus, right } if matches!(left.as_ref(), Node::Literal(Value::Number(n)) if *n == 0.0) => {
+            format!("-{}", operand(right, 7))
+        },
+        Node::Binary { left, operator: TokenType::And, right } if chain(left, right).is_some() => {
+            chain(left, right).unwrap_or_default()
+        },
+        Node::Binary { left, operator, right } => {
+            let precedence = precedence(node);
+            format!("{} {} {}", operand(left, precedence), operator_text(operator), operand(right, precedence + 1))
+        },
+        Node::TypeTest { value, target, negated } => {
+            let target = type_name(target);
+            let article = if target.starts_with(['A', 'E', 'I', 'O', 'U']) { "an" } else { "a" };
+            let not = if *negated { "not " } else { "" };
+            format!("{} is {}{} {}", operand(value, 3), not, article, target)
+        },
+        Node::Comprehension { value, name, list, condition } => {
+            let filter = condition.as_ref().map(|condition| format!(" where {}", operand(condition, 1))).unwrap_or_default();
+            match value.as_ref() {
+                // `the first item of each row in rows`
+                Node::Index { object, key, keyed: false } if matches!(**key, Node::Ordinal { .. })
+                    && matches!(object.as_ref(), Node::Variable(item) if item == name) => {
+                    format!("{} of each {} in {}{}", expression(key), name, operand(list, 1), filter)
+                },
+                value => format!("the {} of each {} in {}{}", operand(value, 1), name, operand(list, 1), filter),
+            }
+        },
+        Node::Ordinal { number, from_end, digits } => {
+            let ordinal = match (*number, *digits) {
+                (1, _) if *from_end => return "the last item".to_string(),
+                (number, false) if number <= ORDINALS.len() => ORDINALS[number - 1].to_string(),
+                (number, _) => format!("{}{}", number, ordinal_suffix(number)),
+            };
+            format!("the {}{} item", ordinal, if *from_end { " to last" } else { "" })
+        },
+        Node::Index { object, key, keyed } => index(object, key, *keyed),
+        Node::Slice { object, first, last } => match (first, last) {
+            (Some(first), Some(last)) => format!("items {} through {} of {}", operand(first, 5), operand(last, 5), operand(object, 7)),
+            (Some(number), None) => format!("everything after {} of {}", item_number(number), operand(object, 7)),
+            (_, number) => format!("everything before {} of {}", number.as_deref().map(item_number).unwrap_or_default(), operand(object, 7)),
+        },
+        Node::ListOf { parts } => {
+            let parts: Vec<String> = parts.iter()
+                .map(|(all, part)| format!("{}{}", if *all { "all of " } else { "" }, operand(part, 1)))
+                .collect();
+            format!("a list of {}", parts.join(", then "))
+        },
+        Node::FollowedBy { left, right } => format!("{} followed by {}", operand(left, 5), operand(right, 6)),
+        Node::SameAs { left, right, negated } => {
+            let not = if *negated { "not " } else { "" };
+            format!("{} is {}the same as {}", operand(left, 3), not, operand(right, 4))
+        },
+        Node::Cast { value, target } => format!("{} as {}", before_as(value), type_name(target)),
+        Node::TypeOf { value } => format!("the type of {}", operand(value, 7)),
+        Node::Conditional { value, condition, otherwise } => {
+            format!("{} if {} otherwise {}", operand(value, 1), operand(condition, 1), operand(otherwise, 0))
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "copy") && args.len() == 1 => {
+            format!("a copy of {}", operand(&args[0], 7))
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "freeze" || name == "wait" || name == "ask") && args.len() == 1 => {
+            format!("{} {}", expression(callee), operand(&args[0], 7))
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "<describe>") && args.len() == 1 => {
+            format!("describe {}", operand(&args[0], 7))
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "ask") && args.len() == 5 => {
+            let mut text = match &args[0] {
+                Node::Literal(Value::String(prompt)) if prompt.is_empty() => "ask for".to_string(),
+                prompt => format!("ask {} for", operand(prompt, 7)),
+            };
+            if let Node::Literal(Value::String(kind)) = &args[1] {
+                text.push_str(&format!(" {} {}", if kind.starts_with(['A', 'E', 'I', 'O', 'U']) { "an" } else { "a" }, kind));
+            }
+            if !matches!(args[2], Node::Literal(Value::Null)) {
+                text.push_str(&format!(" between {} and {}", operand(&args[2], 5), operand(&args[3], 5)));
+            }
+            if !matches!(args[4], Node::Literal(Value::Null)) {
+                let tries = if matches!(args[4], Node::Literal(Value::Number(n)) if n == 1.0) { "try" } else { "tries" };
+                text.push_str(&format!(", up to {} {}", operand(&args[4], 5), tries));
+            }
+            text
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "length_of") && args.len() == 2 => match &args[1] {
+            Node::Literal(Value::String(counting)) if counting != "characters" => format!("the length of {} in {}", operand(&args[0], 7), counting),
+            _ => format!("the length of {}", operand(&args[0], 7)),
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "sort") && args.len() == 2 => match &args[1] {
+            Node::Literal(Value::String(language)) => format!("sort {} alphabetically in {}", expression(&args[0]), language),
+            _ => format!("sort {}", expression(&args[0])),
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "today") && args.is_empty() => {
+            "the date today".to_string()
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if ROUNDING.contains(&name.as_str()))
+            && matches!(args.len(), 1 | 2) => {
+            let way = match callee.as_ref() {
+                Node::Variable(name) if name == "round_down" => " down",
+                Node::Variable(name) if name == "round_up" => " up",
+                Node::Variable(name) if name == "round_even" => " to even",
+                _ => "",
+            };
+            match args.get(1) {
+                Some(places) => format!("round {}{} to {}", operand(&args[0], 8), way, counted(places, "decimal place")),
+                None => format!("round {}{}", operand(&args[0], 8), way),
+            }
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "evaluate")
+            && matches!(args.as_slice(), [_, Node::Literal(Value::Boolean(_))]) => {
+            let isolated = if matches!(args[1], Node::Literal(Value::Boolean(true))) { " in isolation" } else { "" };
+            format!("evaluate {}{}", operand(&args[0], 8), isolated)
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "number_in") && args.len() == 1 => {
+            format!("the number in {}", operand(&args[0], 7))
+        },
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "whole_in") && args.len() == 1 => {
+            format!("try to read {} as a Whole", operand(&args[0], 7))
+        },
+        Node::Call { args, .. } if converted(node) => {
+            format!("{} in {}", operand(&args[0], 1), expression(&args[1]).trim_matches('"'))
+        },
+        Node::Call { args, .. } if written(node) => {
+            let mut options = Vec::new();
+            if matches!(args[2], Node::Literal(Value::Boolean(true))) {
+                options.push("commas".to_string());
+            }
+            if !matches!(args[3], Node::Literal(Value::Null)) {
+                options.push(counted(&args[3], "digit"));
+            }
+            if !matches!(args[1], Node::Literal(Value::Null)) {
+                options.push(counted(&args[1], "decimal place"));
+            }
+            let mut text = format!("{} written", before_as(&args[0]));
+            if matches!(args[4], Node::Literal(Value::Boolean(true))) {
+                text.push_str(" as a percentage");
+            }
+            if !options.is_empty() {
+                text = format!("{} with {}", text, options.join(" and "));
+            }
+            text
+        },
+        Node::Call { callee, args } => {
+            let args: Vec<String> = args.iter().map(expression).collect();
+            format!("{}({})", operand(callee, 8), args.join(", "))
+        },
+        Node::Get { object, name } => get(object, name),
+        Node::SafeGet { object, name } => format!("the {} of {}, if {} exists", name, operand(object, 7), expression(object)),
+        Node::New { class_name, args } => {
+            if args.is_empty() {
+                return format!("new {}", class_name);
+            }
+            if args.iter().all(|(label, _)| label.is_some()) {
+                let args: Vec<String> = args.iter()
+                    .map(|(label, value)| format!("{} {}", label.as_deref().unwrap_or(""), operand(value, 5)))
+                    .collect();
+                return format!("new {} {}", class_name, args.join(" "));
+            }
+            let args: Vec<String> = args.iter().map(|(_, value)| expression(value)).collect();
+            format!("new {} with {}", class_name, args.join(", "))
+        },
+        Node::StringInterpolation { parts } => {
+            let text: String = parts.iter().map(|part| match part {
+                Node::Literal(Value::String(text)) => text.clone(),
+                part => format!("{{{}}}", expression(part)),
+            }).collect();
+            format!("\"{}\"", text)
+        },
+        Node::ArrayLiteral { elements, type_annotation } => {
+            let elements: Vec<String> = elements.iter().map(expression).collect();
+            match type_annotation {
+                Some(typ) => format!("[{}] as {}", elements.join(", "), type_name(typ)),
+                None => format!("[{}]", elements.join(", ")),
+            }
+        },
+        Node::MappingLiteral { .. } => "Mapping".to_string(),
+        Node::Assignment { .. } | Node::Change { .. } | Node::Set { .. } | Node::SetIndex { .. } | Node::VariableDecl { .. } => {
+            simple_statement(node)
+        },
+        Node::TypeAnnotation(_) | Node::ListType { .. } | Node::MappingType { .. } => type_name(node),
+        _ => String::new(),
+    }
+}
+
+fn index(object: &Node, key: &Node, keyed: bool) -> String {
+    match keyed {
+        true => format!("the value for {} in {}", operand(key, 5), operand(object, 7)),
+        false => format!("{} of {}", item_number(key), operand(object, 7)),
+    }
+}
+
+// `a < b and b < c` reads back as `a < b < c`, and `x >= low and x <= high`
+// as `x is between low and high`
+fn chain(left: &Node, right: &Node) -> Option<String> {
+    if let (Node::Binary { left: first, operator: TokenType::GreaterThanOrEqual, right: low },
+        Node::Binary { left: again, operator: TokenType::LessThanOrEqual, right: high }) = (left, right) {
+        if expression(first) == expression(again) {
+            return Some(format!("{} is between {} and {}", operand(first, 5), operand(low, 5), operand(high, 5)));
+        }
+    }
+    comparisons(left, right)
+}
+
+// Comparisons joined by `and`, each starting with the value the one before ended with
+fn comparisons(left: &Node, right: &Node) -> Option<String> {
+    let Node::Binary { left: again, operator: after, right: last } = right else {
+        return None;
+    };
+    let (start, previous) = match left {
+        Node::Binary { left: first, operator: TokenType::And, right: previous } => (comparisons(first, previous)?, previous.as_ref()),
+        Node::Binary { left: first, operator: before, right: middle } if is_ordering(before) => {
+            (format!("{} {} {}", operand(first, 4), operator_text(before), operand(middle, 5)), left)
+        },
+        _ => return None,
+    };
+    match previous {
+        Node::Binary { right: middle, .. } if is_ordering(after) && expression(middle) == expression(again) => {
+            Some(format!("{} {} {}", start, operator_text(after), operand(last, 5)))
+        },
+        _ => None,
+    }
+}
+
+// `item 3` or `the third item`
+fn item_number(number: &Node) -> String {
+    match number {
+        Node::Ordinal { .. } => expression(number),
+        number => format!("item {}", operand(number, 5)),
+    }
+}
+
+// An operand, parenthesized when it binds more loosely than its position needs
+fn operand(node: &Node, needed: u8) -> String {
+    if precedence(node) < needed {
+        format!("({})", expression(node))
+    } else {
+        expression(node)
+    }
+}
+
+// How tightly an expression binds, from `as` (0) up to calls and names (8)
+fn precedence(node: &Node) -> u8 {
+    match node {
+        Node::Cast { .. } | Node::Conditional { .. } | Node::ListOf { .. } | Node::Comprehension { .. } | Node::SafeGet { .. } => 0,
+        node if written(node) || converted(node) => 0,
+        node if percent(node).is_some() => 8,
+        Node::New { args, .. } if !args.is_empty() => 0,
+        // `(new Dog).speak()` reads more clearly than `new Dog.speak()`
+        Node::New { .. } => 7,
+        // and so does `(evaluate code in isolation).message`
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "evaluate")
+            && matches!(args.get(1), Some(Node::Literal(Value::Boolean(true)))) => 7,
+        Node::Binary { left, operator: TokenType::Minus, .. } if matches!(left.as_ref(), Node::Literal(Value::Number(n)) if *n == 0.0) => 7,
+        Node::Binary { operator, .. } => match operator {
+            TokenType::Or => 1,
+            TokenType::And => 2,
+            TokenType::Is | TokenType::NotEquals => 3,
+            TokenType::GreaterThan | TokenType::GreaterThanOrEqual | TokenType::LessThan | TokenType::LessThanOrEqual => 4,
+            TokenType::Plus | TokenType::Minus => 5,
+            _ => 6,
+        },
+        Node::TypeTest { .. } | Node::SameAs { .. } => 3,
+        node if same_text(node).is_some() => 3,
+        Node::FollowedBy { .. } => 5,
+        Node::Index { .. } | Node::Slice { .. } | Node::TypeOf { .. } => 7,
+        _ => 8,
+    }
+}
+
+// `1 decimal place`, but `2 decimal places` or `n decimal places`
+fn counted(count: &Node, unit: &str) -> String {
+    match count {
+        Node::Literal(Value::Number(n)) if *n == 1.0 => format!("1 {}", unit),
+        count => format!("{} {}s", operand(count, 8), unit),
+    }
+}
+
+// Whether a call to `format_number` reads as `x written with ...`, which
+// needs its choices of commas and percentage written out plainly
+fn written(node: &Node) -> bool {
+    match node {
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "format_number") && args.len() == 5 => {
+            let given = |arg: &Node| !matches!(arg, Node::Literal(Value::Null));
+            match (&args[2], &args[4]) {
+                (Node::Literal(Value::Boolean(commas)), Node::Literal(Value::Boolean(percent))) => {
+                    *commas || *percent || given(&args[1]) || given(&args[3])
+                },
+                _ => false,
+            }
+        },
+        _ => false,
+    }
+}
+
+// `a is the same text as b, ignoring case`, as the parser reads it
+fn same_text(node: &Node) -> Option<String> {
+    match node {
+        Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "compare_text") => match args.as_slice() {
+            [left, right, Node::Literal(Value::Boolean(ignoring_case)), Node::Literal(Value::Boolean(negated))] => {
+                let not = if *negated { "not " } else { "" };
+                let ignoring = if *ignoring_case { ", ignoring case" } else { "" };
+                Some(format!("{} is {}the same text as {}{}", operand(left, 3), not, operand(right, 4), ignoring))
+            },
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// `15%` for 15 / 100, as the parser reads it
+fn percent(node: &Node) -> Option<String> {
+    match node {
+        Node::Binary { left, operator: TokenType::Divide, right } => match (left.as_ref(), right.as_ref()) {
+            (Node::Literal(Value::Number(n)), Node::Literal(Value::Number(hundred))) if *n >= 0.0 && *hundred == 100.0 => Some(format!("{}%", n)),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+// Whether a call to `convert` reads as `x in <units>`
+fn converted(node: &Node) -> bool {
+    matches!(node, Node::Call { callee, args } if matches!(callee.as_ref(), Node::Variable(name) if name == "convert")
+        && matches!(args.as_slice(), [_, Node::Literal(Value::String(_))]))
+}
+
+// The value before `as` or `written`, which may be `x in <units>`, as those
+// are read after it
+fn before_as(node: &Node) -> String {
+    match converted(node) {
+        true => expression(node),
+        false => operand(node, 1),
+    }
+}
+
+/// How an operator is written in source.
+pub fn operator_text(operator: &TokenType) -> &'static str {
+    match operator {
+        TokenType::Or => "or",
+        TokenType::And => "and",
+        TokenType::Is => "is",
+        TokenType::NotEquals => "is not",
+        TokenType::GreaterThan => ">",
+        TokenType::GreaterThanOrEqual => ">=",
+        TokenType::LessThan => "<",
+        TokenType::LessThanOrEqual => "<=",
+        TokenType::Plus => "+",
+        TokenType::Minus => "-",
+        TokenType::Multiply => "*",
+        TokenType::Divide => "/",
+        TokenType::Modulo => "%",
+        TokenType::Power => "^",
+        _ => "?",
+    }
+}
+
+fn get(object: &Node, name: &str) -> String {
+    match object {
+        Node::Variable(me) if me == "me" => format!("my {}", name),
+        object => format!("{}.{}", operand(object, 8), name),
+    }
+}
+
+fn literal(value: &Value) -> String {
+    match value {
+        Value::String(text) => format!("\"{}\"", text),
+        Value::Number(n) if *n < 0.0 => format!("-{}", -n),
+        other => other.to_string(),
+    }
+}
+
+fn type_name(node: &Node) -> String {
+    match node {
+        Node::TypeAnnotation(name) => name.clone(),
+        Node::ListType { element_type } => format!("List of {}", type_name(element_type)),
+        Node::MappingType { key_type, value_type } => match (key_type.as_ref(), value_type.as_ref()) {
+            (Node::TypeAnnotation(key), Node::TypeAnnotation(value)) if key == "Any" && value == "Any" => "Mapping".to_string(),
+            _ => format!("Mapping of {} to {}", type_name(key_type), type_name(value_type)),
+        },
+        other => expression(other),
+    }
+}
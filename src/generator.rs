@@ -1,17 +1,30 @@
-use crate::parser::Node;
+use crate::money::Money;
+use crate::units::Quantity;
+use crate::dates::Date;
+use crate::failure::Failure;
+use crate::graphemes::graphemes;
+use crate::parser::{negated, ordinal_suffix, Node};
+use crate::tokenizer::TokenType;
+use std::collections::hash_map::DefaultHasher;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+// How many times `measure how long it takes to` runs its block when not told
+pub const DEFAULT_RUNS: f64 = 10.0;
 
 #[derive(Debug, Clone)]
 pub enum OpCode {
     // Stack Operations
     Push(Value),
     Pop,
-    Duplicate,
-    
+    Dup,
+
     // Variables
     LoadVar(String),
     StoreVar(String),
-    
+    DeclareVar(String, String), // name, type name; stores a value in a new variable of that type
+
     // Arithmetic
     Add,
     Subtract,
@@ -19,40 +32,411 @@ pub enum OpCode {
     Divide,
     Modulo,
     Power,
-    
+
+    // Comparison
+    Equal,
+    NotEqual,
+    SameAs,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    And,
+    Or,
+    Not,                  // pops a Truth and pushes the opposite one
+
     // Control Flow
     Jump(usize),
-    JumpIfFalse(usize),
+    JumpIfFalse(usize),   // pops a Truth, and jumps when it is false
     Call(String, usize),  // function name, arg count
+    CallMethod(String, usize),  // method name, arg count
     Return,
-    
+
+    // Errors
+    PushHandler(usize),   // where to resume, with the error on the stack
+    PopHandler,
+    Raise,
+    Expect(usize, String),       // line, source; checks the Truth on the stack
+    ExpectEqual(usize, String),  // line, source; compares the top two values
+
     // Objects
-    NewObject(String),    // class name
+    NewObject(String, Vec<Option<String>>), // class name, argument labels
+    InitClass(String),    // class name; evaluates shared field defaults
     GetProperty(String),  // property name
     SetProperty(String),  // property name
-    
+
+    // Collections
+    BuildList(usize),     // number of elements
+    JoinLists(usize),     // number of Lists; pushes one List with all their items
+    ForEach(usize),       // where to go when the List under the index on the stack runs out
+    Append,               // adds the value on the stack to the List under it
+    GetIndex,             // pushes the item of a List, Mapping or Text at a key
+    SetIndex,             // pops a value, key and List or Mapping, and sets the item
+    FromEnd(usize),       // turns the item number on the stack into one counting back from the end of the List or Text this far under it
+    Slice,                // pops the last and first item numbers (either may be null) and pushes part of a List or Text
+    BuildMapping(usize),  // number of key/value pairs
+
     // Types
-    CheckType(String),    // type name
-    Cast(String),        // type name
-    
+    Cast(String),         // type name
+    IsInstance(String),   // type or object name; pushes Truth
+    TypeOf,               // replaces the value on the stack with the name of its type
+
     // String Operations
-    Concat,
     Interpolate(usize),  // number of parts
-    CheckAssignmentType,
-    ConvertToString,
+    Concat(usize),       // number of values in a chain of `+`; Text is joined in one buffer
     Show,
 }
 
 #[derive(Debug, Clone)]
 pub enum Value {
     Number(f64),
+    Money(Money),
+    Quantity(Quantity),
+    Date(Date),
     String(String),
     Boolean(bool),
     Null,
-    Object(String),      // class name
-    Promise(String),     // class name
-    List(String),        // class name
-    Mapping(String),     // class name
+    Object(Arc<Instance>),
+    Class(String),       // an object kind, for reaching its shared members
+    // Lists and Mappings are shared, like Objects: assigning one or passing
+    // it to a Task hands over the same collection, not a copy
+    List(Arc<Collection<Vec<Value>>>),
+    Mapping(Arc<Collection<Mapping>>),
+}
+
+/// A heap-allocated object. Objects have identity: two handles are equal only
+/// when they refer to the same instance, regardless of their field values.
+#[derive(Debug)]
+pub struct Instance {
+    pub class_name: String,
+    pub fields: Lock<HashMap<String, Value>>,
+    /// Set by `freeze`: changing a field then fails
+    pub frozen: bool,
+}
+
+/// The items of a List or Mapping. `freeze` makes a frozen one, which can't
+/// be changed.
+#[derive(Debug)]
+pub struct Collection<T> {
+    items: Lock<T>,
+    pub frozen: bool,
+}
+
+/// Something read and changed through shared handles, like a RefCell, but
+/// which can be sent to and shared between threads, so that values (and the
+/// Runtimes holding them) can move to worker threads.
+#[derive(Debug)]
+pub struct Lock<T>(RwLock<T>);
+
+impl<T> Lock<T> {
+    pub fn new(value: T) -> Self {
+        Lock(RwLock::new(value))
+    }
+
+    // A thread that panicked while holding the lock doesn't make the value
+    // unusable, as it wouldn't with a RefCell
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        self.0.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    pub fn borrow_mut(&self) -> RwLockWriteGuard<'_, T> {
+        self.0.write().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Collection<T> {
+    fn new(items: T, frozen: bool) -> Arc<Collection<T>> {
+        Arc::new(Collection { items: Lock::new(items), frozen })
+    }
+
+    pub fn borrow(&self) -> RwLockReadGuard<'_, T> {
+        self.items.borrow()
+    }
+
+    /// The items, to change them; a frozen collection gives nothing
+    pub fn change(&self) -> Option<RwLockWriteGuard<'_, T>> {
+        (!self.frozen).then(|| self.items.borrow_mut())
+    }
+
+    /// A collection with no items yet, frozen or not, which `fill` completes.
+    /// A snapshot rebuilds collections this way, so ones that hold
+    /// themselves can be put back.
+    pub fn unfilled(frozen: bool) -> Arc<Collection<T>> where T: Default {
+        Collection::new(T::default(), frozen)
+    }
+
+    /// Puts in the items of a collection made by `unfilled`.
+    pub fn fill(&self, items: T) {
+        *self.items.borrow_mut() = items;
+    }
+}
+
+/// Names paired with their declared type name, if any.
+pub type TypedNames = Vec<(String, Option<String>)>;
+
+/// A compiled Task. Parameters carry their declared type name, if any.
+#[derive(Debug)]
+pub struct Function {
+    pub name: String,
+    pub params: TypedNames,
+    pub body: Vec<OpCode>,
+    pub lines: LineTable,
+}
+
+/// Maps instructions back to the source they were compiled from: which input
+/// they came from, and the line each statement's first instruction belongs to.
+#[derive(Debug, Clone, Default)]
+pub struct LineTable {
+    pub source: Arc<str>,
+    starts: Vec<(usize, usize)>,  // instruction, line
+}
+
+impl LineTable {
+    pub fn new(source: Arc<str>) -> Self {
+        LineTable { source, starts: Vec::new() }
+    }
+
+    /// A table from each statement's first instruction and line, as `starts`
+    /// lists them.
+    pub fn from_starts(source: Arc<str>, starts: Vec<(usize, usize)>) -> Self {
+        LineTable { source, starts }
+    }
+
+    fn mark(&mut self, instruction: usize, line: usize) {
+        // A statement that compiled to nothing gives way to the next one
+        if let Some(last) = self.starts.last_mut().filter(|(start, _)| *start == instruction) {
+            last.1 = line;
+        } else {
+            self.starts.push((instruction, line));
+        }
+    }
+
+    /// The line of the statement beginning at this instruction, if one does.
+    pub fn starting_at(&self, instruction: usize) -> Option<usize> {
+        self.starts.binary_search_by_key(&instruction, |(start, _)| *start).ok()
+            .map(|position| self.starts[position].1)
+    }
+
+    /// Each statement's first instruction and line.
+    pub fn starts(&self) -> impl Iterator<Item = (usize, usize)> + '_ {
+        self.starts.iter().copied()
+    }
+
+    /// The line of the statement this instruction belongs to.
+    pub fn line_of(&self, instruction: usize) -> Option<usize> {
+        let position = self.starts.partition_point(|(start, _)| *start <= instruction);
+        position.checked_sub(1).map(|position| self.starts[position].1)
+    }
+}
+
+/// A compiled Object declaration. Methods and computed properties are looked
+/// up by name at dispatch time, walking the `base` chain.
+#[derive(Debug)]
+pub struct Class {
+    pub name: String,
+    pub base: Option<String>,
+    pub fields: TypedNames,
+    pub initializer: Arc<Function>,  // evaluates field defaults on a new instance
+    pub methods: HashMap<String, Arc<Function>>,
+    pub getters: HashMap<String, Arc<Function>>,
+    pub setters: HashMap<String, Arc<Function>>,
+    pub constructor: Option<Arc<Function>>,
+    pub constructor_labels: Vec<String>,
+    pub shared_fields: TypedNames,
+    pub shared_initializer: Arc<Function>,
+    pub shared_methods: HashMap<String, Arc<Function>>,
+    pub behaviors: Vec<String>,
+    pub is_behavior: bool,
+}
+
+impl Class {
+    /// Every piece of compiled code the class owns.
+    pub fn functions(&self) -> impl Iterator<Item = &Arc<Function>> {
+        [&self.initializer, &self.shared_initializer].into_iter()
+            .chain(self.constructor.iter())
+            .chain(self.methods.values())
+            .chain(self.getters.values())
+            .chain(self.setters.values())
+            .chain(self.shared_methods.values())
+    }
+}
+
+/// An insertion-ordered mapping from any value to any value.
+///
+/// Keys are located through `Value`'s `Hash`/`Eq` implementations, so numbers,
+/// text, lists, mappings and objects can all be used as keys. A List or
+/// Mapping key is kept as a frozen copy, so changing the one it was made from
+/// can't leave it filed under a hash it no longer has.
+#[derive(Debug, Clone, Default)]
+pub struct Mapping {
+    entries: Vec<(Value, Value)>,
+    index: HashMap<Value, usize>,
+}
+
+impl Mapping {
+    pub fn new() -> Self {
+        Mapping::default()
+    }
+
+    pub fn get(&self, key: &Value) -> Option<&Value> {
+        self.index.get(key).map(|&position| &self.entries[position].1)
+    }
+
+    pub fn insert(&mut self, key: Value, value: Value) {
+        match self.index.get(&key) {
+            Some(&position) => self.entries[position].1 = value,
+            None => {
+                let key = match key {
+                    Value::List(_) | Value::Mapping(_) => key.as_key(&mut HashMap::new()),
+                    key => key,
+                };
+                self.index.insert(key.clone(), self.entries.len());
+                self.entries.push((key, value));
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = (&Value, &Value)> {
+        self.entries.iter().map(|(key, value)| (key, value))
+    }
+}
+
+// How many Lists and Mappings deep a value's hash looks. Past that only
+// their type counts, which keeps a List holding itself from being hashed
+// forever, and still gives equal values equal hashes.
+const HASH_DEPTH: usize = 4;
+
+impl Value {
+    // `comparing` holds the pairs of Lists and Mappings already being
+    // compared further up, which are taken to match so that cycles end
+    fn equal(&self, other: &Value, comparing: &mut Vec<(usize, usize)>) -> bool {
+        let pair = match (self, other) {
+            (Value::List(a), Value::List(b)) => (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize),
+            (Value::Mapping(a), Value::Mapping(b)) => (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize),
+            (Value::Number(a), Value::Number(b)) => return a == b || (a.is_nan() && b.is_nan()),
+            (Value::Money(a), Value::Money(b)) => return a == b,
+            (Value::Quantity(a), Value::Quantity(b)) => return a == b,
+            (Value::Date(a), Value::Date(b)) => return a == b,
+            (Value::String(a), Value::String(b)) => return a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => return a == b,
+            (Value::Null, Value::Null) => return true,
+            (Value::Object(a), Value::Object(b)) => return Arc::ptr_eq(a, b),
+            (Value::Class(a), Value::Class(b)) => return a == b,
+            _ => return false,
+        };
+        if pair.0 == pair.1 || comparing.contains(&pair) {
+            return true;
+        }
+        comparing.push(pair);
+        let equal = match (self, other) {
+            (Value::List(a), Value::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.equal(b, comparing))
+            },
+            (Value::Mapping(a), Value::Mapping(b)) => a.borrow().equal(&b.borrow(), comparing),
+            _ => false,
+        };
+        comparing.pop();
+        equal
+    }
+
+    fn hash_within<H: Hasher>(&self, state: &mut H, depth: usize) {
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Number(n) => {
+                // Normalize so that values which compare equal hash equally
+                let bits = if *n == 0.0 {
+                    0.0f64.to_bits()
+                } else if n.is_nan() {
+                    f64::NAN.to_bits()
+                } else {
+                    n.to_bits()
+                };
+                bits.hash(state);
+            },
+            Value::Money(money) => money.hash(state),
+            Value::Quantity(quantity) => quantity.hash(state),
+            Value::Date(date) => date.hash(state),
+            Value::String(s) => s.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Null => {},
+            Value::Object(instance) => std::ptr::hash(Arc::as_ptr(instance), state),
+            Value::Class(name) => name.hash(state),
+            Value::List(_) | Value::Mapping(_) if depth == 0 => {},
+            Value::List(elements) => {
+                let elements = elements.borrow();
+                elements.len().hash(state);
+                for element in elements.iter() {
+                    element.hash_within(state, depth - 1);
+                }
+            },
+            Value::Mapping(mapping) => mapping.borrow().hash_within(state, depth - 1),
+        }
+    }
+}
+
+// Equality rules:
+// - Numbers compare numerically; NaN equals itself so it can be used as a key
+// - Money equals Money of the same amount and currency, never a number
+// - Quantities are equal when they measure the same amount of the same thing
+// - Text, Truth and Nothing compare by value
+// - Lists compare element-wise, Mappings key-wise regardless of insertion order,
+//   and ones holding themselves compare the same way without going round forever
+// - Objects compare by identity; object kinds by name
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other, &mut Vec::new())
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_within(state, HASH_DEPTH)
+    }
+}
+
+impl PartialEq for Mapping {
+    fn eq(&self, other: &Self) -> bool {
+        self.equal(other, &mut Vec::new())
+    }
+}
+
+impl Eq for Mapping {}
+
+impl Hash for Mapping {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.hash_within(state, HASH_DEPTH)
+    }
+}
+
+impl Mapping {
+    fn equal(&self, other: &Mapping, comparing: &mut Vec<(usize, usize)>) -> bool {
+        self.len() == other.len()
+            && self.iter().all(|(key, value)| other.get(key).is_some_and(|other| value.equal(other, comparing)))
+    }
+
+    fn hash_within<H: Hasher>(&self, state: &mut H, depth: usize) {
+        // Combine entry hashes in an order-independent way to match equality
+        let mut combined: u64 = 0;
+        for (key, value) in self.iter() {
+            let mut hasher = DefaultHasher::new();
+            key.hash_within(&mut hasher, depth);
+            value.hash_within(&mut hasher, depth);
+            combined = combined.wrapping_add(hasher.finish());
+        }
+        self.len().hash(state);
+        combined.hash(state);
+    }
 }
 
 // Add Display implementation for Value
@@ -60,35 +444,332 @@ impl std::fmt::Display for Value {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Value::Number(n) => write!(f, "{}", n),
+            Value::Money(money) => write!(f, "{}", money),
+            Value::Quantity(quantity) => write!(f, "{}", quantity),
+            Value::Date(date) => write!(f, "{}", date),
             Value::String(s) => write!(f, "{}", s),
             Value::Boolean(b) => write!(f, "{}", b),
             Value::Null => write!(f, "null"),
-            Value::Object(name) => write!(f, "[object {}]", name),
-            Value::Promise(name) => write!(f, "[promise {}]", name),
-            Value::List(name) => write!(f, "[list {}]", name),
-            Value::Mapping(name) => write!(f, "[mapping {}]", name),
+            Value::Object(instance) => write!(f, "[object {}]", instance.class_name),
+            Value::Class(name) => write!(f, "[kind {}]", name),
+            Value::List(_) | Value::Mapping(_) => write!(f, "{}", crate::pretty::flat(self)),
         }
     }
 }
 
+impl Value {
+    pub fn list(items: Vec<Value>) -> Value {
+        Value::List(Collection::new(items, false))
+    }
+
+    pub fn mapping(mapping: Mapping) -> Value {
+        Value::Mapping(Collection::new(mapping, false))
+    }
+
+    /// What `a copy of` makes: a new List, Mapping or Object holding the same
+    /// items or field values, which are shared rather than copied in turn.
+    /// Other values have no identity, so they come back as they are.
+    pub fn copy(&self) -> Value {
+        match self {
+            Value::List(items) => Value::list(items.borrow().clone()),
+            Value::Mapping(mapping) => Value::mapping(mapping.borrow().clone()),
+            Value::Object(instance) => Value::Object(Arc::new(Instance {
+                class_name: instance.class_name.clone(),
+                fields: Lock::new(instance.fields.borrow().clone()),
+                frozen: false,
+            })),
+            other => other.clone(),
+        }
+    }
+
+    /// What `freeze` makes: a List, Mapping or Object like this one that
+    /// can't be changed. Its items are not frozen in turn, and a frozen value
+    /// comes back as it is.
+    pub fn freeze(&self) -> Value {
+        match self {
+            Value::List(items) if !items.frozen => Value::List(Collection::new(items.borrow().clone(), true)),
+            Value::Mapping(mapping) if !mapping.frozen => Value::Mapping(Collection::new(mapping.borrow().clone(), true)),
+            Value::Object(instance) if !instance.frozen => Value::Object(Arc::new(Instance {
+                class_name: instance.class_name.clone(),
+                fields: Lock::new(instance.fields.borrow().clone()),
+                frozen: true,
+            })),
+            other => other.clone(),
+        }
+    }
+
+    // The value as a Mapping keeps it for a key: Lists and Mappings, and the
+    // ones inside them, become frozen copies. `made` holds the copies made so
+    // far, so that one holding itself is copied as one that does too.
+    fn as_key(&self, made: &mut HashMap<usize, Value>) -> Value {
+        match self {
+            Value::List(items) => {
+                let id = Arc::as_ptr(items) as usize;
+                if let Some(copy) = made.get(&id) {
+                    return copy.clone();
+                }
+                let copy = Collection::unfilled(true);
+                made.insert(id, Value::List(copy.clone()));
+                let items = items.borrow().clone();
+                copy.fill(items.iter().map(|item| item.as_key(made)).collect());
+                Value::List(copy)
+            },
+            Value::Mapping(mapping) => {
+                let id = Arc::as_ptr(mapping) as usize;
+                if let Some(copy) = made.get(&id) {
+                    return copy.clone();
+                }
+                let copy = Collection::unfilled(true);
+                made.insert(id, Value::Mapping(copy.clone()));
+                let mut keyed = Mapping::new();
+                for (key, value) in mapping.borrow().clone().iter() {
+                    keyed.insert(key.clone(), value.as_key(made));
+                }
+                copy.fill(keyed);
+                Value::Mapping(copy)
+            },
+            other => other.clone(),
+        }
+    }
+
+    /// What `the type of` gives: `Whole`, `List of Text`, `Mapping of Text
+    /// to Any`, an Object's kind and so on. A List's items are named by the
+    /// type they share, with Whole and Decimal sharing Decimal, or `Any`;
+    /// an empty List or Mapping is just `List` or `Mapping`, and one inside
+    /// itself counts as `Any` there.
+    pub fn type_name(&self) -> String {
+        self.type_inside(&mut Vec::new())
+    }
+
+    // The type name, given the Lists and Mappings it is inside
+    fn type_inside(&self, open: &mut Vec<usize>) -> String {
+        fn shared<'a>(values: impl Iterator<Item = &'a Value>, open: &mut Vec<usize>) -> Option<String> {
+            values.map(|value| value.type_inside(open)).reduce(|shared, name| match (shared.as_str(), name.as_str()) {
+                (a, b) if a == b => shared,
+                ("Whole" | "Decimal", "Whole" | "Decimal") => "Decimal".to_string(),
+                _ => "Any".to_string(),
+            })
+        }
+        let id = match self {
+            Value::List(items) => Arc::as_ptr(items) as usize,
+            Value::Mapping(mapping) => Arc::as_ptr(mapping) as usize,
+            _ => 0,
+        };
+        if id != 0 && open.contains(&id) {
+            return "Any".to_string();
+        }
+        open.push(id);
+        let name = match self {
+            Value::Number(n) if n.fract() == 0.0 => "Whole".to_string(),
+            Value::Number(_) => "Decimal".to_string(),
+            Value::Money(_) => "Money".to_string(),
+            Value::Quantity(quantity) => format!("Quantity in {}", quantity.unit.phrase(true)),
+            Value::Date(_) => "Date".to_string(),
+            Value::String(_) => "Text".to_string(),
+            Value::Boolean(_) => "Truth".to_string(),
+            Value::Null => "Nothing".to_string(),
+            Value::Object(instance) => instance.class_name.clone(),
+            Value::Class(_) => "Kind".to_string(),
+            Value::List(items) => match shared(items.borrow().iter(), open) {
+                Some(item) => format!("List of {}", item),
+                None => "List".to_string(),
+            },
+            Value::Mapping(mapping) => {
+                let mapping = mapping.borrow();
+                match (shared(mapping.iter().map(|(key, _)| key), open), shared(mapping.iter().map(|(_, value)| value), open)) {
+                    (Some(key), Some(value)) => format!("Mapping of {} to {}", key, value),
+                    _ => "Mapping".to_string(),
+                }
+            },
+        };
+        open.pop();
+        name
+    }
+
+    /// `item key of` this List, Mapping or Text. Lists and Text count from 1,
+    /// Text by the characters a reader sees (see `graphemes`).
+    pub fn item(&self, key: &Value) -> Result<Value, Failure> {
+        match self {
+            Value::List(items) => {
+                let items = items.borrow();
+                Ok(items[position(key, items.len(), "List")?].clone())
+            },
+            Value::String(text) => {
+                let characters = graphemes(text);
+                let position = position(key, characters.len(), "Text")?;
+                Ok(Value::String(characters[position].to_string()))
+            },
+            Value::Mapping(mapping) => mapping.borrow().get(key).cloned()
+                .ok_or_else(|| Failure::new("KeyError", format!("No value for {} in the Mapping", key.nested()))),
+            other => Err(Failure::new("TypeError", format!("Type mismatch: expected a List, Mapping or Text, got {}", other))),
+        }
+    }
+
+    /// `items first through last of` this List or Text, or with a null last
+    /// `everything after item first of`, or with a null first `everything
+    /// before item last of` it.
+    pub fn slice(&self, first: &Value, last: &Value) -> Result<Value, Failure> {
+        let (count, whole) = match self {
+            Value::List(items) => (items.borrow().len(), "List"),
+            Value::String(text) => (graphemes(text).len(), "Text"),
+            other => return Err(Failure::new("TypeError", format!("Type mismatch: expected a List or Text, got {}", other))),
+        };
+        let range = match (first, last) {
+            (Value::Null, last) => 0..position(last, count, whole)?,
+            (first, Value::Null) => position(first, count, whole)? + 1..count,
+            (first, last) => {
+                let (start, end) = (position(first, count, whole)?, position(last, count, whole)?);
+                if start > end {
+                    return Err(Failure::new("IndexError", format!("Item {} is out of range: it comes before item {}", last, first)));
+                }
+                start..end + 1
+            },
+        };
+        Ok(match self {
+            Value::List(items) => Value::list(items.borrow()[range].to_vec()),
+            _ => Value::String(graphemes(&self.to_string())[range].concat()),
+        })
+    }
+
+    /// The item number of `the number to last item` of this List or Text,
+    /// counting forward from 1 like any other.
+    pub fn counted_back(&self, number: usize) -> Result<Value, Failure> {
+        let (count, whole) = match self {
+            Value::List(items) => (items.borrow().len(), "List"),
+            Value::String(text) => (graphemes(text).len(), "Text"),
+            other => return Err(Failure::new("TypeError", format!("Type mismatch: expected a List or Text, got {}", other))),
+        };
+        match number <= count {
+            true => Ok(Value::Number((count - number + 1) as f64)),
+            false if number == 1 => Err(out_of_range("The last item", count, whole)),
+            false => Err(out_of_range(&format!("The {}{} to last item", number, ordinal_suffix(number)), count, whole)),
+        }
+    }
+
+    /// Sets `item key of` this List or Mapping. A List's item must already
+    /// exist; a Mapping gains the key if it's new.
+    pub fn set_item(&self, key: Value, value: Value) -> Result<(), Failure> {
+        match self {
+            Value::List(items) => {
+                let count = items.borrow().len();
+                let position = position(&key, count, "List")?;
+                let mut items = items.change().ok_or_else(|| Failure::new("FrozenError", "Cannot change an item: this List is frozen"))?;
+                items[position] = value;
+                Ok(())
+            },
+            Value::Mapping(mapping) => {
+                mapping.change().ok_or_else(|| Failure::new("FrozenError", "Cannot change an item: this Mapping is frozen"))?.insert(key, value);
+                Ok(())
+            },
+            Value::String(_) => Err(Failure::new("TypeError", "Type mismatch: Text can't be changed; make new Text instead")),
+            other => Err(Failure::new("TypeError", format!("Type mismatch: expected a List or Mapping, got {}", other))),
+        }
+    }
+
+    /// What `is the same as` asks: like `is`, but Objects of the same kind
+    /// match when their fields do, looking inside Lists, Mappings and Objects
+    /// all the way down.
+    pub fn same_as(&self, other: &Value) -> bool {
+        self.same(other, &mut Vec::new())
+    }
+
+    // `comparing` holds the pairs of Lists, Mappings and Objects already
+    // being compared further up, which are taken to match so that cycles end
+    fn same(&self, other: &Value, comparing: &mut Vec<(usize, usize)>) -> bool {
+        let pair = match (self, other) {
+            (Value::List(a), Value::List(b)) => (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize),
+            (Value::Mapping(a), Value::Mapping(b)) => (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize),
+            (Value::Object(a), Value::Object(b)) => (Arc::as_ptr(a) as usize, Arc::as_ptr(b) as usize),
+            _ => return self == other,
+        };
+        if pair.0 == pair.1 || comparing.contains(&pair) {
+            return true;
+        }
+        comparing.push(pair);
+        let same = match (self, other) {
+            (Value::List(a), Value::List(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().zip(b.iter()).all(|(a, b)| a.same(b, comparing))
+            },
+            (Value::Mapping(a), Value::Mapping(b)) => {
+                let (a, b) = (a.borrow(), b.borrow());
+                a.len() == b.len() && a.iter().all(|(key, a)| b.get(key).is_some_and(|b| a.same(b, comparing)))
+            },
+            (Value::Object(a), Value::Object(b)) if a.class_name == b.class_name => {
+                let (fields, others) = (a.fields.borrow(), b.fields.borrow());
+                fields.len() == others.len()
+                    && fields.iter().all(|(name, a)| others.get(name).is_some_and(|b| a.same(b, comparing)))
+            },
+            _ => false,
+        };
+        comparing.pop();
+        same
+    }
+
+    // Text inside a collection (or a failure report) is quoted so `["a, b"]`
+    // and `["a", "b"]` stay distinguishable
+    pub fn nested(&self) -> String {
+        match self {
+            Value::String(s) => format!("\"{}\"", s),
+            other => other.to_string(),
+        }
+    }
+}
+
+// Where a 1-based item number points in a List or Text with `count` items
+fn position(key: &Value, count: usize, whole: &str) -> Result<usize, Failure> {
+    match key {
+        Value::Number(n) if n.fract() == 0.0 && *n >= 1.0 && *n <= count as f64 => Ok(*n as usize - 1),
+        Value::Number(n) if n.fract() == 0.0 => Err(out_of_range(&format!("Item {}", n), count, whole)),
+        other => Err(Failure::new("TypeError", format!("Type mismatch: expected a Whole item number, got {}", other.nested()))),
+    }
+}
+
+fn out_of_range(item: &str, count: usize, whole: &str) -> Failure {
+    let items = if whole == "Text" { "character" } else { "item" };
+    Failure::new("IndexError", format!("{} is out of range: the {} has {} {}{}", item, whole, count, items, if count == 1 { "" } else { "s" }))
+}
+
 pub struct BytecodeGenerator {
     instructions: Vec<OpCode>,
-    constants: Vec<Value>,
+    lines: LineTable,
     variables: HashMap<String, usize>,
-    current_scope: usize,
-    loop_starts: Vec<usize>,
-    loop_ends: Vec<usize>,
+    functions: Vec<Arc<Function>>,
+    classes: Vec<Arc<Class>>,
+    // How many hidden variables have been made, so each gets its own name
+    hidden: usize,
+    // How many error handlers the code being generated runs under
+    handlers: usize,
+    // The loops around the code being generated, innermost last
+    loops: Vec<LoopExits>,
+    // The `always` blocks of the `do`s around the code being generated, each
+    // with how many handlers there were before its `do`
+    cleanups: Vec<(usize, Node)>,
+}
+
+// A loop being generated: its name, the handlers and `always` blocks it
+// started under, the `stop` jumps to point past its end and the `continue`
+// jumps to point at its next time round
+struct LoopExits {
+    label: Option<String>,
+    handlers: usize,
+    cleanups: usize,
+    stops: Vec<usize>,
+    continues: Vec<usize>,
 }
 
 impl BytecodeGenerator {
-    pub fn new() -> Self {
+    pub fn new(source: Arc<str>) -> Self {
         BytecodeGenerator {
             instructions: Vec::new(),
-            constants: Vec::new(),
+            lines: LineTable::new(source),
             variables: HashMap::new(),
-            current_scope: 0,
-            loop_starts: Vec::new(),
-            loop_ends: Vec::new(),
+            functions: Vec::new(),
+            classes: Vec::new(),
+            hidden: 0,
+            handlers: 0,
+            loops: Vec::new(),
+            cleanups: Vec::new(),
         }
     }
 
@@ -99,6 +780,21 @@ impl BytecodeGenerator {
         Ok(self.instructions.clone())
     }
 
+    /// Where the generated top-level statements start in the source.
+    pub fn lines(&self) -> &LineTable {
+        &self.lines
+    }
+
+    /// Tasks declared by the generated code, to be registered before it runs.
+    pub fn functions(&self) -> &[Arc<Function>] {
+        &self.functions
+    }
+
+    /// Objects declared by the generated code, to be registered before it runs.
+    pub fn classes(&self) -> &[Arc<Class>] {
+        &self.classes
+    }
+
     fn generate_node(&mut self, node: &Node) -> Result<(), String> {
         match node {
             Node::VariableDecl { name, type_annotation, initializer } => {
@@ -110,49 +806,64 @@ impl BytecodeGenerator {
                     self.emit(OpCode::Push(Value::Null));
                 }
 
-                // If there's a type annotation, check it
-                if let Some(type_node) = type_annotation {
-                    if let Node::TypeAnnotation(type_name) = &**type_node {
-                        self.emit(OpCode::CheckType(type_name.clone()));
-                    }
-                }
+                // Store the variable, with its type if it was given one
+                self.emit(match type_annotation {
+                    Some(type_node) => OpCode::DeclareVar(name.clone(), Self::type_name(type_node)),
+                    None => OpCode::StoreVar(name.clone()),
+                });
+                self.variables.insert(name.clone(), self.variables.len());
+                Ok(())
+            },
 
-                // Store the variable
+            Node::Change { name, operator, amount } => {
+                self.emit(OpCode::LoadVar(name.clone()));
+                self.generate_node(amount)?;
+                self.emit(match operator {
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    _ => OpCode::Multiply,
+                });
                 self.emit(OpCode::StoreVar(name.clone()));
                 Ok(())
             },
 
             Node::Assignment { name, value } => {
-                // Generate code for the value first
+                // Generate code for the value first; storing it checks it
+                // against the variable's declared type, if it has one
                 self.generate_node(value)?;
-
-                // Only generate LoadVar and CheckAssignmentType if the variable exists
-                if self.variables.contains_key(name) {
-                    self.emit(OpCode::LoadVar(name.to_string()));
-                    self.emit(OpCode::CheckAssignmentType);
-                }
-                
-                // Store the variable
                 self.emit(OpCode::StoreVar(name.to_string()));
-                
+
                 // Track the variable if it's new
                 if !self.variables.contains_key(name) {
                     self.variables.insert(name.clone(), self.variables.len());
                 }
-                
+
                 Ok(())
             },
 
+            Node::Binary { left, operator: TokenType::Plus, .. } if matches!(**left, Node::Binary { operator: TokenType::Plus, .. }) => {
+                self.generate_sum(node)
+            },
+
             Node::Binary { left, operator, right } => {
                 self.generate_node(left)?;
                 self.generate_node(right)?;
-                
+
                 let opcode = match operator {
-                    crate::tokenizer::TokenType::Plus => OpCode::Add,
-                    crate::tokenizer::TokenType::Minus => OpCode::Subtract,
-                    crate::tokenizer::TokenType::Multiply => OpCode::Multiply,
-                    crate::tokenizer::TokenType::Divide => OpCode::Divide,
-                    crate::tokenizer::TokenType::Modulo => OpCode::Modulo,
+                    TokenType::Plus => OpCode::Add,
+                    TokenType::Minus => OpCode::Subtract,
+                    TokenType::Multiply => OpCode::Multiply,
+                    TokenType::Divide => OpCode::Divide,
+                    TokenType::Modulo => OpCode::Modulo,
+                    TokenType::Power => OpCode::Power,
+                    TokenType::Is => OpCode::Equal,
+                    TokenType::NotEquals => OpCode::NotEqual,
+                    TokenType::GreaterThan => OpCode::Greater,
+                    TokenType::GreaterThanOrEqual => OpCode::GreaterEqual,
+                    TokenType::LessThan => OpCode::Less,
+                    TokenType::LessThanOrEqual => OpCode::LessEqual,
+                    TokenType::And => OpCode::And,
+                    TokenType::Or => OpCode::Or,
                     _ => return Err("Unsupported binary operator".to_string()),
                 };
                 self.instructions.push(opcode);
@@ -160,27 +871,169 @@ impl BytecodeGenerator {
             },
 
             Node::Call { callee, args } => {
-                // Generate code for arguments first
-                for arg in args {
-                    self.generate_node(arg)?;
-                }
-                
-                // Generate code for the callee
                 match **callee {
                     Node::Variable(ref name) => {
+                        for arg in args {
+                            self.generate_node(arg)?;
+                        }
                         self.emit(OpCode::Call(name.clone(), args.len()));
                         Ok(())
                     },
+                    Node::Get { ref object, ref name } => {
+                        // The receiver sits below the arguments on the stack
+                        self.generate_node(object)?;
+                        for arg in args {
+                            self.generate_node(arg)?;
+                        }
+                        self.emit(OpCode::CallMethod(name.clone(), args.len()));
+                        Ok(())
+                    },
                     _ => Err("Only direct function calls are supported".to_string()),
                 }
             },
 
+            Node::TaskDecl { name, params, body, .. } => {
+                let function = self.compile_function(name, params, body, &[])?;
+                self.functions.push(function);
+                Ok(())
+            },
+
+            Node::ObjectDecl { name, .. } => {
+                let class = self.compile_class(node)?;
+                self.classes.push(class);
+
+                // Shared fields are initialized where the declaration appears
+                self.emit(OpCode::InitClass(name.clone()));
+                Ok(())
+            },
+
+            Node::RaiseStmt { message, error_type } => {
+                match error_type {
+                    // `raise "text" as Kind` makes a Kind with that message
+                    Some(kind) => {
+                        self.emit(OpCode::NewObject(Self::type_name(kind), Vec::new()));
+                        self.emit(OpCode::Dup);
+                        self.generate_node(message)?;
+                        self.emit(OpCode::SetProperty("message".to_string()));
+                    },
+                    None => self.generate_node(message)?,
+                }
+                self.emit(OpCode::Raise);
+                Ok(())
+            },
+
+            // Tests only run under `nair test`, which runs each body on its own
+            Node::TestDecl { .. } => Ok(()),
+
+            Node::ExpectStmt { actual, expected, line, source } => {
+                self.generate_node(actual)?;
+                match expected {
+                    Some(expected) => {
+                        self.generate_node(expected)?;
+                        self.emit(OpCode::ExpectEqual(*line, source.clone()));
+                    },
+                    None => self.emit(OpCode::Expect(*line, source.clone())),
+                }
+                Ok(())
+            },
+
+            Node::TryStmt { body, handlers, always } => {
+                if let Some(always) = always {
+                    self.cleanups.push((self.handlers, always.as_ref().clone()));
+                }
+                let try_handler = self.instructions.len();
+                self.emit(OpCode::PushHandler(0));
+                self.handlers += 1;
+                self.generate_node(body)?;
+                self.handlers -= 1;
+                self.emit(OpCode::PopHandler);
+                let mut to_finish = vec![self.instructions.len()];
+                self.emit(OpCode::Jump(0));
+
+                // The error is on the stack here. With an `always` block, errors
+                // from the fail clauses must still pass through it.
+                self.patch_jump(try_handler);
+                let cleanup_handler = self.instructions.len();
+                if always.is_some() {
+                    self.emit(OpCode::PushHandler(0));
+                }
+                for clause in handlers {
+                    let mut skip = None;
+                    if let Some(kind) = &clause.kind {
+                        self.emit(OpCode::Dup);
+                        self.emit(OpCode::IsInstance(Self::type_name(kind)));
+                        skip = Some(self.instructions.len());
+                        self.emit(OpCode::JumpIfFalse(0));
+                    }
+                    match &clause.name {
+                        Some(name) => self.emit(OpCode::StoreVar(name.clone())),
+                        None => self.emit(OpCode::Pop),
+                    }
+                    let cleanup = usize::from(always.is_some());
+                    self.handlers += cleanup;
+                    self.generate_node(&clause.body)?;
+                    self.handlers -= cleanup;
+                    if always.is_some() {
+                        self.emit(OpCode::PopHandler);
+                    }
+                    to_finish.push(self.instructions.len());
+                    self.emit(OpCode::Jump(0));
+                    if let Some(skip) = skip {
+                        self.patch_jump(skip);
+                    }
+                }
+                // No clause matched: pass the error on
+                self.emit(OpCode::Raise);
+
+                if let Some(always) = always {
+                    self.cleanups.pop();
+                    self.patch_jump(cleanup_handler);
+                    self.generate_node(always)?;
+                    self.emit(OpCode::Raise);
+                }
+                for jump in to_finish {
+                    self.patch_jump(jump);
+                }
+                if let Some(always) = always {
+                    self.generate_node(always)?;
+                }
+                Ok(())
+            },
+
+            Node::ReturnStmt(value) => {
+                self.generate_node(value)?;
+                self.generate_return()
+            },
+
+            Node::GuardStmt { condition, value } => {
+                self.generate_node(condition)?;
+                let skip = self.instructions.len();
+                self.emit(OpCode::JumpIfFalse(0));
+                self.generate_node(value)?;
+                self.generate_return()?;
+                self.patch_jump(skip);
+                Ok(())
+            },
+
+            Node::Set { object, name, value } => {
+                self.generate_node(object)?;
+                self.generate_node(value)?;
+                self.emit(OpCode::SetProperty(name.clone()));
+                Ok(())
+            },
+
             Node::ShowStmt(expr) => {
                 self.generate_node(expr)?;
                 self.emit(OpCode::Show);
                 Ok(())
             },
 
+            Node::ExpressionStmt(expr) => {
+                self.generate_node(expr)?;
+                self.emit(OpCode::Pop);
+                Ok(())
+            },
+
             Node::Block(statements) => {
                 for stmt in statements {
                     self.generate_node(stmt)?;
@@ -188,67 +1041,169 @@ impl BytecodeGenerator {
                 Ok(())
             },
 
-            Node::WhenStmt { condition, then_branch, else_branch } => {
-                // Generate condition code
-                self.generate_node(condition)?;
-                
-                // Add jump-if-false instruction (we'll patch the jump address later)
-                let jump_if_false_pos = self.instructions.len();
-                self.instructions.push(OpCode::JumpIfFalse(0));
-                
-                // Generate then branch
-                self.generate_node(then_branch)?;
-                
-                if let Some(else_branch) = else_branch {
-                    // Add jump instruction to skip else branch (we'll patch the address later)
-                    let jump_pos = self.instructions.len();
-                    self.instructions.push(OpCode::Jump(0));
-                    
-                    // Patch the jump-if-false address
-                    let else_start = self.instructions.len();
-                    if let OpCode::JumpIfFalse(ref mut addr) = self.instructions[jump_if_false_pos] {
-                        *addr = else_start;
-                    }
-                    
-                    // Generate else branch
-                    self.generate_node(else_branch)?;
-                    
-                    // Patch the jump address
-                    let after_else = self.instructions.len();
-                    if let OpCode::Jump(ref mut addr) = self.instructions[jump_pos] {
-                        *addr = after_else;
+            Node::Line(line) => {
+                self.lines.mark(self.instructions.len(), *line);
+                Ok(())
+            },
+
+            Node::WhenStmt { condition, then_branch, or_when, else_branch } => {
+                // Each branch that runs jumps past all the ones after it
+                let mut to_end = Vec::new();
+                let branches = std::iter::once((None, condition.as_ref(), then_branch.as_ref()))
+                    .chain(or_when.iter().map(|branch| (Some(branch.line), &branch.condition, &branch.body)));
+                let count = or_when.len() + 1;
+                for (index, (line, condition, body)) in branches.enumerate() {
+                    if let Some(line) = line {
+                        self.lines.mark(self.instructions.len(), line);
                     }
-                } else {
-                    // Patch the jump-if-false address
-                    let after_then = self.instructions.len();
-                    if let OpCode::JumpIfFalse(ref mut addr) = self.instructions[jump_if_false_pos] {
-                        *addr = after_then;
+                    self.generate_condition(condition)?;
+                    let jump_if_false_pos = self.instructions.len();
+                    self.instructions.push(OpCode::JumpIfFalse(0));
+                    self.generate_node(body)?;
+                    if index + 1 < count || else_branch.is_some() {
+                        to_end.push(self.instructions.len());
+                        self.instructions.push(OpCode::Jump(0));
                     }
+                    self.patch_jump(jump_if_false_pos);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.generate_node(else_branch)?;
+                }
+                for jump in to_end {
+                    self.patch_jump(jump);
                 }
                 Ok(())
             },
 
-            Node::LoopStmt { condition, body } => {
+            Node::LoopStmt { condition, body, label } => {
                 let loop_start = self.instructions.len();
-                
+
                 // Generate condition
-                self.generate_node(condition)?;
-                
+                self.generate_condition(condition)?;
+
                 // Add conditional jump to exit loop
                 let jump_if_false_pos = self.instructions.len();
                 self.instructions.push(OpCode::JumpIfFalse(0));
-                
+
                 // Generate loop body
-                self.generate_node(body)?;
-                
+                self.generate_loop(body, label)?;
+                self.next_round();
+
                 // Add jump back to start
                 self.instructions.push(OpCode::Jump(loop_start));
-                
+
                 // Patch the exit jump address
                 let after_loop = self.instructions.len();
                 if let OpCode::JumpIfFalse(ref mut addr) = self.instructions[jump_if_false_pos] {
                     *addr = after_loop;
                 }
+                self.end_loop();
+                Ok(())
+            },
+
+            Node::KeepDoingStmt { body, label } => {
+                let start = self.instructions.len();
+                self.generate_loop(body, label)?;
+                self.next_round();
+                self.emit(OpCode::Jump(start));
+                self.end_loop();
+                Ok(())
+            },
+
+            Node::StopStmt(label) => self.generate_jump("stop", label),
+            Node::ContinueStmt(label) => self.generate_jump("continue", label),
+
+            Node::RepeatStmt { count, counter, body, label } => {
+                // The count and the number of the next time round are kept in
+                // hidden variables, so the body can't change them
+                self.hidden += 1;
+                let limit = format!("<repeat {}>", self.hidden);
+                let next = format!("<repeat {} next>", self.hidden);
+                self.generate_node(count)?;
+                self.emit(OpCode::StoreVar(limit.clone()));
+                self.emit(OpCode::Push(Value::Number(1.0)));
+                self.emit(OpCode::StoreVar(next.clone()));
+
+                let start = self.instructions.len();
+                self.emit(OpCode::LoadVar(next.clone()));
+                self.emit(OpCode::LoadVar(limit));
+                self.emit(OpCode::LessEqual);
+                let exit = self.instructions.len();
+                self.emit(OpCode::JumpIfFalse(0));
+                if let Some(counter) = counter {
+                    self.emit(OpCode::LoadVar(next.clone()));
+                    self.emit(OpCode::StoreVar(counter.clone()));
+                    if !self.variables.contains_key(counter) {
+                        self.variables.insert(counter.clone(), self.variables.len());
+                    }
+                }
+                self.generate_loop(body, label)?;
+                self.next_round();
+                self.emit(OpCode::LoadVar(next.clone()));
+                self.emit(OpCode::Push(Value::Number(1.0)));
+                self.emit(OpCode::Add);
+                self.emit(OpCode::StoreVar(next));
+                self.emit(OpCode::Jump(start));
+                self.patch_jump(exit);
+                self.end_loop();
+                Ok(())
+            },
+
+            Node::MeasureStmt { runs, result, body } => {
+                // Each run's time, in milliseconds, goes on a hidden list
+                // that `<timing>` sums up at the end
+                self.hidden += 1;
+                let limit = format!("<measure {}>", self.hidden);
+                let next = format!("<measure {} next>", self.hidden);
+                let times = format!("<measure {} times>", self.hidden);
+                let started = format!("<measure {} start>", self.hidden);
+                match runs {
+                    Some(runs) => self.generate_node(runs)?,
+                    None => self.emit(OpCode::Push(Value::Number(DEFAULT_RUNS))),
+                }
+                self.emit(OpCode::StoreVar(limit.clone()));
+                self.emit(OpCode::Push(Value::Number(1.0)));
+                self.emit(OpCode::StoreVar(next.clone()));
+                self.emit(OpCode::BuildList(0));
+                self.emit(OpCode::StoreVar(times.clone()));
+
+                let start = self.instructions.len();
+                self.emit(OpCode::LoadVar(next.clone()));
+                self.emit(OpCode::LoadVar(limit));
+                self.emit(OpCode::LessEqual);
+                let exit = self.instructions.len();
+                self.emit(OpCode::JumpIfFalse(0));
+                self.emit(OpCode::Call("<clock>".to_string(), 0));
+                self.emit(OpCode::StoreVar(started.clone()));
+                self.generate_loop(body, &None)?;
+                self.next_round();
+                self.emit(OpCode::LoadVar(times.clone()));
+                self.emit(OpCode::Call("<clock>".to_string(), 0));
+                self.emit(OpCode::LoadVar(started));
+                self.emit(OpCode::Subtract);
+                self.emit(OpCode::Append);
+                self.emit(OpCode::LoadVar(next.clone()));
+                self.emit(OpCode::Push(Value::Number(1.0)));
+                self.emit(OpCode::Add);
+                self.emit(OpCode::StoreVar(next));
+                self.emit(OpCode::Jump(start));
+                self.patch_jump(exit);
+                self.end_loop();
+
+                self.emit(OpCode::LoadVar(times));
+                match result {
+                    Some(result) => {
+                        self.emit(OpCode::Call("<timing>".to_string(), 1));
+                        self.emit(OpCode::StoreVar(result.clone()));
+                        if !self.variables.contains_key(result) {
+                            self.variables.insert(result.clone(), self.variables.len());
+                        }
+                    },
+                    None => {
+                        self.emit(OpCode::Call("<timing report>".to_string(), 1));
+                        self.emit(OpCode::Show);
+                    },
+                }
                 Ok(())
             },
 
@@ -258,31 +1213,185 @@ impl BytecodeGenerator {
                 Ok(())
             },
 
+            Node::SafeGet { object, name } => {
+                // A Nothing object is left on the stack as the result
+                self.generate_node(object)?;
+                self.emit(OpCode::Dup);
+                self.emit(OpCode::Push(Value::Null));
+                self.emit(OpCode::Equal);
+                let present = self.instructions.len();
+                self.emit(OpCode::JumpIfFalse(0));
+                let end = self.instructions.len();
+                self.emit(OpCode::Jump(0));
+                self.patch_jump(present);
+                self.emit(OpCode::GetProperty(name.clone()));
+                self.patch_jump(end);
+                Ok(())
+            },
+
             Node::New { class_name, args } => {
-                for arg in args {
+                for (_, arg) in args {
                     self.generate_node(arg)?;
                 }
-                self.emit(OpCode::NewObject(class_name.clone()));
+                let labels = args.iter().map(|(label, _)| label.clone()).collect();
+                self.emit(OpCode::NewObject(class_name.clone(), labels));
+                Ok(())
+            },
+
+            Node::Cast { value, target } => {
+                self.generate_node(value)?;
+                self.emit(OpCode::Cast(Self::type_name(target)));
+                Ok(())
+            },
+
+            Node::TypeOf { value } => {
+                self.generate_node(value)?;
+                self.emit(OpCode::TypeOf);
+                Ok(())
+            },
+
+            Node::Conditional { value, condition, otherwise } => {
+                self.generate_node(condition)?;
+                let to_otherwise = self.instructions.len();
+                self.emit(OpCode::JumpIfFalse(0));
+                self.generate_node(value)?;
+                let to_end = self.instructions.len();
+                self.emit(OpCode::Jump(0));
+                self.patch_jump(to_otherwise);
+                self.generate_node(otherwise)?;
+                self.patch_jump(to_end);
+                Ok(())
+            },
+
+            Node::SameAs { left, right, negated } => {
+                self.generate_node(left)?;
+                self.generate_node(right)?;
+                self.emit(OpCode::SameAs);
+                if *negated {
+                    self.emit(OpCode::Push(Value::Boolean(false)));
+                    self.emit(OpCode::Equal);
+                }
+                Ok(())
+            },
+
+            Node::TypeTest { value, target, negated } => {
+                self.generate_node(value)?;
+                self.emit(OpCode::IsInstance(Self::type_name(target)));
+                if *negated {
+                    self.emit(OpCode::Push(Value::Boolean(false)));
+                    self.emit(OpCode::Equal);
+                }
                 Ok(())
             },
 
             Node::StringInterpolation { parts } => {
-                self.generate_string_interpolation(parts)?;
+                for part in parts {
+                    self.generate_node(part)?;
+                }
                 self.emit(OpCode::Interpolate(parts.len()));
                 Ok(())
             },
 
-            Node::Literal(value) => {
-                match value {
-                    Value::Number(n) => self.emit(OpCode::Push(Value::Number(*n))),
-                    Value::String(s) => self.emit(OpCode::Push(Value::String(s.clone()))),
-                    Value::Boolean(b) => self.emit(OpCode::Push(Value::Boolean(*b))),
-                    Value::Null => self.emit(OpCode::Push(Value::Null)),
-                    Value::Object(name) => self.emit(OpCode::Push(Value::Object(name.clone()))),
-                    Value::Promise(name) => self.emit(OpCode::Push(Value::Promise(name.clone()))),
-                    Value::List(name) => self.emit(OpCode::Push(Value::List(name.clone()))),
-                    Value::Mapping(name) => self.emit(OpCode::Push(Value::Mapping(name.clone()))),
+            Node::ArrayLiteral { elements, .. } => {
+                for element in elements {
+                    self.generate_node(element)?;
                 }
+                self.emit(OpCode::BuildList(elements.len()));
+                Ok(())
+            },
+
+            Node::Comprehension { value, name, list, condition } => {
+                // The new List grows in a hidden variable while the List
+                // being gone through and the next index wait on the stack
+                self.hidden += 1;
+                let accumulator = format!("<each {}>", self.hidden);
+                self.emit(OpCode::BuildList(0));
+                self.emit(OpCode::StoreVar(accumulator.clone()));
+                self.generate_node(list)?;
+                self.emit(OpCode::Push(Value::Number(0.0)));
+                let start = self.instructions.len();
+                self.emit(OpCode::ForEach(0));
+                self.emit(OpCode::StoreVar(name.clone()));
+                let skip = match condition {
+                    Some(condition) => {
+                        self.generate_node(condition)?;
+                        let skip = self.instructions.len();
+                        self.emit(OpCode::JumpIfFalse(0));
+                        Some(skip)
+                    },
+                    None => None,
+                };
+                self.emit(OpCode::LoadVar(accumulator.clone()));
+                self.generate_node(value)?;
+                self.emit(OpCode::Append);
+                self.emit(OpCode::Jump(start));
+                if let Some(skip) = skip {
+                    self.patch_jump(skip);
+                    self.emit(OpCode::Jump(start));
+                }
+                self.patch_jump(start);
+                self.emit(OpCode::LoadVar(accumulator));
+                Ok(())
+            },
+
+            Node::Index { object, key, .. } => {
+                self.generate_node(object)?;
+                self.generate_item_number(key, 1)?;
+                self.emit(OpCode::GetIndex);
+                Ok(())
+            },
+
+            Node::SetIndex { object, key, value, .. } => {
+                self.generate_node(object)?;
+                self.generate_item_number(key, 1)?;
+                self.generate_node(value)?;
+                self.emit(OpCode::SetIndex);
+                Ok(())
+            },
+
+            Node::Ordinal { number, .. } => {
+                self.emit(OpCode::Push(Value::Number(*number as f64)));
+                Ok(())
+            },
+
+            Node::Slice { object, first, last } => {
+                self.generate_node(object)?;
+                for (depth, bound) in [(1, first), (2, last)] {
+                    match bound {
+                        Some(number) => self.generate_item_number(number, depth)?,
+                        None => self.emit(OpCode::Push(Value::Null)),
+                    }
+                }
+                self.emit(OpCode::Slice);
+                Ok(())
+            },
+
+            Node::ListOf { parts } => self.generate_joined(parts.iter().map(|(all, part)| (*all, part)).collect()),
+
+            Node::FollowedBy { .. } => {
+                // `a followed by b followed by c` joins all three at once
+                let mut lists = Vec::new();
+                let mut node = node;
+                while let Node::FollowedBy { left, right } = node {
+                    lists.push((true, right.as_ref()));
+                    node = left;
+                }
+                lists.push((true, node));
+                lists.reverse();
+                self.generate_joined(lists)
+            },
+
+            Node::MappingLiteral { entries } => {
+                for (key, _, value) in entries {
+                    self.emit(OpCode::Push(Value::String(key.clone())));
+                    self.generate_node(value)?;
+                }
+                self.emit(OpCode::BuildMapping(entries.len()));
+                Ok(())
+            },
+
+            Node::Literal(value) => {
+                self.emit(OpCode::Push(value.clone()));
                 Ok(())
             },
 
@@ -296,54 +1405,310 @@ impl BytecodeGenerator {
         }
     }
 
-    fn generate_type_annotation(&mut self, type_node: Node) -> Result<(), String> {
-        match type_node {
-            Node::TypeAnnotation(type_name) => {
-                // For variable declarations with no initializer, we'll push null first
-                self.instructions.push(OpCode::Push(Value::Null));
-                self.instructions.push(OpCode::CheckType(type_name));
+    // The condition of a `when` or `loop`. The `x is false` that `unless` and
+    // `until` stand for becomes `x` and `Not`, so that x must still be a Truth
+    fn generate_condition(&mut self, condition: &Node) -> Result<(), String> {
+        match negated(condition) {
+            Some(condition) => {
+                self.generate_node(condition)?;
+                self.emit(OpCode::Not);
                 Ok(())
             },
-            _ => Err("Expected type annotation".to_string()),
+            None => self.generate_node(condition),
         }
     }
 
-    fn emit(&mut self, opcode: OpCode) {
-        self.instructions.push(opcode);
+    // Generates the body of a loop, which `stop` leaves; the loop's own code
+    // must call `next_round` where the next time round starts and
+    // `end_loop` once the instruction after it is next
+    fn generate_loop(&mut self, body: &Node, label: &Option<String>) -> Result<(), String> {
+        self.loops.push(LoopExits {
+            label: label.clone(),
+            handlers: self.handlers,
+            cleanups: self.cleanups.len(),
+            stops: Vec::new(),
+            continues: Vec::new(),
+        });
+        self.generate_node(body)
     }
 
-    fn generate_assignment(&mut self, name: &str, value: &Node) -> Result<(), String> {
-        // Generate code for the value first
-        self.generate_node(value)?;
+    fn next_round(&mut self) {
+        if let Some(exits) = self.loops.last_mut() {
+            for jump in std::mem::take(&mut exits.continues) {
+                self.patch_jump(jump);
+            }
+        }
+    }
 
-        // For assignments, we only need LoadVar if the variable exists
-        if self.variables.contains_key(name) {
-            self.emit(OpCode::LoadVar(name.to_string()));
-            self.emit(OpCode::CheckAssignmentType);
+    fn end_loop(&mut self) {
+        if let Some(exits) = self.loops.pop() {
+            for stop in exits.stops {
+                self.patch_jump(stop);
+            }
+        }
+    }
+
+    // Runs the `always` blocks of the `do`s being left, innermost first, from
+    // the one at `from` in `cleanups` out. Each runs outside its own `do`,
+    // under the ones around it, so the handlers and cleanups are left as they
+    // are around the outermost of them.
+    fn generate_cleanups(&mut self, from: usize) -> Result<(), String> {
+        let cleanups = std::mem::take(&mut self.cleanups);
+        for index in (from..cleanups.len()).rev() {
+            let (outer, always) = &cleanups[index];
+            for _ in *outer..self.handlers {
+                self.emit(OpCode::PopHandler);
+            }
+            self.handlers = *outer;
+            self.cleanups = cleanups[..index].to_vec();
+            self.generate_node(always)?;
         }
-        
-        // Store the result
-        self.emit(OpCode::StoreVar(name.to_string()));
         Ok(())
     }
 
-    fn generate_string_interpolation(&mut self, parts: &[Node]) -> Result<(), String> {
-        for part in parts {
-            match part {
-                Node::Literal(Value::String(s)) => {
-                    self.emit(OpCode::Push(Value::String(s.clone())));
-                },
-                Node::Variable(name) => {
-                    self.emit(OpCode::LoadVar(name.clone()));
-                    self.emit(OpCode::ConvertToString);
-                },
-                _ => self.generate_node(part)?,
+    // `output`, or `give back`, with the value to return on the stack: the
+    // `always` blocks of every `do` it leaves run first
+    fn generate_return(&mut self) -> Result<(), String> {
+        let (handlers, cleanups) = (self.handlers, self.cleanups.clone());
+        self.generate_cleanups(0)?;
+        (self.handlers, self.cleanups) = (handlers, cleanups);
+        self.emit(OpCode::Return);
+        Ok(())
+    }
+
+    // `stop` or `continue`, leaving the error handlers entered since the
+    // loop it names (or the innermost one) began and running the `always`
+    // blocks of the `do`s it leaves
+    fn generate_jump(&mut self, word: &str, label: &Option<String>) -> Result<(), String> {
+        let target = match label {
+            Some(label) => self.loops.iter().rposition(|exits| exits.label.as_ref() == Some(label)),
+            None => self.loops.len().checked_sub(1),
+        };
+        let Some(target) = target else {
+            return Err(format!("`{}` can only be used inside a loop", word));
+        };
+        let (handlers, cleanups) = (self.handlers, self.cleanups.clone());
+        self.generate_cleanups(self.loops[target].cleanups)?;
+        for _ in self.loops[target].handlers..self.handlers {
+            self.emit(OpCode::PopHandler);
+        }
+        (self.handlers, self.cleanups) = (handlers, cleanups);
+        let jump = self.instructions.len();
+        self.emit(OpCode::Jump(0));
+        match word {
+            "stop" => self.loops[target].stops.push(jump),
+            _ => self.loops[target].continues.push(jump),
+        }
+        Ok(())
+    }
+
+    // Compiles a Task body in its own scope; `implicit` names (such as `me`) are pre-declared locals
+    fn compile_function(&mut self, name: &str, params: &[Node], body: &Node, implicit: &[&str]) -> Result<Arc<Function>, String> {
+        let mut body_generator = BytecodeGenerator::new(Arc::clone(&self.lines.source));
+        let mut compiled_params = Vec::new();
+
+        for local in implicit {
+            body_generator.variables.insert(local.to_string(), body_generator.variables.len());
+        }
+        for param in params {
+            if let Node::VariableDecl { name, type_annotation, .. } = param {
+                let type_name = type_annotation.as_deref().map(Self::type_name);
+                compiled_params.push((name.clone(), type_name));
+                body_generator.variables.insert(name.clone(), body_generator.variables.len());
+            }
+        }
+
+        body_generator.generate_node(body)?;
+        // Falling off the end of a Task returns Nothing
+        body_generator.emit(OpCode::Push(Value::Null));
+        body_generator.emit(OpCode::Return);
+
+        self.functions.append(&mut body_generator.functions);
+        self.classes.append(&mut body_generator.classes);
+
+        Ok(Arc::new(Function {
+            name: name.to_string(),
+            params: compiled_params,
+            body: body_generator.instructions,
+            lines: body_generator.lines,
+        }))
+    }
+
+    // A chain like `a + b + c + d` adds up in one step, so Text is built
+    // once instead of copied again at every `+`
+    fn generate_sum(&mut self, node: &Node) -> Result<(), String> {
+        let mut parts = Vec::new();
+        let mut left = node;
+        while let Node::Binary { left: rest, operator: TokenType::Plus, right } = left {
+            parts.push(right.as_ref());
+            left = rest;
+        }
+        parts.push(left);
+        for part in parts.iter().rev() {
+            self.generate_node(part)?;
+        }
+        self.instructions.push(OpCode::Concat(parts.len()));
+        Ok(())
+    }
+
+    // An item number, which for `the 2nd to last item` counts back from the
+    // end of the List or Text `depth` places under it on the stack
+    fn generate_item_number(&mut self, number: &Node, depth: usize) -> Result<(), String> {
+        self.generate_node(number)?;
+        if let Node::Ordinal { from_end: true, .. } = number {
+            self.emit(OpCode::FromEnd(depth));
+        }
+        Ok(())
+    }
+
+    // Joins parts into one List: each run of single items becomes a List of
+    // its own, then every List is joined in one step
+    fn generate_joined(&mut self, parts: Vec<(bool, &Node)>) -> Result<(), String> {
+        let (mut lists, mut items) = (0, 0);
+        for (all, part) in parts {
+            if all && items > 0 {
+                self.emit(OpCode::BuildList(items));
+                (lists, items) = (lists + 1, 0);
             }
-            
-            if parts.len() > 1 {
-                self.emit(OpCode::Concat);
+            self.generate_node(part)?;
+            match all {
+                true => lists += 1,
+                false => items += 1,
             }
         }
+        if items > 0 {
+            self.emit(OpCode::BuildList(items));
+            lists += 1;
+        }
+        self.emit(OpCode::JoinLists(lists));
         Ok(())
     }
+
+    // Points a placeholder jump or handler at the next instruction
+    fn patch_jump(&mut self, position: usize) {
+        let target = self.instructions.len();
+        match &mut self.instructions[position] {
+            OpCode::Jump(address) | OpCode::JumpIfFalse(address) | OpCode::PushHandler(address) | OpCode::ForEach(address) => {
+                *address = target
+            },
+            _ => {},
+        }
+    }
+
+    fn compile_class(&mut self, declaration: &Node) -> Result<Arc<Class>, String> {
+        let Node::ObjectDecl { name, base, fields, constructor, methods, properties, shared_fields, shared_methods, behaviors, is_behavior } = declaration else {
+            return Err("Expected an object declaration".to_string());
+        };
+
+        // Field defaults run as a hidden method on each new instance
+        let (field_list, initializer) = self.compile_field_defaults(&format!("{}.<fields>", name), fields)?;
+        let compiled_methods = self.compile_methods(name, methods)?;
+
+        let (constructor, constructor_labels) = match constructor.as_deref() {
+            Some(Node::ConstructorDecl { params, labels, body }) => {
+                let function = self.compile_function(&format!("{}.build", name), params, body, &["me"])?;
+                (Some(function), labels.clone())
+            },
+            _ => (None, Vec::new()),
+        };
+
+        // Shared defaults run once, with `me` bound to the object kind
+        let (shared_field_list, shared_initializer) = self.compile_field_defaults(&format!("{}.<shared>", name), shared_fields)?;
+        let shared_methods = self.compile_methods(name, shared_methods)?;
+
+        let mut getters = HashMap::new();
+        let mut setters = HashMap::new();
+        for property in properties {
+            if let Node::PropertyDecl { name: property_name, setter_param, body, .. } = property {
+                let qualified = format!("{}.{}", name, property_name);
+                match setter_param {
+                    Some(param) => {
+                        let function = self.compile_function(&qualified, std::slice::from_ref(param), body, &["me"])?;
+                        setters.insert(property_name.clone(), function);
+                    },
+                    None => {
+                        let function = self.compile_function(&qualified, &[], body, &["me"])?;
+                        getters.insert(property_name.clone(), function);
+                    },
+                }
+            }
+        }
+
+        let base = match base.as_deref() {
+            Some(Node::TypeAnnotation(base_name)) => Some(base_name.clone()),
+            _ => None,
+        };
+
+        Ok(Arc::new(Class {
+            name: name.to_string(),
+            base,
+            fields: field_list,
+            initializer,
+            methods: compiled_methods,
+            getters,
+            setters,
+            constructor,
+            constructor_labels,
+            shared_fields: shared_field_list,
+            shared_initializer,
+            shared_methods,
+            behaviors: behaviors.clone(),
+            is_behavior: *is_behavior,
+        }))
+    }
+
+    fn compile_methods(&mut self, class_name: &str, methods: &[Node]) -> Result<HashMap<String, Arc<Function>>, String> {
+        let mut compiled = HashMap::new();
+        for method in methods {
+            if let Node::TaskDecl { name: method_name, params, body, .. } = method {
+                let function = self.compile_function(&format!("{}.{}", class_name, method_name), params, body, &["me"])?;
+                compiled.insert(method_name.clone(), function);
+            }
+        }
+        Ok(compiled)
+    }
+
+    // Compiles field declarations into their declared types plus a hidden
+    // method that assigns the defaults onto `me`
+    fn compile_field_defaults(&mut self, qualified: &str, fields: &[Node]) -> Result<(TypedNames, Arc<Function>), String> {
+        let mut assignments = Vec::new();
+        let mut field_list = Vec::new();
+        for field in fields {
+            match field {
+                Node::VariableDecl { name: field_name, type_annotation, initializer: default } => {
+                    field_list.push((field_name.clone(), type_annotation.as_deref().map(Self::type_name)));
+                    assignments.push(Node::Set {
+                        object: Box::new(Node::Variable("me".to_string())),
+                        name: field_name.clone(),
+                        value: default.clone().unwrap_or_else(|| Box::new(Node::Literal(Value::Null))),
+                    });
+                },
+                Node::Assignment { name: field_name, value } => {
+                    field_list.push((field_name.clone(), None));
+                    assignments.push(Node::Set {
+                        object: Box::new(Node::Variable("me".to_string())),
+                        name: field_name.clone(),
+                        value: value.clone(),
+                    });
+                },
+                _ => return Err(format!("Unexpected declaration in {}", qualified)),
+            }
+        }
+        let initializer = self.compile_function(qualified, &[], &Node::Block(assignments), &["me"])?;
+        Ok((field_list, initializer))
+    }
+
+    // Canonical name of a declared type, as understood by DeclareVar
+    fn type_name(type_node: &Node) -> String {
+        match type_node {
+            Node::TypeAnnotation(name) => name.clone(),
+            Node::ListType { .. } => "List".to_string(),
+            Node::MappingType { .. } => "Mapping".to_string(),
+            _ => "Any".to_string(),
+        }
+    }
+
+    fn emit(&mut self, opcode: OpCode) {
+        self.instructions.push(opcode);
+    }
 }
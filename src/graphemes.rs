@@ -0,0 +1,11 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits Text into the characters a reader sees, which Unicode calls
+/// grapheme clusters: `é` written as `e` and a combining accent is one, and
+/// so are a flag made of two regional indicators, a family emoji made of
+/// several people joined together, and a consonant with the vowel sign or
+/// virama written with it. This follows the extended grapheme cluster rules
+/// of Unicode Standard Annex #29, with Unicode's own character tables.
+pub fn graphemes(text: &str) -> Vec<&str> {
+    text.graphemes(true).collect()
+}
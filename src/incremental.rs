@@ -0,0 +1,147 @@
+use crate::language::{self, Language};
+use crate::parser::{Node, Parser, SyntaxError};
+use crate::tokenizer::{Token, TokenType, Tokenizer};
+use std::collections::HashMap;
+
+// Words that carry on the statement above them when they start a line:
+// the `or:` of a `when`, and the `fail`/`always` clauses of a `do`
+const CONTINUATIONS: [&str; 3] = ["or", "fail", "always"];
+
+/// Parses a document that changes a little at a time, as an editor sends it
+/// to the language server on every keystroke. The text is split into its
+/// top-level statements, and a statement whose text is unchanged since the
+/// last parse reuses that parse's tokens, moved to the line it is on now,
+/// and its tree when it hasn't moved.
+#[derive(Default)]
+pub struct IncrementalParser {
+    chunks: HashMap<String, Chunk>,
+}
+
+/// A document as read: its tokens, its statements (an `Error` node for each
+/// one that doesn't parse) and the syntax errors, in order.
+pub struct Parsed {
+    pub tokens: Vec<Token>,
+    pub ast: Vec<Node>,
+    pub errors: Vec<SyntaxError>,
+}
+
+// One top-level statement, with the comments and blank lines after it, and
+// the line it started on when it was parsed
+struct Chunk {
+    start: usize,
+    tokens: Vec<Token>,
+    ast: Vec<Node>,
+}
+
+impl IncrementalParser {
+    /// The whole document, the same as parsing it from scratch would give.
+    /// Errors are found by parsing it from scratch, so they read exactly as
+    /// they would without the cache. Fails only when the text can't be split
+    /// into tokens.
+    pub fn parse(&mut self, source: &str) -> Result<Parsed, String> {
+        let Some(language) = chosen_language(source) else {
+            return parse_whole(source);
+        };
+        let mut previous = std::mem::take(&mut self.chunks);
+        let mut chunks = HashMap::new();
+        let (mut tokens, mut ast) = (Vec::new(), Vec::new());
+        // Chunks are tokenized on their own, so their offsets count from
+        // where the chunk starts in this version of the document
+        let mut offset = 0;
+
+        for (start, text) in split(source, language) {
+            let length = text.len();
+            let chunk = match previous.remove(&text) {
+                Some(chunk) if chunk.start == start => Ok(chunk),
+                Some(chunk) => move_chunk(chunk, start),
+                None => parse_chunk(&text, start, language),
+            };
+            let Ok(chunk) = chunk else {
+                return parse_whole(source);
+            };
+            tokens.extend(chunk.tokens.iter()
+                .filter(|token| token.token_type != TokenType::Eof)
+                .map(|token| Token { offset: token.offset + offset, ..token.clone() }));
+            ast.extend(chunk.ast.iter().cloned());
+            chunks.insert(text, chunk);
+            offset += length;
+        }
+
+        let line = source.lines().count() + 1;
+        tokens.push(Token { token_type: TokenType::Eof, literal: String::new(), line, column: 1, offset: source.len() });
+        self.chunks = chunks;
+        Ok(Parsed { tokens, ast, errors: Vec::new() })
+    }
+}
+
+fn parse_whole(source: &str) -> Result<Parsed, String> {
+    let tokens = Tokenizer::new(source).tokenize()?;
+    let (ast, errors) = Parser::new(tokens.clone()).parse_with_errors();
+    Ok(Parsed { tokens, ast, errors })
+}
+
+fn parse_chunk(text: &str, start: usize, language: Option<&'static Language>) -> Result<Chunk, String> {
+    let mut tokenizer = Tokenizer::new(text);
+    tokenizer.set_first_line(start);
+    tokenizer.set_language(language);
+    let tokens = tokenizer.tokenize()?;
+    let ast = Parser::new(tokens.clone()).parse()?;
+    Ok(Chunk { start, tokens, ast })
+}
+
+// A statement that lines were added or taken away above: its tokens are
+// renumbered rather than read again, and parsed for a tree with the new lines
+fn move_chunk(chunk: Chunk, start: usize) -> Result<Chunk, String> {
+    let tokens: Vec<Token> = chunk.tokens.into_iter()
+        .map(|token| Token { line: token.line - chunk.start + start, ..token })
+        .collect();
+    let ast = Parser::new(tokens.clone()).parse()?;
+    Ok(Chunk { start, tokens, ast })
+}
+
+// The keyword pack the document asks for; None when it names an unknown one
+fn chosen_language(source: &str) -> Option<Option<&'static Language>> {
+    for line in source.lines() {
+        let trimmed = line.trim();
+        if let Some(name) = language::directive(trimmed) {
+            return language::find(name).map(Some);
+        }
+        if !trimmed.is_empty() && !trimmed.starts_with('#') {
+            break;
+        }
+    }
+    Some(None)
+}
+
+// Splits the source where a new top-level statement starts: an unindented
+// line outside brackets, after a line that isn't continued with `\`, that
+// doesn't start with a word continuing the statement above. Each piece is
+// paired with the line it starts on.
+fn split(source: &str, language: Option<&'static Language>) -> Vec<(usize, String)> {
+    let continues = |line: &str| {
+        let word: String = line.chars().take_while(|c| c.is_alphanumeric() || *c == '_').collect::<String>().to_lowercase();
+        let english = language
+            .and_then(|language| language.words.iter().find(|(local, _)| local.to_lowercase() == word))
+            .map(|(_, english)| english.to_string())
+            .unwrap_or(word);
+        CONTINUATIONS.contains(&english.as_str())
+    };
+
+    let mut chunks: Vec<(usize, String)> = Vec::new();
+    let (mut depth, mut continued) = (0i64, false);
+    for (index, line) in source.split_inclusive('\n').enumerate() {
+        let trimmed = line.trim();
+        let code = !trimmed.is_empty() && !trimmed.starts_with('#');
+        let starts = code && !line.starts_with([' ', '\t']) && depth <= 0 && !continued && !continues(trimmed);
+        match chunks.last_mut() {
+            Some((_, text)) if !starts => text.push_str(line),
+            _ => chunks.push((index + 1, line.to_string())),
+        }
+        if code {
+            let code = trimmed.split('#').next().unwrap_or("");
+            depth += code.matches(['(', '[']).count() as i64 - code.matches([')', ']']).count() as i64;
+            continued = code.contains('\\');
+        }
+    }
+    chunks
+}
@@ -0,0 +1,474 @@
+use crate::generator::{Instance, Lock, Mapping, Value};
+use crate::money::{Currency, Money};
+use crate::units::{Quantity, Unit};
+use crate::dates::Date;
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{self, BufRead, Read, Write};
+use std::sync::Arc;
+
+/// A JSON document, for talking to editors and other tools. Object members
+/// keep the order they were written in.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+// Deeper documents are rejected rather than risking the Rust stack
+const MAX_DEPTH: usize = 256;
+
+impl Json {
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut reader = Reader { chars: text.chars().collect(), position: 0 };
+        let value = reader.value(0)?;
+        reader.skip_whitespace();
+        match reader.chars.get(reader.position) {
+            None => Ok(value),
+            Some(c) => Err(format!("Unexpected '{}' after JSON value at {}", c, reader.position)),
+        }
+    }
+
+    /// Builds an object from name/value pairs.
+    pub fn object(members: Vec<(&str, Json)>) -> Json {
+        Json::Object(members.into_iter().map(|(name, value)| (name.to_string(), value)).collect())
+    }
+
+    pub fn text(value: &str) -> Json {
+        Json::String(value.to_string())
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Json> {
+        match self {
+            Json::Object(members) => members.iter().find(|(key, _)| key == name).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Json::Number(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Json {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Json {
+        Json::Bool(b)
+    }
+}
+
+// Values move to and from JSON as the matching JSON types. A Mapping whose
+// keys are all Text becomes a JSON object; Money, quantities, Objects,
+// object kinds and other Mappings become objects tagged `$money`,
+// `$quantity`, `$object`, `$kind` or `$mapping`, so they read back as they
+// were.
+impl Json {
+    /// A value as JSON. Fails for a List, Mapping or Object that holds
+    /// itself, which JSON can't write down.
+    pub fn from_value(value: &Value) -> Result<Json, String> {
+        value_json(value, &mut Vec::new())
+    }
+
+    /// Money as JSON, with the amount as Text under `tag` so no digits are
+    /// lost: `{"money": "-19.99", "currency": "USD"}`.
+    pub fn from_money(money: Money, tag: &str) -> Json {
+        Json::object(vec![(tag, Json::text(&money.amount_text())), ("currency", Json::text(money.currency.code()))])
+    }
+
+    /// Money written by `from_money` with the same tag.
+    pub fn to_money(&self, tag: &str) -> Option<Money> {
+        let currency = Currency::from_code(self.get("currency")?.as_str()?)?;
+        Money::parse(self.get(tag)?.as_str()?, currency).ok()
+    }
+
+    /// A quantity as JSON, with its amount under `tag` and its units in
+    /// words: `{"quantity": 9.8, "unit": "meters per second squared"}`.
+    pub fn from_quantity(quantity: &Quantity, tag: &str) -> Json {
+        Json::object(vec![(tag, Json::Number(quantity.amount)), ("unit", Json::text(&quantity.unit.phrase(true)))])
+    }
+
+    /// A quantity written by `from_quantity` with the same tag.
+    pub fn to_quantity(&self, tag: &str) -> Option<Quantity> {
+        Some(Quantity { amount: self.get(tag)?.as_f64()?, unit: Unit::parse(self.get("unit")?.as_str()?)? })
+    }
+
+    /// A date as JSON, written out under `tag`: `{"date": "2025-06-01"}`.
+    pub fn from_date(date: Date, tag: &str) -> Json {
+        Json::object(vec![(tag, Json::text(&date.iso()))])
+    }
+
+    /// A date written by `from_date` with the same tag.
+    pub fn to_date(&self, tag: &str) -> Option<Date> {
+        Date::parse(self.get(tag)?.as_str()?)
+    }
+
+    /// The value a JSON document describes. An `$object` comes back as an
+    /// Object of that kind with the given fields, whether or not the kind
+    /// is declared.
+    pub fn to_value(&self) -> Value {
+        match self {
+            Json::Null => Value::Null,
+            Json::Bool(b) => Value::Boolean(*b),
+            Json::Number(n) => Value::Number(*n),
+            Json::String(text) => Value::String(text.clone()),
+            Json::Array(items) => Value::list(items.iter().map(Json::to_value).collect()),
+            Json::Object(members) => {
+                if let Some(kind) = self.get("$object").and_then(Json::as_str) {
+                    let fields = match self.get("fields") {
+                        Some(Json::Object(fields)) => fields.iter().map(|(name, value)| (name.clone(), value.to_value())).collect(),
+                        _ => HashMap::new(),
+                    };
+                    return Value::Object(Arc::new(Instance { class_name: kind.to_string(), fields: Lock::new(fields), frozen: false }));
+                }
+                if let Some(kind) = self.get("$kind").and_then(Json::as_str) {
+                    return Value::Class(kind.to_string());
+                }
+                if let Some(money) = self.to_money("$money") {
+                    return Value::Money(money);
+                }
+                if let Some(quantity) = self.to_quantity("$quantity") {
+                    return Value::Quantity(quantity);
+                }
+                if let Some(date) = self.to_date("$date") {
+                    return Value::Date(date);
+                }
+                let mut mapping = Mapping::new();
+                match self.get("$mapping").and_then(Json::as_array) {
+                    Some(entries) => {
+                        for entry in entries {
+                            if let Some([key, value]) = entry.as_array() {
+                                mapping.insert(key.to_value(), value.to_value());
+                            }
+                        }
+                    },
+                    None => {
+                        for (key, value) in members {
+                            mapping.insert(Value::String(key.clone()), value.to_value());
+                        }
+                    },
+                }
+                Value::mapping(mapping)
+            },
+        }
+    }
+}
+
+// `holding` are the Objects this value sits inside
+// `holding` has the Lists, Mappings and Objects being written further up
+fn value_json(value: &Value, holding: &mut Vec<usize>) -> Result<Json, String> {
+    let id = match value {
+        Value::List(items) => Arc::as_ptr(items) as usize,
+        Value::Mapping(mapping) => Arc::as_ptr(mapping) as usize,
+        Value::Object(instance) => Arc::as_ptr(instance) as usize,
+        _ => return Ok(plain_json(value)),
+    };
+    if holding.contains(&id) {
+        return Err(match value {
+            Value::Object(instance) => format!("A {} object holds itself, so it can't be written as JSON", instance.class_name),
+            Value::Mapping(_) => "Cannot write a Mapping that contains itself as JSON".to_string(),
+            _ => "Cannot write a List that contains itself as JSON".to_string(),
+        });
+    }
+    holding.push(id);
+    let json = match value {
+        Value::List(items) => Json::Array(items.borrow().iter().map(|item| value_json(item, holding)).collect::<Result<_, _>>()?),
+        // Text keys that can't be mistaken for a tag make a plain object
+        Value::Mapping(mapping) if mapping.borrow().iter().all(|(key, _)| matches!(key, Value::String(key) if !key.starts_with('$'))) => {
+            Json::Object(mapping.borrow().iter()
+                .map(|(key, value)| Ok((key.to_string(), value_json(value, holding)?)))
+                .collect::<Result<_, String>>()?)
+        },
+        Value::Mapping(mapping) => {
+            let entries = mapping.borrow().iter()
+                .map(|(key, value)| Ok(Json::Array(vec![value_json(key, holding)?, value_json(value, holding)?])))
+                .collect::<Result<_, String>>()?;
+            Json::object(vec![("$mapping", Json::Array(entries))])
+        },
+        Value::Object(instance) => {
+            let fields = instance.fields.borrow();
+            let mut names: Vec<&String> = fields.keys().collect();
+            names.sort();
+            let fields = names.into_iter()
+                .map(|name| Ok((name.clone(), value_json(&fields[name], holding)?)))
+                .collect::<Result<_, String>>()?;
+            Json::object(vec![("$object", Json::text(&instance.class_name)), ("fields", Json::Object(fields))])
+        },
+        other => plain_json(other),
+    };
+    holding.pop();
+    Ok(json)
+}
+
+// A value with nothing inside it as JSON
+fn plain_json(value: &Value) -> Json {
+    match value {
+        Value::Number(n) => Json::Number(*n),
+        Value::Money(money) => Json::from_money(*money, "$money"),
+        Value::Quantity(quantity) => Json::from_quantity(quantity, "$quantity"),
+        Value::Date(date) => Json::from_date(*date, "$date"),
+        Value::String(text) => Json::text(text),
+        Value::Boolean(b) => Json::Bool(*b),
+        Value::Class(name) => Json::object(vec![("$kind", Json::text(name))]),
+        _ => Json::Null,
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            // JSON has no NaN or infinity
+            Json::Number(n) if !n.is_finite() => write!(f, "null"),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(text) => write_string(f, text),
+            Json::Array(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            },
+            Json::Object(members) => {
+                write!(f, "{{")?;
+                for (i, (name, value)) in members.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ",")?;
+                    }
+                    write_string(f, name)?;
+                    write!(f, ":{}", value)?;
+                }
+                write!(f, "}}")
+            },
+        }
+    }
+}
+
+/// Reads one message framed with a Content-Length header, as the Debug
+/// Adapter and Language Server protocols send them. None at end of input.
+pub fn read_message() -> Result<Option<Json>, String> {
+    let stdin = io::stdin();
+    let mut input = stdin.lock();
+    let mut length = None;
+    loop {
+        let mut header = String::new();
+        if input.read_line(&mut header).map_err(|e| format!("Failed to read message: {}", e))? == 0 {
+            return Ok(None);
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some(value) = header.strip_prefix("Content-Length:") {
+            length = value.trim().parse::<usize>().ok();
+        }
+    }
+    let length = length.ok_or("Message without a Content-Length header")?;
+    let mut body = vec![0; length];
+    input.read_exact(&mut body).map_err(|e| format!("Failed to read message: {}", e))?;
+    Json::parse(&String::from_utf8_lossy(&body)).map(Some)
+}
+
+/// Writes one message with a Content-Length header to standard output.
+pub fn write_message(message: &Json) -> Result<(), String> {
+    let body = message.to_string();
+    let mut output = io::stdout().lock();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)
+        .and_then(|_| output.flush())
+        .map_err(|e| format!("Failed to send message: {}", e))
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, text: &str) -> fmt::Result {
+    write!(f, "\"")?;
+    for c in text.chars() {
+        match c {
+            '"' => write!(f, "\\\"")?,
+            '\\' => write!(f, "\\\\")?,
+            '\n' => write!(f, "\\n")?,
+            '\r' => write!(f, "\\r")?,
+            '\t' => write!(f, "\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => write!(f, "{}", c)?,
+        }
+    }
+    write!(f, "\"")
+}
+
+struct Reader {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl Reader {
+    fn value(&mut self, depth: usize) -> Result<Json, String> {
+        if depth > MAX_DEPTH {
+            return Err("JSON is nested too deeply".to_string());
+        }
+        self.skip_whitespace();
+        match self.chars.get(self.position) {
+            Some('{') => {
+                self.position += 1;
+                let mut members = Vec::new();
+                if self.eat('}') {
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_whitespace();
+                    let name = self.string()?;
+                    self.expect(':')?;
+                    members.push((name, self.value(depth + 1)?));
+                    if self.eat('}') {
+                        return Ok(Json::Object(members));
+                    }
+                    self.expect(',')?;
+                }
+            },
+            Some('[') => {
+                self.position += 1;
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value(depth + 1)?);
+                    if self.eat(']') {
+                        return Ok(Json::Array(items));
+                    }
+                    self.expect(',')?;
+                }
+            },
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.word("true", Json::Bool(true)),
+            Some('f') => self.word("false", Json::Bool(false)),
+            Some('n') => self.word("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => self.number(),
+            Some(c) => Err(format!("Unexpected '{}' in JSON at {}", c, self.position)),
+            None => Err("Unexpected end of JSON".to_string()),
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if !self.eat('"') {
+            return Err(format!("Expected a string in JSON at {}", self.position));
+        }
+        let mut text = String::new();
+        loop {
+            let c = *self.chars.get(self.position).ok_or("Unterminated string in JSON")?;
+            self.position += 1;
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escape = *self.chars.get(self.position).ok_or("Unterminated string in JSON")?;
+                    self.position += 1;
+                    text.push(match escape {
+                        'n' => '\n',
+                        't' => '\t',
+                        'r' => '\r',
+                        'b' => '\u{8}',
+                        'f' => '\u{c}',
+                        'u' => self.unicode_escape()?,
+                        other => other,
+                    });
+                },
+                c => text.push(c),
+            }
+        }
+    }
+
+    // \uXXXX, combining surrogate pairs
+    fn unicode_escape(&mut self) -> Result<char, String> {
+        let first = self.hex4()?;
+        if (0xD800..0xDC00).contains(&first) && self.chars.get(self.position..self.position + 2) == Some(&['\\', 'u']) {
+            self.position += 2;
+            let second = self.hex4()?;
+            let combined = 0x10000 + ((first - 0xD800) << 10) + (second.wrapping_sub(0xDC00) & 0x3FF);
+            return Ok(char::from_u32(combined).unwrap_or('\u{FFFD}'));
+        }
+        Ok(char::from_u32(first).unwrap_or('\u{FFFD}'))
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self.chars.iter().skip(self.position).take(4).collect();
+        self.position += 4;
+        u32::from_str_radix(&digits, 16).map_err(|_| format!("Invalid \\u escape '{}' in JSON", digits))
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.position;
+        while self.chars.get(self.position).is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c)) {
+            self.position += 1;
+        }
+        let text: String = self.chars[start..self.position].iter().collect();
+        text.parse::<f64>().map(Json::Number).map_err(|_| format!("Invalid number '{}' in JSON", text))
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.position + word.len();
+        if self.chars.get(self.position..end).is_some_and(|found| found.iter().copied().eq(word.chars())) {
+            self.position = end;
+            Ok(value)
+        } else {
+            Err(format!("Unexpected text in JSON at {}", self.position))
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.position).is_some_and(|c| c.is_whitespace()) {
+            self.position += 1;
+        }
+    }
+
+    fn eat(&mut self, expected: char) -> bool {
+        self.skip_whitespace();
+        if self.chars.get(self.position) == Some(&expected) {
+            self.position += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        if self.eat(expected) {
+            Ok(())
+        } else {
+            Err(format!("Expected '{}' in JSON at {}", expected, self.position))
+        }
+    }
+}
@@ -0,0 +1,457 @@
+use crate::generator::Lock;
+use crate::json::Json;
+use crate::runtime::{Runtime, Step};
+use crate::zmtp::{self, Connection};
+use std::io::Write;
+use std::sync::mpsc::{self, Sender};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+// Jupyter messaging protocol version spoken
+const PROTOCOL_VERSION: &str = "5.3";
+
+// Separates a message's routing frames from its signed parts
+const DELIMITER: &[u8] = b"<IDS|MSG>";
+
+/// Serves notebooks as a Jupyter kernel, with the ports and key from a
+/// connection file; `nair kernel install` registers the kernel with Jupyter
+/// instead. Every cell runs in the one Runtime, so later cells see what
+/// earlier ones declared.
+pub fn run(runtime: &mut Runtime, args: &[String]) -> Result<(), String> {
+    match args {
+        [command] if command == "install" => install(),
+        [connection_file] => serve(runtime, connection_file),
+        _ => Err("Usage: nair kernel connection-file | nair kernel install".to_string()),
+    }
+}
+
+// A request from the shell or control channel, and where to reply
+struct Request {
+    frames: Vec<Vec<u8>>,
+    peer: Connection,
+}
+
+// A message as read off the wire: who to route replies to, and what it says
+struct Message {
+    identities: Vec<Vec<u8>>,
+    header: Json,
+    content: Json,
+}
+
+struct Kernel {
+    key: Vec<u8>,
+    session: String,
+    execution_count: usize,
+    // Everyone subscribed to output and status
+    iopub: Arc<Mutex<Vec<Connection>>>,
+    // What the running cell has shown so far
+    output: Arc<Lock<Vec<u8>>>,
+    sent: usize,
+}
+
+fn serve(runtime: &mut Runtime, connection_file: &str) -> Result<(), String> {
+    let text = std::fs::read_to_string(connection_file).map_err(|e| format!("Error reading '{}': {}", connection_file, e))?;
+    let settings = Json::parse(&text)?;
+    let setting = |name: &str| settings.get(name).and_then(Json::as_str).unwrap_or("").to_string();
+    if !matches!(setting("transport").as_str(), "" | "tcp") {
+        return Err(format!("Only the tcp transport is supported, not '{}'", setting("transport")));
+    }
+    if !matches!(setting("signature_scheme").as_str(), "" | "hmac-sha256") {
+        return Err(format!("Only hmac-sha256 signatures are supported, not '{}'", setting("signature_scheme")));
+    }
+    let address = |port: &str| {
+        let number = settings.get(port).and_then(Json::as_f64).ok_or_else(|| format!("The connection file has no {}", port))?;
+        let ip = match setting("ip") {
+            ip if ip.is_empty() => "127.0.0.1".to_string(),
+            ip => ip,
+        };
+        Ok::<_, String>(format!("{}:{}", ip, number))
+    };
+
+    let key = setting("key").into_bytes();
+    let (sender, requests) = mpsc::channel();
+    for channel in ["shell_port", "control_port"] {
+        let sender = Mutex::new(sender.clone());
+        let (key, interrupt) = (key.clone(), runtime.interrupt_flag());
+        zmtp::bind(&address(channel)?, "ROUTER", move |connection| {
+            let sender = sender.lock().map(|sender| sender.clone());
+            if let Ok(sender) = sender {
+                forward(connection, sender, &key, &interrupt);
+            }
+        })?;
+    }
+    let iopub = Arc::new(Mutex::new(Vec::new()));
+    let subscribers = Arc::clone(&iopub);
+    zmtp::bind(&address("iopub_port")?, "PUB", move |mut connection| {
+        if let (Ok(mut subscribers), Ok(writer)) = (subscribers.lock(), connection.try_clone()) {
+            subscribers.push(writer);
+        }
+        // Everything goes to every subscriber, so subscriptions can be ignored
+        while connection.receive().is_ok() {}
+    })?;
+    zmtp::bind(&address("hb_port")?, "REP", |mut connection| {
+        while let Ok(frames) = connection.receive() {
+            if connection.send(&frames).is_err() {
+                break;
+            }
+        }
+    })?;
+    // Cells read with `ask` from the kernel's own input, so nothing is asked here
+    zmtp::bind(&address("stdin_port")?, "ROUTER", |mut connection| while connection.receive().is_ok() {})?;
+
+    let output = Arc::new(Lock::new(Vec::new()));
+    runtime.set_debug_output(false);
+    runtime.set_output(Box::new(Capture(Arc::clone(&output))));
+    let mut kernel = Kernel {
+        key,
+        session: unique_id(),
+        execution_count: 0,
+        iopub,
+        output,
+        sent: 0,
+    };
+    kernel.publish("status", &Json::object(vec![]), Json::object(vec![("execution_state", Json::text("starting"))]));
+    for mut request in requests {
+        let Some(message) = read(&kernel.key, request.frames) else {
+            continue;
+        };
+        let msg_type = message.header.get("msg_type").and_then(Json::as_str).unwrap_or("").to_string();
+        kernel.publish("status", &message.header, Json::object(vec![("execution_state", Json::text("busy"))]));
+        let reply = kernel.handle(runtime, &msg_type, &message);
+        if let Some(content) = &reply {
+            let reply_type = msg_type.replace("_request", "_reply");
+            let frames = kernel.frames(message.identities.clone(), &reply_type, &message.header, content.clone());
+            // A notebook that went away can't be told anything
+            let _ = request.peer.send(&frames);
+        }
+        kernel.publish("status", &message.header, Json::object(vec![("execution_state", Json::text("idle"))]));
+        if msg_type == "shutdown_request" {
+            break;
+        }
+    }
+    Ok(())
+}
+
+// Passes each request from one notebook connection to the kernel. An
+// interrupt also stops the running cell straight away, since the kernel
+// only reads the next request once the cell is done.
+fn forward(mut connection: Connection, sender: Sender<Request>, key: &[u8], interrupt: &AtomicBool) {
+    while let Ok(frames) = connection.receive() {
+        let Ok(peer) = connection.try_clone() else {
+            break;
+        };
+        let msg_type = read(key, frames.clone()).and_then(|message| message.header.get("msg_type").and_then(Json::as_str).map(str::to_string));
+        if msg_type.as_deref() == Some("interrupt_request") {
+            interrupt.store(true, Ordering::Relaxed);
+        }
+        if sender.send(Request { frames, peer }).is_err() {
+            break;
+        }
+    }
+}
+
+impl Kernel {
+    // The reply's content, or None for messages that get no reply
+    fn handle(&mut self, runtime: &mut Runtime, msg_type: &str, message: &Message) -> Option<Json> {
+        let ok = |mut members: Vec<(&str, Json)>| {
+            members.insert(0, ("status", Json::text("ok")));
+            Some(Json::object(members))
+        };
+        match msg_type {
+            "kernel_info_request" => ok(vec![
+                ("protocol_version", Json::text(PROTOCOL_VERSION)),
+                ("implementation", Json::text("nair")),
+                ("implementation_version", Json::text(env!("CARGO_PKG_VERSION"))),
+                ("language_info", Json::object(vec![
+                    ("name", Json::text("vernacular")),
+                    ("version", Json::text(env!("CARGO_PKG_VERSION"))),
+                    ("mimetype", Json::text("text/x-vernacular")),
+                    ("file_extension", Json::text(".vern")),
+                ])),
+                ("banner", Json::text(&format!("Vernacular Runtime v{}", env!("CARGO_PKG_VERSION")))),
+                ("help_links", Json::Array(Vec::new())),
+            ]),
+            "execute_request" => Some(self.execute(runtime, message)),
+            "is_complete_request" => {
+                let code = message.content.get("code").and_then(Json::as_str).unwrap_or("");
+                let last = code.lines().last().unwrap_or("").trim_end();
+                match last.ends_with(':') || last.ends_with('\\') {
+                    true => Some(Json::object(vec![("status", Json::text("incomplete")), ("indent", Json::text("    "))])),
+                    false => Some(Json::object(vec![("status", Json::text("complete"))])),
+                }
+            },
+            "complete_request" => {
+                let cursor = message.content.get("cursor_pos").cloned().unwrap_or(Json::Number(0.0));
+                ok(vec![
+                    ("matches", Json::Array(Vec::new())),
+                    ("cursor_start", cursor.clone()),
+                    ("cursor_end", cursor),
+                    ("metadata", Json::object(vec![])),
+                ])
+            },
+            "inspect_request" => ok(vec![("found", false.into()), ("data", Json::object(vec![])), ("metadata", Json::object(vec![]))]),
+            "history_request" => ok(vec![("history", Json::Array(Vec::new()))]),
+            "comm_info_request" => ok(vec![("comms", Json::object(vec![]))]),
+            // The running cell was stopped as the request came in
+            "interrupt_request" => ok(vec![]),
+            "shutdown_request" => ok(vec![("restart", message.content.get("restart").cloned().unwrap_or(false.into()))]),
+            _ => None,
+        }
+    }
+
+    // Runs a cell, publishing what it shows and any error it ends with
+    fn execute(&mut self, runtime: &mut Runtime, message: &Message) -> Json {
+        let code = message.content.get("code").and_then(Json::as_str).unwrap_or("");
+        let silent = message.content.get("silent").and_then(Json::as_bool).unwrap_or(false);
+        if !silent {
+            self.execution_count += 1;
+            let input = vec![("code", Json::text(code)), ("execution_count", self.execution_count.into())];
+            self.publish("execute_input", &message.header, Json::object(input));
+        }
+
+        runtime.set_source_name(&format!("[{}]", self.execution_count));
+        // An interrupt sent while no cell was running is for none of them
+        runtime.interrupt_flag().store(false, Ordering::Relaxed);
+        let mut result = runtime.start(code);
+        while result.is_ok() {
+            match runtime.step(1000) {
+                Ok(Step::Paused) => self.flush_output(&message.header, silent),
+                Ok(Step::Finished) => break,
+                Err(error) => result = Err(error),
+            }
+        }
+        self.flush_output(&message.header, silent);
+
+        let count = ("execution_count", self.execution_count.into());
+        match result {
+            Ok(()) => Json::object(vec![
+                ("status", Json::text("ok")),
+                count,
+                ("user_expressions", Json::object(vec![])),
+                ("payload", Json::Array(Vec::new())),
+            ]),
+            Err(error) => {
+                // "ArithmeticError: ..." names its kind; other failures are plain Errors
+                let ename = match error.split_once(": ") {
+                    Some((kind, _)) if !kind.contains(' ') => kind,
+                    _ => "Error",
+                };
+                let error = vec![
+                    ("ename", Json::text(ename)),
+                    ("evalue", Json::text(&error)),
+                    ("traceback", Json::Array(error.lines().map(Json::text).collect())),
+                ];
+                if !silent {
+                    self.publish("error", &message.header, Json::object(error.clone()));
+                }
+                let mut reply = vec![("status", Json::text("error")), count];
+                reply.extend(error);
+                Json::object(reply)
+            },
+        }
+    }
+
+    // Publishes what the cell has shown since the last call, as it happens
+    fn flush_output(&mut self, parent: &Json, silent: bool) {
+        let text = String::from_utf8_lossy(&self.output.borrow()[self.sent..]).into_owned();
+        self.sent += text.len();
+        if !text.is_empty() && !silent {
+            let content = Json::object(vec![("name", Json::text("stdout")), ("text", Json::text(&text))]);
+            self.publish("stream", parent, content);
+        }
+        if self.sent == self.output.borrow().len() {
+            self.output.borrow_mut().clear();
+            self.sent = 0;
+        }
+    }
+
+    // A message's frames, addressed with the given routing frames
+    fn frames(&self, mut frames: Vec<Vec<u8>>, msg_type: &str, parent: &Json, content: Json) -> Vec<Vec<u8>> {
+        let header = Json::object(vec![
+            ("msg_id", Json::text(&unique_id())),
+            ("session", Json::text(&self.session)),
+            ("username", Json::text("kernel")),
+            ("date", Json::text(&now())),
+            ("msg_type", Json::text(msg_type)),
+            ("version", Json::text(PROTOCOL_VERSION)),
+        ]);
+        let parts: Vec<Vec<u8>> = [header, parent.clone(), Json::object(vec![]), content].iter()
+            .map(|part| part.to_string().into_bytes())
+            .collect();
+        frames.push(DELIMITER.to_vec());
+        frames.push(sign(&self.key, &parts).into_bytes());
+        frames.extend(parts);
+        frames
+    }
+
+    // Sends a message to every subscriber, forgetting those that have gone
+    fn publish(&self, msg_type: &str, parent: &Json, content: Json) {
+        let frames = self.frames(vec![msg_type.as_bytes().to_vec()], msg_type, parent, content);
+        if let Ok(mut subscribers) = self.iopub.lock() {
+            subscribers.retain_mut(|subscriber| subscriber.send(&frames).is_ok());
+        }
+    }
+}
+
+// A request's parts, once its signature is checked
+fn read(key: &[u8], mut frames: Vec<Vec<u8>>) -> Option<Message> {
+    let start = frames.iter().position(|frame| frame == DELIMITER)?;
+    let parts = frames.split_off(start);
+    if parts.len() < 6 || parts[1] != sign(key, &parts[2..6]).into_bytes() {
+        return None;
+    }
+    let json = |frame: &[u8]| Json::parse(&String::from_utf8_lossy(frame)).ok();
+    Some(Message { identities: frames, header: json(&parts[2])?, content: json(&parts[5])? })
+}
+
+// The hex HMAC-SHA256 of a message's parts; unsigned without a key
+fn sign(secret: &[u8], parts: &[Vec<u8>]) -> String {
+    if secret.is_empty() {
+        return String::new();
+    }
+    let mut key = [0u8; 64];
+    match secret.len() > key.len() {
+        true => key[..32].copy_from_slice(&sha256(secret)),
+        false => key[..secret.len()].copy_from_slice(secret),
+    }
+    let mut inner: Vec<u8> = key.iter().map(|byte| byte ^ 0x36).collect();
+    for part in parts {
+        inner.extend_from_slice(part);
+    }
+    let mut outer: Vec<u8> = key.iter().map(|byte| byte ^ 0x5c).collect();
+    outer.extend_from_slice(&sha256(&inner));
+    sha256(&outer).iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+// Keeps what cells show, for the kernel to publish
+struct Capture(Arc<Lock<Vec<u8>>>);
+
+impl Write for Capture {
+    fn write(&mut self, bytes: &[u8]) -> std::io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+// Writes the kernel spec that tells Jupyter how to start this kernel
+fn install() -> Result<(), String> {
+    let data = match std::env::var_os("JUPYTER_DATA_DIR") {
+        Some(directory) => std::path::PathBuf::from(directory),
+        None => {
+            let home = std::env::var_os("HOME").or_else(|| std::env::var_os("APPDATA")).ok_or("Can't find your home directory")?;
+            let home = std::path::PathBuf::from(home);
+            match std::env::consts::OS {
+                "macos" => home.join("Library").join("Jupyter"),
+                "windows" => home.join("jupyter"),
+                _ => home.join(".local").join("share").join("jupyter"),
+            }
+        },
+    };
+    let directory = data.join("kernels").join("vernacular");
+    let exe = std::env::current_exe().map_err(|e| format!("Can't find the nair executable: {}", e))?;
+    let spec = Json::object(vec![
+        ("argv", Json::Array(vec![
+            Json::text(&exe.to_string_lossy()),
+            Json::text("kernel"),
+            Json::text("{connection_file}"),
+        ])),
+        ("display_name", Json::text("Vernacular")),
+        ("language", Json::text("vernacular")),
+    ]);
+    std::fs::create_dir_all(&directory).map_err(|e| format!("Error creating '{}': {}", directory.display(), e))?;
+    let path = directory.join("kernel.json");
+    std::fs::write(&path, spec.to_string()).map_err(|e| format!("Error writing '{}': {}", path.display(), e))?;
+    println!("Installed the Vernacular kernel in {}", directory.display());
+    Ok(())
+}
+
+// An id for a session or message, unique enough for Jupyter
+fn unique_id() -> String {
+    static COUNTER: std::sync::atomic::AtomicU64 = std::sync::atomic::AtomicU64::new(0);
+    let count = COUNTER.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+    let nanos = SystemTime::now().duration_since(UNIX_EPOCH).map(|time| time.as_nanos()).unwrap_or(0);
+    let digest = sha256(format!("{}-{}-{}", std::process::id(), nanos, count).as_bytes());
+    let hex: String = digest[..16].iter().map(|byte| format!("{:02x}", byte)).collect();
+    format!("{}-{}-{}-{}-{}", &hex[..8], &hex[8..12], &hex[12..16], &hex[16..20], &hex[20..])
+}
+
+// The current time in ISO 8601, as message headers carry it
+fn now() -> String {
+    let time = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let (days, seconds) = ((time.as_secs() / 86_400) as i64, time.as_secs() % 86_400);
+    // Civil date from days since 1970-01-01, after Howard Hinnant
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}.{:06}Z", year, month, day,
+        seconds / 3600, seconds / 60 % 60, seconds % 60, time.subsec_micros())
+}
+
+fn sha256(data: &[u8]) -> [u8; 32] {
+    const K: [u32; 64] = [
+        0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+        0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+        0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+        0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+        0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+        0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+        0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+        0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+    ];
+    let mut state: [u32; 8] = [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19];
+    let mut message = data.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 {
+        message.push(0);
+    }
+    message.extend_from_slice(&((data.len() as u64) * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, added) in state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(added);
+        }
+    }
+
+    let mut digest = [0u8; 32];
+    for (bytes, word) in digest.chunks_mut(4).zip(state) {
+        bytes.copy_from_slice(&word.to_be_bytes());
+    }
+    digest
+}
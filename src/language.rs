@@ -0,0 +1,142 @@
+/// A keyword pack: words in another language and the English keywords they
+/// stand for. A word may stand for a phrase, such as `mientras` for
+/// `loop while`. The English keywords keep working alongside the pack.
+pub struct Language {
+    pub code: &'static str,
+    pub name: &'static str,
+    pub words: &'static [(&'static str, &'static str)],
+}
+
+/// Other words every file may use for a built-in verb or value, and the
+/// keyword each one means: `say "hi"` is `show "hi"`, `make Point` is `new
+/// Point` and `yes` is `true`.
+pub static SYNONYMS: [(&str, &str); 7] = [
+    ("display", "show"),
+    ("print", "show"),
+    ("say", "show"),
+    ("make", "new"),
+    ("create", "new"),
+    ("yes", "true"),
+    ("no", "false"),
+];
+
+/// Every keyword pack a file can choose with `# language: CODE`.
+pub static LANGUAGES: [Language; 2] = [
+    Language {
+        code: "es",
+        name: "Español",
+        words: &[
+            ("es", "is"),
+            ("como", "as"),
+            ("de", "of"),
+            ("para", "to"),
+            ("cuando", "when"),
+            ("si", "when"),
+            ("o", "or"),
+            ("sino", "or"),
+            ("hacer", "do"),
+            ("fallar", "fail"),
+            ("siempre", "always"),
+            ("hereda", "inherits"),
+            ("devuelve", "returns"),
+            ("requiere", "requires"),
+            ("devolviendo", "returning"),
+            ("nuevo", "new"),
+            ("con", "with"),
+            ("usando", "using"),
+            ("repetir", "loop"),
+            ("mientras", "loop while"),
+            ("devolver", "output"),
+            ("lanzar", "raise"),
+            ("mostrar", "show"),
+            ("esperar", "expect"),
+            ("y", "and"),
+            ("cada", "each"),
+            ("mi", "my"),
+            ("sobre", "about"),
+            ("yo", "me"),
+            ("no", "not"),
+            ("compartido", "shared"),
+            ("incluye", "includes"),
+            ("Tarea", "Task"),
+            ("Objeto", "Object"),
+            ("Comportamiento", "Behavior"),
+            ("construir", "build"),
+            ("Entero", "Whole"),
+            ("Texto", "Text"),
+            ("Verdad", "Truth"),
+            ("Nada", "Nothing"),
+            ("Lista", "List"),
+            ("Mapa", "Mapping"),
+            ("Número", "Number"),
+            ("verdadero", "true"),
+            ("falso", "false"),
+            ("nulo", "null"),
+        ],
+    },
+    Language {
+        code: "fr",
+        name: "Français",
+        words: &[
+            ("est", "is"),
+            ("comme", "as"),
+            ("de", "of"),
+            ("pour", "to"),
+            ("quand", "when"),
+            ("si", "when"),
+            ("ou", "or"),
+            ("sinon", "or"),
+            ("faire", "do"),
+            ("échouer", "fail"),
+            ("toujours", "always"),
+            ("hérite", "inherits"),
+            ("renvoie", "returns"),
+            ("requiert", "requires"),
+            ("renvoyant", "returning"),
+            ("nouveau", "new"),
+            ("avec", "with"),
+            ("utilisant", "using"),
+            ("boucle", "loop"),
+            ("tantque", "loop while"),
+            ("sortie", "output"),
+            ("lever", "raise"),
+            ("afficher", "show"),
+            ("attendre", "expect"),
+            ("et", "and"),
+            ("chaque", "each"),
+            ("devient", "becomes"),
+            ("mon", "my"),
+            ("propos", "about"),
+            ("moi", "me"),
+            ("pas", "not"),
+            ("partagé", "shared"),
+            ("inclut", "includes"),
+            ("Tâche", "Task"),
+            ("Objet", "Object"),
+            ("Comportement", "Behavior"),
+            ("construire", "build"),
+            ("Entier", "Whole"),
+            ("Décimal", "Decimal"),
+            ("Texte", "Text"),
+            ("Vérité", "Truth"),
+            ("Rien", "Nothing"),
+            ("Liste", "List"),
+            ("Dictionnaire", "Mapping"),
+            ("Nombre", "Number"),
+            ("Erreur", "Error"),
+            ("vrai", "true"),
+            ("faux", "false"),
+            ("nul", "null"),
+        ],
+    },
+];
+
+/// The language named by a `# language: NAME` comment line.
+pub fn directive(line: &str) -> Option<&str> {
+    line.trim().strip_prefix('#')?.trim().strip_prefix("language:").map(str::trim)
+}
+
+/// The pack with this code or name, ignoring case.
+pub fn find(name: &str) -> Option<&'static Language> {
+    LANGUAGES.iter().find(|language| language.code.eq_ignore_ascii_case(name) || language.name.to_lowercase() == name.to_lowercase())
+}
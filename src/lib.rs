@@ -0,0 +1,58 @@
+//! Vernacular's interpreter, for programs that embed it. Build a [`Runtime`]
+//! (with [`Runtime::with_capabilities`] for code you don't trust), give it
+//! variables with `set_global`, and run source with `process_input`, or a
+//! little at a time with `start` and `step`. `get_global`, `stats` and
+//! `snapshot` read back what a program left behind. The `nair` command is
+//! built on the modules below.
+
+mod tokenizer;
+mod language;
+pub mod runtime;
+mod parser;
+pub mod generator;
+mod analyzer;
+pub mod test_runner;
+mod memory;
+mod debugger;
+pub mod dap;
+pub mod json;
+mod profiler;
+mod coverage;
+pub mod lsp;
+pub mod formatter;
+pub mod linter;
+pub mod ast_json;
+mod incremental;
+pub mod cache;
+pub mod bundle;
+mod lowering;
+pub mod emit_js;
+pub mod emit_py;
+pub mod emit_wasm;
+pub mod watch;
+mod zmtp;
+pub mod kernel;
+mod tutorial;
+mod snapshot;
+pub mod remote;
+pub mod serve;
+pub mod numbers;
+mod money;
+mod units;
+mod dates;
+mod failure;
+pub mod collation;
+mod graphemes;
+pub mod config;
+pub mod theme;
+mod pretty;
+
+pub use crate::collation::Collation;
+pub use crate::dates::Date;
+pub use crate::failure::Failure;
+pub use crate::generator::{Collection, Instance, Lock, Mapping, Value};
+pub use crate::money::{Currency, Money};
+pub use crate::numbers::Numbers;
+pub use crate::runtime::{Capability, Runtime, Stats, Step};
+pub use crate::snapshot::Snapshot;
+pub use crate::units::Quantity;
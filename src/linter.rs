@@ -0,0 +1,367 @@
+use crate::formatter::{comment, expression, loop_header, measure_header, repeat_header, simple_statement, when_header};
+use crate::generator::Value;
+use crate::parser::{FailClause, Node, OrWhen, Parser};
+use crate::test_runner;
+use crate::tokenizer::{Token, TokenType, Tokenizer};
+use std::path::PathBuf;
+
+/// Every lint and what it looks for.
+pub const RULES: [(&str, &str); 4] = [
+    ("unused-result", "an expression whose value is thrown away"),
+    ("ambiguous-phrasing", "wording that reads two ways, like `a and b or c`"),
+    ("truth-comparison", "comparing a Truth with true, like `done is true`"),
+    ("long-statement", "a statement too long to read at a glance"),
+];
+
+// Statements longer than this, once formatted, are hard to take in
+const MAX_STATEMENT: usize = 120;
+
+// How many times `--fix` re-checks a file, since one fix can reveal another
+const FIX_ROUNDS: usize = 10;
+
+/// How `nair lint` runs.
+#[derive(Default)]
+pub struct LintOptions {
+    /// Rewrite files with the fixes that can be made automatically
+    pub fix: bool,
+    /// Rules to skip
+    pub allow: Vec<String>,
+}
+
+/// One problem found by the linter. `fix`, when present, is the canonical
+/// text that should replace the statement (or block header) on `line`.
+pub struct Warning {
+    pub line: usize,
+    pub rule: &'static str,
+    pub message: String,
+    pub fix: Option<String>,
+}
+
+/// Lints every source file in the given files or directories (the current
+/// directory when none are given). Fails if any warnings remain.
+pub fn lint_files(paths: &[String], options: &LintOptions) -> Result<(), String> {
+    for rule in &options.allow {
+        if !RULES.iter().any(|(name, _)| name == rule) {
+            let names: Vec<&str> = RULES.iter().map(|(name, _)| *name).collect();
+            return Err(format!("Unknown lint '{}'; the lints are {}", rule, names.join(", ")));
+        }
+    }
+    let roots: Vec<PathBuf> = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+    let mut files = Vec::new();
+    for root in &roots {
+        test_runner::collect_files(root, &mut files)?;
+    }
+    files.sort();
+
+    let (mut remaining, mut fixable) = (0, 0);
+    for file in &files {
+        let mut source = std::fs::read_to_string(file).map_err(|e| format!("Error reading '{}': {}", file.display(), e))?;
+        let mut warnings = lint(&source, options).map_err(|error| format!("{}: {}", file.display(), error))?;
+
+        if options.fix {
+            let mut fixed = 0;
+            for _ in 0..FIX_ROUNDS {
+                let (updated, count) = apply_fixes(&source, &warnings);
+                if count == 0 {
+                    break;
+                }
+                fixed += count;
+                source = updated;
+                warnings = lint(&source, options).map_err(|error| format!("{}: {}", file.display(), error))?;
+            }
+            if fixed > 0 {
+                std::fs::write(file, &source).map_err(|e| format!("Error writing '{}': {}", file.display(), e))?;
+                println!("Fixed {} warning(s) in {}", fixed, file.display());
+            }
+        }
+
+        for warning in &warnings {
+            println!("{}:{}: {}: {}", file.display(), warning.line, warning.rule, warning.message);
+            if let Some(fix) = &warning.fix {
+                println!("    fix: {}", fix);
+                fixable += 1;
+            }
+        }
+        remaining += warnings.len();
+    }
+
+    match (remaining, fixable) {
+        (0, _) => Ok(()),
+        (remaining, 0) => Err(format!("{} lint warning(s)", remaining)),
+        (remaining, fixable) => Err(format!("{} lint warning(s), {} fixable with --fix", remaining, fixable)),
+    }
+}
+
+/// The warnings for one program, in source order, leaving out allowed rules.
+pub fn lint(source: &str, options: &LintOptions) -> Result<Vec<Warning>, String> {
+    let tokens = Tokenizer::new(source).tokenize()?;
+    let ast = Parser::new(tokens.clone()).parse()?;
+    let mut linter = Linter { warnings: Vec::new(), tokens };
+    linter.statements(&ast);
+
+    let mut warnings = linter.warnings;
+    warnings.retain(|warning| !options.allow.iter().any(|rule| rule == warning.rule));
+    warnings.sort_by_key(|warning| warning.line);
+    Ok(warnings)
+}
+
+// Replaces each line that has a fix, when the statement fits on that line.
+// Returns the new source and how many fixes were made.
+fn apply_fixes(source: &str, warnings: &[Warning]) -> (String, usize) {
+    let mut lines: Vec<String> = source.lines().map(str::to_string).collect();
+    let mut fixed_lines: Vec<usize> = Vec::new();
+    for warning in warnings {
+        let Some(fix) = &warning.fix else {
+            continue;
+        };
+        // One fix per line per round; the next round sees the result
+        if fixed_lines.contains(&warning.line) {
+            continue;
+        }
+        let Some(text) = lines.get(warning.line - 1) else {
+            continue;
+        };
+        let note = comment(text);
+        let code = note.map(|note| &text[..text.len() - note.len()]).unwrap_or(text).trim();
+        let opens = code.matches(['(', '[']).count();
+        let closes = code.matches([')', ']']).count();
+        let whole = code.ends_with(':') == fix.ends_with(':') && !code.ends_with('\\') && opens == closes;
+        if !whole {
+            continue;
+        }
+        let indent = &text[..text.len() - text.trim_start().len()];
+        let note = note.map(|note| format!("  {}", note)).unwrap_or_default();
+        let fixed = format!("{}{}{}", indent, fix, note);
+        if fixed != *text {
+            lines[warning.line - 1] = fixed;
+            fixed_lines.push(warning.line);
+        }
+    }
+    let mut result = lines.join("\n");
+    if source.ends_with('\n') {
+        result.push('\n');
+    }
+    (result, fixed_lines.len())
+}
+
+struct Linter {
+    warnings: Vec<Warning>,
+    // The AST forgets parentheses, so grouping is checked on the tokens
+    tokens: Vec<Token>,
+}
+
+impl Linter {
+    fn statements(&mut self, nodes: &[Node]) {
+        let mut line = 0;
+        for node in nodes {
+            match node {
+                Node::Line(start) => line = *start,
+                Node::Block(statements) => self.statements(statements),
+                statement => self.statement(statement, line),
+            }
+        }
+    }
+
+    fn statement(&mut self, node: &Node, line: usize) {
+        match node {
+            Node::TaskDecl { body, .. } | Node::TestDecl { body, .. } => self.body(body),
+            Node::ObjectDecl { constructor, methods, properties, shared_methods, .. } => {
+                for member in constructor.iter().map(|constructor| constructor.as_ref()).chain(methods).chain(properties).chain(shared_methods) {
+                    if let Node::TaskDecl { body, .. } | Node::PropertyDecl { body, .. } | Node::ConstructorDecl { body, .. } = member {
+                        self.body(body);
+                    }
+                }
+            },
+            Node::WhenStmt { condition, then_branch, or_when, else_branch } => {
+                self.expressions(line, &[condition], &when_header(condition));
+                self.body(then_branch);
+                for OrWhen { line, condition, body } in or_when {
+                    self.expressions(*line, &[condition], &format!("or when {}:", expression(condition)));
+                    self.body(body);
+                }
+                if let Some(else_branch) = else_branch {
+                    self.body(else_branch);
+                }
+            },
+            Node::LoopStmt { condition, body, label } => {
+                self.expressions(line, &[condition], &loop_header(condition, label));
+                self.body(body);
+            },
+            Node::KeepDoingStmt { body, .. } => self.body(body),
+            Node::MeasureStmt { runs, result, body } => {
+                let values: Vec<&Node> = runs.iter().map(|runs| &**runs).collect();
+                self.expressions(line, &values, &measure_header(runs, result));
+                self.body(body);
+            },
+            Node::RepeatStmt { count, counter, body, label } => {
+                self.expressions(line, &[count], &repeat_header(count, counter, label));
+                self.body(body);
+            },
+            Node::TryStmt { body, handlers, always } => {
+                self.body(body);
+                for FailClause { body, .. } in handlers {
+                    self.body(body);
+                }
+                if let Some(always) = always {
+                    self.body(always);
+                }
+            },
+            Node::ExpressionStmt(value) => {
+                if !matches!(value.as_ref(), Node::Call { .. }) {
+                    self.warn(line, "unused-result", format!("The value of `{}` is never used", expression(value)), None);
+                }
+                self.expressions(line, &[value], &simple_statement(node));
+            },
+            Node::ShowStmt(value) => {
+                let compares = self.statement_tokens(line).iter().skip(1)
+                    .scan(0, |depth, token| {
+                        *depth += bracket_change(token);
+                        Some(*depth == 0 && matches!(token.token_type, TokenType::Is | TokenType::NotEquals))
+                    })
+                    .any(|compares| compares);
+                if compares {
+                    let text = expression(value);
+                    self.warn(line, "ambiguous-phrasing",
+                        format!("`show {}` reads like it changes something; it shows whether {}", text, text),
+                        Some(format!("show ({})", text)));
+                }
+                self.expressions(line, &[value], &simple_statement(node));
+            },
+            simple => {
+                let values: Vec<&Node> = match simple {
+                    Node::VariableDecl { initializer, .. } => initializer.iter().map(|value| value.as_ref()).collect(),
+                    Node::Assignment { value, .. } | Node::ReturnStmt(value) => vec![value],
+                    Node::GuardStmt { condition, value } => vec![condition, value],
+                    Node::Change { amount, .. } => vec![amount],
+                    Node::Set { object, value, .. } => vec![object, value],
+                    Node::SetIndex { object, key, value, .. } => vec![object, key, value],
+                    Node::RaiseStmt { message, .. } => vec![message],
+                    Node::ExpectStmt { actual, expected, .. } => std::iter::once(actual.as_ref()).chain(expected.as_deref()).collect(),
+                    _ => Vec::new(),
+                };
+                self.expressions(line, &values, &simple_statement(simple));
+            },
+        }
+    }
+
+    fn body(&mut self, block: &Node) {
+        match block {
+            Node::Block(statements) => self.statements(statements),
+            statement => self.statement(statement, 0),
+        }
+    }
+
+    // Checks the expressions of one statement, whose canonical text is `text`
+    fn expressions(&mut self, line: usize, values: &[&Node], text: &str) {
+        let length = text.chars().count();
+        if length > MAX_STATEMENT {
+            self.warn(line, "long-statement",
+                format!("This statement is {} characters long; consider naming some of its parts", length), None);
+        }
+        let mut pending: Vec<&Node> = values.to_vec();
+        while let Some(node) = pending.pop() {
+            match node {
+                Node::Binary { left, operator: TokenType::Or, right } if self.mixes_and_or(line) => {
+                    for side in [left, right] {
+                        if let Node::Binary { operator: TokenType::And, .. } = side.as_ref() {
+                            let part = expression(side);
+                            self.warn(line, "ambiguous-phrasing",
+                                format!("`{}` mixes 'and' with 'or'; parentheses would show that `{}` goes first", expression(node), part),
+                                Some(text.replacen(&part, &format!("({})", part), 1)));
+                        }
+                    }
+                },
+                Node::Binary { left, operator: operator @ (TokenType::Is | TokenType::NotEquals), right } => {
+                    let kept = match (left.as_ref(), right.as_ref()) {
+                        (other, Node::Literal(Value::Boolean(truth))) | (Node::Literal(Value::Boolean(truth)), other) => Some((other, truth)),
+                        _ => None,
+                    };
+                    // Without a `not`, only comparisons that keep the value can be simplified
+                    if let Some((other, _)) = kept.filter(|(_, truth)| **truth == (*operator == TokenType::Is)) {
+                        let whole = expression(node);
+                        self.warn(line, "truth-comparison",
+                            format!("`{}` is the same as `{}`", whole, expression(other)),
+                            Some(text.replacen(&whole, &expression(other), 1)));
+                    }
+                },
+                _ => {},
+            }
+            pending.extend(children(node));
+        }
+    }
+
+    // The tokens of the statement starting on `line`, up to the end of its logical line
+    fn statement_tokens(&self, line: usize) -> &[Token] {
+        let layout = |token: &Token| matches!(token.token_type, TokenType::Indent | TokenType::Dedent | TokenType::NewLine);
+        let first = self.tokens.partition_point(|token| token.line < line);
+        let Some(start) = self.tokens[first..].iter().position(|token| !layout(token)).map(|offset| first + offset) else {
+            return &[];
+        };
+        let end = self.tokens[start..].iter().position(|token| token.token_type == TokenType::NewLine)
+            .map(|length| start + length)
+            .unwrap_or(self.tokens.len());
+        &self.tokens[start..end]
+    }
+
+    // Whether the statement uses 'and' and 'or' side by side, without
+    // parentheses to group them
+    fn mixes_and_or(&self, line: usize) -> bool {
+        let mut levels = vec![(false, false)];
+        for token in self.statement_tokens(line) {
+            match bracket_change(token) {
+                1 => levels.push((false, false)),
+                -1 if levels.len() > 1 => {
+                    levels.pop();
+                },
+                _ => {},
+            }
+            if let Some(level) = levels.last_mut() {
+                match token.token_type {
+                    TokenType::And => level.0 = true,
+                    TokenType::Or => level.1 = true,
+                    _ => {},
+                }
+                if *level == (true, true) {
+                    return true;
+                }
+            }
+        }
+        false
+    }
+
+    fn warn(&mut self, line: usize, rule: &'static str, message: String, fix: Option<String>) {
+        self.warnings.push(Warning { line, rule, message, fix });
+    }
+}
+
+fn bracket_change(token: &Token) -> i32 {
+    match token.token_type {
+        TokenType::OpenParen | TokenType::OpenBracket => 1,
+        TokenType::CloseParen | TokenType::CloseBracket => -1,
+        _ => 0,
+    }
+}
+
+// The expressions directly inside an expression
+fn children(node: &Node) -> Vec<&Node> {
+    match node {
+        Node::Binary { left, right, .. } | Node::SameAs { left, right, .. } | Node::FollowedBy { left, right } => vec![left, right],
+        Node::ListOf { parts } => parts.iter().map(|(_, part)| part).collect(),
+        Node::Index { object, key, .. } => vec![object, key],
+        Node::Conditional { value, condition, otherwise } => vec![condition, value, otherwise],
+        Node::Slice { object, first, last } => [Some(object), first.as_ref(), last.as_ref()].into_iter().flatten().map(|node| node.as_ref()).collect(),
+        Node::Comprehension { value, list, condition, .. } => [list, value].into_iter().chain(condition).map(|node| node.as_ref()).collect(),
+        Node::Call { callee, args } => std::iter::once(callee.as_ref()).chain(args).collect(),
+        Node::Get { object, .. } | Node::SafeGet { object, .. } => vec![object],
+        Node::Cast { value, .. } | Node::TypeTest { value, .. } | Node::TypeOf { value } => vec![value],
+        Node::New { args, .. } => args.iter().map(|(_, value)| value).collect(),
+        Node::StringInterpolation { parts } => parts.iter().collect(),
+        Node::ArrayLiteral { elements, .. } => elements.iter().collect(),
+        Node::MappingLiteral { entries } => entries.iter().map(|(_, _, value)| value).collect(),
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,762 @@
+use crate::analyzer::Analyzer;
+use crate::generator::{Value, DEFAULT_RUNS};
+use crate::parser::{FailClause, Node, Parser, REFLECTION, ROUNDING};
+use crate::tokenizer::{TokenType, Tokenizer};
+use std::collections::HashMap;
+
+/// The kinds of error every program can use without declaring them.
+pub const ERROR_KINDS: [&str; 14] = ["Error", "TypeError", "NameError", "PropertyError", "ArgumentError", "RecursionError",
+    "IndexError", "KeyError", "FrozenError", "SandboxViolation", "ExpectationFailed", "ArithmeticError", "InputError", "StackError"];
+
+/// A program reduced to what every translation needs, whatever language it
+/// is written in: Tasks and Objects gathered up front, Objects after the
+/// kinds they build on, and the variables each body assigns listed so they
+/// can be declared. Tests are left out, as they only run under `nair test`.
+pub struct Program {
+    /// Variables the top-level statements assign
+    pub globals: Vec<String>,
+    pub objects: Vec<Object>,
+    pub tasks: Vec<Function>,
+    pub statements: Vec<Stmt>,
+}
+
+pub struct Object {
+    pub name: String,
+    pub base: Option<Kind>,
+    pub behaviors: Vec<Kind>,
+    pub fields: Vec<(String, Expr)>,
+    pub build: Option<Function>,
+    pub getters: Vec<Function>,
+    pub setters: Vec<Function>,
+    pub methods: Vec<Function>,
+    pub shared_fields: Vec<(String, Expr)>,
+    pub shared_methods: Vec<Function>,
+}
+
+pub struct Function {
+    pub name: String,
+    pub params: Vec<String>,
+    /// The type each parameter is declared as, or `Any`
+    pub types: Vec<String>,
+    pub returns: Option<String>,
+    /// Variables the body assigns that aren't parameters
+    pub locals: Vec<String>,
+    pub body: Vec<Stmt>,
+}
+
+/// An Object kind, either declared by the program or one of the built-in
+/// error kinds.
+#[derive(Clone)]
+pub enum Kind {
+    Declared(String),
+    BuiltIn(String),
+}
+
+pub enum Stmt {
+    Assign(String, Expr),
+    Set(Expr, String, Expr),
+    /// `item key of object is value`
+    SetItem(Expr, Expr, Expr),
+    Show(Expr),
+    Return(Expr),
+    Eval(Expr),
+    /// `raise value`: Text or an error
+    Raise(Expr),
+    /// `raise message as Kind`
+    RaiseAs(Kind, Expr),
+    Expect(Expr, usize, String),
+    ExpectEqual(Expr, Expr, usize, String),
+    /// Each condition with its branch, then the branch for when none hold
+    If(Vec<(Expr, Vec<Stmt>)>, Option<Vec<Stmt>>),
+    While(Expr, Vec<Stmt>),
+    /// `keep doing the following`
+    Forever(Vec<Stmt>),
+    /// `stop`, leaving the innermost loop or the one named
+    Break(Option<String>),
+    /// `continue`, going on to the next time round that loop
+    Continue(Option<String>),
+    /// A loop given a name that `Break` and `Continue` can use
+    Named(String, Box<Stmt>),
+    /// `repeat count times`, setting the counter (when named) to 1, 2, ...
+    Repeat(Expr, Option<String>, Vec<Stmt>),
+    /// `measure how long it takes to`: runs the block that many times, then
+    /// keeps the timing in the variable (when named) or shows it
+    Measure(Expr, Option<String>, Vec<Stmt>),
+    Try { body: Vec<Stmt>, handlers: Vec<Handler>, always: Option<Vec<Stmt>> },
+    /// Sets an Object kind's shared fields, where its declaration appears
+    InitShared(String),
+}
+
+/// A fail clause. One without a kind catches every error.
+pub struct Handler {
+    pub kind: Option<Kind>,
+    pub name: Option<String>,
+    pub body: Vec<Stmt>,
+}
+
+pub enum Expr {
+    Number(f64),
+    Text(String),
+    Truth(bool),
+    Nothing,
+    Variable(String),
+    Me,
+    Kind(Kind),
+    /// Arithmetic, ordering, `and`/`or`, and `is` where one side is a plain
+    /// number, text, truth or nothing, so the target's own equality will do
+    Binary(Box<Expr>, Operator, Box<Expr>),
+    Negate(Box<Expr>),
+    /// `is` and `is not` between values that may be collections
+    Equal(Box<Expr>, Box<Expr>, bool),
+    /// `is the same as`, which looks inside Objects too
+    SameAs(Box<Expr>, Box<Expr>, bool),
+    IsA(Box<Expr>, Kind, bool),
+    IsType(Box<Expr>, String, bool),
+    Cast(Box<Expr>, String),
+    /// `the type of x`
+    TypeOf(Box<Expr>),
+    /// `value if condition otherwise other`, as condition, value, other
+    Choose(Box<Expr>, Box<Expr>, Box<Expr>),
+    Show(Vec<Expr>),
+    /// `a copy of x`: a new List, Mapping or Object holding the same items
+    Copy(Box<Expr>),
+    /// `freeze x`: a List, Mapping or Object like x that can't be changed
+    Freeze(Box<Expr>),
+    /// `round x`, `round x down` and the like: the built-in Task's name, the
+    /// number and the decimal places, if given
+    Round(String, Box<Expr>, Option<Box<Expr>>),
+    /// `the number in x`, or (when marked) `try to read x as a Whole`:
+    /// nothing unless the Text holds such a number
+    ReadNumber(Box<Expr>, bool),
+    /// `properties_of(object)` and the other built-in Tasks that reach an
+    /// Object's members by names held in Text
+    Reflect(String, Vec<Expr>),
+    /// `a is the same text as b`: the two Texts, whether to ignore case and
+    /// whether it is `is not`
+    CompareText(Vec<Expr>),
+    /// `normalize(text)`: the Text trimmed, in one case
+    Normalize(Box<Expr>),
+    /// `x written with ...`: the number, its decimal places, whether to
+    /// separate thousands, the fewest digits and whether it is a percentage
+    Written(Vec<Expr>),
+    /// `wrapping_add(a, b)` and the like: Whole arithmetic that wraps around
+    /// instead of failing when the answer is too big
+    Wrapping(Operator, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>),
+    MethodCall(Box<Expr>, String, Vec<Expr>),
+    Get(Box<Expr>, String),
+    /// `the name of x, if x exists`: nothing when x is
+    SafeGet(Box<Expr>, String),
+    /// Arguments in the order the nearest `build` takes them
+    New(Kind, Vec<Expr>),
+    /// An error the interpreter would raise when evaluating this
+    Fail(Kind, String),
+    Template(Vec<Part>),
+    List(Vec<Expr>),
+    /// `item key of object`, counting from 1 in Lists and Text
+    Item(Box<Expr>, Box<Expr>),
+    /// The item number of `the number to last item`, in whatever List or
+    /// Text it picks from
+    FromEnd(usize),
+    /// `items first through last of object`, or with no last (`everything
+    /// after item first`) or no first (`everything before item last`)
+    Slice(Box<Expr>, Option<Box<Expr>>, Option<Box<Expr>>),
+    /// `the value of each name in list where condition`
+    Each { value: Box<Expr>, name: String, list: Box<Expr>, condition: Option<Box<Expr>> },
+    /// Items, and (when marked) Lists whose items all go in, joined into one List
+    Joined(Vec<(bool, Expr)>),
+    Mapping(Vec<(String, Expr)>),
+    Assign(String, Box<Expr>),
+    Set(Box<Expr>, String, Box<Expr>),
+}
+
+pub enum Part {
+    Text(String),
+    Value(Expr),
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Operator {
+    Or,
+    And,
+    Equal,
+    NotEqual,
+    Greater,
+    GreaterEqual,
+    Less,
+    LessEqual,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Power,
+}
+
+impl Expr {
+    /// Whether this gives a number, whatever values the program runs with.
+    pub fn numeric(&self) -> bool {
+        match self {
+            Expr::Number(_) | Expr::Round(..) | Expr::Wrapping(..) => true,
+            Expr::Negate(value) => value.numeric(),
+            Expr::Binary(left, Operator::Add | Operator::Subtract | Operator::Multiply | Operator::Divide | Operator::Modulo | Operator::Power, right) => {
+                left.numeric() && right.numeric()
+            },
+            _ => false,
+        }
+    }
+
+    // Whether this gives Text, whatever values the program runs with
+    fn textual(&self) -> bool {
+        match self {
+            Expr::Text(_) | Expr::Template(_) | Expr::Written(_) | Expr::Normalize(_) => true,
+            Expr::Binary(left, Operator::Add, right) => left.textual() && right.textual(),
+            _ => false,
+        }
+    }
+}
+
+/// Whether `left operator right` can use the target language's own operator:
+/// arithmetic on operands known to be numbers, `+` and orderings on two known
+/// Texts too, and any `and`, `or`, `is` or `is not`. Anything else goes
+/// through the runtime's checked helpers, which raise the `TypeError` the
+/// interpreter would where the target would carry on with a wrong answer.
+pub fn native(left: &Expr, operator: Operator, right: &Expr) -> bool {
+    let numbers = left.numeric() && right.numeric();
+    match operator {
+        Operator::Or | Operator::And | Operator::Equal | Operator::NotEqual => true,
+        Operator::Add | Operator::Greater | Operator::GreaterEqual | Operator::Less | Operator::LessEqual => {
+            numbers || (left.textual() && right.textual())
+        },
+        _ => numbers,
+    }
+}
+
+/// Reads, parses and type checks a program for translation.
+pub fn check_file(path: &str) -> Result<Vec<Node>, String> {
+    let source = std::fs::read_to_string(path).map_err(|e| format!("Error reading '{}': {}", path, e))?;
+    let tokens = Tokenizer::new(&source).tokenize()?;
+    let ast = Parser::new(tokens).parse()?;
+    let mut analyzer = Analyzer::new();
+    analyzer.classes.extend(ERROR_KINDS.map(str::to_string));
+    analyzer.analyze(&ast)?;
+    Ok(ast)
+}
+
+pub fn lower(ast: &[Node]) -> Program {
+    let (mut tasks, mut objects) = (Vec::new(), Vec::new());
+    declarations(ast, &mut tasks, &mut objects);
+    let lowering = Lowering {
+        objects: objects.iter().filter_map(|object| match object {
+            Node::ObjectDecl { name, .. } => Some((name.as_str(), *object)),
+            _ => None,
+        }).collect(),
+    };
+    Program {
+        globals: assigned(ast, &[]),
+        objects: lowering.in_dependency_order(&objects).into_iter().filter_map(|object| lowering.object(object)).collect(),
+        tasks: tasks.into_iter().filter_map(|task| match task {
+            Node::TaskDecl { name, params, return_type, body, .. } => Some(lowering.function(name, params, return_type.as_deref(), body)),
+            _ => None,
+        }).collect(),
+        statements: lowering.statements(ast),
+    }
+}
+
+struct Lowering<'a> {
+    objects: HashMap<&'a str, &'a Node>,
+}
+
+impl<'a> Lowering<'a> {
+    // Objects after the kinds and behaviors they build on
+    fn in_dependency_order(&self, objects: &[&'a Node]) -> Vec<&'a Node> {
+        fn visit<'a>(known: &HashMap<&str, &'a Node>, object: &'a Node, visiting: &mut Vec<*const Node>, ordered: &mut Vec<&'a Node>) {
+            // A kind that inherits from itself is reported by the interpreter, not here
+            if ordered.iter().any(|done| std::ptr::eq(*done, object)) || visiting.contains(&(object as *const Node)) {
+                return;
+            }
+            visiting.push(object);
+            if let Node::ObjectDecl { base, behaviors, .. } = object {
+                let base = base.as_deref().map(type_name);
+                for dependency in base.iter().chain(behaviors).filter_map(|name| known.get(name.as_str())) {
+                    visit(known, dependency, visiting, ordered);
+                }
+            }
+            ordered.push(object);
+        }
+        let mut ordered = Vec::new();
+        for object in objects {
+            visit(&self.objects, object, &mut Vec::new(), &mut ordered);
+        }
+        ordered
+    }
+
+    fn object(&self, object: &Node) -> Option<Object> {
+        let Node::ObjectDecl { name, base, fields, constructor, methods, properties, shared_fields, shared_methods, behaviors, .. } = object else {
+            return None;
+        };
+        let tasks = |tasks: &[Node]| tasks.iter().filter_map(|task| match task {
+            Node::TaskDecl { name, params, return_type, body, .. } => Some(self.function(name, params, return_type.as_deref(), body)),
+            _ => None,
+        }).collect();
+        let (mut getters, mut setters) = (Vec::new(), Vec::new());
+        for property in properties {
+            match property {
+                Node::PropertyDecl { name, setter_param: Some(param), body, .. } => {
+                    setters.push(self.function(name, std::slice::from_ref(param), None, body));
+                },
+                Node::PropertyDecl { name, return_type, body, .. } => getters.push(self.function(name, &[], return_type.as_deref(), body)),
+                _ => {},
+            }
+        }
+        Some(Object {
+            name: name.clone(),
+            base: base.as_deref().map(|base| self.kind(&type_name(base))),
+            behaviors: behaviors.iter().map(|behavior| self.kind(behavior)).collect(),
+            fields: self.fields(fields),
+            build: match constructor.as_deref() {
+                Some(Node::ConstructorDecl { params, body, .. }) => Some(self.function("build", params, None, body)),
+                _ => None,
+            },
+            getters,
+            setters,
+            methods: tasks(methods),
+            shared_fields: self.fields(shared_fields),
+            shared_methods: tasks(shared_methods),
+        })
+    }
+
+    fn fields(&self, fields: &[Node]) -> Vec<(String, Expr)> {
+        fields.iter().filter_map(|field| match field {
+            Node::VariableDecl { name, initializer, .. } => {
+                Some((name.clone(), initializer.as_deref().map(|value| self.expression(value)).unwrap_or(Expr::Nothing)))
+            },
+            Node::Assignment { name, value } => Some((name.clone(), self.expression(value))),
+            _ => None,
+        }).collect()
+    }
+
+    fn function(&self, name: &str, params: &[Node], returns: Option<&Node>, body: &Node) -> Function {
+        let (params, types): (Vec<String>, Vec<String>) = params.iter().filter_map(|param| match param {
+            Node::VariableDecl { name, type_annotation, .. } => {
+                Some((name.clone(), type_annotation.as_deref().map(type_name).unwrap_or_else(|| "Any".to_string())))
+            },
+            _ => None,
+        }).unzip();
+        Function {
+            name: name.to_string(),
+            locals: assigned(std::slice::from_ref(body), &params),
+            params,
+            types,
+            returns: returns.map(type_name),
+            body: self.block(body),
+        }
+    }
+
+    fn block(&self, block: &Node) -> Vec<Stmt> {
+        match block {
+            Node::Block(statements) => self.statements(statements),
+            statement => self.statements(std::slice::from_ref(statement)),
+        }
+    }
+
+    fn statements(&self, nodes: &[Node]) -> Vec<Stmt> {
+        let mut statements = Vec::new();
+        for node in nodes {
+            self.statement(node, &mut statements);
+        }
+        statements
+    }
+
+    fn statement(&self, node: &Node, statements: &mut Vec<Stmt>) {
+        statements.push(match node {
+            Node::VariableDecl { name, initializer, .. } => {
+                Stmt::Assign(name.clone(), initializer.as_deref().map(|value| self.expression(value)).unwrap_or(Expr::Nothing))
+            },
+            Node::Assignment { name, value } => Stmt::Assign(name.clone(), self.expression(value)),
+            Node::Change { name, operator, amount } => {
+                let value = Node::Binary { left: Box::new(Node::Variable(name.clone())), operator: operator.clone(), right: amount.clone() };
+                Stmt::Assign(name.clone(), self.expression(&value))
+            },
+            Node::Set { object, name, value } => Stmt::Set(self.expression(object), name.clone(), self.expression(value)),
+            Node::SetIndex { object, key, value, .. } => {
+                Stmt::SetItem(self.expression(object), self.expression(key), self.expression(value))
+            },
+            Node::ShowStmt(value) => Stmt::Show(self.expression(value)),
+            Node::ReturnStmt(value) => Stmt::Return(self.expression(value)),
+            Node::GuardStmt { condition, value } => {
+                Stmt::If(vec![(self.expression(condition), vec![Stmt::Return(self.expression(value))])], None)
+            },
+            Node::ExpressionStmt(value) => Stmt::Eval(self.expression(value)),
+            Node::RaiseStmt { message, error_type: Some(kind) } => Stmt::RaiseAs(self.kind(&type_name(kind)), self.expression(message)),
+            Node::RaiseStmt { message, error_type: None } => Stmt::Raise(self.expression(message)),
+            Node::ExpectStmt { actual, expected: Some(expected), line, source } => {
+                Stmt::ExpectEqual(self.expression(actual), self.expression(expected), *line, source.clone())
+            },
+            Node::ExpectStmt { actual, expected: None, line, source } => Stmt::Expect(self.expression(actual), *line, source.clone()),
+            Node::WhenStmt { condition, then_branch, or_when, else_branch } => {
+                let mut branches = vec![(self.expression(condition), self.block(then_branch))];
+                for branch in or_when {
+                    branches.push((self.expression(&branch.condition), self.block(&branch.body)));
+                }
+                let mut otherwise = None;
+                let mut else_branch = else_branch.as_deref();
+                // `or:` holding nothing but another `when` continues the chain
+                while let Some(branch) = else_branch {
+                    match only_statement(branch) {
+                        Node::WhenStmt { condition, then_branch, or_when, else_branch: next } => {
+                            branches.push((self.expression(condition), self.block(then_branch)));
+                            for branch in or_when {
+                                branches.push((self.expression(&branch.condition), self.block(&branch.body)));
+                            }
+                            else_branch = next.as_deref();
+                        },
+                        _ => {
+                            otherwise = Some(self.block(branch));
+                            else_branch = None;
+                        },
+                    }
+                }
+                Stmt::If(branches, otherwise)
+            },
+            Node::LoopStmt { condition, body, label } => named(label, Stmt::While(self.expression(condition), self.block(body))),
+            Node::KeepDoingStmt { body, label } => named(label, Stmt::Forever(self.block(body))),
+            Node::StopStmt(label) => Stmt::Break(label.clone()),
+            Node::ContinueStmt(label) => Stmt::Continue(label.clone()),
+            Node::RepeatStmt { count, counter, body, label } => {
+                named(label, Stmt::Repeat(self.expression(count), counter.clone(), self.block(body)))
+            },
+            Node::MeasureStmt { runs, result, body } => {
+                let runs = runs.as_deref().map_or(Expr::Number(DEFAULT_RUNS), |runs| self.expression(runs));
+                Stmt::Measure(runs, result.clone(), self.block(body))
+            },
+            Node::TryStmt { body, handlers, always } => Stmt::Try {
+                body: self.block(body),
+                handlers: handlers.iter().map(|FailClause { name, kind, body }| Handler {
+                    kind: kind.as_ref().map(|kind| self.kind(&type_name(kind))),
+                    name: name.clone(),
+                    body: self.block(body),
+                }).collect(),
+                always: always.as_deref().map(|always| self.block(always)),
+            },
+            Node::ObjectDecl { name, shared_fields, .. } if !shared_fields.is_empty() => Stmt::InitShared(name.clone()),
+            Node::Block(nodes) => {
+                for node in nodes {
+                    self.statement(node, statements);
+                }
+                return;
+            },
+            // Declarations are gathered up front; tests and line markers are dropped
+            _ => return,
+        });
+    }
+
+    fn expression(&self, node: &Node) -> Expr {
+        let boxed = |node: &Node| Box::new(self.expression(node));
+        match node {
+            Node::Literal(Value::Number(n)) => Expr::Number(*n),
+            Node::Literal(Value::String(text)) => Expr::Text(text.clone()),
+            Node::Literal(Value::Boolean(b)) => Expr::Truth(*b),
+            Node::Literal(Value::Money(_)) => {
+                Expr::Fail(Kind::BuiltIn("Error".to_string()), "Money needs the Vernacular runtime to keep it to the cent".to_string())
+            },
+            Node::Literal(Value::Date(_)) => {
+                Expr::Fail(Kind::BuiltIn("Error".to_string()), "Dates need the Vernacular runtime".to_string())
+            },
+            Node::Literal(Value::Quantity(_)) => {
+                Expr::Fail(Kind::BuiltIn("Error".to_string()), "Units of measure need the Vernacular runtime".to_string())
+            },
+            Node::Literal(_) => Expr::Nothing,
+            Node::Variable(name) if name == "me" => Expr::Me,
+            Node::Variable(name) if self.is_object_kind(name) => Expr::Kind(self.kind(name)),
+            Node::Variable(name) => Expr::Variable(name.clone()),
+            // The parser reads `-x` as `0 - x`
+            Node::Binary { left, operator: TokenType::Minus, right } if matches!(left.as_ref(), Node::Literal(Value::Number(n)) if *n == 0.0) => {
+                Expr::Negate(boxed(right))
+            },
+            Node::Binary { left, operator: operator @ (TokenType::Is | TokenType::NotEquals), right } if !scalar(left) && !scalar(right) => {
+                Expr::Equal(boxed(left), boxed(right), *operator == TokenType::NotEquals)
+            },
+            Node::Binary { left, operator, right } => {
+                let operator = match operator {
+                    TokenType::Or => Operator::Or,
+                    TokenType::And => Operator::And,
+                    TokenType::Is => Operator::Equal,
+                    TokenType::NotEquals => Operator::NotEqual,
+                    TokenType::GreaterThan => Operator::Greater,
+                    TokenType::GreaterThanOrEqual => Operator::GreaterEqual,
+                    TokenType::LessThan => Operator::Less,
+                    TokenType::LessThanOrEqual => Operator::LessEqual,
+                    TokenType::Plus => Operator::Add,
+                    TokenType::Minus => Operator::Subtract,
+                    TokenType::Multiply => Operator::Multiply,
+                    TokenType::Divide => Operator::Divide,
+                    TokenType::Modulo => Operator::Modulo,
+                    _ => Operator::Power,
+                };
+                Expr::Binary(boxed(left), operator, boxed(right))
+            },
+            Node::SameAs { left, right, negated } => Expr::SameAs(boxed(left), boxed(right), *negated),
+            Node::TypeTest { value, target, negated } => {
+                let target = type_name(target);
+                match self.is_object_kind(&target) {
+                    true => Expr::IsA(boxed(value), self.kind(&target), *negated),
+                    false => Expr::IsType(boxed(value), target, *negated),
+                }
+            },
+            Node::Cast { value, target } => Expr::Cast(boxed(value), type_name(target)),
+            Node::TypeOf { value } => Expr::TypeOf(boxed(value)),
+            Node::Conditional { value, condition, otherwise } => Expr::Choose(boxed(condition), boxed(value), boxed(otherwise)),
+            Node::Call { callee, args } => {
+                let args = args.iter().map(|arg| self.expression(arg)).collect();
+                match callee.as_ref() {
+                    Node::Variable(name) if name == "show" => Expr::Show(args),
+                    Node::Variable(name) if name == "copy" && args.len() == 1 => Expr::Copy(Box::new(args.into_iter().next().unwrap())),
+                    Node::Variable(name) if name == "freeze" && args.len() == 1 => Expr::Freeze(Box::new(args.into_iter().next().unwrap())),
+                    Node::Variable(name) if ROUNDING.contains(&name.as_str()) && matches!(args.len(), 1 | 2) => {
+                        let mut args = args.into_iter().map(Box::new);
+                        Expr::Round(name.clone(), args.next().unwrap(), args.next())
+                    },
+                    Node::Variable(name) if (name == "number_in" || name == "whole_in") && args.len() == 1 => {
+                        Expr::ReadNumber(Box::new(args.into_iter().next().unwrap()), name == "whole_in")
+                    },
+                    Node::Variable(name) if name == "format_number" && args.len() == 5 => Expr::Written(args),
+                    Node::Variable(name) if name == "compare_text" && args.len() == 4 => Expr::CompareText(args),
+                    Node::Variable(name) if name == "normalize" && args.len() == 1 => Expr::Normalize(Box::new(args.into_iter().next().unwrap())),
+                    Node::Variable(name) if REFLECTION.contains(&name.as_str()) => Expr::Reflect(name.clone(), args),
+                    Node::Variable(name) if name == "today" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "Dates need the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "convert" || name == "wait" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "Units of measure need the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "ask" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "'ask' needs the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "<describe>" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "'describe' needs the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "length_of" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "'the length of' needs the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "sort" || name == "collate" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "Sorting needs the Vernacular runtime".to_string())
+                    },
+                    Node::Variable(name) if name == "evaluate" => {
+                        Expr::Fail(Kind::BuiltIn("Error".to_string()), "'evaluate' needs the Vernacular runtime to compile code".to_string())
+                    },
+                    Node::Variable(name) if args.len() == 2 && wrapping(name).is_some() => {
+                        let mut args = args.into_iter().map(Box::new);
+                        Expr::Wrapping(wrapping(name).unwrap(), args.next().unwrap(), args.next().unwrap())
+                    },
+                    Node::Variable(name) => Expr::Call(name.clone(), args),
+                    Node::Get { object, name } => Expr::MethodCall(boxed(object), name.clone(), args),
+                    _ => Expr::Fail(Kind::BuiltIn("Error".to_string()), "Only direct function calls are supported".to_string()),
+                }
+            },
+            Node::Get { object, name } => Expr::Get(boxed(object), name.clone()),
+            Node::SafeGet { object, name } => Expr::SafeGet(boxed(object), name.clone()),
+            Node::New { class_name, args } => match self.in_parameter_order(class_name, args) {
+                Ok(args) => Expr::New(self.kind(class_name), args.into_iter().map(|arg| self.expression(arg)).collect()),
+                Err(message) => Expr::Fail(Kind::BuiltIn("ArgumentError".to_string()), message),
+            },
+            Node::StringInterpolation { parts } => Expr::Template(parts.iter().map(|part| match part {
+                Node::Literal(Value::String(text)) => Part::Text(text.clone()),
+                part => Part::Value(self.expression(part)),
+            }).collect()),
+            Node::Index { object, key, .. } => Expr::Item(boxed(object), boxed(key)),
+            Node::Ordinal { number, from_end: false, .. } => Expr::Number(*number as f64),
+            Node::Ordinal { number, .. } => Expr::FromEnd(*number),
+            Node::Slice { object, first, last } => {
+                Expr::Slice(boxed(object), first.as_ref().map(|first| boxed(first)), last.as_ref().map(|last| boxed(last)))
+            },
+            Node::Comprehension { value, name, list, condition } => Expr::Each {
+                value: boxed(value),
+                name: name.clone(),
+                list: boxed(list),
+                condition: condition.as_ref().map(|condition| boxed(condition)),
+            },
+            Node::ListOf { parts } => Expr::Joined(parts.iter().map(|(all, part)| (*all, self.expression(part))).collect()),
+            Node::FollowedBy { left, right } => {
+                let mut parts = match self.expression(left) {
+                    Expr::Joined(parts) if matches!(left.as_ref(), Node::FollowedBy { .. }) => parts,
+                    left => vec![(true, left)],
+                };
+                parts.push((true, self.expression(right)));
+                Expr::Joined(parts)
+            },
+            Node::ArrayLiteral { elements, .. } => Expr::List(elements.iter().map(|element| self.expression(element)).collect()),
+            Node::MappingLiteral { entries } => {
+                Expr::Mapping(entries.iter().map(|(key, _, value)| (key.clone(), self.expression(value))).collect())
+            },
+            Node::Assignment { name, value } => Expr::Assign(name.clone(), boxed(value)),
+            Node::Set { object, name, value } => Expr::Set(boxed(object), name.clone(), boxed(value)),
+            _ => Expr::Nothing,
+        }
+    }
+
+    fn kind(&self, name: &str) -> Kind {
+        match !self.objects.contains_key(name) && ERROR_KINDS.contains(&name) {
+            true => Kind::BuiltIn(name.to_string()),
+            false => Kind::Declared(name.to_string()),
+        }
+    }
+
+    fn is_object_kind(&self, name: &str) -> bool {
+        self.objects.contains_key(name) || ERROR_KINDS.contains(&name)
+    }
+
+    // Labeled arguments to `new` put in the order the nearest `build` takes them
+    fn in_parameter_order<'n>(&self, class_name: &str, args: &'n [(Option<String>, Node)]) -> Result<Vec<&'n Node>, String> {
+        let positional = || args.iter().map(|(_, arg)| arg).collect();
+        if args.iter().all(|(label, _)| label.is_none()) {
+            return Ok(positional());
+        }
+        if args.iter().any(|(label, _)| label.is_none()) {
+            return Err(format!("new {} cannot mix labeled and unlabeled arguments", class_name));
+        }
+        let mut current = self.objects.get(class_name);
+        let labels = loop {
+            match current {
+                Some(Node::ObjectDecl { constructor: Some(constructor), .. }) => match constructor.as_ref() {
+                    Node::ConstructorDecl { labels, .. } => break labels,
+                    _ => return Ok(positional()),
+                },
+                Some(Node::ObjectDecl { base: Some(base), .. }) => current = self.objects.get(type_name(base).as_str()),
+                _ => return Ok(positional()),
+            }
+        };
+        let mut ordered: Vec<Option<&Node>> = vec![None; labels.len()];
+        for (label, arg) in args {
+            let label = label.as_deref().unwrap_or("");
+            let position = labels.iter().position(|known| known == label)
+                .ok_or_else(|| format!("new {} has no argument labeled '{}'", class_name, label))?;
+            if ordered[position].replace(arg).is_some() {
+                return Err(format!("new {} was given '{}' twice", class_name, label));
+            }
+        }
+        ordered.into_iter().zip(labels)
+            .map(|(arg, label)| arg.ok_or_else(|| format!("new {} is missing '{}'", class_name, label)))
+            .collect()
+    }
+}
+
+// Tasks and Objects declared anywhere in the program, except inside tests
+fn declarations<'a>(nodes: &'a [Node], tasks: &mut Vec<&'a Node>, objects: &mut Vec<&'a Node>) {
+    fn visit<'a>(node: &'a Node, tasks: &mut Vec<&'a Node>, objects: &mut Vec<&'a Node>) {
+        match node {
+            Node::TaskDecl { body, .. } => {
+                tasks.push(node);
+                visit(body, tasks, objects);
+            },
+            Node::ObjectDecl { .. } => objects.push(node),
+            Node::Block(statements) => statements.iter().for_each(|statement| visit(statement, tasks, objects)),
+            Node::WhenStmt { then_branch, or_when, else_branch, .. } => {
+                visit(then_branch, tasks, objects);
+                or_when.iter().for_each(|branch| visit(&branch.body, tasks, objects));
+                else_branch.iter().for_each(|branch| visit(branch, tasks, objects));
+            },
+            Node::LoopStmt { body, .. } | Node::RepeatStmt { body, .. } | Node::KeepDoingStmt { body, .. }
+                | Node::MeasureStmt { body, .. } => visit(body, tasks, objects),
+            Node::TryStmt { body, handlers, always } => {
+                visit(body, tasks, objects);
+                handlers.iter().for_each(|handler| visit(&handler.body, tasks, objects));
+                always.iter().for_each(|always| visit(always, tasks, objects));
+            },
+            _ => {},
+        }
+    }
+    for node in nodes {
+        visit(node, tasks, objects);
+    }
+}
+
+// Variables given a value by these statements, outside any Task they
+// declare; `skip` are already declared
+fn assigned(nodes: &[Node], skip: &[String]) -> Vec<String> {
+    fn add(names: &mut Vec<String>, name: &str) {
+        if !names.iter().any(|known| known == name) {
+            names.push(name.to_string());
+        }
+    }
+    fn visit(node: &Node, names: &mut Vec<String>) {
+        match node {
+            Node::VariableDecl { name, .. } | Node::Assignment { name, .. } | Node::Change { name, .. } => add(names, name),
+            Node::Block(statements) => statements.iter().for_each(|statement| visit(statement, names)),
+            Node::WhenStmt { then_branch, or_when, else_branch, .. } => {
+                visit(then_branch, names);
+                or_when.iter().for_each(|branch| visit(&branch.body, names));
+                else_branch.iter().for_each(|branch| visit(branch, names));
+            },
+            Node::LoopStmt { body, .. } | Node::KeepDoingStmt { body, .. } => visit(body, names),
+            Node::RepeatStmt { counter: named, body, .. } | Node::MeasureStmt { result: named, body, .. } => {
+                if let Some(name) = named {
+                    add(names, name);
+                }
+                visit(body, names);
+            },
+            Node::TryStmt { body, handlers, always } => {
+                visit(body, names);
+                for handler in handlers {
+                    if let Some(name) = &handler.name {
+                        add(names, name);
+                    }
+                    visit(&handler.body, names);
+                }
+                always.iter().for_each(|always| visit(always, names));
+            },
+            _ => {},
+        }
+    }
+    let mut names = Vec::new();
+    for node in nodes {
+        visit(node, &mut names);
+    }
+    names.retain(|name| !skip.contains(name));
+    names
+}
+
+// The statement a block holds, when it holds just one
+fn only_statement(node: &Node) -> &Node {
+    match node {
+        Node::Block(statements) => {
+            let mut code = statements.iter().filter(|statement| !matches!(statement, Node::Line(_)));
+            match (code.next(), code.next()) {
+                (Some(statement), None) => statement,
+                _ => node,
+            }
+        },
+        node => node,
+    }
+}
+
+// A loop, under its name if it has one
+fn named(label: &Option<String>, statement: Stmt) -> Stmt {
+    match label {
+        Some(label) => Stmt::Named(label.clone(), Box::new(statement)),
+        None => statement,
+    }
+}
+
+// The arithmetic a built-in `wrapping_...` Task does
+fn wrapping(name: &str) -> Option<Operator> {
+    match name {
+        "wrapping_add" => Some(Operator::Add),
+        "wrapping_subtract" => Some(Operator::Subtract),
+        "wrapping_multiply" => Some(Operator::Multiply),
+        _ => None,
+    }
+}
+
+fn scalar(node: &Node) -> bool {
+    matches!(node, Node::Literal(Value::Number(_) | Value::String(_) | Value::Boolean(_) | Value::Null))
+}
+
+fn type_name(node: &Node) -> String {
+    match node {
+        Node::TypeAnnotation(name) => name.clone(),
+        Node::ListType { .. } => "List".to_string(),
+        Node::MappingType { .. } => "Mapping".to_string(),
+        _ => "Any".to_string(),
+    }
+}
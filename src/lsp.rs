@@ -0,0 +1,296 @@
+use crate::analyzer::Analyzer;
+use crate::incremental::{IncrementalParser, Parsed};
+use crate::json::{self, Json};
+use crate::parser::{self, Node, SyntaxError};
+use crate::tokenizer::{Token, TokenType, KEYWORDS};
+use std::collections::HashMap;
+
+// LSP completion item kinds
+const FUNCTION_KIND: usize = 3;
+const VARIABLE_KIND: usize = 6;
+const CLASS_KIND: usize = 7;
+const KEYWORD_KIND: usize = 14;
+
+// JSON-RPC error for requests the server does not know
+const METHOD_NOT_FOUND: f64 = -32601.0;
+
+/// What an open document declares: its tokens, the names defined in it,
+/// and what the analyzer learned about its variables. The parser keeps the
+/// statements of the last version so an edit only re-parses what changed.
+struct Document {
+    text: String,
+    tokens: Vec<Token>,
+    // The statements that parsed, with Error nodes for those that didn't
+    ast: Vec<Node>,
+    syntax_errors: Vec<SyntaxError>,
+    // Why the text couldn't be split into tokens, if it couldn't
+    unreadable: Option<String>,
+    declarations: Vec<Declaration>,
+    analyzer: Analyzer,
+    parser: IncrementalParser,
+}
+
+struct Declaration {
+    name: String,
+    kind: usize,
+    token: usize,
+}
+
+/// Serves one editor over the Language Server Protocol: diagnostics as
+/// documents change, hover, go-to-definition and completion.
+pub fn serve() -> Result<(), String> {
+    let mut documents: HashMap<String, Document> = HashMap::new();
+    let mut shutting_down = false;
+
+    while let Some(message) = json::read_message()? {
+        let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+        let params = message.get("params");
+        let param = |name: &str| params.and_then(|params| params.get(name));
+        let uri = param("textDocument").and_then(|document| document.get("uri")).and_then(Json::as_str).unwrap_or("").to_string();
+
+        let result = match method {
+            "initialize" => Json::object(vec![
+                ("capabilities", Json::object(vec![
+                    ("textDocumentSync", 1.into()),  // the whole document on every change
+                    ("positionEncoding", Json::text("utf-16")),
+                    ("hoverProvider", true.into()),
+                    ("definitionProvider", true.into()),
+                    ("completionProvider", Json::object(vec![])),
+                ])),
+                ("serverInfo", Json::object(vec![("name", Json::text("nair"))])),
+            ]),
+            "initialized" => continue,
+            "textDocument/didOpen" | "textDocument/didChange" => {
+                let text = match method {
+                    "textDocument/didOpen" => param("textDocument").and_then(|document| document.get("text")),
+                    _ => param("contentChanges").and_then(Json::as_array).and_then(|changes| changes.last()).and_then(|change| change.get("text")),
+                };
+                let parser = documents.remove(&uri).map(|document| document.parser).unwrap_or_default();
+                let mut document = Document::new(text.and_then(Json::as_str).unwrap_or(""), parser);
+                publish(&uri, document.diagnostics())?;
+                documents.insert(uri, document);
+                continue;
+            },
+            "textDocument/didClose" => {
+                documents.remove(&uri);
+                publish(&uri, Vec::new())?;
+                continue;
+            },
+            "textDocument/hover" | "textDocument/definition" | "textDocument/completion" => {
+                let position = param("position");
+                let coordinate = |name: &str| position.and_then(|position| position.get(name)).and_then(Json::as_f64).unwrap_or(0.0) as usize;
+                let (line, character) = (coordinate("line") + 1, coordinate("character"));
+                match (documents.get(&uri), method) {
+                    (None, _) => Json::Null,
+                    (Some(document), "textDocument/hover") => document.hover(line, character),
+                    (Some(document), "textDocument/definition") => document.definition(&uri, line, character),
+                    (Some(document), _) => document.completion(),
+                }
+            },
+            "shutdown" => {
+                shutting_down = true;
+                Json::Null
+            },
+            "exit" => return if shutting_down { Ok(()) } else { Err("The editor exited without shutting down".to_string()) },
+            // Notifications need no answer, even when they are not understood
+            _ if message.get("id").is_none() => continue,
+            other => {
+                json::write_message(&Json::object(vec![
+                    ("jsonrpc", Json::text("2.0")),
+                    ("id", message.get("id").cloned().unwrap_or(Json::Null)),
+                    ("error", Json::object(vec![
+                        ("code", Json::Number(METHOD_NOT_FOUND)),
+                        ("message", Json::text(&format!("'{}' is not supported", other))),
+                    ])),
+                ]))?;
+                continue;
+            },
+        };
+        json::write_message(&Json::object(vec![
+            ("jsonrpc", Json::text("2.0")),
+            ("id", message.get("id").cloned().unwrap_or(Json::Null)),
+            ("result", result),
+        ]))?;
+    }
+    Ok(())
+}
+
+fn publish(uri: &str, diagnostics: Vec<Json>) -> Result<(), String> {
+    json::write_message(&Json::object(vec![
+        ("jsonrpc", Json::text("2.0")),
+        ("method", Json::text("textDocument/publishDiagnostics")),
+        ("params", Json::object(vec![("uri", Json::text(uri)), ("diagnostics", Json::Array(diagnostics))])),
+    ]))
+}
+
+impl Document {
+    fn new(text: &str, mut parser: IncrementalParser) -> Self {
+        let (Parsed { tokens, ast, errors }, unreadable) = match parser.parse(text) {
+            Ok(parsed) => (parsed, None),
+            Err(error) => (Parsed { tokens: Vec::new(), ast: Vec::new(), errors: Vec::new() }, Some(error)),
+        };
+        let declarations = declarations(&tokens);
+        Document {
+            text: text.to_string(),
+            tokens,
+            ast,
+            syntax_errors: errors,
+            unreadable,
+            declarations,
+            analyzer: Analyzer::new(),
+            parser,
+        }
+    }
+
+    // Checks the document as the Runtime would before running it, going on
+    // past statements that don't parse to check the rest. A syntax error
+    // marks the token it was found at, anything else the whole line.
+    fn diagnostics(&mut self) -> Vec<Json> {
+        let mut problems: Vec<(Json, String)> = self.syntax_errors.iter().map(|error| {
+            let (line, start, end) = (error.token.line - 1, start_column(&error.token), error.token.column.saturating_sub(1));
+            let place = if end > start { self.range(line, start, end) } else { self.line_range(error.token.line) };
+            (place, error.message.clone())
+        }).collect();
+        if let Some(error) = &self.unreadable {
+            problems.push((self.line_range(parser::error_line(error)), error.clone()));
+        }
+        self.analyzer.strict = Analyzer::strict_directive(self.text.lines());
+        if let Err(error) = self.analyzer.analyze(&self.ast) {
+            problems.push((self.line_range(self.analyzer.current_line()), error));
+        }
+        problems.into_iter().map(|(place, message)| Json::object(vec![
+            ("range", place),
+            ("severity", 1.into()),
+            ("source", Json::text("nair")),
+            ("message", Json::text(&message)),
+        ])).collect()
+    }
+
+    // All of a line, counting from 1
+    fn line_range(&self, line: usize) -> Json {
+        let line = line.max(1) - 1;
+        let width = self.text.lines().nth(line).map(|text| text.encode_utf16().count()).unwrap_or(0);
+        range(line, 0, width)
+    }
+
+    // Characters along a line, counting from 0, as the UTF-16 code units
+    // editors count positions in
+    fn range(&self, line: usize, start: usize, end: usize) -> Json {
+        let text = self.text.lines().nth(line).unwrap_or("");
+        let units = |character: usize| text.chars().take(character).map(char::len_utf16).sum::<usize>();
+        range(line, units(start), units(end))
+    }
+
+    // How many characters along a line, counting from 1, an editor's
+    // position `units` UTF-16 code units along it is
+    fn character(&self, line: usize, units: usize) -> usize {
+        let mut counted = 0;
+        self.text.lines().nth(line - 1).unwrap_or("").chars()
+            .take_while(|c| {
+                counted += c.len_utf16();
+                counted <= units
+            })
+            .count()
+    }
+
+    fn hover(&self, line: usize, character: usize) -> Json {
+        let Some(token) = self.token_at(line, character) else {
+            return Json::Null;
+        };
+        let name = &self.tokens[token].literal;
+        let contents = match (self.analyzer.variables.get(name), self.declaration_of(name)) {
+            (_, Some(declaration)) if declaration.kind != VARIABLE_KIND => self.line_text(self.tokens[declaration.token].line),
+            (Some(typ), _) => format!("{} as {}", name, typ),
+            (None, Some(declaration)) => self.line_text(self.tokens[declaration.token].line),
+            (None, None) => return Json::Null,
+        };
+        Json::object(vec![("contents", Json::object(vec![
+            ("kind", Json::text("plaintext")),
+            ("value", Json::text(&contents)),
+        ]))])
+    }
+
+    fn definition(&self, uri: &str, line: usize, character: usize) -> Json {
+        let declaration = self.token_at(line, character)
+            .and_then(|token| self.declaration_of(&self.tokens[token].literal));
+        match declaration {
+            Some(declaration) => {
+                let token = &self.tokens[declaration.token];
+                let start = start_column(token);
+                Json::object(vec![
+                    ("uri", Json::text(uri)),
+                    ("range", self.range(token.line - 1, start, start + token.literal.chars().count())),
+                ])
+            },
+            None => Json::Null,
+        }
+    }
+
+    fn completion(&self) -> Json {
+        let mut items: Vec<Json> = Vec::new();
+        let mut seen: Vec<&str> = Vec::new();
+        let names = self.declarations.iter().map(|declaration| (declaration.name.as_str(), declaration.kind));
+        let keywords = KEYWORDS.iter().map(|(word, _)| (*word, KEYWORD_KIND));
+        for (label, kind) in names.chain(keywords) {
+            if !seen.contains(&label) {
+                seen.push(label);
+                items.push(Json::object(vec![("label", Json::text(label)), ("kind", kind.into())]));
+            }
+        }
+        Json::Array(items)
+    }
+
+    // The identifier under the cursor; `line` is 1-based, `character` counts
+    // UTF-16 code units from 0
+    fn token_at(&self, line: usize, character: usize) -> Option<usize> {
+        let character = self.character(line, character);
+        self.tokens.iter().position(|token| {
+            let start = start_column(token);
+            matches!(token.token_type, TokenType::Identifier(_))
+                && token.line == line
+                && (start..=start + token.literal.chars().count()).contains(&character)
+        })
+    }
+
+    fn declaration_of(&self, name: &str) -> Option<&Declaration> {
+        self.declarations.iter().find(|declaration| declaration.name == name)
+    }
+
+    fn line_text(&self, line: usize) -> String {
+        self.text.lines().nth(line - 1).unwrap_or("").trim().to_string()
+    }
+}
+
+// Names introduced by `Task NAME`, `Object NAME`, `Behavior NAME`, and
+// statements starting `NAME is` or `NAME as`, in the order they appear
+fn declarations(tokens: &[Token]) -> Vec<Declaration> {
+    let mut declarations: Vec<Declaration> = Vec::new();
+    for (i, token) in tokens.iter().enumerate() {
+        let TokenType::Identifier(name) = &token.token_type else {
+            continue;
+        };
+        let previous = tokens[..i].iter().rev()
+            .find(|token| !matches!(token.token_type, TokenType::Indent | TokenType::Dedent));
+        let next = tokens.get(i + 1).map(|token| &token.token_type);
+        let kind = match (previous.map(|token| &token.token_type), next) {
+            (Some(TokenType::Task), _) => FUNCTION_KIND,
+            (Some(TokenType::Object | TokenType::Behavior), _) => CLASS_KIND,
+            (None | Some(TokenType::NewLine), Some(TokenType::Is | TokenType::As)) => VARIABLE_KIND,
+            _ => continue,
+        };
+        if !declarations.iter().any(|declaration| &declaration.name == name) {
+            declarations.push(Declaration { name: name.clone(), kind, token: i });
+        }
+    }
+    declarations
+}
+
+// Editors count columns from 0
+fn start_column(token: &Token) -> usize {
+    token.start_column() - 1
+}
+
+fn range(line: usize, start: usize, end: usize) -> Json {
+    let position = |character: usize| Json::object(vec![("line", line.into()), ("character", character.into())]);
+    Json::object(vec![("start", position(start)), ("end", position(end))])
+}
@@ -1,27 +1,276 @@
-mod tokenizer;
-mod runtime;
-mod parser;
-mod generator;
-mod analyzer;
-
-use crate::runtime::Runtime;
+use nair::{ast_json, bundle, cache, dap, emit_js, emit_py, emit_wasm, formatter, kernel, linter, lsp, remote, serve, test_runner, watch};
+use nair::runtime::{Capability, Runtime};
+use nair::generator::Value;
+use nair::json::Json;
+use nair::formatter::FormatOptions;
+use nair::linter::LintOptions;
+use nair::numbers::Numbers;
+use nair::collation::Collation;
+use nair::config::Config;
+use nair::theme::Theme;
+use nair::serve::ServeOptions;
+use nair::test_runner::TestOptions;
 use std::env;
+use std::process::ExitCode;
+
+fn main() -> ExitCode {
+    let mut args: Vec<String> = env::args().collect();
+
+    // --no-color (or NO_COLOR) leaves errors, warnings and values uncolored,
+    // as they are anyway when not written to a terminal
+    let theme = Theme::detect(!take_flag(&mut args, "--no-color"));
+    match start(args, theme) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(error) => {
+            eprintln!("{}", theme.failure(&format!("Error: {}", error)));
+            ExitCode::FAILURE
+        },
+    }
+}
+
+fn start(mut args: Vec<String>, theme: Theme) -> Result<(), String> {
+    // An executable made by `nair build` runs its program and nothing else
+    if let Some(result) = bundle::run_embedded() {
+        return result;
+    }
+
+    // --sandbox keeps the program from touching files or pausing
+    let mut runtime = match take_flag(&mut args, "--sandbox") {
+        true => Runtime::with_capabilities(&Capability::SANDBOXED),
+        false => Runtime::new(),
+    };
+    runtime.set_theme(theme);
+
+    // --quiet shows only the program's own output
+    if take_flag(&mut args, "--quiet") {
+        runtime.set_debug_output(false);
+    }
+
+    // --strict-keywords stops `Show` and `WHEN` from counting as keywords
+    if take_flag(&mut args, "--strict-keywords") {
+        runtime.set_strict_keywords(true);
+    }
+
+    // --strict has the analyzer insist on types, as `# strict` does for one file
+    if take_flag(&mut args, "--strict") {
+        runtime.set_strict(true);
+    }
 
-fn main() -> Result<(), String> {
-    let args: Vec<String> = env::args().collect();
-    let mut runtime = Runtime::new();
+    // --strict-numbers reads only digits as numbers, so `twenty` is a name
+    if take_flag(&mut args, "--strict-numbers") {
+        runtime.set_number_words(false);
+    }
+
+    // --numbers exact does Decimal arithmetic on the digits as written, so
+    // 0.1 + 0.2 is 0.3; --numbers float (the default) is faster
+    if let Some(name) = take_value(&mut args, "--numbers")? {
+        runtime.set_numbers(Numbers::from_name(&name).ok_or("--numbers is float or exact")?);
+    }
+
+    // --collation es sorts Text in Spanish alphabetical order unless a
+    // program names another language
+    if let Some(name) = take_value(&mut args, "--collation")? {
+        runtime.set_collation(Collation::named(&name).ok_or_else(|| format!("--collation is {}", Collation::names()))?);
+    }
 
-    match args.len() {
+    // Running a file reuses what an earlier run compiled, unless --no-cache
+    if !take_flag(&mut args, "--no-cache") {
+        if let Some(directory) = cache::default_directory() {
+            runtime.set_bytecode_cache(cache::BytecodeCache::new(directory));
+        }
+    }
+
+    // --max-depth N limits how deeply Tasks may call each other
+    if let Some(depth) = take_number(&mut args, "--max-depth")? {
+        runtime.set_max_call_depth(depth as usize);
+    }
+
+    // --max-stack N limits how many values may wait on the operand stack
+    if let Some(depth) = take_number(&mut args, "--max-stack")? {
+        runtime.set_max_stack_depth(depth as usize);
+    }
+
+    // --fuel N stops the program after N instructions
+    let fuel = take_number(&mut args, "--fuel")?;
+    if let Some(fuel) = fuel {
+        runtime.set_fuel(fuel);
+    }
+
+    // --memory-limit N stops the program once its values hold more than N bytes
+    let memory_limit = take_number(&mut args, "--memory-limit")?.map(|bytes| bytes as usize);
+    if let Some(bytes) = memory_limit {
+        runtime.set_memory_limit(bytes);
+    }
+
+    // --set NAME=VALUE gives the program a variable holding the JSON value,
+    // or the text if it isn't JSON; --get NAME prints a variable as JSON
+    // once the program has finished
+    while let Some(setting) = take_value(&mut args, "--set")? {
+        let (name, value) = setting.split_once('=').ok_or("--set needs NAME=VALUE")?;
+        runtime.set_global(name.trim(), literal(value));
+    }
+    let mut results = Vec::new();
+    while let Some(name) = take_value(&mut args, "--get")? {
+        results.push(name);
+    }
+
+    // --debug pauses before the first statement and takes debugger commands
+    if take_flag(&mut args, "--debug") {
+        runtime.enable_debugger();
+    }
+
+    // --deterministic makes output identical from run to run
+    let deterministic = take_flag(&mut args, "--deterministic");
+    runtime.set_deterministic(deterministic);
+
+    // --profile reports where the program spent its time; --flamegraph FILE
+    // also writes the time per call chain for flamegraph tools
+    let flamegraph = take_value(&mut args, "--flamegraph")?;
+    let profiling = take_flag(&mut args, "--profile") || flamegraph.is_some();
+    if profiling {
+        runtime.enable_profiler(!deterministic);
+    }
+
+    // --trace FILE logs every instruction the program runs as JSON lines
+    if let Some(path) = take_value(&mut args, "--trace")? {
+        let file = std::fs::File::create(&path).map_err(|e| format!("Error creating '{}': {}", path, e))?;
+        runtime.set_trace(Box::new(std::io::BufWriter::new(file)));
+    }
+
+    // --dap speaks the Debug Adapter Protocol on standard input and output
+    if take_flag(&mut args, "--dap") {
+        runtime.set_debug_output(false);
+        return dap::serve(runtime);
+    }
+
+    // nair test --coverage [--lcov FILE] reports which statements the tests ran
+    let tests = TestOptions {
+        deterministic,
+        coverage: take_flag(&mut args, "--coverage"),
+        lcov: take_value(&mut args, "--lcov")?,
+    };
+
+    // nair fmt --check lists unformatted files; --stdout prints instead of rewriting
+    let format = FormatOptions {
+        check: take_flag(&mut args, "--check"),
+        stdout: take_flag(&mut args, "--stdout"),
+    };
+
+    // nair lint --fix applies the fixes it can; --allow a,b skips those lints
+    let lint = LintOptions {
+        fix: take_flag(&mut args, "--fix"),
+        allow: take_value(&mut args, "--allow")?
+            .map(|rules| rules.split(',').map(|rule| rule.trim().to_string()).collect())
+            .unwrap_or_default(),
+    };
+
+    // nair parse --json prints the syntax tree and diagnostics for tools
+    let json = take_flag(&mut args, "--json");
+
+    // nair serve gives each program it runs the --fuel and --memory-limit given
+    let serving = ServeOptions { fuel, memory_limit };
+
+    let result = run(&mut runtime, &args, &tests, &format, &lint, json, &serving);
+    if result.is_ok() {
+        for name in &results {
+            let value = runtime.get_global(name).ok_or_else(|| format!("The program has no variable named '{}'", name))?;
+            println!("{}: {}", name, Json::from_value(&value)?);
+        }
+    }
+
+    if let Some(report) = runtime.profile_report() {
+        eprint!("{}", report);
+    }
+    if let (Some(path), Some(stacks)) = (flamegraph, runtime.profile_folded_stacks()) {
+        std::fs::write(&path, stacks).map_err(|e| format!("Error writing '{}': {}", path, e))?;
+    }
+    result
+}
+
+fn run(runtime: &mut Runtime, args: &[String], tests: &TestOptions, format: &FormatOptions, lint: &LintOptions, json: bool, serving: &ServeOptions) -> Result<(), String> {
+    match args {
         // No arguments - run REPL
-        1 => runtime.run_repl(),
+        [] | [_] => runtime.run_repl(Config::load(runtime.theme())),
+
+        // The REPL, shared over TCP with --listen: nair repl [--listen address [--token token]]
+        [_, command, rest @ ..] if command == "repl" => remote::run(runtime, rest),
+
+        // Test runner: nair test [files or directories]
+        [_, command, paths @ ..] if command == "test" => test_runner::run_tests(paths, tests),
+
+        // Formatter: nair fmt [files or directories]
+        [_, command, paths @ ..] if command == "fmt" => formatter::format_files(paths, format),
+
+        // Linter: nair lint [files or directories]
+        [_, command, paths @ ..] if command == "lint" => linter::lint_files(paths, lint),
+
+        // Syntax tree: nair parse [--json] files
+        [_, command, paths @ ..] if command == "parse" => ast_json::parse_files(paths, json),
+
+        // Standalone executable: nair build script [-o output]
+        [_, command, rest @ ..] if command == "build" => bundle::build(runtime, rest),
+
+        // JavaScript version of a program: nair emit-js script
+        [_, command, path] if command == "emit-js" => emit_js::emit_file(path),
+        // Python version of a program: nair emit-py script
+        [_, command, path] if command == "emit-py" => emit_py::emit_file(path),
+        // WebAssembly for the Tasks that only work with numbers: nair emit-wasm script [-o output]
+        [_, command, rest @ ..] if command == "emit-wasm" => emit_wasm::emit_file(rest),
+
+        // Re-running a script whenever it is saved: nair watch [--keep-state] script
+        [_, command, rest @ ..] if command == "watch" => watch::watch(runtime, rest),
+
+        // Jupyter notebooks: nair kernel connection-file | nair kernel install
+        [_, command, rest @ ..] if command == "kernel" => kernel::run(runtime, rest),
+
+        // Running programs sent over HTTP, for playgrounds: nair serve [--listen address]
+        [_, command, rest @ ..] if command == "serve" => serve::serve(rest, serving),
+
+        // Language server for editors: nair lsp
+        [_, command] if command == "lsp" => lsp::serve(),
         
         // File argument provided
-        2 => {
-            let file_path = &args[1];
-            runtime.run_file(file_path)
-        },
+        [_, file_path] => runtime.run_file(file_path),
         
         // Invalid number of arguments
-        _ => Err("Usage: nair [script]".to_string()),
+        _ => Err("Usage: nair [--quiet] [--no-color] [--sandbox] [--strict] [--strict-keywords] [--strict-numbers] [--numbers float|exact] [--collation LANGUAGE] [--no-cache] [--debug | --dap] [--profile] [--flamegraph FILE] [--trace FILE] [--max-depth N] [--max-stack N] [--fuel N] [--memory-limit N] [--deterministic] [--set NAME=VALUE] [--get NAME] [script] | nair test [--deterministic] [--coverage] [--lcov FILE] [paths...] | nair fmt [--check | --stdout] [paths...] | nair lint [--fix] [--allow RULES] [paths...] | nair parse [--json] files... | nair build script [-o output] | nair emit-js script | nair emit-py script | nair emit-wasm script [-o output] | nair watch [--keep-state] script | nair repl [--listen address [--token token]] | nair kernel connection-file | nair kernel install | nair serve [--listen address] [--fuel N] [--memory-limit N] | nair lsp".to_string()),
     }
 }
+
+// Removes a flag from the arguments, saying whether it was there
+fn take_flag(args: &mut Vec<String>, flag: &str) -> bool {
+    match args.iter().position(|arg| arg == flag) {
+        Some(position) => {
+            args.remove(position);
+            true
+        },
+        None => false,
+    }
+}
+
+// Removes a flag and the value after it
+fn take_value(args: &mut Vec<String>, flag: &str) -> Result<Option<String>, String> {
+    let Some(position) = args.iter().position(|arg| arg == flag) else {
+        return Ok(None);
+    };
+    if position + 1 >= args.len() {
+        return Err(format!("{} needs a value", flag));
+    }
+    let value = args.remove(position + 1);
+    args.remove(position);
+    Ok(Some(value))
+}
+
+// A value given on the command line: JSON, or else text
+fn literal(value: &str) -> Value {
+    match Json::parse(value) {
+        Ok(json) => json.to_value(),
+        Err(_) => Value::String(value.to_string()),
+    }
+}
+
+fn take_number(args: &mut Vec<String>, flag: &str) -> Result<Option<u64>, String> {
+    take_value(args, flag)?
+        .map(|value| value.parse::<u64>().map_err(|_| format!("{} needs a whole number", flag)))
+        .transpose()
+}
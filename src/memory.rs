@@ -0,0 +1,70 @@
+use crate::generator::{Instance, Value};
+use std::collections::HashSet;
+use std::mem::size_of;
+use std::sync::Arc;
+
+/// Approximate bytes held by values, counting each object, list and mapping
+/// once however many handles reach it.
+#[derive(Default)]
+pub struct HeapMeter {
+    seen: HashSet<*const ()>,
+    pub bytes: usize,
+}
+
+impl HeapMeter {
+    pub fn new() -> Self {
+        HeapMeter::default()
+    }
+
+    pub fn add_name(&mut self, name: &str) {
+        self.bytes += size_of::<String>() + name.len();
+    }
+
+    pub fn add(&mut self, value: &Value) {
+        self.bytes += size_of::<Value>();
+        match value {
+            Value::String(text) => self.bytes += text.capacity(),
+            Value::Class(name) => self.bytes += name.capacity(),
+            Value::List(items) => {
+                if self.seen.insert(Arc::as_ptr(items).cast()) {
+                    for item in items.borrow().iter() {
+                        self.add(item);
+                    }
+                }
+            },
+            Value::Mapping(mapping) => {
+                // Keys are stored twice: in entry order and in the lookup index
+                if self.seen.insert(Arc::as_ptr(mapping).cast()) {
+                    for (key, entry) in mapping.borrow().iter() {
+                        self.add(key);
+                        self.add(key);
+                        self.add(entry);
+                    }
+                }
+            },
+            Value::Object(instance) => {
+                if self.seen.insert(Arc::as_ptr(instance).cast()) {
+                    self.bytes += size_of::<Instance>() + instance.class_name.capacity();
+                    for (name, field) in instance.fields.borrow().iter() {
+                        self.add_name(name);
+                        self.add(field);
+                    }
+                }
+            },
+            Value::Number(_) | Value::Money(_) | Value::Quantity(_) | Value::Date(_) | Value::Boolean(_) | Value::Null => {},
+        }
+    }
+}
+
+/// Bytes a value adds when it is first made, not counting objects it only
+/// refers to (their fields were counted as they were set).
+pub fn fresh_size(value: &Value) -> usize {
+    match value {
+        Value::String(text) => size_of::<Value>() + text.capacity(),
+        Value::List(items) => size_of::<Value>() + items.borrow().iter().map(fresh_size).sum::<usize>(),
+        Value::Mapping(mapping) => size_of::<Value>()
+            + mapping.borrow().iter().map(|(key, entry)| 2 * fresh_size(key) + fresh_size(entry)).sum::<usize>(),
+        Value::Object(_) => size_of::<Value>() + size_of::<Instance>(),
+        _ => size_of::<Value>(),
+    }
+}
@@ -0,0 +1,195 @@
+use crate::failure::Failure;
+use crate::numbers;
+
+/// The currencies Money can be in, each with its symbol, the words for it
+/// and how many digits its amounts have after the point.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Dollar,
+    Euro,
+    Pound,
+    Yen,
+}
+
+// Currency, code, symbol, names and decimal places
+const CURRENCIES: [(Currency, &str, char, [&str; 2], u32); 4] = [
+    (Currency::Dollar, "USD", '$', ["dollar", "dollars"], 2),
+    (Currency::Euro, "EUR", '€', ["euro", "euros"], 2),
+    (Currency::Pound, "GBP", '£', ["pound", "pounds"], 2),
+    (Currency::Yen, "JPY", '¥', ["yen", "yen"], 0),
+];
+
+impl Currency {
+    fn entry(self) -> &'static (Currency, &'static str, char, [&'static str; 2], u32) {
+        CURRENCIES.iter().find(|entry| entry.0 == self).expect("every currency is listed")
+    }
+
+    /// The three-letter code, like `USD`.
+    pub fn code(self) -> &'static str {
+        self.entry().1
+    }
+
+    pub fn symbol(self) -> char {
+        self.entry().2
+    }
+
+    /// Digits after the point: 2 for cents, 0 for yen.
+    pub fn places(self) -> u32 {
+        self.entry().4
+    }
+
+    pub fn from_code(code: &str) -> Option<Currency> {
+        CURRENCIES.iter().find(|entry| entry.1 == code).map(|entry| entry.0)
+    }
+
+    pub fn from_symbol(symbol: char) -> Option<Currency> {
+        CURRENCIES.iter().find(|entry| entry.2 == symbol).map(|entry| entry.0)
+    }
+
+    // `dollars`, `euro` and so on
+    fn from_word(word: &str) -> Option<Currency> {
+        CURRENCIES.iter().find(|entry| entry.3.contains(&word)).map(|entry| entry.0)
+    }
+
+    fn unit(self) -> i64 {
+        10i64.pow(self.places())
+    }
+}
+
+// What an amount too big for its count of cents fails with
+fn too_much() -> Failure {
+    Failure::new("ArithmeticError", "That many cents does not fit in a Whole")
+}
+
+/// An amount of money, held exactly as a count of the currency's smallest
+/// unit (cents, for dollars), so sums never drift the way Decimals do.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub amount: i64,
+    pub currency: Currency,
+}
+
+impl Money {
+    /// Money from digits as written, like `19.99` in `$19.99`, which may
+    /// not have more places than the currency.
+    pub fn parse(digits: &str, currency: Currency) -> Result<Money, String> {
+        let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+        if fraction.len() > currency.places() as usize {
+            return Err(format!("{} has {} decimal places at most, not {}", currency.code(), currency.places(), digits));
+        }
+        let fraction = format!("{:0<1$}", fraction, currency.places() as usize);
+        format!("{}{}", whole, fraction).parse().map(|amount| Money { amount, currency })
+            .map_err(|_| format!("{} in cents does not fit in a Whole", digits))
+    }
+
+    /// The currency `19.99 dollars` names, with `cents` counting hundredths
+    /// of a dollar.
+    pub fn from_words(digits: &str, word: &str) -> Option<Result<Money, String>> {
+        if word == "cents" || word == "cent" {
+            return Some(digits.parse().map(|amount| Money { amount, currency: Currency::Dollar })
+                .map_err(|_| format!("{} cents is not a whole number of cents", digits)));
+        }
+        Currency::from_word(word).map(|currency| Money::parse(digits, currency))
+    }
+
+    /// The amount in whole units of the currency, like 19.99 for $19.99.
+    pub fn to_number(self) -> f64 {
+        format!("{}e-{}", self.amount, self.currency.places()).parse().unwrap_or(f64::NAN)
+    }
+
+    /// `a + b` or `a - b`, which must be in the same currency.
+    pub fn add(self, other: Money, subtract: bool) -> Result<Money, Failure> {
+        let other = self.same_currency(other, if subtract { "subtract" } else { "add" })?;
+        let amount = match subtract {
+            true => self.amount.checked_sub(other.amount),
+            false => self.amount.checked_add(other.amount),
+        };
+        amount.map(|amount| Money { amount, ..self }).ok_or_else(too_much)
+    }
+
+    /// `a * n`, to the nearest cent, with halves rounding away from zero as
+    /// `round` has them.
+    pub fn times(self, factor: f64) -> Result<Money, Failure> {
+        let (digits, scale) = numbers::digits(factor).ok_or_else(too_much)?;
+        let exact = (self.amount as i128).checked_mul(digits).ok_or_else(too_much)?;
+        // A factor with that many places is too small to come to a cent
+        let Some(denominator) = 10i128.checked_pow(scale) else {
+            return Ok(Money { amount: 0, ..self });
+        };
+        self.rounded(exact, denominator)
+    }
+
+    /// `a / n`, to the nearest cent.
+    pub fn divided_by(self, divisor: f64) -> Result<Money, Failure> {
+        let (digits, scale) = numbers::digits(divisor).ok_or_else(too_much)?;
+        if digits == 0 {
+            return Err(Failure::new("ArithmeticError", format!("Cannot divide {} by zero", self)));
+        }
+        let exact = (self.amount as i128).checked_mul(10i128.checked_pow(scale).ok_or_else(too_much)?).ok_or_else(too_much)?;
+        self.rounded(exact, digits)
+    }
+
+    /// `a / b` for two amounts in the same currency: how many times b goes
+    /// into a, as a Decimal.
+    pub fn ratio(self, other: Money) -> Result<f64, Failure> {
+        let other = self.same_currency(other, "divide")?;
+        match other.amount {
+            0 => Err(Failure::new("ArithmeticError", format!("Cannot divide {} by zero", self))),
+            amount => Ok(self.amount as f64 / amount as f64),
+        }
+    }
+
+    pub fn compare(self, other: Money) -> Result<std::cmp::Ordering, Failure> {
+        Ok(self.amount.cmp(&self.same_currency(other, "compare")?.amount))
+    }
+
+    fn same_currency(self, other: Money, doing: &str) -> Result<Money, Failure> {
+        match self.currency == other.currency {
+            true => Ok(other),
+            false => Err(Failure::new("TypeError", format!("Type mismatch: cannot {} {} and {}, which are in different currencies", doing, self, other))),
+        }
+    }
+
+    // numerator / denominator units, to the nearest one
+    fn rounded(self, numerator: i128, denominator: i128) -> Result<Money, Failure> {
+        let (quotient, rest) = (numerator / denominator, numerator % denominator);
+        let away = rest.unsigned_abs() * 2 >= denominator.unsigned_abs();
+        let quotient = if away { quotient + if (numerator < 0) == (denominator < 0) { 1 } else { -1 } } else { quotient };
+        i64::try_from(quotient).map(|amount| Money { amount, ..self }).map_err(|_| too_much())
+    }
+
+    /// The amount as shown, with its symbol and every decimal place, and
+    /// commas between the thousands if asked for: `-$1,234.50`.
+    pub fn written(self, commas: bool) -> String {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        format!("{}{}{}", sign, self.currency.symbol(), self.digits(commas))
+    }
+
+    // The amount without its sign or symbol
+    fn digits(self, commas: bool) -> String {
+        let unit = self.currency.unit().unsigned_abs();
+        let (whole, fraction) = (self.amount.unsigned_abs() / unit, self.amount.unsigned_abs() % unit);
+        let mut whole = whole.to_string();
+        if commas {
+            let groups: Vec<&str> = whole.as_bytes().rchunks(3).rev().map(|group| std::str::from_utf8(group).unwrap()).collect();
+            whole = groups.join(",");
+        }
+        match self.currency.places() {
+            0 => whole,
+            places => format!("{}.{:0width$}", whole, fraction, width = places as usize),
+        }
+    }
+
+    /// The amount with its sign but not its symbol, as `parse` reads it
+    /// back: `-19.99`.
+    pub fn amount_text(self) -> String {
+        let sign = if self.amount < 0 { "-" } else { "" };
+        format!("{}{}", sign, self.digits(false))
+    }
+}
+
+impl std::fmt::Display for Money {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.written(false))
+    }
+}
@@ -0,0 +1,123 @@
+/// How a Runtime does arithmetic on Decimals. `Float` is the machine's own
+/// arithmetic: fast, but 0.1 + 0.2 gives 0.30000000000000004. `Exact` works
+/// on the digits as written, so 0.1 + 0.2 is 0.3 and 1.1 * 1.1 is 1.21, at
+/// some cost in speed. Both keep numbers to about 16 significant digits, and
+/// a division that never ends, like 1 / 3, is cut off there.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Numbers {
+    #[default]
+    Float,
+    Exact,
+}
+
+// More digits than this don't fit in the i128 the exact arithmetic uses
+const MAX_DIGITS: usize = 36;
+
+impl Numbers {
+    pub fn from_name(name: &str) -> Option<Numbers> {
+        match name {
+            "float" => Some(Numbers::Float),
+            "exact" => Some(Numbers::Exact),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Numbers::Float => "float",
+            Numbers::Exact => "exact",
+        }
+    }
+
+    /// `x symbol y` for Decimals, which `op` does in machine arithmetic.
+    /// Exact arithmetic falls back to it for powers and for numbers with
+    /// too many digits to work with exactly.
+    pub fn arithmetic(self, symbol: &str, x: f64, y: f64, op: impl Fn(f64, f64) -> f64) -> f64 {
+        if self == Numbers::Float {
+            return op(x, y);
+        }
+        let exact = digits(x).zip(digits(y)).and_then(|(x, y)| match symbol {
+            "+" => add(x, y),
+            "-" => add(x, (-y.0, y.1)),
+            "*" => Some((x.0.checked_mul(y.0)?, x.1 + y.1)),
+            "/" => divide(x, y),
+            "%" => remainder(x, y),
+            _ => None,
+        });
+        exact.map(number).unwrap_or_else(|| op(x, y))
+    }
+
+    /// The number a cast to Decimal or Whole starts from. Exact numbers drop
+    /// the noise machine arithmetic (say, a square root) leaves in the last
+    /// digits, so 2.9999999999999996 counts as the 3 it was meant to be.
+    /// Converting between units of measure settles the same way.
+    pub fn settle(self, n: f64) -> f64 {
+        match self {
+            Numbers::Exact if n.is_finite() => format!("{:.14e}", n).parse().unwrap_or(n),
+            _ => n,
+        }
+    }
+}
+
+/// A number as its digits and how many of them follow the point, from the
+/// shortest way of writing it that reads back as the same f64.
+pub fn digits(n: f64) -> Option<(i128, u32)> {
+    if !n.is_finite() {
+        return None;
+    }
+    let written = n.abs().to_string();
+    let (whole, fraction) = written.split_once('.').unwrap_or((&written, ""));
+    let all = format!("{}{}", whole, fraction);
+    let all = all.trim_start_matches('0');
+    if all.len() > MAX_DIGITS {
+        return None;
+    }
+    let value: i128 = if all.is_empty() { 0 } else { all.parse().ok()? };
+    Some((if n < 0.0 { -value } else { value }, fraction.len() as u32))
+}
+
+// The f64 closest to digits with a point placed before the last `scale`
+fn number((value, scale): (i128, u32)) -> f64 {
+    format!("{}e-{}", value, scale).parse().unwrap_or(f64::NAN)
+}
+
+// Both numbers with the same digits after the point
+fn align(x: (i128, u32), y: (i128, u32)) -> Option<(i128, i128, u32)> {
+    let scale = x.1.max(y.1);
+    let widen = |(value, own): (i128, u32)| value.checked_mul(10i128.checked_pow(scale - own)?);
+    Some((widen(x)?, widen(y)?, scale))
+}
+
+fn add(x: (i128, u32), y: (i128, u32)) -> Option<(i128, u32)> {
+    let (x, y, scale) = align(x, y)?;
+    Some((x.checked_add(y)?, scale))
+}
+
+// As many digits of the quotient as fit, the last rounded half to even
+fn divide(x: (i128, u32), y: (i128, u32)) -> Option<(i128, u32)> {
+    if y.0 == 0 {
+        return None;
+    }
+    let mut shift = 0;
+    while shift < MAX_DIGITS as u32 && x.0.checked_mul(10i128.pow(shift + 1)).is_some() {
+        shift += 1;
+    }
+    let numerator = x.0 * 10i128.pow(shift);
+    let (mut quotient, rest) = (numerator / y.0, numerator % y.0);
+    let twice = rest.unsigned_abs() * 2;
+    if twice > y.0.unsigned_abs() || (twice == y.0.unsigned_abs() && quotient % 2 != 0) {
+        quotient += if (numerator < 0) == (y.0 < 0) { 1 } else { -1 };
+    }
+    let scale = (x.1 + shift) as i64 - y.1 as i64;
+    match u32::try_from(scale) {
+        Ok(scale) => Some((quotient, scale)),
+        Err(_) => Some((quotient.checked_mul(10i128.checked_pow(scale.unsigned_abs() as u32)?)?, 0)),
+    }
+}
+
+// What is left of x after taking away whole multiples of y, with the sign
+// of x, as `%` has it for machine numbers
+fn remainder(x: (i128, u32), y: (i128, u32)) -> Option<(i128, u32)> {
+    let (x, y, scale) = align(x, y)?;
+    Some((x.checked_rem(y)?, scale))
+}
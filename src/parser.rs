@@ -1,5 +1,10 @@
-use crate::{analyzer::Type, tokenizer::{Token, TokenType}};
+use crate::tokenizer::{Token, TokenType, Tokenizer};
 use crate::generator::Value;
+use crate::money::Money;
+use crate::units::{Quantity, Unit};
+use crate::dates::Date;
+use crate::collation::Collation;
+use crate::formatter::{expression, operator_text};
 
 #[derive(Debug, Clone)]
 pub enum Node {
@@ -18,27 +23,107 @@ pub enum Node {
     ObjectDecl {
         name: String,
         base: Option<Box<Node>>,
+        fields: Vec<Node>,
         constructor: Option<Box<Node>>,
         methods: Vec<Node>,
+        properties: Vec<Node>,
+        shared_fields: Vec<Node>,
+        shared_methods: Vec<Node>,
+        behaviors: Vec<String>,
+        // A reusable behavior bundle rather than an object kind
+        is_behavior: bool,
+    },
+    // `build` block run by `new`; each parameter has a label used for
+    // labeled arguments (the parameter name unless one is given)
+    ConstructorDecl {
+        params: Vec<Node>,
+        labels: Vec<String>,
+        body: Box<Node>,
+    },
+    // Computed property: a getter (`about area returns Decimal:`) or a
+    // setter (`about area becomes value:`) declared inside an object
+    PropertyDecl {
+        name: String,
+        setter_param: Option<Box<Node>>,
+        return_type: Option<Box<Node>>,
+        body: Box<Node>,
     },
 
     // Statements
     Block(Vec<Node>),
+    Line(usize),    // marks where the next statement starts in the source
+    // A statement that didn't parse, with why; it stands in for the
+    // statement so the ones after it can still be read
+    Error(String),
     ExpressionStmt(Box<Node>),
+    // `output value`, `give back value` or `stop here` (giving back Nothing)
     ReturnStmt(Box<Node>),
+    // `if x is Nothing, give back 0`: gives back the value when the
+    // condition holds
+    GuardStmt {
+        condition: Box<Node>,
+        value: Box<Node>,
+    },
     WhenStmt {
         condition: Box<Node>,
         then_branch: Box<Node>,
+        or_when: Vec<OrWhen>,
         else_branch: Option<Box<Node>>,
     },
+    // Loops may be named with `, called the outer loop` at the end of
+    // their header, which `stop` and `continue` can then refer to
     LoopStmt {
         condition: Box<Node>,
         body: Box<Node>,
+        label: Option<String>,
+    },
+    // `keep doing the following:`, which runs until a `stop`
+    KeepDoingStmt {
+        body: Box<Node>,
+        label: Option<String>,
+    },
+    // Leaves the loop it's in, or the one named (`stop the outer loop`)
+    StopStmt(Option<String>),
+    // Goes on to the next time round that loop
+    ContinueStmt(Option<String>),
+    // `repeat 5 times, counting with i:`
+    RepeatStmt {
+        count: Box<Node>,
+        counter: Option<String>,
+        body: Box<Node>,
+        label: Option<String>,
+    },
+    // `measure how long it takes to, 20 times, calling it sorting:`. Without
+    // a name the timing is shown rather than kept.
+    MeasureStmt {
+        runs: Option<Box<Node>>,
+        result: Option<String>,
+        body: Box<Node>,
     },
     ShowStmt(Box<Node>),
+    // `raise value [as Kind]`; the value is a message or an error object
     RaiseStmt {
         message: Box<Node>,
-        error_type: Box<Node>,
+        error_type: Option<Box<Node>>,
+    },
+    // `expect that X` (expected is None) or `expect X to equal Y`.
+    // Keeps the line and source text for failure reports.
+    ExpectStmt {
+        actual: Box<Node>,
+        expected: Option<Box<Node>>,
+        line: usize,
+        source: String,
+    },
+    // `to test adding numbers:`; only run by the test runner
+    TestDecl {
+        name: String,
+        body: Box<Node>,
+    },
+    // do: ... fail [name] [as Kind]: ... always: ...
+    TryStmt {
+        body: Box<Node>,
+        handlers: Vec<FailClause>,
+        always: Option<Box<Node>>,
     },
 
     // Expressions
@@ -55,15 +140,103 @@ pub enum Node {
         object: Box<Node>,
         name: String,
     },
+    // `the name of x, if x exists`: Nothing when x is Nothing
+    SafeGet {
+        object: Box<Node>,
+        name: String,
+    },
     Literal(Value),
     Variable(String),
     Assignment {
         name: String,
         value: Box<Node>,
     },
+    // `increase x by 2` (Plus), `decrease x by 1` (Minus) or `double x`
+    // (Multiply by 2): a variable changed by an amount
+    Change {
+        name: String,
+        operator: TokenType,
+        amount: Box<Node>,
+    },
+    Set {
+        object: Box<Node>,
+        name: String,
+        value: Box<Node>,
+    },
     New {
         class_name: String,
-        args: Vec<Node>,
+        args: Vec<(Option<String>, Node)>, // optional label per argument
+    },
+    Cast {
+        value: Box<Node>,
+        target: Box<Node>,
+    },
+    // `the type of x`: the name of x's type, as Text
+    TypeOf {
+        value: Box<Node>,
+    },
+    // `a if condition otherwise b`
+    Conditional {
+        value: Box<Node>,
+        condition: Box<Node>,
+        otherwise: Box<Node>,
+    },
+    // `a list of 1, then all of xs`: items, and Lists whose items all go in
+    ListOf {
+        parts: Vec<(bool, Node)>,
+    },
+    // `the n * n of each n in numbers where n > 2`: a new List
+    Comprehension {
+        value: Box<Node>,
+        name: String,
+        list: Box<Node>,
+        condition: Option<Box<Node>>,
+    },
+    // `item 3 of xs` / `the value for "name" in ages` (keyed): one item of
+    // a List, Mapping or Text
+    Index {
+        object: Box<Node>,
+        key: Box<Node>,
+        keyed: bool,
+    },
+    // The item number in `the third item`, `the 3rd item` (digits) or `the
+    // second to last item` (from the end, where `the last item` is 1)
+    Ordinal {
+        number: usize,
+        from_end: bool,
+        digits: bool,
+    },
+    // `items 2 through 5 of xs` (first and last, both included),
+    // `everything after item 3 of xs` (first only) or `everything before
+    // item 3 of xs` (last only): a new List or Text from part of one
+    Slice {
+        object: Box<Node>,
+        first: Option<Box<Node>>,
+        last: Option<Box<Node>>,
+    },
+    // `item 3 of xs is 10` / `the value for "name" in ages is 36`
+    SetIndex {
+        object: Box<Node>,
+        key: Box<Node>,
+        keyed: bool,
+        value: Box<Node>,
+    },
+    // `xs followed by ys`: one List's items, then another's
+    FollowedBy {
+        left: Box<Node>,
+        right: Box<Node>,
+    },
+    // `a is the same as b` / `a is not the same as b`
+    SameAs {
+        left: Box<Node>,
+        right: Box<Node>,
+        negated: bool,
+    },
+    // `value is a Type` / `value is not a Type`
+    TypeTest {
+        value: Box<Node>,
+        target: Box<Node>,
+        negated: bool,
     },
 
     // Types
@@ -78,114 +251,423 @@ pub enum Node {
     StringInterpolation {
         parts: Vec<Node>,
     },
-    PromiseType {
-        value_type: Box<Node>,
-    },
     ArrayLiteral {
         elements: Vec<Node>,
         type_annotation: Option<Box<Node>>,
     },
-    ObjectLiteral {
-        fields: Vec<(String, Node)>,
-    },
-    MethodCall {
-        object: Box<Node>,
-        method: String,
-        args: Vec<Node>,
-    },
-    WithExpr {
-        base: Box<Node>,
-        args: Vec<Node>,
-    },
-    UsingExpr {
-        base: Box<Node>,
-        args: Vec<Node>,
-    },
-    MatchExpr {
-        value: Box<Node>,
-        cases: Vec<(Node, Node)>,
-    },
-    EmitStmt(Box<Node>),
-    AwaitExpr {
-        value: Box<Node>,
-    },
-    PropertyAccess {
-        object: Box<Node>,
-        property: String,
-    },
     MappingLiteral {
         entries: Vec<(String, Option<Node>, Node)>, // (param_name, optional_type, value)
     },
 }
 
+/// One `or when` branch of a `when`, tried when the branches before it weren't taken.
+#[derive(Debug, Clone)]
+pub struct OrWhen {
+    pub line: usize,
+    pub condition: Node,
+    pub body: Node,
+}
+
+/// One `fail` clause of a `do` block. A clause without a kind catches every error.
+#[derive(Debug, Clone)]
+pub struct FailClause {
+    pub name: Option<String>,
+    pub kind: Option<Node>,
+    pub body: Node,
+}
+
+/// The line a tokenizer or parser error is on, from the "line N" in its
+/// message; 1 when it names none.
+pub fn error_line(message: &str) -> usize {
+    message.split("line ").nth(1)
+        .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+        .and_then(|digits| digits.parse().ok())
+        .unwrap_or(1)
+}
+
+/// Whether an operator orders two values, like `<`.
+pub fn is_ordering(operator: &TokenType) -> bool {
+    matches!(operator, TokenType::GreaterThan | TokenType::GreaterThanOrEqual | TokenType::LessThan | TokenType::LessThanOrEqual)
+}
+
+/// The condition inside `condition is false`, which is what `unless` and
+/// `loop until` test.
+pub fn negated(condition: &Node) -> Option<&Node> {
+    match condition {
+        Node::Binary { left, operator: TokenType::Is, right } if matches!(**right, Node::Literal(Value::Boolean(false))) => Some(left),
+        _ => None,
+    }
+}
+
+/// Ordinals written as words, from `first` on.
+pub const ORDINALS: [&str; 10] = ["first", "second", "third", "fourth", "fifth", "sixth", "seventh", "eighth", "ninth", "tenth"];
+
+/// The built-in Tasks `round x`, `round x down`, `round x up` and `round x
+/// to even` call.
+pub const ROUNDING: [&str; 4] = ["round", "round_down", "round_up", "round_even"];
+
+/// The built-in Tasks that reach an Object's members by names held in Text.
+pub const REFLECTION: [&str; 4] = ["properties_of", "has_task", "get_property", "set_property"];
+
+/// The ending of an ordinal written in digits: `st` for 1st, `th` for 11th.
+pub fn ordinal_suffix(number: usize) -> &'static str {
+    match (number % 10, number % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    }
+}
+
+// The name, List and optional condition of `of each <name> in <list> where ...`
+type EachIn = (String, Box<Node>, Option<Box<Node>>);
+
+// How deeply expressions, types and blocks may nest before parsing stops,
+// keeping the recursive parser (and the passes after it) off the end of the
+// stack. A debug build on an 8 MiB stack, the usual main thread, overflows
+// at about 229 levels in the tree walks after parsing, so this leaves room
+// for the REPL's and the language server's own frames.
+const MAX_NESTING: usize = 200;
+
 pub struct Parser {
     tokens: Vec<Token>,
     current: usize,
+    depth: usize,
+    // Why each statement that didn't parse failed, in order
+    errors: Vec<SyntaxError>,
+}
+
+/// Why a statement didn't parse, and the token reading it stopped at.
+#[derive(Debug, Clone)]
+pub struct SyntaxError {
+    pub message: String,
+    pub token: Token,
 }
 
 impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
+    pub fn new(mut tokens: Vec<Token>) -> Self {
+        // Lookahead relies on the stream always ending in Eof
+        if !matches!(tokens.last(), Some(Token { token_type: TokenType::Eof, .. })) {
+            let line = tokens.last().map(|token| token.line).unwrap_or(1);
+            let offset = tokens.last().map(|token| token.offset + token.literal.len()).unwrap_or(0);
+            tokens.push(Token { token_type: TokenType::Eof, literal: String::new(), line, column: 0, offset });
+        }
         Parser {
             tokens,
             current: 0,
+            depth: 0,
+            errors: Vec::new(),
         }
     }
 
+    /// The program's statements, or the first syntax error in it.
     pub fn parse(&mut self) -> Result<Vec<Node>, String> {
+        let (statements, errors) = self.parse_with_errors();
+        match errors.into_iter().next() {
+            Some(error) => Err(error.message),
+            None => Ok(statements),
+        }
+    }
+
+    /// Reads the whole program however many statements don't parse. Each
+    /// one that doesn't becomes an `Error` node, and reading carries on
+    /// with the next statement, so a tool can report every mistake at once
+    /// and still look at the rest. Gives the errors in the order found.
+    pub fn parse_with_errors(&mut self) -> (Vec<Node>, Vec<SyntaxError>) {
         let mut statements = Vec::new();
+        self.skip_newlines();
         while !self.is_at_end() {
-            statements.push(self.declaration()?);
+            self.located_statement(&mut statements);
+            self.skip_newlines();
         }
-        Ok(statements)
+        (statements, std::mem::take(&mut self.errors))
     }
 
-    fn declaration(&mut self) -> Result<Node, String> {
-        if let TokenType::Identifier(name) = &self.peek().token_type {
-            let name = name.clone();
+    // A statement preceded by the line it starts on, or an Error node in
+    // its place when it doesn't parse
+    fn located_statement(&mut self, statements: &mut Vec<Node>) {
+        statements.push(Node::Line(self.peek().line));
+        let start = self.current;
+        match self.statement() {
+            Ok(statement) => statements.push(statement),
+            Err(error) => {
+                let token = self.peek().clone();
+                self.synchronize(start);
+                self.errors.push(SyntaxError { message: error.clone(), token });
+                statements.push(Node::Error(error));
+            },
+        }
+    }
+
+    // Skips what is left of a statement that failed to parse, along with
+    // the block under it, stopping where the next statement at its level
+    // starts or where the block it is in ends
+    fn synchronize(&mut self, start: usize) {
+        // How many blocks the failed statement had opened when it stopped
+        let mut depth = self.tokens[start..self.current].iter().fold(0i64, |depth, token| match token.token_type {
+            TokenType::Indent => depth + 1,
+            TokenType::Dedent => depth - 1,
+            _ => depth,
+        }).max(0);
+        let finished = matches!(self.previous().token_type, TokenType::NewLine | TokenType::Dedent);
+        if depth == 0 && self.current > start && finished {
+            return;
+        }
+        // A statement that failed on its first token still moves on
+        if self.current == start {
             self.advance();
+        }
+        while !self.is_at_end() {
+            match self.peek().token_type {
+                TokenType::Dedent if depth == 0 => return,
+                TokenType::Dedent => {
+                    self.advance();
+                    depth -= 1;
+                    if depth == 0 {
+                        return;
+                    }
+                },
+                TokenType::Indent => {
+                    self.advance();
+                    depth += 1;
+                },
+                TokenType::NewLine if depth == 0 => {
+                    self.skip_newlines();
+                    if !self.check(&TokenType::Indent) {
+                        return;
+                    }
+                },
+                _ => {
+                    self.advance();
+                },
+            }
+        }
+    }
 
-            if self.match_token(&[TokenType::As]) {
-                let type_node = self.type_annotation()?;
-                
-                if matches!(type_node, Node::MappingType { .. }) {
-                    self.consume(&TokenType::Includes, "Expected 'includes' after mapping type")?;
-                    
-                    let initializer = Some(Box::new(self.mapping_initializer()?));
-                    Ok(Node::VariableDecl {
-                        name,
-                        type_annotation: Some(Box::new(type_node)),
-                        initializer,
-                    })
-                } else {
-                    if self.match_token(&[TokenType::Is]) {
-                        // Regular assignment without type annotation
-                        Ok(Node::VariableDecl {
-                            name,
-                            type_annotation: None,
-                            initializer: Some(Box::new(self.expression()?)),
-                        })
-                    } else {
-                        Err("Expected 'as' or 'is' after identifier".to_string())
+    fn statement(&mut self) -> Result<Node, String> {
+        let statement = match self.peek().token_type {
+            TokenType::Show => {
+                self.advance(); // Consume 'show'
+                let expr = self.expression()?;
+                Node::ShowStmt(Box::new(expr))
+            },
+            TokenType::When => {
+                self.advance(); // Consume 'when'
+                return self.when_statement(false);
+            },
+            TokenType::Loop => {
+                self.advance(); // Consume 'loop'
+                return self.loop_statement();
+            },
+            TokenType::Task => {
+                self.advance(); // Consume 'Task'
+                return self.task_declaration();
+            },
+            TokenType::Object => {
+                self.advance(); // Consume 'Object'
+                return self.object_declaration(false);
+            },
+            TokenType::Behavior => {
+                self.advance(); // Consume 'Behavior'
+                return self.object_declaration(true);
+            },
+            TokenType::Do => {
+                self.advance(); // Consume 'do'
+                return self.try_statement();
+            },
+            TokenType::To if matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "test") => {
+                self.advance(); // Consume 'to'
+                self.advance(); // Consume 'test'
+                return self.test_declaration();
+            },
+            TokenType::Raise => {
+                self.advance(); // Consume 'raise'
+                self.raise_statement()?
+            },
+            TokenType::Expect => {
+                self.advance(); // Consume 'expect'
+                self.expect_statement()?
+            },
+            TokenType::Output => {
+                self.advance(); // Consume 'output'
+                self.return_statement()?
+            },
+            TokenType::Identifier(_) if matches!(self.peek_next_type(), TokenType::As | TokenType::Is) => {
+                self.declaration()?
+            },
+            // `ten is 3` would make a variable that `ten` never reads, since
+            // it is the number 10 wherever a number can go
+            TokenType::Number(n) if self.peek().literal.starts_with(char::is_alphabetic) && matches!(self.peek_next_type(), TokenType::As | TokenType::Is) => {
+                return Err(format!("'{}' is read as the number {}, so it can't be a variable; use another name, or run with --strict-numbers to read number words as names (line {})",
+                    self.peek().literal, n, self.peek().line));
+            },
+            TokenType::Identifier(_) if self.at_change() => self.change()?,
+            TokenType::Identifier(_) if self.at_words(&["sort"]) && Self::starts_operand(self.peek_next_type()) => self.sort()?,
+            TokenType::Identifier(_) if self.at_words(&["give", "back"]) => {
+                self.advance(); // Consume 'give'
+                self.advance(); // Consume 'back'
+                self.return_statement()?
+            },
+            TokenType::Identifier(_) if self.at_words(&["stop", "here"]) => {
+                self.advance(); // Consume 'stop'
+                self.advance(); // Consume 'here'
+                Node::ReturnStmt(Box::new(Node::Literal(Value::Null)))
+            },
+            TokenType::Identifier(_) if self.at_words(&["if"]) && !Self::ends_statement(self.peek_next_type()) => {
+                self.advance(); // Consume 'if'
+                self.guard_statement()?
+            },
+            TokenType::Identifier(_) if self.at_words(&["unless"]) && Self::starts_operand(self.peek_next_type()) => {
+                self.advance(); // Consume 'unless'
+                return self.when_statement(true);
+            },
+            TokenType::Identifier(_) if self.at_words(&["repeat", "until"]) => {
+                self.advance(); // Consume 'repeat'
+                return self.loop_statement();
+            },
+            TokenType::Identifier(_) if self.at_words(&["keep", "doing"]) => {
+                self.advance(); // Consume 'keep'
+                self.advance(); // Consume 'doing'
+                if !self.at_words(&["the", "following"]) {
+                    return Err(format!("Expected 'the following:' after 'keep doing' (line {})", self.peek().line));
+                }
+                self.advance();
+                self.advance();
+                let label = match self.match_token(&[TokenType::Comma]) {
+                    true => Some(self.loop_label()?),
+                    false => None,
+                };
+                self.consume(&TokenType::Colon, "Expected ':' after 'keep doing the following'")?;
+                return Ok(Node::KeepDoingStmt { body: Box::new(self.block()?), label });
+            },
+            TokenType::Identifier(_) if (self.at_words(&["stop"]) || self.at_words(&["continue"]))
+                && (Self::ends_statement(self.peek_next_type()) || matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "the")) => {
+                let word = self.consume_identifier("Expected 'stop' or 'continue'")?;
+                let label = match Self::ends_statement(&self.peek().token_type) {
+                    true => None,
+                    false => Some(self.loop_name(&word)?),
+                };
+                match word.as_str() {
+                    "stop" => Node::StopStmt(label),
+                    _ => Node::ContinueStmt(label),
+                }
+            },
+            TokenType::Identifier(_) if self.at_words(&["repeat"]) && Self::starts_operand(self.peek_next_type()) => {
+                self.advance(); // Consume 'repeat'
+                return self.repeat_statement();
+            },
+            TokenType::Identifier(_) if self.at_words(&["measure", "how", "long", "it", "takes"]) => {
+                for _ in 0..5 {
+                    self.advance();
+                }
+                self.consume(&TokenType::To, "Expected 'to' after 'measure how long it takes'")?;
+                return self.measure_statement();
+            },
+            TokenType::My | TokenType::Identifier(_) => self.property_assignment_or_expression()?,
+            _ => self.expression_statement()?,
+        };
+
+        self.end_of_statement()?;
+        Ok(statement)
+    }
+
+    fn at_change(&self) -> bool {
+        (self.at_words(&["increase"]) || self.at_words(&["decrease"]) || self.at_words(&["double"]))
+            && matches!(self.peek_next_type(), TokenType::Identifier(_))
+    }
+
+    // Parses `increase <name> by <amount>`, `decrease <name> by <amount>` or `double <name>`
+    fn change(&mut self) -> Result<Node, String> {
+        let verb = self.consume_identifier("Expected 'increase', 'decrease' or 'double'")?;
+        self.skip_article();
+        let name = self.consume_identifier(&format!("Expected a variable name after '{}'", verb))?;
+        let (operator, amount) = match verb.as_str() {
+            "double" => (TokenType::Multiply, Node::Literal(Value::Number(2.0))),
+            _ if self.at_words(&["by"]) => {
+                self.advance();
+                let operator = if verb == "increase" { TokenType::Plus } else { TokenType::Minus };
+                let mut amount = self.expression()?;
+                // `increase price by 10%` adds 10% of the price
+                if let Node::Binary { operator: TokenType::Divide, right, .. } = &amount {
+                    if matches!(right.as_ref(), Node::Literal(Value::Number(n)) if *n == 100.0) && self.previous().token_type == TokenType::Modulo {
+                        amount = Node::Binary { left: Box::new(amount), operator: TokenType::Multiply, right: Box::new(Node::Variable(name.clone())) };
                     }
                 }
-            } else if self.match_token(&[TokenType::Is]) {
-                // Regular assignment without type annotation
+                (operator, amount)
+            },
+            _ => return Err(format!("Expected 'by' after '{} {}' on line {}", verb, name, self.peek().line)),
+        };
+        Ok(Node::Change { name, operator, amount: Box::new(amount) })
+    }
+
+    // Parses `sort <list>` or `sort <list> alphabetically in <language>`,
+    // which puts the List in order where it is: a call to `sort` with the
+    // language, or nothing for the Runtime's own
+    fn sort(&mut self) -> Result<Node, String> {
+        self.advance(); // Consume 'sort'
+        self.skip_article();
+        let list = self.expression()?;
+        let language = match self.at_words(&["alphabetically", "in"]) {
+            true => {
+                self.current += 2;
+                let line = self.peek().line;
+                let name = self.consume_identifier("Expected a language after 'alphabetically in'")?;
+                let language = Collation::named(&name).ok_or_else(|| {
+                    format!("Unknown language for sorting on line {}: {}; try {}", line, name, Collation::names())
+                })?;
+                Node::Literal(language.name().map_or(Value::Null, |name| Value::String(name.to_string())))
+            },
+            false => {
+                if self.at_words(&["alphabetically"]) {
+                    self.advance();
+                }
+                Node::Literal(Value::Null)
+            },
+        };
+        Ok(Node::ExpressionStmt(Box::new(Node::Call {
+            callee: Box::new(Node::Variable("sort".to_string())),
+            args: vec![list, language],
+        })))
+    }
+
+    fn declaration(&mut self) -> Result<Node, String> {
+        let name = self.consume_identifier("Expected identifier")?;
+
+        if self.match_token(&[TokenType::As]) {
+            let type_node = self.type_annotation()?;
+
+            if matches!(type_node, Node::MappingType { .. }) && self.match_token(&[TokenType::Includes]) {
+                let initializer = Some(Box::new(self.mapping_initializer()?));
                 Ok(Node::VariableDecl {
                     name,
-                    type_annotation: None,
-                    initializer: Some(Box::new(self.expression()?)),
+                    type_annotation: Some(Box::new(type_node)),
+                    initializer,
                 })
             } else {
-                Err("Expected 'as' or 'is' after identifier".to_string())
+                let initializer = if self.match_token(&[TokenType::Is]) {
+                    Some(Box::new(self.expression()?))
+                } else {
+                    None
+                };
+                Ok(Node::VariableDecl {
+                    name,
+                    type_annotation: Some(Box::new(type_node)),
+                    initializer,
+                })
             }
+        } else if self.match_token(&[TokenType::Is]) {
+            // Untyped assignment; declares the variable as Any if it doesn't exist yet
+            let value = Box::new(self.expression()?);
+            Ok(Node::Assignment { name, value })
         } else {
-            Err("Expected identifier".to_string())
+            Err("Expected 'as' or 'is' after identifier".to_string())
         }
     }
 
-    fn Task_declaration(&mut self) -> Result<Node, String> {
+    fn task_declaration(&mut self) -> Result<Node, String> {
         let name = self.consume_identifier("Expected Task name")?;
-        
+
         let mut params = Vec::new();
         if self.match_token(&[TokenType::Requires]) {
             params = self.parameter_list()?;
@@ -208,44 +690,151 @@ impl Parser {
         })
     }
 
-    fn object_declaration(&mut self) -> Result<Node, String> {
+    fn object_declaration(&mut self, is_behavior: bool) -> Result<Node, String> {
         let name = self.consume_identifier("Expected object name")?;
-        
-        let base = if self.match_token(&[TokenType::Extends]) {
-            Some(Box::new(Node::TypeAnnotation(self.consume_identifier("Expected base class name")?)))
+
+        let base = if !is_behavior && self.match_token(&[TokenType::Extends]) {
+            // Error kinds inherit from the built-in Error object
+            let base_name = if self.match_token(&[TokenType::TypeError]) {
+                "Error".to_string()
+            } else {
+                self.consume_identifier("Expected base class name")?
+            };
+            Some(Box::new(Node::TypeAnnotation(base_name)))
         } else {
             None
         };
 
         self.consume(&TokenType::Colon, "Expected ':' after object declaration")?;
+        self.consume(&TokenType::NewLine, "Expected newline after ':'")?;
+        self.consume(&TokenType::Indent, "Expected indented object body")?;
 
+        let mut fields = Vec::new();
         let mut methods = Vec::new();
+        let mut properties = Vec::new();
+        let mut shared_fields = Vec::new();
+        let mut shared_methods = Vec::new();
+        let mut behaviors = Vec::new();
         let mut constructor = None;
 
-        while !self.check(&TokenType::EOF) && !self.is_at_end() {
+        while !self.check(&TokenType::Dedent) && !self.is_at_end() {
+            if is_behavior && (self.check(&TokenType::Build) || self.check(&TokenType::Shared)) {
+                return Err(format!("Behavior {} can only declare fields, tasks and properties", name));
+            }
+            if self.at_word("also") || self.at_word("behaves") {
+                // also behaves like Swimmer, Flyer
+                if !is_behavior {
+                    behaviors.extend(self.behaves_like()?);
+                    self.end_of_statement()?;
+                    self.skip_newlines();
+                    continue;
+                }
+                return Err(format!("Behavior {} cannot include other behaviors", name));
+            }
             if self.match_token(&[TokenType::Build]) {
                 if constructor.is_some() {
                     return Err("Object can only have one constructor".to_string());
                 }
                 constructor = Some(Box::new(self.constructor_declaration()?));
             } else if self.match_token(&[TokenType::Task]) {
-                methods.push(self.Task_declaration()?);
+                methods.push(self.task_declaration()?);
+            } else if self.match_token(&[TokenType::About]) {
+                properties.push(self.property_declaration()?);
+            } else if self.match_token(&[TokenType::Shared]) {
+                // Class-level members belong to the object kind itself, not its instances
+                if self.match_token(&[TokenType::Task]) {
+                    shared_methods.push(self.task_declaration()?);
+                } else {
+                    shared_fields.push(self.declaration()?);
+                    self.end_of_statement()?;
+                }
             } else {
-                break;
+                fields.push(self.declaration()?);
+                self.end_of_statement()?;
             }
+            self.skip_newlines();
         }
+        self.match_token(&[TokenType::Dedent]);
 
         Ok(Node::ObjectDecl {
             name,
             base,
+            fields,
             constructor,
             methods,
+            properties,
+            shared_fields,
+            shared_methods,
+            behaviors,
+            is_behavior,
+        })
+    }
+
+    fn behaves_like(&mut self) -> Result<Vec<String>, String> {
+        if self.at_word("also") {
+            self.advance();
+        }
+        if !self.at_word("behaves") {
+            return Err(format!("Expected 'behaves like' at line {}", self.peek().line));
+        }
+        self.advance();
+        if !self.at_word("like") {
+            return Err(format!("Expected 'like' after 'behaves' at line {}", self.peek().line));
+        }
+        self.advance();
+
+        let mut behaviors = Vec::new();
+        loop {
+            // Optional article: behaves like a Swimmer
+            if self.at_type_article() {
+                self.advance();
+            }
+            behaviors.push(self.consume_identifier("Expected behavior name")?);
+            if !self.match_token(&[TokenType::Comma, TokenType::And]) {
+                break;
+            }
+        }
+        Ok(behaviors)
+    }
+
+    // Contextual words like `also` that are only special in one position
+    fn at_word(&self, word: &str) -> bool {
+        matches!(&self.peek().token_type, TokenType::Identifier(name) if name == word)
+    }
+
+    fn property_declaration(&mut self) -> Result<Node, String> {
+        let name = self.consume_identifier("Expected property name after 'about'")?;
+
+        let setter_param = if self.match_token(&[TokenType::Becomes]) {
+            let mut params = self.parameter_list()?;
+            if params.len() != 1 {
+                return Err(format!("Setter for '{}' must take exactly one value", name));
+            }
+            params.pop().map(Box::new)
+        } else {
+            None
+        };
+
+        let return_type = if setter_param.is_none() && self.match_token(&[TokenType::Returns, TokenType::Returning]) {
+            Some(Box::new(self.type_annotation()?))
+        } else {
+            None
+        };
+
+        self.consume(&TokenType::Colon, "Expected ':' after property declaration")?;
+        let body = Box::new(self.block()?);
+
+        Ok(Node::PropertyDecl {
+            name,
+            setter_param,
+            return_type,
+            body,
         })
     }
 
     fn parameter_list(&mut self) -> Result<Vec<Node>, String> {
         let mut params = Vec::new();
-        
+
         loop {
             let name = self.consume_identifier("Expected parameter name")?;
             let type_annotation = if self.match_token(&[TokenType::As]) {
@@ -253,7 +842,7 @@ impl Parser {
             } else {
                 None
             };
-            
+
             params.push(Node::VariableDecl {
                 name,
                 type_annotation,
@@ -269,21 +858,27 @@ impl Parser {
     }
 
     fn type_annotation(&mut self) -> Result<Node, String> {
+        self.nested(Self::type_name)
+    }
+
+    fn type_name(&mut self) -> Result<Node, String> {
         match &self.peek().token_type {
             TokenType::TypeMapping => {
                 self.advance();
-                
-                // Check if there's an explicit type
+
+                // Check if there's an explicit type: Mapping of <key> to <value>
                 if self.match_token(&[TokenType::Of]) {
+                    let key_type = Box::new(self.type_annotation()?);
+                    self.consume(&TokenType::To, "Expected 'to' after mapping key type")?;
                     let value_type = Box::new(self.type_annotation()?);
                     Ok(Node::MappingType {
-                        key_type: Box::new(Node::TypeAnnotation("Text".to_string())),
+                        key_type,
                         value_type,
                     })
                 } else {
                     // Default to Any
                     Ok(Node::MappingType {
-                        key_type: Box::new(Node::TypeAnnotation("Text".to_string())),
+                        key_type: Box::new(Node::TypeAnnotation("Any".to_string())),
                         value_type: Box::new(Node::TypeAnnotation("Any".to_string())),
                     })
                 }
@@ -302,7 +897,7 @@ impl Parser {
             },
             TokenType::TypeLogic => {
                 self.advance();
-                Ok(Node::TypeAnnotation("Logic".to_string()))
+                Ok(Node::TypeAnnotation("Truth".to_string()))
             },
             TokenType::TypeNothing => {
                 self.advance();
@@ -314,19 +909,16 @@ impl Parser {
                     let element_type = Box::new(self.type_annotation()?);
                     self.consume(&TokenType::CloseBracket, "Expected ']' after type parameter")?;
                     Ok(Node::ListType { element_type })
+                } else if self.match_token(&[TokenType::Of]) {
+                    let element_type = Box::new(self.type_annotation()?);
+                    Ok(Node::ListType { element_type })
                 } else {
                     Ok(Node::TypeAnnotation("List".to_string()))
                 }
             },
             TokenType::TypePromise => {
                 self.advance();
-                if self.match_token(&[TokenType::OpenBracket]) {
-                    let value_type = Box::new(self.type_annotation()?);
-                    self.consume(&TokenType::CloseBracket, "Expected ']' after type parameter")?;
-                    Ok(Node::PromiseType { value_type })
-                } else {
-                    Ok(Node::TypeAnnotation("Promise".to_string()))
-                }
+                Ok(Node::TypeAnnotation("Promise".to_string()))
             },
             TokenType::TypeAny => {
                 self.advance();
@@ -340,149 +932,225 @@ impl Parser {
                 self.advance();
                 Ok(Node::TypeAnnotation("Error".to_string()))
             },
-            _ => Err("Expected type name".to_string()),
+            // `Quantity in meters per second`
+            TokenType::Identifier(name) if name == "Quantity" && self.tokens.get(self.current + 1)
+                .is_some_and(|token| matches!(&token.token_type, TokenType::Identifier(word) if word == "in")) => {
+                self.current += 2;
+                let (unit, length) = Unit::read(&self.words_at(self.current))
+                    .ok_or_else(|| format!("Expected units after 'Quantity in' on line {}", self.peek().line))?;
+                self.current += length;
+                Ok(Node::TypeAnnotation(format!("Quantity in {}", unit.phrase(true))))
+            },
+            TokenType::Identifier(name) => {
+                let name = name.clone();
+                self.advance();
+                Ok(Node::TypeAnnotation(name))
+            },
+            _ => Err("Expected type name".to_string()),
         }
     }
 
+    // Parses the body following a ':' — either an indented block or a single inline statement.
+    // A block counts as two levels of nesting, since the passes after parsing
+    // take about twice the stack for a statement as for an expression.
     fn block(&mut self) -> Result<Node, String> {
+        self.nested(|parser| parser.deepen().and_then(|()| parser.block_statements()))
+    }
+
+    fn block_statements(&mut self) -> Result<Node, String> {
         let mut statements = Vec::new();
-        
-        while !self.is_at_end() && !self.check(&TokenType::EOF) {
-            statements.push(self.declaration()?);
+
+        if !self.match_token(&[TokenType::NewLine]) {
+            self.located_statement(&mut statements);
+            return Ok(Node::Block(statements));
         }
-        
+
+        self.skip_newlines();
+        self.consume(&TokenType::Indent, "Expected indented block")?;
+        while !self.check(&TokenType::Dedent) && !self.is_at_end() {
+            self.located_statement(&mut statements);
+            self.skip_newlines();
+        }
+        self.match_token(&[TokenType::Dedent]);
+
         Ok(Node::Block(statements))
     }
 
+    // build [defaults] [label] name [as Type], ...:
     fn constructor_declaration(&mut self) -> Result<Node, String> {
-        self.consume(&TokenType::Defaults, "Expected 'defaults' after 'build'")?;
-        let params = self.parameter_list()?;
+        self.match_token(&[TokenType::Defaults]);
+        let mut params = Vec::new();
+        let mut labels = Vec::new();
+
+        if !self.check(&TokenType::Colon) {
+            loop {
+                let first = self.consume_identifier("Expected parameter name")?;
+                // A second word means the first one labels the argument at `new`
+                let (label, name) = if matches!(self.peek().token_type, TokenType::Identifier(_)) {
+                    (first, self.consume_identifier("Expected parameter name")?)
+                } else {
+                    (first.clone(), first)
+                };
+                let type_annotation = if self.match_token(&[TokenType::As]) {
+                    Some(Box::new(self.type_annotation()?))
+                } else {
+                    None
+                };
+
+                labels.push(label);
+                params.push(Node::VariableDecl {
+                    name,
+                    type_annotation,
+                    initializer: None,
+                });
+
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+
         self.consume(&TokenType::Colon, "Expected ':' after constructor parameters")?;
         let body = Box::new(self.block()?);
-        
-        Ok(Node::TaskDecl {
-            name: "build".to_string(),
+
+        Ok(Node::ConstructorDecl {
             params,
-            return_type: None,
+            labels,
             body,
         })
     }
 
     fn expression(&mut self) -> Result<Node, String> {
-        match self.peek().token_type {
-            TokenType::Identifier(_) => {
-                let name = self.consume_identifier("Expected identifier")?;
-                Ok(Node::Variable(name))
-            },
-            TokenType::String(_) => self.string_literal(),
-            TokenType::Number(_) => {
-                if let TokenType::Number(n) = self.peek().token_type {
-                    self.advance();
-                    Ok(Node::Literal(Value::Number(n)))
-                } else {
-                    Err("Expected number".to_string())
-                }
-            },
-            TokenType::Boolean(_) => {
-                if let TokenType::Boolean(b) = self.peek().token_type {
+        self.nested(Self::cast_expression)
+    }
+
+    fn cast_expression(&mut self) -> Result<Node, String> {
+        let mut expr = self.or()?;
+
+        if self.at_words(&["in"]) {
+            self.in_units(&mut expr);
+        }
+
+        // Trailing `as <Type>` converts the value
+        if self.check(&TokenType::As) && Self::is_type_token(self.peek_next_type()) {
+            self.advance();
+            let target = Box::new(self.type_annotation()?);
+            expr = Node::Cast {
+                value: Box::new(expr),
+                target,
+            };
+        }
+
+        if self.at_words(&["written"]) && matches!(self.peek_next_type(), TokenType::With | TokenType::As) {
+            self.written(&mut expr)?;
+        }
+
+        if self.at_words(&["if"]) {
+            return self.conditional(expr);
+        }
+        Ok(expr)
+    }
+
+    // `x written as a percentage with commas and 2 decimal places`: turns the
+    // number already parsed into a call to the built-in Task `format_number`
+    // with its decimal places, its thousands separators, the fewest digits
+    // before the point and whether it is a percentage. Works in place, as
+    // every nested expression passes through here.
+    fn written(&mut self, value: &mut Node) -> Result<(), String> {
+        let line = self.peek().line;
+        self.advance();
+        let percent = self.check(&TokenType::As);
+        if percent {
+            self.advance();
+            if !self.at_words(&["a", "percentage"]) {
+                return Err(format!("Expected 'a percentage' after 'written as' on line {}", line));
+            }
+            self.current += 2;
+        }
+        let (mut places, mut commas, mut digits) = (Node::Literal(Value::Null), false, Node::Literal(Value::Null));
+        if self.check(&TokenType::With) {
+            loop {
+                self.advance();
+                if self.at_words(&["commas"]) {
                     self.advance();
-                    Ok(Node::Literal(Value::Boolean(b)))
+                    commas = true;
                 } else {
-                    Err("Expected boolean".to_string())
-                }
-            },
-            TokenType::Null => {
-                self.advance();
-                Ok(Node::Literal(Value::Null))
-            },
-            TokenType::New => {
-                self.new_expression()
-            },
-            TokenType::Await => {
-                Ok(Node::AwaitExpr {
-                    value: Box::new(self.expression()?),
-                })
-            },
-            TokenType::Quote => {
-                let mut parts = Vec::new();
-                while !self.check(&TokenType::Quote) && !self.is_at_end() {
-                    if self.match_token(&[TokenType::LeftBrace]) {
-                        let expr = self.expression()?;
-                        self.consume(&TokenType::RightBrace, "Expected '}' after expression")?;
-                        parts.push(expr);
+                    let count = self.unary()?;
+                    if self.at_words(&["decimal", "places"]) || self.at_words(&["decimal", "place"]) {
+                        self.current += 2;
+                        places = count;
+                    } else if self.at_words(&["digits"]) || self.at_words(&["digit"]) {
+                        self.advance();
+                        digits = count;
                     } else {
-                        let text = self.consume_string_part()?;
-                        parts.push(Node::Literal(Value::String(text)));
+                        return Err(format!("Expected 'decimal places' or 'digits' after the number on line {}", line));
                     }
                 }
-                self.consume(&TokenType::Quote, "Expected '\"' after string")?;
-                Ok(Node::StringInterpolation { parts })
-            },
-            TokenType::TypeMapping => {
-                let mut entries = Vec::new();
-                loop {
-                    let param_name = self.consume_identifier("Expected parameter name")?;
-                    let (param_type, value) = if self.match_token(&[TokenType::As]) {
-                        let param_type = self.type_annotation()?;
-                        self.consume(&TokenType::Is, "Expected 'is' after type")?;
-                        let value = self.expression()?;
-                        (Some(param_type), value)
-                    } else if self.match_token(&[TokenType::Is]) {
-                        let value = self.expression()?;
-                        (None, value)
-                    } else {
-                        return Err("Expected 'as' or 'is' after parameter name".to_string());
-                    };
-                    entries.push((param_name, param_type, value));
-                    if !self.match_token(&[TokenType::Comma]) {
-                        break;
-                    }
-                    while self.peek().token_type == TokenType::NewLine {
-                        self.advance();
-                    }
+                if !self.check(&TokenType::And) {
+                    break;
                 }
-                Ok(Node::MappingLiteral { entries })
-            },
-            TokenType::TypeList => {
-                self.advance();
-                let element_type = Box::new(self.type_annotation()?);
-                self.consume(&TokenType::CloseBracket, "Expected ']' after type parameter")?;
-                Ok(Node::ListType { element_type })
-            },
-            TokenType::TypePromise => {
-                self.advance();
-                let value_type = Box::new(self.type_annotation()?);
-                self.consume(&TokenType::CloseBracket, "Expected ']' after type parameter")?;
-                Ok(Node::PromiseType { value_type })
-            },
-            // TokenType::TypeAnnotation => {
-            //     let type_name = self.consume_identifier("Expected type name")?;
-            //     match type_name.as_str() {
-            //         "Mapping" => Ok(Node::MappingType {
-            //             key_type: Box::new(Node::TypeAnnotation("Text".to_string())),
-            //             value_type: Box::new(Node::TypeAnnotation("Any".to_string())),
-            //         }),
-            //         "Whole" => Ok(Node::TypeAnnotation("Whole".to_string())),
-            //         "Decimal" => Ok(Node::TypeAnnotation("Decimal".to_string())),
-            //         "Text" => Ok(Node::TypeAnnotation("Text".to_string())),
-            //         "Truth" => Ok(Node::TypeAnnotation("Logic".to_string())),
-            //         "Nothing" => Ok(Node::TypeAnnotation("Nothing".to_string())),
-            //         "Any" => Ok(Node::TypeAnnotation("Any".to_string())),
-            //         "Number" => Ok(Node::TypeAnnotation("Number".to_string())),
-            //         "Error" => Ok(Node::TypeAnnotation("Error".to_string())),
-            //         _ => Err(format!("Unknown type: {}", type_name)),
-            //     }
-            // },
-            _ => Err("Expected expression".to_string()),
+            }
+        }
+        let number = std::mem::replace(value, Node::Literal(Value::Null));
+        *value = Node::Call {
+            callee: Box::new(Node::Variable("format_number".to_string())),
+            args: vec![number, places, Node::Literal(Value::Boolean(commas)), digits, Node::Literal(Value::Boolean(percent))],
+        };
+        Ok(())
+    }
+
+    // `distance in kilometers`: when units follow the `in`, turns the
+    // quantity already parsed into a call to the built-in Task `convert`
+    // with the units written out
+    fn in_units(&mut self, value: &mut Node) {
+        let Some((unit, length)) = Unit::read(&self.words_at(self.current + 1)) else {
+            return;
+        };
+        self.current += 1 + length;
+        let quantity = std::mem::replace(value, Node::Literal(Value::Null));
+        *value = Node::Call {
+            callee: Box::new(Node::Variable("convert".to_string())),
+            args: vec![quantity, Node::Literal(Value::String(unit.phrase(true)))],
+        };
+    }
+
+    // `a if condition otherwise b`; the `otherwise` side may itself be
+    // another conditional, so chains read left to right
+    fn conditional(&mut self, value: Node) -> Result<Node, String> {
+        self.advance();
+        let condition = self.or()?;
+        if !self.at_words(&["otherwise"]) {
+            return Err(format!("Expected 'otherwise' after the condition (line {})", self.peek().line));
         }
+        self.advance();
+        let otherwise = self.expression()?;
+        Ok(Node::Conditional {
+            value: Box::new(value),
+            condition: Box::new(condition),
+            otherwise: Box::new(otherwise),
+        })
+    }
+
+    fn is_type_token(token_type: &TokenType) -> bool {
+        matches!(token_type,
+            TokenType::TypeWhole | TokenType::TypeDecimal | TokenType::TypeText |
+            TokenType::TypeLogic | TokenType::TypeNothing | TokenType::TypeList |
+            TokenType::TypeMapping | TokenType::TypeAny | TokenType::TypeNumber |
+            TokenType::TypeError | TokenType::TypePromise)
     }
 
     fn new_expression(&mut self) -> Result<Node, String> {
         let class_name = self.consume_identifier("Expected class name after 'new'")?;
         let mut args = Vec::new();
 
-        if self.match_token(&[TokenType::With]) {
-            args = self.argument_list()?;
+        if self.match_token(&[TokenType::With, TokenType::Using]) {
+            args = self.argument_list()?.into_iter().map(|arg| (None, arg)).collect();
+        } else {
+            // Labeled arguments: new Dog named "Rex" aged 3
+            while matches!(self.peek().token_type, TokenType::Identifier(_)) {
+                let label = self.consume_identifier("Expected argument label")?;
+                args.push((Some(label), self.term()?));
+            }
         }
 
         Ok(Node::New {
@@ -491,43 +1159,20 @@ impl Parser {
         })
     }
 
-    fn assignment(&mut self) -> Result<Node, String> {
-        let name = match &self.tokens[self.current - 1] {
-            Token { token_type: TokenType::Identifier(id), .. } => id.clone(),
-            _ => return Err("Expected identifier".to_string()),
-        };
-        
-        // Check if this is a new variable declaration with 'as' keyword
-        if self.match_token(&[TokenType::As]) {
-            let type_annotation = self.type_annotation()?;
-            let initializer = if self.match_token(&[TokenType::Is]) {
-                Some(Box::new(self.expression()?))
-            } else {
-                None
-            };
-            Ok(Node::VariableDecl {
-                name,
-                type_annotation: Some(Box::new(type_annotation)),
-                initializer,
-            })
-        } else if self.match_token(&[TokenType::Is]) {
-            // This is an assignment to an existing variable
-            let value = Box::new(self.expression()?);
-            Ok(Node::Assignment { name, value })
-        } else {
-            Err("Expected 'as' or 'is' after identifier".to_string())
-        }
-    }
-
     fn or(&mut self) -> Result<Node, String> {
         let mut expr = self.and()?;
 
         while self.match_token(&[TokenType::Or]) {
-            let operator = self.previous().token_type.clone();
+            self.deepen()?;
+            let (operator, line) = (self.previous().token_type.clone(), self.previous().line);
+            let parenthesized = self.check(&TokenType::OpenParen);
             let right = Box::new(self.and()?);
+            if !parenthesized {
+                Self::unambiguous(&expr, &operator, &right, line)?;
+            }
             expr = Node::Binary {
                 left: Box::new(expr),
-                operator: operator.clone(),
+                operator,
                 right,
             };
         }
@@ -539,11 +1184,16 @@ impl Parser {
         let mut expr = self.equality()?;
 
         while self.match_token(&[TokenType::And]) {
-            let operator = self.previous().token_type.clone();
+            self.deepen()?;
+            let (operator, line) = (self.previous().token_type.clone(), self.previous().line);
+            let parenthesized = self.check(&TokenType::OpenParen);
             let right = Box::new(self.equality()?);
+            if !parenthesized {
+                Self::unambiguous(&expr, &operator, &right, line)?;
+            }
             expr = Node::Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right,
             };
         }
@@ -551,15 +1201,61 @@ impl Parser {
         Ok(expr)
     }
 
+    // `colour is "red" or "blue"` might compare colour with both values, or
+    // join the comparison with a bare value. Rather than pick one, the
+    // error spells out both readings and asks for the one that was meant.
+    fn unambiguous(left: &Node, operator: &TokenType, right: &Node, line: usize) -> Result<(), String> {
+        let Node::Binary { left: subject, operator: comparison, .. } = left else {
+            return Ok(());
+        };
+        let compares = matches!(comparison,
+            TokenType::Is | TokenType::NotEquals | TokenType::GreaterThan | TokenType::GreaterThanOrEqual |
+            TokenType::LessThan | TokenType::LessThanOrEqual);
+        let bare = matches!(right,
+            Node::Literal(Value::Number(_) | Value::String(_)) | Node::StringInterpolation { .. } | Node::ArrayLiteral { .. });
+        if !compares || !bare {
+            return Ok(());
+        }
+        let compared = Node::Binary { left: subject.clone(), operator: comparison.clone(), right: Box::new(right.clone()) };
+        let operator = operator_text(operator);
+        Err(format!(
+            "Ambiguous phrasing on line {}: `{} {} {}` could mean `{} {} {}` or `({}) {} {}`. Write out the one you mean",
+            line, expression(left), operator, expression(right),
+            expression(left), operator, expression(&compared),
+            expression(left), operator, expression(right)))
+    }
+
     fn equality(&mut self) -> Result<Node, String> {
         let mut expr = self.comparison()?;
 
-        while self.match_token(&[TokenType::Is]) {
-            let operator = self.previous().token_type.clone();
+        while self.match_token(&[TokenType::Is, TokenType::NotEquals]) {
+            self.deepen()?;
+            let mut operator = self.previous().token_type.clone();
+            let spelled = operator == TokenType::Is;
+            if spelled && self.match_token(&[TokenType::Not]) {
+                operator = TokenType::NotEquals;
+            }
+            if self.at_type_article() {
+                self.advance();
+                expr = Node::TypeTest {
+                    value: Box::new(expr),
+                    target: Box::new(self.type_annotation()?),
+                    negated: operator == TokenType::NotEquals,
+                };
+                continue;
+            }
+            if spelled && (self.at_same_as() || self.at_same_text()) {
+                expr = self.same_as(expr, operator == TokenType::NotEquals)?;
+                continue;
+            }
+            if spelled && self.at_spelled_comparison() {
+                expr = self.spelled_comparison(expr, operator == TokenType::NotEquals)?;
+                continue;
+            }
             let right = Box::new(self.comparison()?);
             expr = Node::Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right,
             };
         }
@@ -567,31 +1263,142 @@ impl Parser {
         Ok(expr)
     }
 
+    // `a`/`an` followed by a type name starts a type test rather than naming a variable
+    fn at_type_article(&self) -> bool {
+        let is_article = matches!(&self.peek().token_type, TokenType::Identifier(word) if word == "a" || word == "an");
+        let next = self.peek_next_type();
+        is_article && (Self::is_type_token(next) || matches!(next, TokenType::Identifier(_))) && !self.at_copy() && !self.at_list_of()
+    }
+
+    fn same_as(&mut self, left: Node, negated: bool) -> Result<Node, String> {
+        if self.at_same_text() {
+            return self.same_text(left, negated);
+        }
+        self.current += 3;
+        Ok(Node::SameAs {
+            left: Box::new(left),
+            right: Box::new(self.comparison()?),
+            negated,
+        })
+    }
+
+    // `the same as` after `is` asks for a deep comparison
+    fn at_same_as(&self) -> bool {
+        self.at_words(&["the", "same"]) && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::As))
+    }
+
+    // `x is the same text as y` compares two Texts, and with `, ignoring
+    // case` after it, compares them as if both were in lowercase. It reads
+    // as a call to `compare_text`, which takes whether to ignore case and
+    // whether the comparison was `is not`.
+    fn same_text(&mut self, left: Node, negated: bool) -> Result<Node, String> {
+        self.current += 4;
+        let right = self.comparison()?;
+        let word = |offset: usize| self.tokens.get(self.current + offset).map(|token| &token.token_type);
+        let ignoring_case = self.check(&TokenType::Comma)
+            && matches!(word(1), Some(TokenType::Identifier(word)) if word == "ignoring")
+            && matches!(word(2), Some(TokenType::Identifier(word)) if word == "case");
+        if ignoring_case {
+            self.current += 3;
+        }
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable("compare_text".to_string())),
+            args: vec![left, right, Node::Literal(Value::Boolean(ignoring_case)), Node::Literal(Value::Boolean(negated))],
+        })
+    }
+
+    fn at_same_text(&self) -> bool {
+        self.at_words(&["the", "same", "text"]) && matches!(self.tokens.get(self.current + 3).map(|token| &token.token_type), Some(TokenType::As))
+    }
+
+    // `is less than`, `is greater than` or `is between` after `is`
+    fn at_spelled_comparison(&self) -> bool {
+        self.at_words(&["less", "than"]) || self.at_words(&["greater", "than"])
+            || (self.at_words(&["between"]) && Self::starts_operand(self.peek_next_type()))
+    }
+
+    // `x is less than y` and `x is greater than y` are `x < y` and `x > y`,
+    // chaining the same way; `x is between low and high` is `x >= low and
+    // x <= high`. With `not`, each means the opposite.
+    fn spelled_comparison(&mut self, left: Node, negated: bool) -> Result<Node, String> {
+        if self.at_words(&["between"]) {
+            self.advance();
+            let low = self.term()?;
+            self.consume(&TokenType::And, "Expected 'and' between the two ends after 'between'")?;
+            let high = self.term()?;
+            let (below, above, joined) = match negated {
+                false => (TokenType::GreaterThanOrEqual, TokenType::LessThanOrEqual, TokenType::And),
+                true => (TokenType::LessThan, TokenType::GreaterThan, TokenType::Or),
+            };
+            return Ok(Node::Binary {
+                left: Box::new(Node::Binary { left: Box::new(left.clone()), operator: below, right: Box::new(low) }),
+                operator: joined,
+                right: Box::new(Node::Binary { left: Box::new(left), operator: above, right: Box::new(high) }),
+            });
+        }
+        let operator = match (self.at_words(&["less"]), negated) {
+            (true, false) => TokenType::LessThan,
+            (true, true) => TokenType::GreaterThanOrEqual,
+            (false, false) => TokenType::GreaterThan,
+            (false, true) => TokenType::LessThanOrEqual,
+        };
+        self.current += 2;
+        let right = self.term()?;
+        Ok(Self::compare(left, operator, right))
+    }
+
     fn comparison(&mut self) -> Result<Node, String> {
         let mut expr = self.term()?;
 
-        while self.match_token(&[TokenType::GreaterThan]) {
+        while self.match_token(&[
+            TokenType::GreaterThan,
+            TokenType::GreaterThanOrEqual,
+            TokenType::LessThan,
+            TokenType::LessThanOrEqual,
+        ]) {
+            self.deepen()?;
             let operator = self.previous().token_type.clone();
-            let right = Box::new(self.term()?);
-            expr = Node::Binary {
-                left: Box::new(expr),
-                operator: operator,
-                right,
-            };
+            let right = self.term()?;
+            expr = Self::compare(expr, operator, right);
         }
 
         Ok(expr)
     }
 
+    // `0 < x < 100` means `0 < x and x < 100`: a comparison after another
+    // compares the value in the middle again
+    fn compare(left: Node, operator: TokenType, right: Node) -> Node {
+        let last = match &left {
+            Node::Binary { operator: TokenType::And, right: last, .. } => last.as_ref(),
+            last => last,
+        };
+        match last {
+            Node::Binary { operator: previous, right: middle, .. } if is_ordering(previous) => Node::Binary {
+                right: Box::new(Node::Binary { left: middle.clone(), operator, right: Box::new(right) }),
+                left: Box::new(left),
+                operator: TokenType::And,
+            },
+            _ => Node::Binary { left: Box::new(left), operator, right: Box::new(right) },
+        }
+    }
+
     fn term(&mut self) -> Result<Node, String> {
         let mut expr = self.factor()?;
 
-        while self.match_token(&[TokenType::Plus, TokenType::Minus]) {
+        loop {
+            if self.at_words(&["followed", "by"]) {
+                expr = self.followed_by(expr)?;
+                continue;
+            }
+            if !self.match_token(&[TokenType::Plus, TokenType::Minus]) {
+                break;
+            }
+            self.deepen()?;
             let operator = self.previous().token_type.clone();
             let right = Box::new(self.factor()?);
             expr = Node::Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right,
             };
         }
@@ -599,15 +1406,25 @@ impl Parser {
         Ok(expr)
     }
 
+    fn followed_by(&mut self, left: Node) -> Result<Node, String> {
+        self.deepen()?;
+        self.current += 2;
+        Ok(Node::FollowedBy {
+            left: Box::new(left),
+            right: Box::new(self.factor()?),
+        })
+    }
+
     fn factor(&mut self) -> Result<Node, String> {
         let mut expr = self.unary()?;
 
-        while self.match_token(&[TokenType::Multiply, TokenType::Divide]) {
+        while self.match_token(&[TokenType::Multiply, TokenType::Divide, TokenType::Modulo, TokenType::Power]) {
+            self.deepen()?;
             let operator = self.previous().token_type.clone();
             let right = Box::new(self.unary()?);
             expr = Node::Binary {
                 left: Box::new(expr),
-                operator: operator,
+                operator,
                 right,
             };
         }
@@ -617,6 +1434,7 @@ impl Parser {
 
     fn unary(&mut self) -> Result<Node, String> {
         if self.match_token(&[TokenType::Minus]) {
+            self.deepen()?;
             let operator = self.previous_token_type();
             let right = Box::new(self.unary()?);
             Ok(Node::Binary {
@@ -633,6 +1451,9 @@ impl Parser {
         let mut expr = self.primary()?;
 
         loop {
+            if self.check(&TokenType::OpenParen) || self.check(&TokenType::Dot) || self.check(&TokenType::Using) {
+                self.deepen()?;
+            }
             if self.match_token(&[TokenType::OpenParen]) {
                 expr = self.finish_call(expr)?;
             } else if self.match_token(&[TokenType::Dot]) {
@@ -641,6 +1462,12 @@ impl Parser {
                     object: Box::new(expr),
                     name,
                 };
+            } else if self.match_token(&[TokenType::Using]) {
+                let args = self.argument_list()?;
+                expr = Node::Call {
+                    callee: Box::new(expr),
+                    args,
+                };
             } else {
                 break;
             }
@@ -650,17 +1477,7 @@ impl Parser {
     }
 
     fn finish_call(&mut self, callee: Node) -> Result<Node, String> {
-        let mut arguments = Vec::new();
-
-        if !self.check(&TokenType::CloseParen) {
-            loop {
-                arguments.push(self.expression()?);
-                if !self.match_token(&[TokenType::Comma]) {
-                    break;
-                }
-            }
-        }
-
+        let arguments = self.argument_list()?;
         self.consume(&TokenType::CloseParen, "Expected ')' after arguments")?;
 
         Ok(Node::Call {
@@ -672,110 +1489,643 @@ impl Parser {
     fn primary(&mut self) -> Result<Node, String> {
         let token = self.peek().clone();
         match token.token_type {
+            // `a copy of x` and `freeze x` call the built-in Tasks
+            TokenType::Identifier(_) if self.at_copy() => self.builtin_call("copy", 3),
+            TokenType::Identifier(_) if self.at_list_of() => self.list_of(),
+            TokenType::Identifier(_) if self.at_words(&["the", "value", "for"]) => self.index(true),
+            TokenType::Identifier(_) if self.at_slice() => self.slice(),
+            TokenType::Identifier(_) if self.ordinal_at().is_some() => self.ordinal_item(),
+            TokenType::Identifier(_) if self.at_words(&["the", "number", "in"]) => self.builtin_call("number_in", 3),
+            TokenType::Identifier(_) if self.at_date() => self.date(),
+            TokenType::Identifier(_) if self.at_words(&["try"]) && matches!(self.peek_next_type(), TokenType::To) => self.try_to_read(),
+            TokenType::Identifier(_) if self.at_type_of() => self.type_of(),
+            TokenType::Identifier(_) if self.at_length_of() => self.length_of(),
+            TokenType::Identifier(_) if self.at_property_of() => self.property_of(),
+            TokenType::Identifier(name) if name == "the" && Self::starts_operand(self.peek_next_type()) => self.comprehension(),
+            TokenType::Identifier(name) if name == "item" && Self::starts_operand(self.peek_next_type()) => self.item_or_name(),
+            TokenType::Identifier(name) if name == "freeze" && Self::starts_operand(self.peek_next_type()) => {
+                self.builtin_call("freeze", 1)
+            },
+            TokenType::Identifier(name) if name == "wait" && Self::starts_operand(self.peek_next_type()) => self.builtin_call("wait", 1),
+            // `describe people`, unlike a call to a Task named describe
+            TokenType::Identifier(name) if name == "describe"
+                && Self::starts_operand(self.peek_next_type()) && !matches!(self.peek_next_type(), TokenType::OpenParen) => {
+                self.builtin_call("<describe>", 1)
+            },
+            TokenType::Identifier(name) if name == "ask"
+                && (Self::starts_operand(self.peek_next_type()) && !self.at_words(&["ask", "is"]) || self.at_words(&["ask", "for"])) => self.ask(),
+            TokenType::Identifier(name) if name == "round" && Self::starts_operand(self.peek_next_type()) => self.round(),
+            TokenType::Identifier(name) if name == "evaluate" && Self::starts_operand(self.peek_next_type()) => self.evaluate(),
             TokenType::Identifier(name) => {
                 self.advance();
                 Ok(Node::Variable(name))
             },
-            TokenType::String(value) => {
+            TokenType::String(_) => self.string_literal(),
+            // `19.99 dollars`, `50 cents`
+            TokenType::Number(_) if matches!(self.peek_next_type(), TokenType::Identifier(word) if Money::from_words("0", word).is_some()) => {
+                self.money_in_words()
+            },
+            TokenType::Number(value) => self.number(value),
+            TokenType::Money(money) => {
                 self.advance();
-                Ok(Node::Literal(Value::String(value)))
+                Ok(Node::Literal(Value::Money(money)))
             },
-            TokenType::LeftBrace => {
+            TokenType::Boolean(value) => {
                 self.advance();
-                let expr = self.expression()?;
-                self.consume(&TokenType::RightBrace, "Expected '}' after expression")?;
-                Ok(expr)
+                Ok(Node::Literal(Value::Boolean(value)))
             },
-            TokenType::Quote => {
+            TokenType::Null => {
                 self.advance();
-                let mut parts = Vec::new();
-                
-                while !self.check(&TokenType::Quote) && !self.is_at_end() {
-                    if self.match_token(&[TokenType::LeftBrace]) {
-                        let expr = self.expression()?;
-                        self.consume(&TokenType::RightBrace, "Expected '}' after expression")?;
-                        parts.push(expr);
-                    } else {
-                        let text = self.consume_string_part()?;
-                        parts.push(Node::Literal(Value::String(text)));
-                    }
-                }
-                
-                self.consume(&TokenType::Quote, "Expected '\"' after string")?;
-                Ok(Node::StringInterpolation { parts })
+                Ok(Node::Literal(Value::Null))
             },
-            TokenType::Number(value) => {
+            TokenType::New => {
                 self.advance();
-                Ok(Node::Literal(Value::Number(value)))
+                self.new_expression()
             },
-            TokenType::Boolean(value) => {
+            TokenType::My => {
                 self.advance();
-                Ok(Node::Literal(Value::Boolean(value)))
+                let name = self.consume_identifier("Expected property name after 'my'")?;
+                Ok(Node::Get {
+                    object: Box::new(Node::Variable("me".to_string())),
+                    name,
+                })
             },
-            TokenType::Null => {
+            TokenType::Me => {
                 self.advance();
-                Ok(Node::Literal(Value::Null))
+                Ok(Node::Variable("me".to_string()))
+            },
+            TokenType::OpenParen => {
+                self.advance();
+                let expr = self.expression()?;
+                self.consume(&TokenType::CloseParen, "Expected ')' after expression")?;
+                Ok(expr)
+            },
+            TokenType::OpenBracket => {
+                self.advance();
+                self.list_literal()
             },
             TokenType::TypeMapping => {
                 self.advance();
                 Ok(Node::MappingLiteral { entries: Vec::new() })
             },
-            _ => Err("Expected expression".to_string()),
+            _ => Err(format!("Expected expression, found '{}' on line {}", token.literal, token.line)),
+        }
+    }
+
+    // Parses `a list of 1, then all of xs, ...`; each `all of` part must be a List
+    fn list_of(&mut self) -> Result<Node, String> {
+        self.current += 3;
+        let mut parts = Vec::new();
+        loop {
+            let all = self.at_words(&["all"]) && self.peek_next_type() == &TokenType::Of;
+            if all {
+                self.current += 2;
+            }
+            parts.push((all, self.expression()?));
+            if !(self.check(&TokenType::Comma) && matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "then")) {
+                break;
+            }
+            self.current += 2;
         }
+        Ok(Node::ListOf { parts })
+    }
+
+    // Only `item <key> of` indexes; otherwise `item` is a name
+    fn item_or_name(&mut self) -> Result<Node, String> {
+        let start = self.current;
+        self.index(false).or_else(|_| {
+            self.current = start + 1;
+            Ok(Node::Variable("item".to_string()))
+        })
     }
 
-    fn consume_string_part(&mut self) -> Result<String, String> {
-        if let TokenType::StringPart(text) = &self.peek().token_type {
-            let text = text.clone();
+    // Parses `item <key> of <object>` or (keyed) `the value for <key> in <object>`
+    fn index(&mut self, keyed: bool) -> Result<Node, String> {
+        self.current += if keyed { 3 } else { 1 };
+        let key = Box::new(self.term()?);
+        if !keyed {
+            self.consume(&TokenType::Of, "Expected 'of' after the item number")?;
+        } else if self.at_words(&["in"]) {
             self.advance();
-            Ok(text)
         } else {
-            Err("Expected string part".to_string())
+            return Err(format!("Expected 'in' after the key on line {}", self.peek().line));
         }
+        let object = Box::new(self.unary()?);
+        Ok(Node::Index { object, key, keyed })
     }
 
-    fn when_statement(&mut self) -> Result<Node, String> {
-        let condition = Box::new(self.expression()?);
-        self.consume(&TokenType::Colon, "Expected ':' after when condition")?;
-        let then_branch = Box::new(self.block()?);
-        
-        let else_branch = if self.match_token(&[TokenType::Or]) {
-            self.consume(&TokenType::Colon, "Expected ':' after 'or'")?;
-            Some(Box::new(self.block()?))
+    // Only `items <first> through <last> of` slices; otherwise `items` is a name
+    fn slice(&mut self) -> Result<Node, String> {
+        let start = self.current;
+        let named = self.at_words(&["items"]);
+        self.slice_of().or_else(|error| match named {
+            true => {
+                self.current = start + 1;
+                Ok(Node::Variable("items".to_string()))
+            },
+            false => Err(error),
+        })
+    }
+
+    // Parses `items <first> through <last> of <object>` or
+    // `everything after/before item <number> of <object>`
+    fn slice_of(&mut self) -> Result<Node, String> {
+        let (first, last) = if self.at_words(&["items"]) {
+            self.advance();
+            let first = Box::new(self.term()?);
+            if !self.at_words(&["through"]) {
+                return Err(format!("Expected 'through' after the first item number on line {}", self.peek().line));
+            }
+            self.advance();
+            (Some(first), Some(Box::new(self.term()?)))
         } else {
-            None
+            let after = self.at_words(&["everything", "after"]);
+            self.current += 2;
+            let number = match self.ordinal_at() {
+                Some(_) => self.ordinal()?,
+                None if self.at_words(&["item"]) => {
+                    self.advance();
+                    self.term()?
+                },
+                None => return Err(format!("Expected 'item' or an ordinal such as 'the first item' on line {}", self.peek().line)),
+            };
+            if after { (Some(Box::new(number)), None) } else { (None, Some(Box::new(number))) }
         };
+        self.consume(&TokenType::Of, "Expected 'of' after the item number")?;
+        let object = Box::new(self.unary()?);
+        Ok(Node::Slice { object, first, last })
+    }
 
-        Ok(Node::WhenStmt {
-            condition,
-            then_branch,
-            else_branch,
-        })
+    // Parses `the <ordinal> item of <object>`, or `the <ordinal> item of each
+    // <name> in <list>` for that item of every List in a List
+    fn ordinal_item(&mut self) -> Result<Node, String> {
+        let key = Box::new(self.ordinal()?);
+        if self.check(&TokenType::Of) && matches!(self.peek_next_type(), TokenType::Each) {
+            let (name, list, condition) = self.each_in()?;
+            let value = Box::new(Node::Index { object: Box::new(Node::Variable(name.clone())), key, keyed: false });
+            return Ok(Node::Comprehension { value, name, list, condition });
+        }
+        self.consume(&TokenType::Of, "Expected 'of' after the ordinal item")?;
+        let object = Box::new(self.unary()?);
+        Ok(Node::Index { object, key, keyed: false })
     }
 
-    fn loop_statement(&mut self) -> Result<Node, String> {
-        self.consume(&TokenType::While, "Expected 'while' after 'loop'")?;
-        let condition = Box::new(self.expression()?);
-        self.consume(&TokenType::Colon, "Expected ':' after loop condition")?;
-        let body = Box::new(self.block()?);
+    // Parses the ordinal that `ordinal_at` found
+    fn ordinal(&mut self) -> Result<Node, String> {
+        let (number, from_end, digits, length) = self.ordinal_at().ok_or("Expected an ordinal such as 'the first item'")?;
+        if number < 1.0 || number.fract() != 0.0 {
+            return Err(format!("Item numbers count from 1, so there is no {}{} item on line {}",
+                number, ordinal_suffix(number as usize), self.peek().line));
+        }
+        self.current += length;
+        Ok(Node::Ordinal { number: number as usize, from_end, digits })
+    }
 
-        Ok(Node::LoopStmt { condition, body })
+    // `19.99 dollars` or `50 cents`
+    fn money_in_words(&mut self) -> Result<Node, String> {
+        let digits = Self::digits(self.advance());
+        let word = self.advance().literal.clone();
+        let money = Money::from_words(&digits, &word).unwrap_or_else(|| Err(format!("Unknown currency '{}'", word)));
+        money.map(|money| Node::Literal(Value::Money(money)))
+            .map_err(|error| format!("{} on line {}", error, self.previous().line))
     }
 
-    fn show_statement(&mut self) -> Result<Node, String> {
-        self.advance(); // Consume 'show'
-        let expr = self.expression()?;
-        Ok(Node::ShowStmt(Box::new(expr)))
+    // Whether the `%` here makes the number before it a percentage: it
+    // touches the number but not an operand after it, as in `x % 3` or `17%5`
+    fn at_percent(&self) -> bool {
+        let (number, percent) = (self.previous(), self.peek());
+        let touching = |next: &Token| next.offset == percent.offset + 1 && Self::starts_operand(&next.token_type);
+        self.check(&TokenType::Modulo) && percent.offset == number.offset + number.literal.len()
+            && !self.tokens.get(self.current + 1).is_some_and(touching)
     }
 
-    fn raise_statement(&mut self) -> Result<Node, String> {
-        let message = Box::new(self.expression()?);
-        self.consume(&TokenType::As, "Expected 'as' after raise message")?;
-        let error_type = Box::new(self.type_annotation()?);
-        
-        Ok(Node::RaiseStmt {
-            message,
-            error_type,
+    // `15%` is 15 / 100, and `15% of price` is 15 / 100 * price
+    fn percentage(&mut self, amount: f64) -> Result<Node, String> {
+        self.advance();
+        let percent = Node::Binary {
+            left: Box::new(Node::Literal(Value::Number(amount))),
+            operator: TokenType::Divide,
+            right: Box::new(Node::Literal(Value::Number(100.0))),
+        };
+        if !self.match_token(&[TokenType::Of]) {
+            return Ok(percent);
+        }
+        self.skip_article();
+        Ok(Node::Binary { left: Box::new(percent), operator: TokenType::Multiply, right: Box::new(self.unary()?) })
+    }
+
+    // Skips the `the` in `15% of the price` or `increase the price by 10%`,
+    // unless it starts a phrase like `the type of` or `the third item`
+    fn skip_article(&mut self) {
+        let phrase = matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Of))
+            || self.at_words(&["the", "number", "in"]) || self.at_words(&["the", "value", "for"])
+            || self.at_date() || self.ordinal_at().is_some();
+        if self.at_words(&["the"]) && matches!(self.peek_next_type(), TokenType::Identifier(_)) && !phrase {
+            self.advance();
+        }
+    }
+
+    // Whether a date starts here: `June 1, 2025`, or `the date today`
+    fn at_date(&self) -> bool {
+        let token = |offset: usize| self.tokens.get(self.current + offset).map(|token| &token.token_type);
+        self.at_words(&["the", "date", "today"]) || matches!(
+            (token(0), token(1), token(2), token(3)),
+            (Some(TokenType::Identifier(month)), Some(TokenType::Number(_)), Some(TokenType::Comma), Some(TokenType::Number(_))) if Date::is_month(month)
+        )
+    }
+
+    fn date(&mut self) -> Result<Node, String> {
+        if self.at_words(&["the", "date", "today"]) {
+            self.current += 3;
+            return Ok(Node::Call { callee: Box::new(Node::Variable("today".to_string())), args: Vec::new() });
+        }
+        let line = self.peek().line;
+        let month = self.advance().literal.clone();
+        let day = Self::digits(self.advance());
+        self.advance();
+        let year = Self::digits(self.advance());
+        match Date::new(&month, day.parse().unwrap_or(0.0), year.parse().unwrap_or(0.0)) {
+            Some(date) => Ok(Node::Literal(Value::Date(date))),
+            None => Err(format!("There is no {} {}, {} on the calendar (line {})", month, day, year, line)),
+        }
+    }
+
+    // A number, or with units after it a quantity: `5 meters`, `9.8 meters
+    // per second squared`, or with `%` right after it a percentage
+    fn number(&mut self, amount: f64) -> Result<Node, String> {
+        if Self::number_word(self.peek()) && matches!(self.peek_next_type(), TokenType::OpenParen) {
+            return Ok(Node::Variable(self.advance().literal.clone()));
+        }
+        self.advance();
+        if self.at_percent() {
+            return self.percentage(amount);
+        }
+        Ok(Node::Literal(match Unit::read(&self.words_at(self.current)) {
+            Some((unit, length)) => {
+                self.current += length;
+                Value::Quantity(Quantity { amount, unit })
+            },
+            None => Value::Number(amount),
+        }))
+    }
+
+    // `the third item`, `the 3rd item`, `the last item` or `the second to
+    // last item` here: its number, whether it counts from the end, whether
+    // it is in digits, and how many tokens it takes
+    fn ordinal_at(&self) -> Option<(f64, bool, bool, usize)> {
+        let word = |offset: usize| match self.tokens.get(self.current + offset).map(|token| &token.token_type) {
+            Some(TokenType::Identifier(word)) => word.as_str(),
+            _ => "",
+        };
+        let (number, digits, length) = match self.tokens.get(self.current + 1).map(|token| &token.token_type) {
+            _ if word(0) != "the" => return None,
+            Some(TokenType::Identifier(last)) if last == "last" => (1.0, false, 1),
+            Some(TokenType::Identifier(ordinal)) => (ORDINALS.iter().position(|word| word == ordinal)? as f64 + 1.0, false, 2),
+            Some(TokenType::Number(number)) if matches!(word(2), "st" | "nd" | "rd" | "th") => (*number, true, 3),
+            _ => return None,
+        };
+        let to = matches!(self.tokens.get(self.current + length).map(|token| &token.token_type), Some(TokenType::To));
+        let (from_end, length) = match (length, to, word(length + 1)) {
+            (1, ..) => (true, 2),
+            (_, true, "last") => (true, length + 2),
+            _ => (false, length),
+        };
+        (word(length) == "item").then_some((number, from_end, digits, length + 1))
+    }
+
+    // Parses `the <name> of <object>`, which reads a property as
+    // `object.name` does, or with `, if <object> exists` gives Nothing
+    // when the object is Nothing
+    fn property_of(&mut self) -> Result<Node, String> {
+        self.advance(); // Consume 'the'
+        let name = self.consume_identifier("Expected a property name after 'the'")?;
+        self.advance(); // Consume 'of'
+        let object = Box::new(self.unary()?);
+        if !(self.check(&TokenType::Comma) && matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "if")) {
+            return Ok(Node::Get { object, name });
+        }
+        self.current += 2;
+        let checked = self.unary()?;
+        if !self.at_words(&["exists"]) {
+            return Err(format!("Expected 'exists' after 'if {}' on line {}", expression(&checked), self.peek().line));
+        }
+        self.advance();
+        if expression(&checked) != expression(&object) {
+            return Err(format!("`the {} of {}` can only check that {} exists, not {} (line {})",
+                name, expression(&object), expression(&object), expression(&checked), self.previous().line));
+        }
+        Ok(Node::SafeGet { object, name })
+    }
+
+    // Parses `the <value> of each <name> in <list>`, with an optional `where <condition>`
+    fn comprehension(&mut self) -> Result<Node, String> {
+        self.advance();
+        let value = Box::new(self.expression()?);
+        let (name, list, condition) = self.each_in()?;
+        Ok(Node::Comprehension { value, name, list, condition })
+    }
+
+    // Parses `of each <name> in <list> [where <condition>]`
+    fn each_in(&mut self) -> Result<EachIn, String> {
+        self.consume(&TokenType::Of, "Expected 'of each' after the value of 'the'")?;
+        self.consume(&TokenType::Each, "Expected 'each' after 'of'")?;
+        let name = self.consume_identifier("Expected a name after 'each'")?;
+        if !self.at_words(&["in"]) {
+            return Err(format!("Expected 'in' after 'each {}' on line {}", name, self.peek().line));
+        }
+        self.advance();
+        let list = Box::new(self.expression()?);
+        let condition = match self.at_words(&["where"]) {
+            true => {
+                self.advance();
+                Some(Box::new(self.expression()?))
+            },
+            false => None,
+        };
+        Ok((name, list, condition))
+    }
+
+    // Parses the elements of `[a, b, c]` after the opening bracket, with an optional `as List[T]`
+    fn list_literal(&mut self) -> Result<Node, String> {
+        let mut elements = Vec::new();
+
+        if !self.check(&TokenType::CloseBracket) {
+            loop {
+                elements.push(self.expression()?);
+                if !self.match_token(&[TokenType::Comma]) {
+                    break;
+                }
+            }
+        }
+        self.consume(&TokenType::CloseBracket, "Expected ']' after list elements")?;
+
+        let type_annotation = if self.check(&TokenType::As) && self.peek_next_type() == &TokenType::TypeList {
+            self.advance();
+            Some(Box::new(self.type_annotation()?))
+        } else {
+            None
+        };
+
+        Ok(Node::ArrayLiteral {
+            elements,
+            type_annotation,
+        })
+    }
+
+    // `unless x:` is `when x is false:`
+    fn when_statement(&mut self, unless: bool) -> Result<Node, String> {
+        let mut condition = self.expression()?;
+        if unless {
+            condition = Self::negate(condition);
+        }
+        let condition = Box::new(condition);
+        self.consume(&TokenType::Colon, "Expected ':' after when condition")?;
+        let then_branch = Box::new(self.block()?);
+
+        let mut or_when = Vec::new();
+        let mut else_branch = None;
+        while self.check_after_newlines(&TokenType::Or) {
+            self.skip_newlines();
+            self.advance(); // Consume 'or'
+            if self.check(&TokenType::When) {
+                let line = self.advance().line;
+                let condition = self.expression()?;
+                self.consume(&TokenType::Colon, "Expected ':' after when condition")?;
+                or_when.push(OrWhen { line, condition, body: self.block()? });
+            } else {
+                self.consume(&TokenType::Colon, "Expected ':' after 'or'")?;
+                else_branch = Some(Box::new(self.block()?));
+                break;
+            }
+        }
+
+        Ok(Node::WhenStmt {
+            condition,
+            then_branch,
+            or_when,
+            else_branch,
+        })
+    }
+
+    fn test_declaration(&mut self) -> Result<Node, String> {
+        let start = self.current;
+        while !self.check(&TokenType::Colon) && !self.check(&TokenType::NewLine) && !self.is_at_end() {
+            self.advance();
+        }
+        let name = match &self.tokens[start..self.current] {
+            [Token { token_type: TokenType::String(name), .. }] => name.clone(),
+            [] => return Err(format!("Expected a description after 'to test' at line {}", self.peek().line)),
+            _ => self.source_text(start, self.current),
+        };
+        self.consume(&TokenType::Colon, "Expected ':' after test description")?;
+        let body = Box::new(self.block()?);
+
+        Ok(Node::TestDecl { name, body })
+    }
+
+    fn expect_statement(&mut self) -> Result<Node, String> {
+        let line = self.previous().line;
+        if self.at_word("that") {
+            self.advance();
+            let start = self.current;
+            let actual = Box::new(self.expression()?);
+            return Ok(Node::ExpectStmt {
+                actual,
+                expected: None,
+                line,
+                source: self.source_text(start, self.current),
+            });
+        }
+
+        let start = self.current;
+        let actual = Box::new(self.expression()?);
+        let source = self.source_text(start, self.current);
+        self.consume(&TokenType::To, "Expected 'that' or 'to equal' after 'expect'")?;
+        if !self.at_word("equal") {
+            return Err(format!("Expected 'equal' after 'expect ... to' at line {}", line));
+        }
+        self.advance();
+        let expected = Some(Box::new(self.expression()?));
+
+        Ok(Node::ExpectStmt { actual, expected, line, source })
+    }
+
+    // The source text of tokens[start..end], respaced
+    fn source_text(&self, start: usize, end: usize) -> String {
+        let mut text = String::new();
+        let mut previous: Option<&TokenType> = None;
+        for token in &self.tokens[start..end] {
+            let tight_after = matches!(previous, Some(TokenType::OpenParen | TokenType::OpenBracket | TokenType::Dot));
+            let tight_before = matches!(token.token_type,
+                TokenType::CloseParen | TokenType::CloseBracket | TokenType::Comma | TokenType::Dot);
+            // Calls and indexing hug the name before them
+            let opens_call = matches!(token.token_type, TokenType::OpenParen | TokenType::OpenBracket)
+                && matches!(previous, Some(TokenType::Identifier(_) | TokenType::CloseParen | TokenType::CloseBracket));
+            if previous.is_some() && !tight_after && !tight_before && !opens_call {
+                text.push(' ');
+            }
+            match &token.token_type {
+                TokenType::String(content) => text.push_str(&format!("\"{}\"", content)),
+                _ => text.push_str(&token.literal),
+            }
+            previous = Some(&token.token_type);
+        }
+        text
+    }
+
+    fn try_statement(&mut self) -> Result<Node, String> {
+        self.consume(&TokenType::Colon, "Expected ':' after 'do'")?;
+        let body = Box::new(self.block()?);
+
+        let mut handlers = Vec::new();
+        while self.check_after_newlines(&TokenType::Fail) {
+            self.skip_newlines();
+            self.advance(); // Consume 'fail'
+            let name = match &self.peek().token_type {
+                TokenType::Identifier(_) => Some(self.consume_identifier("Expected error name")?),
+                _ => None,
+            };
+            let kind = if self.match_token(&[TokenType::As]) {
+                Some(self.type_annotation()?)
+            } else {
+                None
+            };
+            self.consume(&TokenType::Colon, "Expected ':' after fail clause")?;
+            let body = self.block()?;
+            handlers.push(FailClause { name, kind, body });
+        }
+
+        let always = if self.check_after_newlines(&TokenType::Always) {
+            self.skip_newlines();
+            self.advance(); // Consume 'always'
+            self.consume(&TokenType::Colon, "Expected ':' after 'always'")?;
+            Some(Box::new(self.block()?))
+        } else {
+            None
+        };
+
+        if handlers.is_empty() && always.is_none() {
+            return Err(format!("Expected 'fail' or 'always' after 'do' block at line {}", self.peek().line));
+        }
+
+        Ok(Node::TryStmt { body, handlers, always })
+    }
+
+    // `loop until x:` (or `repeat until x:`) is `loop while x is false:`
+    fn loop_statement(&mut self) -> Result<Node, String> {
+        let until = self.at_words(&["until"]);
+        if until {
+            self.advance();
+        } else {
+            self.consume(&TokenType::While, "Expected 'while' or 'until' after 'loop'")?;
+        }
+        let mut condition = self.expression()?;
+        if until {
+            condition = Self::negate(condition);
+        }
+        let condition = Box::new(condition);
+        let label = match self.match_token(&[TokenType::Comma]) {
+            true => Some(self.loop_label()?),
+            false => None,
+        };
+        self.consume(&TokenType::Colon, "Expected ':' after loop condition")?;
+        let body = Box::new(self.block()?);
+
+        Ok(Node::LoopStmt { condition, body, label })
+    }
+
+    // Parses `called the <name> loop` after the comma ending a loop header
+    fn loop_label(&mut self) -> Result<String, String> {
+        if !self.at_words(&["called"]) {
+            return Err(format!("Expected 'called the <name> loop' after ',' (line {})", self.peek().line));
+        }
+        self.advance();
+        self.loop_name("called")
+    }
+
+    // Parses `the <name> loop` after `word`, giving the name
+    fn loop_name(&mut self, word: &str) -> Result<String, String> {
+        if !self.at_words(&["the"]) {
+            return Err(format!("Expected 'the <name> loop' after '{}' (line {})", word, self.peek().line));
+        }
+        self.advance();
+        let name = self.consume_identifier(&format!("Expected a name after '{} the'", word))?;
+        self.consume(&TokenType::Loop, &format!("Expected 'loop' after '{} the {}'", word, name))?;
+        Ok(name)
+    }
+
+    // Parses `<count> times[, counting with <name>]:` after `repeat`
+    fn repeat_statement(&mut self) -> Result<Node, String> {
+        let count = Box::new(self.expression()?);
+        if !self.at_words(&["times"]) {
+            return Err(format!("Expected 'times' after the number of repeats (line {})", self.peek().line));
+        }
+        self.advance();
+        let mut counter = None;
+        if self.check(&TokenType::Comma) && matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "counting") {
+            self.advance(); // Consume ','
+            self.advance(); // Consume 'counting'
+            self.consume(&TokenType::With, "Expected 'with' after 'counting'")?;
+            counter = Some(self.consume_identifier("Expected a variable name after 'counting with'")?);
+        }
+        let label = match self.match_token(&[TokenType::Comma]) {
+            true if counter.is_none() && !self.at_words(&["called"]) => {
+                return Err(format!("Expected 'counting with' or 'called' after ',' (line {})", self.peek().line));
+            },
+            true => Some(self.loop_label()?),
+            false => None,
+        };
+        self.consume(&TokenType::Colon, "Expected ':' after 'times'")?;
+        let body = Box::new(self.block()?);
+
+        Ok(Node::RepeatStmt { count, counter, body, label })
+    }
+
+    // Parses `[, <runs> times][, calling it <name>]:` after `measure how long it takes to`
+    fn measure_statement(&mut self) -> Result<Node, String> {
+        let mut runs = None;
+        let mut result = None;
+        while self.match_token(&[TokenType::Comma]) {
+            if result.is_none() && self.at_words(&["calling", "it"]) {
+                self.advance(); // Consume 'calling'
+                self.advance(); // Consume 'it'
+                result = Some(self.consume_identifier("Expected a variable name after 'calling it'")?);
+            } else if runs.is_none() && result.is_none() {
+                runs = Some(Box::new(self.expression()?));
+                if !self.at_words(&["times"]) {
+                    return Err(format!("Expected 'times' after the number of runs (line {})", self.peek().line));
+                }
+                self.advance();
+            } else {
+                return Err(format!("Expected 'calling it <name>' after ',' (line {})", self.peek().line));
+            }
+        }
+        self.consume(&TokenType::Colon, "Expected ':' after 'measure how long it takes to'")?;
+        let body = Box::new(self.block()?);
+
+        Ok(Node::MeasureStmt { runs, result, body })
+    }
+
+    fn negate(condition: Node) -> Node {
+        Node::Binary {
+            left: Box::new(condition),
+            operator: TokenType::Is,
+            right: Box::new(Node::Literal(Value::Boolean(false))),
+        }
+    }
+
+    fn raise_statement(&mut self) -> Result<Node, String> {
+        // Parsed below `expression` so the trailing `as Kind` isn't taken as a cast
+        let message = Box::new(self.nested(Self::or)?);
+        let error_type = if self.match_token(&[TokenType::As]) {
+            Some(Box::new(self.type_annotation()?))
+        } else {
+            None
+        };
+
+        Ok(Node::RaiseStmt {
+            message,
+            error_type,
         })
     }
 
@@ -784,6 +2134,45 @@ impl Parser {
         Ok(Node::ReturnStmt(value))
     }
 
+    // Parses `<condition>, give back <value>` (or `, output <value>` or
+    // `, stop here`) after `if`
+    fn guard_statement(&mut self) -> Result<Node, String> {
+        let condition = Box::new(self.expression()?);
+        self.consume(&TokenType::Comma, "Expected ',' after the condition of 'if'")?;
+        let value = if self.at_words(&["give", "back"]) {
+            self.advance();
+            self.advance();
+            self.expression()?
+        } else if self.match_token(&[TokenType::Output]) {
+            self.expression()?
+        } else if self.at_words(&["stop", "here"]) {
+            self.advance();
+            self.advance();
+            Node::Literal(Value::Null)
+        } else {
+            return Err(format!("Expected 'give back' or 'stop here' after 'if' and its condition (line {})", self.peek().line));
+        };
+        Ok(Node::GuardStmt { condition, value: Box::new(value) })
+    }
+
+    // `my name is value` / `box.width is 3` / `item 1 of xs is 3`, falling back to a plain expression statement
+    fn property_assignment_or_expression(&mut self) -> Result<Node, String> {
+        let start = self.current;
+        match self.call() {
+            Ok(Node::Get { object, name }) if self.match_token(&[TokenType::Is]) => {
+                let value = Box::new(self.expression()?);
+                return Ok(Node::Set { object, name, value });
+            },
+            Ok(Node::Index { object, key, keyed }) if self.match_token(&[TokenType::Is]) => {
+                let value = Box::new(self.expression()?);
+                return Ok(Node::SetIndex { object, key, keyed, value });
+            },
+            _ => {},
+        }
+        self.current = start;
+        self.expression_statement()
+    }
+
     fn expression_statement(&mut self) -> Result<Node, String> {
         let expr = self.expression()?;
         Ok(Node::ExpressionStmt(Box::new(expr)))
@@ -796,16 +2185,16 @@ impl Parser {
         } else {
             return Err("Expected string literal".to_string());
         };
-        
+
         // Now advance the parser
         self.advance();
-        
+
         // Process the string content
         if string_content.contains('{') && string_content.contains('}') {
             let mut parts = Vec::new();
             let mut current_text = String::new();
-            let mut chars = string_content.chars().peekable();
-            
+            let mut chars = string_content.chars();
+
             while let Some(c) = chars.next() {
                 if c == '{' {
                     // Add accumulated text if any
@@ -813,39 +2202,49 @@ impl Parser {
                         parts.push(Node::Literal(Value::String(current_text.clone())));
                         current_text.clear();
                     }
-                    
-                    // Collect variable name
-                    let mut var_name = String::new();
-                    while let Some(&next_char) = chars.peek() {
+
+                    // Collect the embedded expression up to the closing brace
+                    let mut source = String::new();
+                    for next_char in chars.by_ref() {
                         if next_char == '}' {
-                            chars.next(); // consume the '}'
                             break;
                         }
-                        var_name.push(chars.next().unwrap());
+                        source.push(next_char);
                     }
-                    
-                    // Add variable reference
-                    parts.push(Node::Variable(var_name));
+
+                    parts.push(Self::interpolated_expression(&source)?);
                 } else {
                     current_text.push(c);
                 }
             }
-            
+
             // Add any remaining text
             if !current_text.is_empty() {
                 parts.push(Node::Literal(Value::String(current_text)));
             }
-            
+
             Ok(Node::StringInterpolation { parts })
         } else {
             Ok(Node::Literal(Value::String(string_content)))
         }
     }
 
+    // Parses the source inside `{...}` of a string as a standalone expression
+    fn interpolated_expression(source: &str) -> Result<Node, String> {
+        let tokens = Tokenizer::new(source.trim()).tokenize()?;
+        let mut parser = Parser::new(tokens);
+        let expr = parser.expression()?;
+        parser.skip_newlines();
+        if !parser.is_at_end() {
+            return Err(format!("Unexpected input in interpolation '{{{}}}'", source));
+        }
+        Ok(expr)
+    }
+
     fn argument_list(&mut self) -> Result<Vec<Node>, String> {
         let mut args = Vec::new();
 
-        if !self.check(&TokenType::CloseParen) && !self.is_at_end() {
+        if !self.check(&TokenType::CloseParen) && !self.check(&TokenType::NewLine) && !self.is_at_end() {
             loop {
                 args.push(self.expression()?);
                 if !self.match_token(&[TokenType::Comma]) {
@@ -857,12 +2256,280 @@ impl Parser {
         Ok(args)
     }
 
+    fn mapping_initializer(&mut self) -> Result<Node, String> {
+        let mut entries = Vec::new();
+
+        loop {
+            // Parse parameter name
+            let param_name = self.consume_identifier("Expected parameter name")?;
+
+            // Handle both explicit and implicit type declarations
+            let (param_type, value) = if self.match_token(&[TokenType::As]) {
+                // Explicit type: param as Type is value
+                let param_type = self.type_annotation()?;
+                self.consume(&TokenType::Is, "Expected 'is' after type")?;
+                let value = self.expression()?;
+                (Some(param_type), value)
+            } else if self.match_token(&[TokenType::Is]) {
+                // Implicit type: param is value
+                let value = self.expression()?;
+                (None, value)
+            } else {
+                return Err("Expected 'as' or 'is' after parameter name".to_string());
+            };
+
+            entries.push((param_name, param_type, value));
+
+            if !self.match_token(&[TokenType::Comma]) {
+                break;
+            }
+        }
+
+        Ok(Node::MappingLiteral { entries })
+    }
+
+    // Runs a nested parse one level deeper, restoring the depth afterwards
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T, String>) -> Result<T, String> {
+        let saved = self.depth;
+        let result = self.deepen().and_then(|()| parse(self));
+        self.depth = saved;
+        result
+    }
+
+    fn deepen(&mut self) -> Result<(), String> {
+        self.depth += 1;
+        if self.depth > MAX_NESTING {
+            return Err(format!("Code is nested too deeply (line {})", self.peek().line));
+        }
+        Ok(())
+    }
+
     fn peek(&self) -> &Token {
         &self.tokens[self.current]
     }
 
+    fn peek_next_type(&self) -> &TokenType {
+        match self.tokens.get(self.current + 1) {
+            Some(token) => &token.token_type,
+            None => &TokenType::Eof,
+        }
+    }
+
+    // A built-in Task written as words before its one argument
+    fn builtin_call(&mut self, name: &str, words: usize) -> Result<Node, String> {
+        self.current += words;
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable(name.to_string())),
+            args: vec![self.unary()?],
+        })
+    }
+
+    // `ask "Name? "` reads a line as Text. `ask "Age? " for a Whole between
+    // 1 and 120, up to 3 tries` asks again until the answer is a Whole in
+    // range: a call to `ask` with the prompt, the type, the two ends and the
+    // number of tries, any of which but the type may be nothing.
+    fn ask(&mut self) -> Result<Node, String> {
+        self.advance(); // Consume 'ask'
+        let prompt = match self.at_words(&["for"]) {
+            true => Node::Literal(Value::String(String::new())),
+            false => self.unary()?,
+        };
+        let callee = Box::new(Node::Variable("ask".to_string()));
+        if !self.at_words(&["for"]) {
+            return Ok(Node::Call { callee, args: vec![prompt] });
+        }
+        let line = self.peek().line;
+        self.advance();
+        if !self.at_words(&["a"]) && !self.at_words(&["an"]) {
+            return Err(format!("Expected 'a' or 'an' after 'ask ... for' on line {}", line));
+        }
+        self.advance();
+        let kind = match self.type_annotation()? {
+            Node::TypeAnnotation(name) if name == "Number" => "Decimal".to_string(),
+            Node::TypeAnnotation(name) if ["Whole", "Decimal", "Truth", "Text"].contains(&name.as_str()) => name,
+            _ => return Err(format!("Can only ask for a Whole, Decimal, Truth or Text on line {}", line)),
+        };
+        let (mut low, mut high) = (Node::Literal(Value::Null), Node::Literal(Value::Null));
+        if self.at_words(&["between"]) {
+            self.advance();
+            low = self.term()?;
+            self.consume(&TokenType::And, "Expected 'and' between the two ends after 'between'")?;
+            high = self.term()?;
+        }
+        let mut tries = Node::Literal(Value::Null);
+        let up_to = self.check(&TokenType::Comma)
+            && matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "up")
+            && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::To));
+        if up_to {
+            self.current += 3;
+            tries = self.term()?;
+            if !self.at_words(&["tries"]) && !self.at_words(&["try"]) {
+                return Err(format!("Expected 'tries' after 'up to {}' on line {}", expression(&tries), line));
+            }
+            self.advance();
+        }
+        Ok(Node::Call { callee, args: vec![prompt, Node::Literal(Value::String(kind)), low, high, tries] })
+    }
+
+    // `the length of x`, or for Text `the length of x in bytes` or `in code
+    // points`, which call the built-in Task `length_of` with what to count
+    fn length_of(&mut self) -> Result<Node, String> {
+        self.current += 3;
+        let value = self.unary()?;
+        let counting = if self.at_words(&["in", "bytes"]) {
+            self.current += 2;
+            "bytes"
+        } else if self.at_words(&["in", "code", "points"]) {
+            self.current += 3;
+            "code points"
+        } else {
+            "characters"
+        };
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable("length_of".to_string())),
+            args: vec![value, Node::Literal(Value::String(counting.to_string()))],
+        })
+    }
+
+    fn type_of(&mut self) -> Result<Node, String> {
+        self.current += 3;
+        Ok(Node::TypeOf { value: Box::new(self.unary()?) })
+    }
+
+    // `try to read x as a Whole`, or `as a Decimal` (`as a number`), which
+    // call the built-in Tasks `whole_in` and `number_in`
+    fn try_to_read(&mut self) -> Result<Node, String> {
+        let line = self.peek().line;
+        if !matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Identifier(word)) if word == "read") {
+            return Err(format!("Expected 'read' after 'try to' on line {}", line));
+        }
+        self.current += 3;
+        let text = self.unary()?;
+        self.consume(&TokenType::As, "Expected 'as a Whole' or 'as a Decimal' after the text to read")?;
+        if self.at_words(&["a"]) {
+            self.advance();
+        }
+        let name = match self.peek().token_type {
+            TokenType::TypeWhole => "whole_in",
+            TokenType::TypeDecimal | TokenType::TypeNumber => "number_in",
+            TokenType::Identifier(ref word) if word == "number" => "number_in",
+            _ => return Err(format!("Expected 'Whole' or 'Decimal' after 'try to read ... as' on line {}", line)),
+        };
+        self.advance();
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable(name.to_string())),
+            args: vec![text],
+        })
+    }
+
+    // `round x`, then `down`, `up` or `to even` (halves go to the even
+    // neighbour), then `to N decimal places`; each way calls its own
+    // built-in Task
+    fn round(&mut self) -> Result<Node, String> {
+        self.advance();
+        let mut args = vec![self.unary()?];
+        let name = if self.at_words(&["down"]) || self.at_words(&["up"]) {
+            let name = if self.at_words(&["down"]) { "round_down" } else { "round_up" };
+            self.advance();
+            name
+        } else if self.check(&TokenType::To) && matches!(self.peek_next_type(), TokenType::Identifier(word) if word == "even") {
+            self.current += 2;
+            "round_even"
+        } else {
+            "round"
+        };
+        if self.check(&TokenType::To) {
+            self.advance();
+            args.push(self.unary()?);
+            if !self.at_words(&["decimal", "places"]) && !self.at_words(&["decimal", "place"]) {
+                return Err(format!("Expected 'decimal places' after the number to round to on line {}", self.peek().line));
+            }
+            self.current += 2;
+        }
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable(name.to_string())),
+            args,
+        })
+    }
+
+    // `evaluate code`, or `evaluate code in isolation` to run it apart from
+    // this program's variables, Tasks and Objects
+    fn evaluate(&mut self) -> Result<Node, String> {
+        self.advance();
+        let code = self.unary()?;
+        let isolated = self.at_words(&["in", "isolation"]);
+        if isolated {
+            self.current += 2;
+        }
+        Ok(Node::Call {
+            callee: Box::new(Node::Variable("evaluate".to_string())),
+            args: vec![code, Node::Literal(Value::Boolean(isolated))],
+        })
+    }
+
+    // `a copy of` and `a list of` start an expression rather than naming a variable `a`
+    fn at_copy(&self) -> bool {
+        self.at_words(&["a", "copy"]) && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Of))
+    }
+
+    // `the type of x` names x's type rather than reading a property
+    fn at_type_of(&self) -> bool {
+        self.at_words(&["the", "type"]) && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Of))
+    }
+
+    // `the length of x` counts x's items rather than reading a property
+    fn at_length_of(&self) -> bool {
+        self.at_words(&["the", "length"]) && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Of))
+            && !matches!(self.tokens.get(self.current + 3).map(|token| &token.token_type), Some(TokenType::Each))
+    }
+
+    // `the name of x` reads a property, unless it is `the ... of each`
+    fn at_property_of(&self) -> bool {
+        let token = |offset: usize| self.tokens.get(self.current + offset).map(|token| &token.token_type);
+        self.at_words(&["the"]) && matches!(token(1), Some(TokenType::Identifier(_)))
+            && matches!(token(2), Some(TokenType::Of)) && !matches!(token(3), Some(TokenType::Each))
+    }
+
+    fn at_list_of(&self) -> bool {
+        self.at_words(&["a", "list"]) && matches!(self.tokens.get(self.current + 2).map(|token| &token.token_type), Some(TokenType::Of))
+    }
+
+    fn at_slice(&self) -> bool {
+        self.at_words(&["everything", "after"]) || self.at_words(&["everything", "before"])
+            || (self.at_words(&["items"]) && Self::starts_operand(self.peek_next_type()))
+    }
+
+    // The plain words from a token on, up to the first that isn't one
+    fn words_at(&self, start: usize) -> Vec<&str> {
+        self.tokens.get(start..).unwrap_or_default().iter()
+            .map_while(|token| match &token.token_type {
+                TokenType::Identifier(word) => Some(word.as_str()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    // Whether the next tokens are these plain words
+    fn at_words(&self, words: &[&str]) -> bool {
+        words.iter().enumerate().all(|(offset, expected)| matches!(
+            self.tokens.get(self.current + offset).map(|token| &token.token_type),
+            Some(TokenType::Identifier(word)) if word == expected
+        ))
+    }
+
+    // Whether a token can follow the last word of a statement
+    fn ends_statement(token: &TokenType) -> bool {
+        matches!(token, TokenType::NewLine | TokenType::Dedent | TokenType::Eof)
+    }
+
+    // Whether a token can start the value after a word like `freeze`
+    fn starts_operand(token: &TokenType) -> bool {
+        matches!(token, TokenType::Identifier(_) | TokenType::Number(_) | TokenType::String(_) | TokenType::OpenParen
+            | TokenType::OpenBracket | TokenType::My | TokenType::New)
+    }
+
     fn is_at_end(&self) -> bool {
-        matches!(self.peek().token_type, TokenType::EOF)
+        matches!(self.peek().token_type, TokenType::Eof)
     }
 
     fn advance(&mut self) -> &Token {
@@ -873,7 +2540,7 @@ impl Parser {
     }
 
     fn previous(&self) -> &Token {
-        &self.tokens[self.current - 1]
+        &self.tokens[self.current.saturating_sub(1)]
     }
 
     fn check(&self, token_type: &TokenType) -> bool {
@@ -883,6 +2550,15 @@ impl Parser {
         &self.peek().token_type == token_type
     }
 
+    // Looks past blank lines without consuming them
+    fn check_after_newlines(&self, token_type: &TokenType) -> bool {
+        let mut index = self.current;
+        while let Some(Token { token_type: TokenType::NewLine, .. }) = self.tokens.get(index) {
+            index += 1;
+        }
+        matches!(self.tokens.get(index), Some(token) if &token.token_type == token_type)
+    }
+
     fn match_token(&mut self, types: &[TokenType]) -> bool {
         for t in types {
             if self.check(t) {
@@ -897,7 +2573,7 @@ impl Parser {
         if self.check(token_type) {
             Ok(self.advance())
         } else {
-            Err(message.to_string())
+            Err(format!("{} (line {})", message, self.peek().line))
         }
     }
 
@@ -906,211 +2582,44 @@ impl Parser {
             let name = name.clone();
             self.advance();
             Ok(name)
+        } else if Self::number_word(self.peek()) {
+            Ok(self.advance().literal.clone())
         } else {
-            Err(message.to_string())
+            Err(format!("{} (line {})", message, self.peek().line))
         }
     }
 
-    fn statement(&mut self) -> Result<Node, String> {
-        match self.peek().token_type {
-            TokenType::Show => {
-                self.advance(); // Consume 'show'
-                match &self.peek().token_type {
-                    TokenType::Identifier(_) => {
-                        let name = self.consume_identifier("Expected variable name after 'show'")?;
-                        Ok(Node::ShowStmt(Box::new(Node::Variable(name))))
-                    },
-                    TokenType::String(_) => {
-                        let expr = self.string_literal()?;
-                        Ok(Node::ShowStmt(Box::new(expr)))
-                    },
-                    TokenType::Number(_) => {
-                        if let TokenType::Number(n) = self.advance().token_type {
-                            Ok(Node::ShowStmt(Box::new(Node::Literal(Value::Number(n)))))
-                        } else {
-                            Err("Expected number".to_string())
-                        }
-                    },
-                    TokenType::Boolean(_) => {
-                        let expr = self.boolean_literal()?;
-                        Ok(Node::ShowStmt(Box::new(expr)))
-                    },
-                    TokenType::Null | TokenType::TypeMapping => {
-                        Ok(Node::ShowStmt(Box::new(Node::Literal(Value::Null))))
-                    },
-                    TokenType::TypePromise => {
-                        let expr = self.promise_literal()?;
-                        Ok(Node::ShowStmt(Box::new(expr)))
-                    },
-                    TokenType::TypeList => {
-                        let expr = self.list_literal()?;
-                        Ok(Node::ShowStmt(Box::new(expr)))
-                    },
-                    _ => Err("Expected variable name, string, or number after 'show'".to_string()),
-                }
-            },
-            TokenType::Raise => {
-                self.advance();
-                self.raise_statement()
-            },
-            TokenType::Returns => {
-                self.advance();
-                self.return_statement()
-            },
-            TokenType::Requires => {
-                self.advance(); // Consume 'requires'
-                self.declaration()
-            },
-            TokenType::Returning => {
-                self.advance(); // Consume 'returning'
-                self.declaration()
-            },
-            TokenType::Emit => {
-                self.advance(); // Consume 'emit'
-                self.declaration()
-            },
-            TokenType::Using => {
-                self.advance(); // Consume 'using'
-                self.declaration()
-            },
-            TokenType::With => {
-                self.advance(); // Consume 'with'
-                self.declaration()
-            },
-            TokenType::As => {
-                self.advance(); // Consume 'as'
-                self.declaration()
-            },
-            TokenType::Is => {
-                self.advance(); // Consume 'is'
-                self.declaration()
-            },
-            TokenType::To => {
-                self.advance(); // Consume 'to'
-                self.declaration()
-            },
-            TokenType::Of => {
-                self.advance(); // Consume 'of'
-                self.declaration()
-            },
-            TokenType::At => {
-                self.advance(); // Consume 'at'
-                self.declaration()
-            },
-            TokenType::And => {
-                self.advance(); // Consume 'and'
-                self.declaration()
-            },
-            TokenType::Each => {
-                self.advance(); // Consume 'each'
-                self.declaration()
-            },
-            TokenType::Becomes => {
-                self.advance(); // Consume 'becomes'
-                self.declaration()
-            },
-            TokenType::My => {
-                self.advance(); // Consume 'my'
-                self.declaration()
-            },
-            TokenType::About => {
-                self.advance(); // Consume 'about'
-                self.declaration()
-            },
-            TokenType::Me => {
-                self.advance(); // Consume 'me'
-                self.declaration()
-            },
-            TokenType::Loop => {
-                self.advance(); // Consume 'loop'
-                self.loop_statement()
-            },
-            TokenType::While => {
-                self.advance(); // Consume 'while'
-                self.loop_statement()
-            },
-            TokenType::Emit => {
-                self.advance(); // Consume 'Emit'
-                self.declaration()
-            },
-            TokenType::Match => {
-                self.advance(); // Consume 'match'
-                self.declaration()
-            },
-            TokenType::Output => {
-                self.advance(); // Consume 'output'
-                self.declaration()
-            },
-            _ => self.expression_statement(),
+    // A number token's digits, for one spelled out in words as well
+    fn digits(token: &Token) -> String {
+        match token.token_type {
+            TokenType::Number(number) if !token.literal.starts_with(|c: char| c.is_ascii_digit()) => number.to_string(),
+            _ => token.literal.clone(),
         }
     }
 
-    fn previous_token_type(&mut self) -> TokenType {
-        self.previous().token_type.clone()
+    // Whether a token is a one-word number like `seven`, which is still a
+    // name where only a name can go: `Task seven:`, `seven()`, `seven is 7`
+    fn number_word(token: &Token) -> bool {
+        matches!(token.token_type, TokenType::Number(_)) && token.literal.chars().all(char::is_alphabetic)
     }
 
-    fn mapping_initializer(&mut self) -> Result<Node, String> {
-        let mut entries = Vec::new();
-        
-        loop {
-            // Parse parameter name
-            let param_name = self.consume_identifier("Expected parameter name")?;
-            
-            // Handle both explicit and implicit type declarations
-            let (param_type, value) = if self.match_token(&[TokenType::As]) {
-                // Explicit type: param as Type is value
-                let param_type = self.type_annotation()?;
-                self.consume(&TokenType::Is, "Expected 'is' after type")?;
-                let value = self.expression()?;
-                (Some(param_type), value)
-            } else if self.match_token(&[TokenType::Is]) {
-                // Implicit type: param is value
-                let value = self.expression()?;
-                (None, value)
-            } else {
-                return Err("Expected 'as' or 'is' after parameter name".to_string());
-            };
-            
-            entries.push((param_name, param_type, value));
-            
-            if !self.match_token(&[TokenType::Comma]) {
-                break;
-            }
-            
-            // Skip any newlines after comma
-            while self.peek().token_type == TokenType::NewLine {
-                self.advance();
-            }
+    fn skip_newlines(&mut self) {
+        while self.check(&TokenType::NewLine) {
+            self.advance();
         }
-        
-        Ok(Node::MappingLiteral { entries })
     }
 
-    fn type_from_annotation(&mut self, type_node: &Node) -> Result<Type, String> {
-        match type_node {
-            Node::MappingType { key_type, value_type } => {
-                let key = self.type_from_annotation(key_type)?;
-                let value = self.type_from_annotation(value_type)?;
-                Ok(Type::Map {
-                    key: Box::new(key),
-                    value: Box::new(value),
-                })
-            },
-            Node::TypeAnnotation(type_name) => {
-                match type_name.as_str() {
-                    "Whole" => Ok(Type::Whole),
-                    "Decimal" => Ok(Type::Decimal),
-                    "Text" => Ok(Type::Text),
-                    "Truth" => Ok(Type::Truth),
-                    "Nothing" => Ok(Type::Nothing),
-                    "Any" => Ok(Type::Any),
-                    "Promise" => Ok(Type::Promise(Box::new(Type::Any))),
-                    "List" => Ok(Type::List(Box::new(Type::Any))),
-                    "Mapping" => Ok(Type::Map { key: Box::new(Type::Text), value: Box::new(Type::Any) }),
-                    _ => Err(format!("Unknown type: {}", type_name)),
-                }
-            },
-            _ => Err("Invalid type annotation".to_string()),
+    // A simple statement must be followed by a line break (or the end of a block)
+    fn end_of_statement(&mut self) -> Result<(), String> {
+        if self.match_token(&[TokenType::NewLine]) || self.check(&TokenType::Dedent) || self.is_at_end() {
+            Ok(())
+        } else {
+            let token = self.peek();
+            Err(format!("Unexpected '{}' on line {}", token.literal, token.line))
         }
     }
+
+    fn previous_token_type(&mut self) -> TokenType {
+        self.previous().token_type.clone()
+    }
 }
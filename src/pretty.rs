@@ -0,0 +1,175 @@
+use crate::generator::Value;
+use std::sync::Arc;
+
+// Columns a value may take on one line before `show` spreads it over several
+const WIDTH: usize = 80;
+
+// How many Lists, Mappings and Objects deep values are written before `...`
+// stands in for the rest
+const MAX_DEPTH: usize = 6;
+
+/// What `show` writes for a value. A List or Mapping that fits on a line is
+/// written on it, as `[1, 2, 3]`; a longer one puts each item on a line of
+/// its own, indented under the bracket that opens it. Past six levels deep,
+/// and where a List or Mapping holds itself, `[...]` or `{...}` stands in.
+/// `paint` is given each other value and its text, to color it.
+pub fn show(value: &Value, paint: &dyn Fn(&Value, &str) -> String) -> String {
+    Printer { paint, open: Vec::new() }.spread(value, false, 0, 0)
+}
+
+/// A value on one line, as `Display` writes it.
+pub fn flat(value: &Value) -> String {
+    Printer { paint: &plain, open: Vec::new() }.flat(value, false, 0)
+}
+
+/// What `describe` writes: the value's type and, for a List, Mapping or
+/// Object, each item, entry or field on a line of its own with its type,
+/// however short.
+pub fn describe(value: &Value) -> String {
+    let mut lines = Vec::new();
+    Printer { paint: &plain, open: Vec::new() }.describe(value, String::new(), 0, &mut lines);
+    lines.join("\n")
+}
+
+fn plain(_: &Value, text: &str) -> String {
+    text.to_string()
+}
+
+struct Printer<'a> {
+    paint: &'a dyn Fn(&Value, &str) -> String,
+    // The Lists, Mappings and Objects being written, to notice one inside itself
+    open: Vec<usize>,
+}
+
+// Where a List, Mapping or Object is held, which is the same for every
+// handle to it
+fn identity(value: &Value) -> Option<usize> {
+    match value {
+        Value::List(items) => Some(Arc::as_ptr(items) as usize),
+        Value::Mapping(mapping) => Some(Arc::as_ptr(mapping) as usize),
+        Value::Object(instance) => Some(Arc::as_ptr(instance) as usize),
+        _ => None,
+    }
+}
+
+impl Printer<'_> {
+    // What stands in for a List or Mapping too deep to write, or inside
+    // itself
+    fn elided(&self, value: &Value, depth: usize) -> Option<&'static str> {
+        let cut = depth >= MAX_DEPTH || identity(value).is_some_and(|id| self.open.contains(&id));
+        match value {
+            Value::List(_) if cut => Some("[...]"),
+            Value::Mapping(_) if cut => Some("{...}"),
+            _ => None,
+        }
+    }
+
+    // Each item of a List, or key and value of a Mapping
+    fn entries(value: &Value) -> Vec<(Option<Value>, Value)> {
+        match value {
+            Value::List(items) => items.borrow().iter().map(|item| (None, item.clone())).collect(),
+            Value::Mapping(mapping) => mapping.borrow().iter().map(|(key, value)| (Some(key.clone()), value.clone())).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    fn flat(&mut self, value: &Value, nested: bool, depth: usize) -> String {
+        if let Some(elided) = self.elided(value, depth) {
+            return elided.to_string();
+        }
+        let (open, close) = match value {
+            Value::List(_) => ("[", "]"),
+            Value::Mapping(_) => ("{", "}"),
+            Value::String(_) if nested => return (self.paint)(value, &value.nested()),
+            other => return (self.paint)(other, &other.to_string()),
+        };
+        self.open.extend(identity(value));
+        let items: Vec<String> = Self::entries(value).iter()
+            .map(|(key, item)| match key {
+                Some(key) => format!("{}: {}", self.flat(key, true, depth + 1), self.flat(item, true, depth + 1)),
+                None => self.flat(item, true, depth + 1),
+            })
+            .collect();
+        self.open.pop();
+        format!("{}{}{}", open, items.join(", "), close)
+    }
+
+    // A value starting `column` characters along a line, `depth` levels in,
+    // whose lines are indented two spaces a level
+    fn spread(&mut self, value: &Value, nested: bool, column: usize, depth: usize) -> String {
+        let width = Printer { paint: &plain, open: self.open.clone() }.flat(value, nested, depth).chars().count();
+        let (open, close) = match value {
+            _ if column + width <= WIDTH || self.elided(value, depth).is_some() => return self.flat(value, nested, depth),
+            Value::List(_) => ("[", "]"),
+            Value::Mapping(_) => ("{", "}"),
+            _ => return self.flat(value, nested, depth),
+        };
+        self.open.extend(identity(value));
+        let indent = "  ".repeat(depth + 1);
+        let items: Vec<String> = Self::entries(value).iter()
+            .map(|(key, item)| match key {
+                Some(key) => {
+                    let key = self.flat(key, true, depth + 1);
+                    let column = indent.len() + key.chars().count() + 2;
+                    format!("{}{}: {}", indent, key, self.spread(item, true, column, depth + 1))
+                },
+                None => format!("{}{}", indent, self.spread(item, true, indent.len(), depth + 1)),
+            })
+            .collect();
+        self.open.pop();
+        format!("{}\n{}\n{}{}", open, items.join(",\n"), "  ".repeat(depth), close)
+    }
+
+    // Adds a line for the value, after `label`, and lines for what it holds
+    fn describe(&mut self, value: &Value, label: String, depth: usize, lines: &mut Vec<String>) {
+        let indent = "  ".repeat(depth);
+        let frozen = match value {
+            Value::List(items) => items.frozen,
+            Value::Mapping(mapping) => mapping.frozen,
+            Value::Object(instance) => instance.frozen,
+            _ => false,
+        };
+        let kind = format!("{}{}", if frozen { "frozen " } else { "" }, value.type_name());
+        let count = |count: usize, one: &str, many: &str| match count {
+            0 => format!("{}, empty", kind),
+            1 => format!("{}, 1 {}", kind, one),
+            _ => format!("{}, {} {}", kind, count, many),
+        };
+        let (heading, parts): (String, Vec<(String, Value)>) = match value {
+            Value::List(items) => {
+                let items = items.borrow();
+                (count(items.len(), "item", "items"), items.iter().enumerate().map(|(at, item)| ((at + 1).to_string(), item.clone())).collect())
+            },
+            Value::Mapping(mapping) => {
+                let mapping = mapping.borrow();
+                (count(mapping.len(), "entry", "entries"), mapping.iter().map(|(key, value)| (key.nested(), value.clone())).collect())
+            },
+            Value::Object(instance) => {
+                let fields = instance.fields.borrow();
+                let mut names: Vec<&String> = fields.keys().collect();
+                names.sort();
+                (count(names.len(), "field", "fields"), names.into_iter().map(|name| (name.clone(), fields[name].clone())).collect())
+            },
+            Value::String(_) => (format!("{} {}", kind, value.nested()), Vec::new()),
+            other => (format!("{} {}", kind, other), Vec::new()),
+        };
+        let label = if label.is_empty() { label } else { format!("{}: ", label) };
+        let id = identity(value);
+        if id.is_some_and(|id| self.open.contains(&id)) {
+            lines.push(format!("{}{}{}, the one it is inside", indent, label, kind));
+            return;
+        }
+        if depth >= MAX_DEPTH && !parts.is_empty() {
+            lines.push(format!("{}{}{} ...", indent, label, heading));
+            return;
+        }
+        lines.push(format!("{}{}{}", indent, label, heading));
+        if let Some(id) = id {
+            self.open.push(id);
+            for (name, part) in parts {
+                self.describe(&part, name, depth + 1, lines);
+            }
+            self.open.pop();
+        }
+    }
+}
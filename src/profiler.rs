@@ -0,0 +1,169 @@
+use crate::generator::OpCode;
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem::Discriminant;
+use std::sync::Arc;
+use std::time::Duration;
+
+// How many entries each section of the report lists
+const REPORT_ROWS: usize = 10;
+
+/// Counts what a program spends its instructions and time on: each opcode,
+/// each Task, each source line, and each chain of calls (for flamegraphs).
+pub struct Profiler {
+    show_times: bool,
+    instructions: u64,
+    elapsed: Duration,
+    opcodes: HashMap<Discriminant<OpCode>, (String, u64)>,
+    tasks: HashMap<String, TaskStats>,
+    lines: HashMap<(Arc<str>, usize), (u64, Duration)>,
+    // Calls in progress as "outer;inner", with where each name starts
+    stack: String,
+    cuts: Vec<usize>,
+    stacks: HashMap<String, Duration>,
+}
+
+#[derive(Default)]
+struct TaskStats {
+    calls: u64,
+    instructions: u64,
+    own_time: Duration,
+}
+
+impl Profiler {
+    /// A profiler whose report leaves out timings when `show_times` is off,
+    /// so it reads the same on every run.
+    pub fn new(show_times: bool) -> Self {
+        Profiler {
+            show_times,
+            instructions: 0,
+            elapsed: Duration::ZERO,
+            opcodes: HashMap::new(),
+            tasks: HashMap::new(),
+            lines: HashMap::new(),
+            stack: String::new(),
+            cuts: Vec::new(),
+            stacks: HashMap::new(),
+        }
+    }
+
+    /// Brings the profiler's view of the calls in progress up to date. A
+    /// single instruction only ever starts calls or only ever ends them, so
+    /// comparing depths is enough.
+    pub fn enter<'a>(&mut self, depth: usize, task_at: impl Fn(usize) -> &'a str) {
+        while self.cuts.len() > depth {
+            if let Some(cut) = self.cuts.pop() {
+                self.stack.truncate(cut.saturating_sub(1));
+            }
+        }
+        while self.cuts.len() < depth {
+            let name = task_at(self.cuts.len());
+            if !self.cuts.is_empty() {
+                self.stack.push(';');
+            }
+            self.cuts.push(self.stack.len());
+            self.stack.push_str(name);
+            self.tasks.entry(name.to_string()).or_default().calls += 1;
+        }
+    }
+
+    /// Charges one instruction of the innermost call.
+    pub fn record(&mut self, opcode: &OpCode, line: Option<(Arc<str>, usize)>, elapsed: Duration) {
+        self.instructions += 1;
+        self.elapsed += elapsed;
+
+        let entry = self.opcodes.entry(std::mem::discriminant(opcode))
+            .or_insert_with(|| (opcode_name(opcode), 0));
+        entry.1 += 1;
+
+        let task = self.cuts.last().map(|&cut| &self.stack[cut..]).unwrap_or("");
+        if let Some(stats) = self.tasks.get_mut(task) {
+            stats.instructions += 1;
+            stats.own_time += elapsed;
+        }
+        if let Some(line) = line {
+            let entry = self.lines.entry(line).or_default();
+            entry.0 += 1;
+            entry.1 += elapsed;
+        }
+        match self.stacks.get_mut(&self.stack) {
+            Some(total) => *total += elapsed,
+            None => {
+                self.stacks.insert(self.stack.clone(), elapsed);
+            },
+        }
+    }
+
+    /// Call chains with the nanoseconds spent in each, one per line, as
+    /// flamegraph tools expect.
+    pub fn folded_stacks(&self) -> String {
+        let mut stacks: Vec<_> = self.stacks.iter().collect();
+        stacks.sort();
+        stacks.iter().map(|(stack, time)| format!("{} {}\n", stack, time.as_nanos())).collect()
+    }
+
+    /// A summary of the hottest Tasks, lines and opcodes. `line_text` gives
+    /// the source of a line, when it is known.
+    pub fn report(&self, line_text: impl Fn(&str, usize) -> Option<String>) -> String {
+        let mut report = String::new();
+        let _ = write!(report, "Profile: {} instructions", self.instructions);
+        if self.show_times {
+            let _ = write!(report, " in {}", millis(self.elapsed));
+        }
+        report.push('\n');
+
+        // A Task's total includes the Tasks it calls, counted once however deeply it recurses
+        let mut totals: HashMap<&str, Duration> = HashMap::new();
+        for (stack, time) in &self.stacks {
+            let mut seen: Vec<&str> = Vec::new();
+            for task in stack.split(';').filter(|task| !task.is_empty()) {
+                if !seen.contains(&task) {
+                    seen.push(task);
+                    *totals.entry(task).or_default() += *time;
+                }
+            }
+        }
+        let mut tasks: Vec<_> = self.tasks.iter().collect();
+        tasks.sort_by(|(a_name, a), (b_name, b)| b.instructions.cmp(&a.instructions).then(a_name.cmp(b_name)));
+        report.push_str("\nTasks\n");
+        let _ = writeln!(report, "  {:>8}  {:>12}{}  task", "calls", "instructions",
+            if self.show_times { format!("  {:>10}  {:>10}", "total", "self") } else { String::new() });
+        for (name, stats) in tasks.iter().take(REPORT_ROWS) {
+            let times = match self.show_times {
+                true => format!("  {:>10}  {:>10}", millis(totals.get(name.as_str()).copied().unwrap_or_default()), millis(stats.own_time)),
+                false => String::new(),
+            };
+            let _ = writeln!(report, "  {:>8}  {:>12}{}  {}", stats.calls, stats.instructions, times, name);
+        }
+
+        let mut lines: Vec<_> = self.lines.iter().collect();
+        lines.sort_by(|(a_line, a), (b_line, b)| b.0.cmp(&a.0).then(a_line.cmp(b_line)));
+        report.push_str("\nHottest lines\n");
+        for ((source, line), (count, time)) in lines.iter().take(REPORT_ROWS) {
+            let time = if self.show_times { format!("  {:>10}", millis(*time)) } else { String::new() };
+            let text = line_text(source, *line).map(|text| format!("  | {}", text)).unwrap_or_default();
+            let _ = writeln!(report, "  {:>12}{}  {}:{}{}", count, time, source, line, text);
+        }
+
+        let mut opcodes: Vec<_> = self.opcodes.values().collect();
+        opcodes.sort_by(|(a_name, a), (b_name, b)| b.cmp(a).then(a_name.cmp(b_name)));
+        report.push_str("\nOpcodes\n");
+        for (name, count) in opcodes.iter().take(REPORT_ROWS) {
+            let _ = writeln!(report, "  {:>12}  {}", count, name);
+        }
+        report
+    }
+}
+
+// `Push(Number(1.0))` is counted as `Push`
+fn opcode_name(opcode: &OpCode) -> String {
+    let name = format!("{:?}", opcode);
+    match name.find('(') {
+        Some(end) => name[..end].to_string(),
+        None => name,
+    }
+}
+
+fn millis(duration: Duration) -> String {
+    format!("{:.3} ms", duration.as_secs_f64() * 1000.0)
+}
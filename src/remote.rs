@@ -0,0 +1,103 @@
+use crate::config::Config;
+use crate::theme::Theme;
+use crate::generator::Lock;
+use crate::runtime::{ReplSession, Runtime, REPL_BANNER};
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex, PoisonError};
+
+const USAGE: &str = "Usage: nair repl [--listen address [--token token]]";
+
+/// Runs the REPL, at the terminal or, with `--listen`, over TCP for editors
+/// and web pages to share. A client is sent the banner and a prompt, sends
+/// lines as they would be typed, and gets back what the REPL says followed
+/// by the next prompt. Every client works in the one Runtime, taking turns
+/// a line at a time, each with its own unfinished input and checkpoint.
+/// With `--token`, the first line a client sends must be the token.
+pub fn run(runtime: &mut Runtime, args: &[String]) -> Result<(), String> {
+    let mut address = None;
+    let mut token = None;
+    let mut rest = args.iter();
+    while let Some(flag) = rest.next() {
+        match (flag.as_str(), rest.next()) {
+            ("--listen", Some(value)) => address = Some(value.clone()),
+            ("--token", Some(value)) => token = Some(value.clone()),
+            _ => return Err(USAGE.to_string()),
+        }
+    }
+    match (address, token) {
+        (None, None) => runtime.run_repl(Config::load(runtime.theme())),
+        (None, Some(_)) => Err(USAGE.to_string()),
+        (Some(address), token) => serve(runtime, &address, token),
+    }
+}
+
+fn serve(runtime: &mut Runtime, address: &str, token: Option<String>) -> Result<(), String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    let bound = listener.local_addr().map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    eprintln!("Serving the REPL on {} (Ctrl-C stops)", bound);
+
+    let said = Arc::new(Lock::new(Vec::new()));
+    runtime.set_output(Box::new(Said(Arc::clone(&said))));
+    // Clients may not be terminals, so what they are sent stays plain
+    runtime.set_theme(Theme::default());
+    // Clients send code, not answers to `ask`
+    runtime.set_input(Box::new(io::empty()));
+    // Clients take turns with the Runtime, a line at a time
+    let shared = Mutex::new(runtime);
+    std::thread::scope(|scope| {
+        for stream in listener.incoming().flatten() {
+            let (shared, said, token) = (&shared, &said, token.as_deref());
+            scope.spawn(move || {
+                // A client that went away mid-line needs nothing more
+                let _ = client(stream, token, shared, said);
+            });
+        }
+    });
+    Ok(())
+}
+
+// Runs one client's lines in the shared Runtime and sends back what it says
+fn client(stream: TcpStream, token: Option<&str>, shared: &Mutex<&mut Runtime>, said: &Lock<Vec<u8>>) -> io::Result<()> {
+    let mut writer = stream.try_clone()?;
+    let mut lines = BufReader::new(stream).lines();
+    if let Some(token) = token {
+        writer.write_all(b"Token: ")?;
+        if lines.next().transpose()?.as_deref().map(str::trim_end) != Some(token) {
+            return writer.write_all(b"Error: wrong token\n");
+        }
+    }
+    writer.write_all(format!("{}\n> ", REPL_BANNER).as_bytes())?;
+    let mut session = ReplSession::remote();
+    for line in lines {
+        let line = line?;
+        let (mut reply, open) = {
+            let mut runtime = shared.lock().unwrap_or_else(PoisonError::into_inner);
+            let open = runtime.repl_line(line.trim_end(), &mut session).unwrap_or_else(|error| {
+                said.borrow_mut().extend_from_slice(format!("Error: {}\n", error).as_bytes());
+                true
+            });
+            (String::from_utf8_lossy(&std::mem::take(&mut *said.borrow_mut())).into_owned(), open)
+        };
+        if !open {
+            return writer.write_all(reply.as_bytes());
+        }
+        reply.push_str(session.prompt());
+        writer.write_all(reply.as_bytes())?;
+    }
+    Ok(())
+}
+
+// Keeps what the REPL and the programs it runs say, to send to the client
+struct Said(Arc<Lock<Vec<u8>>>);
+
+impl Write for Said {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,603 @@
+// Vernacular values in JavaScript. Numbers, Text, Truth and Nothing are plain
+// numbers, strings, booleans and null; Lists are arrays. Objects extend
+// V.Object, and errors are Objects that extend V.Error.
+const V = (() => {
+    "use strict";
+
+    class Mapping {
+        constructor(entries) {
+            this.entries = new Map(entries);
+        }
+    }
+
+    // Field defaults run for the kind each Object inherits from first, and a
+    // kind's behaviors before the kind itself; then the nearest `build` runs
+    class VObject {
+        constructor(...args) {
+            for (const kind of lineage(this.constructor).reverse()) {
+                if (Object.hasOwn(kind.prototype, "$fields")) {
+                    kind.prototype.$fields.call(this);
+                }
+            }
+            if (typeof this.build === "function") {
+                this.build(...args);
+            } else if (args.length > 0) {
+                throw error(ArgumentError, `${name(this.constructor)} has no 'build' block, so it takes no arguments`);
+            }
+        }
+    }
+
+    class VError extends VObject {
+        $fields() {
+            this.message = "";
+        }
+    }
+
+    class TypeError extends VError {}
+    class NameError extends VError {}
+    class PropertyError extends VError {}
+    class ArgumentError extends VError {}
+    class RecursionError extends VError {}
+    class IndexError extends VError {}
+    class KeyError extends VError {}
+    class FrozenError extends VError {}
+    class SandboxViolation extends VError {}
+    class ExpectationFailed extends VError {}
+    class ArithmeticError extends VError {}
+    class InputError extends VError {}
+    class StackError extends VError {}
+    VError.$name = "Error";
+
+    // A kind, then its behaviors, then the same for each kind it inherits from
+    function lineage(kind) {
+        const kinds = [];
+        for (; kind && kind !== VObject; kind = Object.getPrototypeOf(kind)) {
+            kinds.push(kind, ...(Object.hasOwn(kind, "$behaviors") ? kind.$behaviors : []));
+        }
+        return kinds;
+    }
+
+    // Gives a kind the members of its behaviors that it doesn't declare itself
+    function behave(kind, ...behaviors) {
+        kind.$behaviors = behaviors;
+        for (const behavior of behaviors) {
+            for (const [member, descriptor] of Object.entries(Object.getOwnPropertyDescriptors(behavior.prototype))) {
+                if (!["constructor", "$fields", "build"].includes(member) && !Object.hasOwn(kind.prototype, member)) {
+                    Object.defineProperty(kind.prototype, member, descriptor);
+                }
+            }
+        }
+    }
+
+    function name(kind) {
+        return Object.hasOwn(kind, "$name") ? kind.$name : kind.name;
+    }
+
+    function isKind(value) {
+        return typeof value === "function" && value.prototype instanceof VObject;
+    }
+
+    function number(n) {
+        if (Number.isNaN(n)) return "NaN";
+        if (!Number.isFinite(n)) return n > 0 ? "inf" : "-inf";
+        if (Object.is(n, -0)) return "-0";
+        const text = String(n);
+        const exponent = text.indexOf("e");
+        if (exponent < 0) return text;
+        // Written out in full, without an exponent
+        const sign = n < 0 ? "-" : "";
+        const digits = text.slice(sign.length, exponent).replace(".", "");
+        const point = text.slice(sign.length, exponent).split(".")[0].length + Number(text.slice(exponent + 1));
+        if (point <= 0) return `${sign}0.${"0".repeat(-point)}${digits}`;
+        return `${sign}${digits.padEnd(point, "0")}`;
+    }
+
+    function text(value) {
+        if (value === null) return "null";
+        if (typeof value === "number") return number(value);
+        if (typeof value === "string") return value;
+        if (typeof value === "boolean") return String(value);
+        if (Array.isArray(value)) return `[${value.map(nested).join(", ")}]`;
+        if (value instanceof Mapping) {
+            return `{${[...value.entries].map(([key, item]) => `${nested(key)}: ${nested(item)}`).join(", ")}}`;
+        }
+        if (value instanceof VObject) return `[object ${name(value.constructor)}]`;
+        if (isKind(value)) return `[kind ${name(value)}]`;
+        return String(value);
+    }
+
+    // Text inside a collection is quoted
+    function nested(value) {
+        return typeof value === "string" ? `"${value}"` : text(value);
+    }
+
+    function show(value) {
+        console.log(text(value));
+    }
+
+    // Lists compare element-wise, Mappings key-wise, Objects by identity
+    function equal(a, b) {
+        if (typeof a === "number" && typeof b === "number") return a === b || (Number.isNaN(a) && Number.isNaN(b));
+        if (Array.isArray(a) && Array.isArray(b)) return a.length === b.length && a.every((item, i) => equal(item, b[i]));
+        if (a instanceof Mapping && b instanceof Mapping) {
+            return a.entries.size === b.entries.size
+                && [...a.entries].every(([key, item]) => b.entries.has(key) && equal(item, b.entries.get(key)));
+        }
+        return a === b;
+    }
+
+    // `a + b`, `a - b`, `a * b` and `a ^ b` where the operands may not be
+    // numbers: Text joins only Text, and Wholes fail rather than losing
+    // precision past the largest, as the interpreter has them
+    function arithmetic(operator, a, b) {
+        if (typeof a === "string" && operator === "+") {
+            if (typeof b !== "string") throw error(TypeError, "Can only concatenate strings");
+            return a + b;
+        }
+        numbers(a, b);
+        const [x, y] = [whole(a), whole(b)];
+        if (x !== null && y !== null && (operator !== "^" || y >= 0n)) {
+            // A power past 53 of anything but -1, 0 or 1 is too big already
+            const exact = operator === "+" ? x + y : operator === "-" ? x - y : operator === "*" ? x * y
+                : Math.abs(a) <= 1 || b <= 53 ? x ** y : null;
+            if (exact === null || exact < -(2n ** 53n) || exact >= 2n ** 53n) {
+                throw error(ArithmeticError, `${number(a)} ${operator} ${number(b)} does not fit in a Whole`);
+            }
+            return Number(exact);
+        }
+        switch (operator) {
+            case "+": return a + b;
+            case "-": return a - b;
+            case "*": return a * b;
+            default: return a ** b;
+        }
+    }
+
+    // A Whole as a BigInt, or null for a Decimal
+    function whole(n) {
+        return Number.isInteger(n) && n >= -(2 ** 53) && n < 2 ** 53 ? BigInt(n) : null;
+    }
+
+    function numbers(a, b) {
+        if (typeof a !== "number" || typeof b !== "number") {
+            throw error(TypeError, "Invalid operands for arithmetic operation");
+        }
+    }
+
+    // `a < b` and the other orderings, which take two numbers or two Texts
+    function compare(operator, a, b) {
+        const both = (type) => typeof a === type && typeof b === type;
+        if (!both("number") && !both("string")) throw error(TypeError, `Cannot compare ${text(a)} with ${text(b)}`);
+        if (Number.isNaN(a) || Number.isNaN(b)) throw error(ArithmeticError, "Cannot order NaN");
+        switch (operator) {
+            case ">": return a > b;
+            case ">=": return a >= b;
+            case "<": return a < b;
+            default: return a <= b;
+        }
+    }
+
+    // `a / b` and `a % b`, failing instead of giving Infinity or NaN
+    function divide(a, b) {
+        numbers(a, b);
+        if (b === 0) throw error(ArithmeticError, `Cannot divide ${text(a)} by zero`);
+        return a / b;
+    }
+
+    function remainder(a, b) {
+        numbers(a, b);
+        if (b === 0) throw error(ArithmeticError, `Cannot find the remainder of ${text(a)} divided by zero`);
+        return a % b;
+    }
+
+    // `the type of value`: `Whole`, `List of Text`, an Object's kind and so
+    // on; a List's items are named by the type they share, or `Any`
+    function typeOf(value) {
+        const shared = (values) => values.map(typeOf).reduce((a, b) => {
+            if (a === b) return a;
+            return ["Whole", "Decimal"].includes(a) && ["Whole", "Decimal"].includes(b) ? "Decimal" : "Any";
+        });
+        if (value === null) return "Nothing";
+        if (typeof value === "number") return Number.isInteger(value) ? "Whole" : "Decimal";
+        if (typeof value === "string") return "Text";
+        if (typeof value === "boolean") return "Truth";
+        if (Array.isArray(value)) return value.length > 0 ? `List of ${shared(value)}` : "List";
+        if (value instanceof Mapping) {
+            if (value.entries.size === 0) return "Mapping";
+            return `Mapping of ${shared([...value.entries.keys()])} to ${shared([...value.entries.values()])}`;
+        }
+        if (isKind(value)) return "Kind";
+        return name(value.constructor);
+    }
+
+    // `properties_of(object)`: its fields in the order they are declared,
+    // base objects first, then its computed properties
+    function propertiesOf(object) {
+        if (!(object instanceof VObject)) {
+            throw error(TypeError, `Type mismatch: 'properties_of' requires an Object, got ${nested(object)}`);
+        }
+        const fields = Object.keys(object).filter((field) => !field.startsWith("$"));
+        const computed = new Set();
+        for (const kind of lineage(object.constructor)) {
+            for (const [member, descriptor] of Object.entries(Object.getOwnPropertyDescriptors(kind.prototype))) {
+                if (descriptor.get && !fields.includes(member)) computed.add(member);
+            }
+        }
+        return [...fields, ...[...computed].sort()];
+    }
+
+    // `has_task(object, name)`, which also asks a kind about its shared tasks
+    function hasTask(object, member) {
+        if (!(object instanceof VObject) && !isKind(object)) {
+            throw error(TypeError, `Type mismatch: 'has_task' requires an Object, got ${nested(object)}`);
+        }
+        return typeof object[member] === "function" && !Object.hasOwn(Function.prototype, member);
+    }
+
+    // `get_property(object, name)` and `set_property(object, name, value)`,
+    // for a property named by Text worked out as the program runs
+    function getProperty(object, member) {
+        if (object === null || typeof object !== "object" && !isKind(object) || !(member in object)) {
+            throw error(PropertyError, `${typeOf(object)} has no property '${member}'`);
+        }
+        return object[member];
+    }
+
+    function setProperty(object, member, value) {
+        object[member] = value;
+        return null;
+    }
+
+    // The clock `measure how long it takes to` times runs by, in milliseconds
+    function clock() {
+        return performance.now();
+    }
+
+    // What a named measurement keeps: the runs and each time in milliseconds
+    function timing(times) {
+        if (times.length === 0) throw error(VError, "'measure how long it takes to' stopped before its block ran once");
+        const sorted = [...times].sort((a, b) => a - b);
+        const middle = Math.floor(sorted.length / 2);
+        const median = sorted.length % 2 === 0 ? (sorted[middle - 1] + sorted[middle]) / 2 : sorted[middle];
+        const mean = sorted.reduce((sum, time) => sum + time, 0) / sorted.length;
+        return new Mapping([["runs", sorted.length], ["fastest", sorted[0]], ["median", median], ["mean", mean]]);
+    }
+
+    // What an unnamed measurement shows
+    function timingReport(times) {
+        const found = timing(times).entries;
+        const runs = found.get("runs");
+        const ms = (name) => `${found.get(name).toFixed(3)} ms`;
+        return `${runs} run${runs === 1 ? "" : "s"}: fastest ${ms("fastest")}, median ${ms("median")}, mean ${ms("mean")}`;
+    }
+
+    // `round x`, `round x down`, `round x up` or `round x to even`, to a
+    // whole number or to the decimal places given
+    function round(name, value, places = 0) {
+        if (typeof value !== "number" || !Number.isInteger(places) || places < 0) {
+            throw error(TypeError, "Type mismatch: rounding requires a number and a Whole number of decimal places");
+        }
+        const scale = 10 ** places;
+        const scaled = value * scale;
+        const floor = Math.floor(scaled);
+        switch (name) {
+            case "round_down": return floor / scale;
+            case "round_up": return Math.ceil(scaled) / scale;
+            case "round_even": {
+                const even = scaled - floor > 0.5 || (scaled - floor === 0.5 && floor % 2 !== 0);
+                return (even ? floor + 1 : floor) / scale;
+            }
+            default: return Math.sign(scaled) * Math.round(Math.abs(scaled)) / scale;
+        }
+    }
+
+    // `the number in x` and `try to read x as a Whole`: the number the Text
+    // holds, which may have commas between its thousands, or nothing when it
+    // holds anything else
+    function readNumber(text, whole) {
+        if (typeof text !== "string") {
+            throw error(TypeError, `Type mismatch: only Text can be read as a number, got ${nested(text)}`);
+        }
+        const written = text.trim();
+        if (!/^[+-]?(\d{1,3}(,\d{3})+|\d+|(?=\.))(\.\d+)?$/.test(written)) return null;
+        if (whole) {
+            // Read exactly, so a number just past the Wholes isn't rounded into them
+            const [digits, fraction = ""] = written.replaceAll(",", "").split(".");
+            const exact = BigInt(/\d/.test(digits) ? digits : `${digits}0`);
+            return /[1-9]/.test(fraction) || exact < -(2n ** 53n) || exact >= 2n ** 53n ? null : Number(exact);
+        }
+        // `-0` is read as 0, as arithmetic would give it
+        return Number(written.replaceAll(",", "")) + 0;
+    }
+
+    // Text with the differences between cases taken away, uppercasing first
+    // to spell out letters like `ß` that have no single capital
+    function foldCase(text) {
+        return text.toUpperCase().toLowerCase();
+    }
+
+    // `a is the same text as b`, ignoring case if asked, or the opposite
+    // with `is not`
+    function compareText(left, right, ignoringCase, negated) {
+        for (const value of [left, right]) {
+            if (typeof value !== "string") {
+                throw error(TypeError, `Type mismatch: only Text can be compared as text, got ${nested(value)}`);
+            }
+        }
+        const same = ignoringCase ? foldCase(left) === foldCase(right) : left === right;
+        return same !== negated;
+    }
+
+    // `normalize(text)`: the Text without spaces around it or differences
+    // of case
+    function normalize(text) {
+        if (typeof text !== "string") {
+            throw error(TypeError, `Type mismatch: only Text can be normalized, got ${nested(text)}`);
+        }
+        return foldCase(text.trim());
+    }
+
+    // `x written with ...`: the number to the decimal places asked for, or
+    // as it would show, with thousands separated by commas and at least as
+    // many digits before the point as asked for
+    function formatNumber(value, places, commas, digits, percent) {
+        if (typeof value !== "number") {
+            throw error(TypeError, `Type mismatch: only numbers can be written with decimal places or commas, got ${nested(value)}`);
+        }
+        for (const [count, what] of [[places, "decimal places"], [digits, "digits"]]) {
+            if (count !== null && !(Number.isInteger(count) && count >= 0)) {
+                throw error(TypeError, `Type mismatch: expected a Whole number of ${what}, got ${nested(count)}`);
+            }
+        }
+        let written;
+        if (places !== null) {
+            // Halves round away from zero, as `round` has them
+            const scale = 10 ** places;
+            written = (Math.round(Math.abs(value) * (percent ? 100 : 1) * scale) / scale).toFixed(places);
+        } else if (percent) {
+            // Moving the point two places along keeps 0.07 from showing as
+            // 7.000000000000001%
+            const [whole, fraction = ""] = number(Math.abs(value)).split(".");
+            const shifted = fraction.padEnd(2, "0");
+            const before = (whole + shifted.slice(0, 2)).replace(/^0+/, "") || "0";
+            written = shifted.length > 2 ? `${before}.${shifted.slice(2)}` : before;
+        } else {
+            written = number(Math.abs(value));
+        }
+        const point = written.includes(".") ? written.indexOf(".") : written.length;
+        let whole = written.slice(0, point).padStart(digits ?? 0, "0");
+        if (commas) whole = whole.replace(/\B(?=(\d{3})+$)/g, ",");
+        // No minus sign on a number that rounds to zero
+        const sign = value < 0 && /[1-9]/.test(written) ? "-" : "";
+        return `${sign}${whole}${written.slice(point)}${percent ? "%" : ""}`;
+    }
+
+    // `wrapping_add(a, b)` and the like: Whole arithmetic that wraps around
+    // past the largest or smallest Whole instead of failing, as a 54-bit
+    // integer would
+    function wrapping(operator, a, b) {
+        if (!Number.isInteger(a) || !Number.isInteger(b)) {
+            throw error(TypeError, "Type mismatch: wrapping arithmetic requires two Whole numbers");
+        }
+        const [x, y] = [BigInt(a), BigInt(b)];
+        return Number(BigInt.asIntN(54, operator === "+" ? x + y : operator === "-" ? x - y : x * y));
+    }
+
+    // `item key of` a List, Mapping or Text; Lists and Text count from 1
+    function item(object, key) {
+        if (Array.isArray(object)) return object[position(key, object.length, "List")];
+        if (typeof object === "string") {
+            const characters = [...object];
+            return characters[position(key, characters.length, "Text")];
+        }
+        if (object instanceof Mapping && !(key instanceof FromEnd)) {
+            if (!object.entries.has(key)) throw error(KeyError, `No value for ${nested(key)} in the Mapping`);
+            return object.entries.get(key);
+        }
+        throw error(TypeError, `Type mismatch: expected a List, Mapping or Text, got ${text(object)}`);
+    }
+
+    function setItem(object, key, value) {
+        if (Array.isArray(object)) {
+            const at = position(key, object.length, "List");
+            if (Object.isFrozen(object)) throw error(FrozenError, "Cannot change an item: this List is frozen");
+            object[at] = value;
+        } else if (object instanceof Mapping && !(key instanceof FromEnd)) {
+            if (Object.isFrozen(object)) throw error(FrozenError, "Cannot change an item: this Mapping is frozen");
+            object.entries.set(key, value);
+        } else if (typeof object === "string") {
+            throw error(TypeError, "Type mismatch: Text can't be changed; make new Text instead");
+        } else {
+            throw error(TypeError, `Type mismatch: expected a List or Mapping, got ${text(object)}`);
+        }
+    }
+
+    // `items first through last of` a List or Text, or with no last
+    // `everything after item first`, or with no first `everything before item last`
+    function slice(object, first, last) {
+        const whole = Array.isArray(object) ? object : typeof object === "string" ? [...object] : null;
+        if (whole === null) throw error(TypeError, `Type mismatch: expected a List or Text, got ${text(object)}`);
+        const name = Array.isArray(object) ? "List" : "Text";
+        let start = 0, end = whole.length;
+        if (first === null) {
+            end = position(last, whole.length, name);
+        } else if (last === null) {
+            start = position(first, whole.length, name) + 1;
+        } else {
+            [start, end] = [position(first, whole.length, name), position(last, whole.length, name) + 1];
+            if (start >= end) throw error(IndexError, `Item ${number(last)} is out of range: it comes before item ${number(first)}`);
+        }
+        const part = whole.slice(start, end);
+        return Array.isArray(object) ? part : part.join("");
+    }
+
+    // Where a 1-based item number points in a List or Text with `count` items
+    function position(key, count, whole) {
+        if (key instanceof FromEnd) {
+            if (key.number <= count) return count - key.number;
+            const item = key.number === 1 ? "The last item" : `The ${key.number}${suffix(key.number)} to last item`;
+            throw error(IndexError, outOfRange(item, count, whole));
+        }
+        if (!Number.isInteger(key)) throw error(TypeError, `Type mismatch: expected a Whole item number, got ${nested(key)}`);
+        if (key < 1 || key > count) throw error(IndexError, outOfRange(`Item ${number(key)}`, count, whole));
+        return key - 1;
+    }
+
+    function outOfRange(item, count, whole) {
+        const items = whole === "Text" ? "character" : "item";
+        return `${item} is out of range: the ${whole} has ${count} ${items}${count === 1 ? "" : "s"}`;
+    }
+
+    // `the number to last item`, worked out once the List or Text is known
+    class FromEnd {
+        constructor(number) {
+            this.number = number;
+        }
+    }
+
+    function fromEnd(number) {
+        return new FromEnd(number);
+    }
+
+    function suffix(number) {
+        if (number % 100 >= 11 && number % 100 <= 13) return "th";
+        return ["th", "st", "nd", "rd"][number % 10] ?? "th";
+    }
+
+    // A List whose items are joined into another
+    function items(value) {
+        if (Array.isArray(value)) return value;
+        throw error(TypeError, `Type mismatch: expected a List, got ${text(value)}`);
+    }
+
+    // A new List, Mapping or Object with the same items; they aren't copied themselves
+    function copy(value) {
+        if (Array.isArray(value)) return [...value];
+        if (value instanceof Mapping) return new Mapping(value.entries);
+        if (value instanceof VObject) return Object.assign(Object.create(Object.getPrototypeOf(value)), value);
+        return value;
+    }
+
+    // A List, Mapping or Object like this one that can't be changed; its
+    // items aren't frozen in turn
+    function freeze(value) {
+        if (Object.isFrozen(value)) return value;
+        return Object.freeze(copy(value));
+    }
+
+    // What `is the same as` asks: like equal, but Objects of the same kind
+    // match when their fields do. Pairs already being compared are taken to
+    // match so that cycles end.
+    function same(a, b, comparing = []) {
+        if (Array.isArray(a) && Array.isArray(b)) {
+            return a.length === b.length && a.every((item, i) => same(item, b[i], comparing));
+        }
+        if (a instanceof Mapping && b instanceof Mapping) {
+            return a.entries.size === b.entries.size
+                && [...a.entries].every(([key, item]) => b.entries.has(key) && same(item, b.entries.get(key), comparing));
+        }
+        if (a instanceof VObject && b instanceof VObject && a !== b) {
+            if (a.constructor !== b.constructor) return false;
+            if (comparing.some(([x, y]) => x === a && y === b)) return true;
+            const fields = Object.keys(a);
+            if (fields.length !== Object.keys(b).length) return false;
+            comparing.push([a, b]);
+            const result = fields.every(field => Object.hasOwn(b, field) && same(a[field], b[field], comparing));
+            comparing.pop();
+            return result;
+        }
+        return equal(a, b);
+    }
+
+    // Whether a value is of a built-in type
+    function is(value, type) {
+        switch (type) {
+            case "Whole": return Number.isInteger(value);
+            case "Decimal": return typeof value === "number";
+            case "Text": return typeof value === "string";
+            case "Truth": case "Logic": return typeof value === "boolean";
+            case "Nothing": return value === null;
+            case "Object": return value instanceof VObject || isKind(value);
+            case "List": return Array.isArray(value);
+            case "Mapping": return value instanceof Mapping;
+            case "Any": return true;
+            case "Promise": return false;
+            default: throw error(NameError, `Unknown type: ${type}`);
+        }
+    }
+
+    // Whether a value is an Object of a kind, inherits from it or behaves like it
+    function isA(value, kind) {
+        return value instanceof VObject && lineage(value.constructor).includes(kind);
+    }
+
+    function as(value, type) {
+        if (type === "Whole" && typeof value === "number") return Math.floor(value);
+        if (type === "Decimal" && typeof value === "number") return value;
+        if (type === "Text") return text(value);
+        if (type === "Truth" && typeof value === "boolean") return value;
+        throw error(VError, `Cannot cast ${text(value)} to ${type}`);
+    }
+
+    function error(kind, message) {
+        const made = new kind();
+        made.message = message;
+        return made;
+    }
+
+    function fail(kind, message) {
+        throw error(kind, message);
+    }
+
+    // What `raise` throws: an error, or an Error with the given message
+    function raise(value) {
+        if (typeof value === "string") return error(VError, value);
+        if (isA(value, VError)) return value;
+        return error(VError, `Only Text or errors can be raised, not ${text(value)}`);
+    }
+
+    // What a fail clause receives, including JavaScript's own errors
+    function caught(thrown) {
+        if (thrown instanceof VObject) return thrown;
+        if (thrown instanceof globalThis.RangeError) return error(RecursionError, "Too much recursion");
+        if (thrown instanceof globalThis.ReferenceError) {
+            return error(NameError, thrown.message.replace(/^(.*) is not defined$/, "Undefined variable: $1"));
+        }
+        const frozen = thrown instanceof globalThis.TypeError
+            && /^Cannot assign to read only property '(.*)' of object '(?:#<(.*)>|.*)'$/.exec(thrown.message);
+        if (frozen) return error(FrozenError, `Cannot change '${frozen[1]}': this ${frozen[2] || "List"} is frozen`);
+        if (thrown instanceof globalThis.TypeError) return error(PropertyError, thrown.message);
+        return error(VError, String(thrown && thrown.message || thrown));
+    }
+
+    function expect(value, line, source) {
+        if (value === true) return;
+        const message = `expected that ${source} (line ${line}), but it was ${text(value)}`;
+        throw Object.assign(error(ExpectationFailed, message), { actual: value, expected: true, line });
+    }
+
+    function expectEqual(actual, expected, line, source) {
+        if (equal(actual, expected)) return;
+        const message = `expected ${source} to equal ${nested(expected)} (line ${line}), but it was ${nested(actual)}`;
+        throw Object.assign(error(ExpectationFailed, message), { actual, expected, line });
+    }
+
+    // Runs the program's top-level statements, reporting an uncaught error
+    // as the interpreter does
+    function main(program) {
+        try {
+            program();
+        } catch (thrown) {
+            const failure = caught(thrown);
+            const kind = name(failure.constructor);
+            console.error(failure.message ? `${kind}: ${failure.message}` : kind);
+            if (typeof process !== "undefined") process.exitCode = 1;
+        }
+    }
+
+    return {
+        Object: VObject, Error: VError, TypeError, NameError, PropertyError, ArgumentError, RecursionError,
+        IndexError, KeyError, FrozenError, SandboxViolation, ExpectationFailed, ArithmeticError, InputError, StackError, Mapping,
+        behave, text, show, equal, same, arithmetic, compare, divide, remainder, typeOf, propertiesOf, hasTask, getProperty, setProperty, round, clock, timing, timingReport, formatNumber, readNumber, compareText, normalize, wrapping, item, setItem, fromEnd, slice, items, copy, freeze, is, isA, as, error, fail, raise, caught, expect, expectEqual, main,
+    };
+})();
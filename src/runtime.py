@@ -0,0 +1,661 @@
+import math
+
+
+# Vernacular values in Python. Numbers, Text, Truth and Nothing are plain
+# numbers, strs, bools and None; Lists are lists. Objects extend V.Object,
+# and errors are Objects that extend V.Error.
+def _vernacular():
+    import builtins
+    import re
+    import sys
+    import time
+    from types import SimpleNamespace
+
+    class Mapping:
+        frozen = False
+
+        def __init__(self, entries):
+            self.entries = dict(entries)
+
+    # What `freeze` makes of a List
+    class FrozenList(list):
+        def __setitem__(self, index, item):
+            raise error(FrozenError, "Cannot change an item: this List is frozen")
+
+        def __delitem__(self, index):
+            raise error(FrozenError, "Cannot change an item: this List is frozen")
+
+    # Field defaults run for the kind each Object inherits from first, and a
+    # kind's behaviors before the kind itself; then the nearest `build` runs
+    class Object:
+        def __init__(self, *args):
+            for kind in reversed(lineage(type(self))):
+                if "_fields" in vars(kind):
+                    vars(kind)["_fields"](self)
+            if callable(getattr(type(self), "build", None)):
+                self.build(*args)
+            elif args:
+                raise error(ArgumentError, f"{name(type(self))} has no 'build' block, so it takes no arguments")
+
+        def __setattr__(self, field, value):
+            if vars(self).get("_frozen"):
+                raise error(FrozenError, f"Cannot change '{field}': this {name(type(self))} is frozen")
+            super().__setattr__(field, value)
+
+    class Error(Object, Exception):
+        def _fields(self):
+            self.message = ""
+
+    class TypeError(Error):
+        pass
+
+    class NameError(Error):
+        pass
+
+    class PropertyError(Error):
+        pass
+
+    class ArgumentError(Error):
+        pass
+
+    class RecursionError(Error):
+        pass
+
+    class IndexError(Error):
+        pass
+
+    class KeyError(Error):
+        pass
+
+    class FrozenError(Error):
+        pass
+
+    class SandboxViolation(Error):
+        pass
+
+    class ExpectationFailed(Error):
+        pass
+
+    class ArithmeticError(Error):
+        pass
+
+    class InputError(Error):
+        pass
+
+    class StackError(Error):
+        pass
+
+    # A kind, then its behaviors, then the same for each kind it inherits from
+    def lineage(kind):
+        kinds = []
+        while kind is not Object:
+            kinds += [kind, *vars(kind).get("_behaviors", ())]
+            kind = kind.__bases__[0]
+        return kinds
+
+    # Gives a kind the members of its behaviors that it doesn't declare itself
+    def behave(kind, *behaviors):
+        kind._behaviors = behaviors
+        for behavior in behaviors:
+            for member, value in vars(behavior).items():
+                if not member.startswith("__") and member not in ("_fields", "_shared", "_behaviors", "_name", "build") \
+                        and member not in vars(kind):
+                    setattr(kind, member, value)
+
+    def name(kind):
+        return vars(kind).get("_name", kind.__name__)
+
+    def is_kind(value):
+        return isinstance(value, type) and issubclass(value, Object)
+
+    def is_number(value):
+        return isinstance(value, (int, float)) and not isinstance(value, bool)
+
+    def number(n):
+        if isinstance(n, int):
+            return str(n)
+        if math.isnan(n):
+            return "NaN"
+        if math.isinf(n):
+            return "inf" if n > 0 else "-inf"
+        written = repr(n)
+        if "e" not in written:
+            return written.removesuffix(".0")
+        # Written out in full, without an exponent
+        mantissa, exponent = written.split("e")
+        sign = "-" if n < 0 else ""
+        mantissa = mantissa.removeprefix("-")
+        digits = mantissa.replace(".", "")
+        point = len(mantissa.split(".")[0]) + int(exponent)
+        if point <= 0:
+            return f"{sign}0.{'0' * -point}{digits}"
+        return f"{sign}{digits.ljust(point, '0')}"
+
+    def text(value):
+        if value is None:
+            return "null"
+        if isinstance(value, bool):
+            return "true" if value else "false"
+        if is_number(value):
+            return number(value)
+        if isinstance(value, str):
+            return value
+        if isinstance(value, list):
+            return f"[{', '.join(map(nested, value))}]"
+        if isinstance(value, Mapping):
+            return "{" + ", ".join(f"{nested(key)}: {nested(item)}" for key, item in value.entries.items()) + "}"
+        if isinstance(value, Object):
+            return f"[object {name(type(value))}]"
+        if is_kind(value):
+            return f"[kind {name(value)}]"
+        return str(value)
+
+    # Text inside a collection is quoted
+    def nested(value):
+        return f'"{value}"' if isinstance(value, str) else text(value)
+
+    def show(value):
+        print(text(value))
+
+    # Lists compare element-wise, Mappings key-wise, Objects by identity
+    def equal(a, b):
+        if is_number(a) and is_number(b):
+            return a == b or (a != a and b != b)
+        if isinstance(a, list) and isinstance(b, list):
+            return len(a) == len(b) and all(equal(x, y) for x, y in zip(a, b))
+        if isinstance(a, Mapping) and isinstance(b, Mapping):
+            return len(a.entries) == len(b.entries) \
+                and all(key in b.entries and equal(item, b.entries[key]) for key, item in a.entries.items())
+        if isinstance(a, (str, bool)):
+            return type(a) is type(b) and a == b
+        return a is b
+
+    # `item key of` a List, Mapping or Text; Lists and Text count from 1
+    def item(target, key):
+        if isinstance(target, (list, str)):
+            return target[position(key, len(target), "Text" if isinstance(target, str) else "List")]
+        if isinstance(target, Mapping) and not isinstance(key, FromEnd):
+            if key not in target.entries:
+                raise error(KeyError, f"No value for {nested(key)} in the Mapping")
+            return target.entries[key]
+        raise error(TypeError, f"Type mismatch: expected a List, Mapping or Text, got {text(target)}")
+
+    def set_item(target, key, value):
+        if isinstance(target, list):
+            at = position(key, len(target), "List")
+            if isinstance(target, FrozenList):
+                raise error(FrozenError, "Cannot change an item: this List is frozen")
+            target[at] = value
+        elif isinstance(target, Mapping) and not isinstance(key, FromEnd):
+            if target.frozen:
+                raise error(FrozenError, "Cannot change an item: this Mapping is frozen")
+            target.entries[key] = value
+        elif isinstance(target, str):
+            raise error(TypeError, "Type mismatch: Text can't be changed; make new Text instead")
+        else:
+            raise error(TypeError, f"Type mismatch: expected a List or Mapping, got {text(target)}")
+
+    # `items first through last of` a List or Text, or with no last
+    # `everything after item first`, or with no first `everything before item last`
+    def slice(target, first, last):
+        if not isinstance(target, (list, str)):
+            raise error(TypeError, f"Type mismatch: expected a List or Text, got {text(target)}")
+        whole = "Text" if isinstance(target, str) else "List"
+        if first is None:
+            return target[:position(last, len(target), whole)]
+        if last is None:
+            return target[position(first, len(target), whole) + 1:]
+        start, end = position(first, len(target), whole), position(last, len(target), whole) + 1
+        if start >= end:
+            raise error(IndexError, f"Item {number(last)} is out of range: it comes before item {number(first)}")
+        return target[start:end]
+
+    # Where a 1-based item number points in a List or Text with `count` items
+    def position(key, count, whole):
+        if isinstance(key, FromEnd):
+            if key.number <= count:
+                return count - key.number
+            item = "The last item" if key.number == 1 else f"The {key.number}{suffix(key.number)} to last item"
+            raise error(IndexError, out_of_range(item, count, whole))
+        if not is_number(key) or not float(key).is_integer():
+            raise error(TypeError, f"Type mismatch: expected a Whole item number, got {nested(key)}")
+        if key < 1 or key > count:
+            raise error(IndexError, out_of_range(f"Item {number(key)}", count, whole))
+        return int(key) - 1
+
+    def out_of_range(item, count, whole):
+        items = "character" if whole == "Text" else "item"
+        return f"{item} is out of range: the {whole} has {count} {items}{'' if count == 1 else 's'}"
+
+    # `the number to last item`, worked out once the List or Text is known
+    class FromEnd:
+        def __init__(self, number):
+            self.number = number
+
+    def from_end(number):
+        return FromEnd(number)
+
+    def suffix(number):
+        if 11 <= number % 100 <= 13:
+            return "th"
+        return {1: "st", 2: "nd", 3: "rd"}.get(number % 10, "th")
+
+    # A List whose items are joined into another
+    def items(value):
+        if isinstance(value, list):
+            return value
+        raise error(TypeError, f"Type mismatch: expected a List, got {text(value)}")
+
+    # `the name of value, if value exists`
+    def get_if(value, name):
+        return None if value is None else getattr(value, name)
+
+    # `stop` (or, when `again`, `continue`) for the loop called `label`, from
+    # a loop inside it; not an Exception, so `fail` clauses let it through
+    class Jump(BaseException):
+        def __init__(self, label, again):
+            self.label, self.again = label, again
+
+    # The counts 1, 2, ... up to n, for `repeat n times`
+    def times(n):
+        count = 1
+        while count <= n:
+            yield count
+            count += 1
+
+    # The clock `measure how long it takes to` times runs by, in milliseconds
+    def clock():
+        return time.perf_counter() * 1000
+
+    # What a named measurement keeps: the runs and each time in milliseconds
+    def timing(times):
+        if not times:
+            raise error(Error, "'measure how long it takes to' stopped before its block ran once")
+        ordered = sorted(times)
+        middle = len(ordered) // 2
+        median = (ordered[middle - 1] + ordered[middle]) / 2 if len(ordered) % 2 == 0 else ordered[middle]
+        mean = sum(ordered) / len(ordered)
+        return Mapping([("runs", len(ordered)), ("fastest", ordered[0]), ("median", median), ("mean", mean)])
+
+    # What an unnamed measurement shows
+    def timing_report(times):
+        found = timing(times).entries
+        runs = found["runs"]
+        return (f"{runs} run{'' if runs == 1 else 's'}: fastest {found['fastest']:.3f} ms, "
+                f"median {found['median']:.3f} ms, mean {found['mean']:.3f} ms")
+
+    # A new List, Mapping or Object with the same items; they aren't copied themselves
+    def copy(value):
+        if isinstance(value, list):
+            return list(value)
+        if isinstance(value, Mapping):
+            return Mapping(value.entries)
+        if isinstance(value, Object):
+            made = type(value).__new__(type(value))
+            vars(made).update(vars(value))
+            vars(made).pop("_frozen", None)
+            return made
+        return value
+
+    # A List, Mapping or Object like this one that can't be changed; its
+    # items aren't frozen in turn
+    def freeze(value):
+        if isinstance(value, list) and not isinstance(value, FrozenList):
+            return FrozenList(value)
+        if isinstance(value, Mapping) and not value.frozen:
+            made = copy(value)
+            made.frozen = True
+            return made
+        if isinstance(value, Object) and not vars(value).get("_frozen"):
+            made = copy(value)
+            vars(made)["_frozen"] = True
+            return made
+        return value
+
+    # What `is the same as` asks: like equal, but Objects of the same kind
+    # match when their fields do. Pairs already being compared are taken to
+    # match so that cycles end.
+    def same(a, b, comparing=()):
+        if isinstance(a, list) and isinstance(b, list):
+            return len(a) == len(b) and all(same(x, y, comparing) for x, y in zip(a, b))
+        if isinstance(a, Mapping) and isinstance(b, Mapping):
+            return len(a.entries) == len(b.entries) \
+                and all(key in b.entries and same(item, b.entries[key], comparing) for key, item in a.entries.items())
+        if isinstance(a, Object) and isinstance(b, Object) and a is not b:
+            if type(a) is not type(b):
+                return False
+            if any(x is a and y is b for x, y in comparing):
+                return True
+            fields, others = fields_of(a), fields_of(b)
+            return fields.keys() == others.keys() \
+                and all(same(item, others[field], (*comparing, (a, b))) for field, item in fields.items())
+        return equal(a, b)
+
+    def fields_of(value):
+        return {field: item for field, item in vars(value).items() if field != "_frozen"}
+
+    # `a + b`, `a - b`, `a * b` and `a ^ b` where the operands may not be
+    # numbers: Text joins only Text, and Wholes fail rather than going past
+    # the largest the interpreter has, as Python's own ints would
+    def arithmetic(operator, a, b):
+        if isinstance(a, str) and operator == "+":
+            if not isinstance(b, str):
+                raise error(TypeError, "Can only concatenate strings")
+            return a + b
+        numbers(a, b)
+        x, y = whole(a), whole(b)
+        if x is not None and y is not None and (operator != "^" or y >= 0):
+            # A power past 53 of anything but -1, 0 or 1 is too big already
+            exact = x + y if operator == "+" else x - y if operator == "-" else x * y if operator == "*" \
+                else x ** y if abs(x) <= 1 or y <= 53 else None
+            if exact is None or not -(2 ** 53) <= exact < 2 ** 53:
+                raise error(ArithmeticError, f"{number(x)} {operator} {number(y)} does not fit in a Whole")
+            return exact
+        if operator == "+":
+            return a + b
+        if operator == "-":
+            return a - b
+        return a * b if operator == "*" else a ** b
+
+    # A Whole as an int, or None for a Decimal
+    def whole(n):
+        return int(n) if -(2 ** 53) <= n < 2 ** 53 and float(n).is_integer() else None
+
+    def numbers(a, b):
+        if not is_number(a) or not is_number(b):
+            raise error(TypeError, "Invalid operands for arithmetic operation")
+
+    # `a < b` and the other orderings, which take two numbers or two Texts
+    def compare(operator, a, b):
+        if not (is_number(a) and is_number(b)) and not (isinstance(a, str) and isinstance(b, str)):
+            raise error(TypeError, f"Cannot compare {text(a)} with {text(b)}")
+        if isinstance(a, float) and math.isnan(a) or isinstance(b, float) and math.isnan(b):
+            raise error(ArithmeticError, "Cannot order NaN")
+        if operator == ">":
+            return a > b
+        if operator == ">=":
+            return a >= b
+        return a < b if operator == "<" else a <= b
+
+    # Arithmetic as Decimals do it, where Python would raise instead
+    def divide(a, b):
+        numbers(a, b)
+        if b == 0:
+            raise error(ArithmeticError, f"Cannot divide {text(a)} by zero")
+        return a / b
+
+    def remainder(a, b):
+        numbers(a, b)
+        if b == 0:
+            raise error(ArithmeticError, f"Cannot find the remainder of {text(a)} divided by zero")
+        return math.fmod(a, b)
+
+    # `the type of value`: `Whole`, `List of Text`, an Object's kind and so
+    # on; a List's items are named by the type they share, or `Any`
+    def type_of(value):
+        def shared(values):
+            names = {type_of(item) for item in values}
+            if len(names) == 1:
+                return names.pop()
+            return "Decimal" if names == {"Whole", "Decimal"} else "Any"
+        if value is None:
+            return "Nothing"
+        if isinstance(value, bool):
+            return "Truth"
+        if is_number(value):
+            return "Whole" if float(value).is_integer() else "Decimal"
+        if isinstance(value, str):
+            return "Text"
+        if isinstance(value, list):
+            return f"List of {shared(value)}" if value else "List"
+        if isinstance(value, Mapping):
+            if not value.entries:
+                return "Mapping"
+            return f"Mapping of {shared(value.entries.keys())} to {shared(value.entries.values())}"
+        if is_kind(value):
+            return "Kind"
+        return name(type(value))
+
+    # `properties_of(object)`: its fields in the order they are declared,
+    # base objects first, then its computed properties
+    def properties_of(value):
+        if not isinstance(value, Object):
+            raise error(TypeError, f"Type mismatch: 'properties_of' requires an Object, got {nested(value)}")
+        fields = [field for field in vars(value) if not field.startswith("_")]
+        computed = {member for kind in lineage(type(value)) for member, found in vars(kind).items()
+                    if isinstance(found, property) and found.fget and member not in fields}
+        return fields + sorted(computed)
+
+    # `has_task(object, name)`, which also asks a kind about its shared tasks
+    def has_task(value, member):
+        if not isinstance(value, Object) and not is_kind(value):
+            raise error(TypeError, f"Type mismatch: 'has_task' requires an Object, got {nested(value)}")
+        return not member.startswith("_") and callable(getattr(value, member, None))
+
+    # `get_property(object, name)` and `set_property(object, name, value)`,
+    # for a property named by Text worked out as the program runs
+    def get_property(value, member):
+        if not hasattr(value, member) or member.startswith("_"):
+            raise error(PropertyError, f"{type_of(value)} has no property '{member}'")
+        return getattr(value, member)
+
+    def set_property(value, member, item):
+        setattr(value, member, item)
+        return None
+
+    # `round x`, `round x down`, `round x up` or `round x to even`, to a
+    # whole number or to the decimal places given
+    def round_number(name, value, places=0):
+        if not is_number(value) or not is_number(places) or places != int(places) or places < 0:
+            raise error(TypeError, "Type mismatch: rounding requires a number and a Whole number of decimal places")
+        scale = 10 ** int(places)
+        scaled = value * scale
+        match name:
+            case "round_down":
+                rounded = math.floor(scaled)
+            case "round_up":
+                rounded = math.ceil(scaled)
+            case "round_even":
+                rounded = round(scaled)
+            case _:
+                rounded = int(math.copysign(math.floor(abs(scaled) + 0.5), scaled))
+        return rounded if places == 0 else rounded / scale
+
+    # `the number in x` and `try to read x as a Whole`: the number the Text
+    # holds, which may have commas between its thousands, or nothing when it
+    # holds anything else
+    def read_number(text, whole):
+        if not isinstance(text, str):
+            raise error(TypeError, f"Type mismatch: only Text can be read as a number, got {nested(text)}")
+        written = text.strip()
+        if not re.fullmatch(r"[+-]?(\d{1,3}(,\d{3})+|\d+|(?=\.))(\.\d+)?", written, re.ASCII):
+            return None
+        if whole:
+            # Read exactly, so a number just past the Wholes isn't rounded into them
+            digits, _, fraction = written.replace(",", "").partition(".")
+            exact = int(digits if digits.strip("+-") else digits + "0")
+            return exact if not fraction.strip("0") and -2 ** 53 <= exact < 2 ** 53 else None
+        number = float(written.replace(",", ""))
+        if number == int(number) and -2 ** 53 <= number < 2 ** 53:
+            return int(number)
+        return number
+
+    # Text with the differences between cases taken away, uppercasing first
+    # to spell out letters like `ß` that have no single capital
+    def fold_case(text):
+        return text.upper().lower()
+
+    # `a is the same text as b`, ignoring case if asked, or the opposite
+    # with `is not`
+    def compare_text(left, right, ignoring_case, negated):
+        for value in (left, right):
+            if not isinstance(value, str):
+                raise error(TypeError, f"Type mismatch: only Text can be compared as text, got {nested(value)}")
+        same = fold_case(left) == fold_case(right) if ignoring_case else left == right
+        return same != negated
+
+    # `normalize(text)`: the Text without spaces around it or differences
+    # of case
+    def normalize(text):
+        if not isinstance(text, str):
+            raise error(TypeError, f"Type mismatch: only Text can be normalized, got {nested(text)}")
+        return fold_case(text.strip())
+
+    # `x written with ...`: the number to the decimal places asked for, or
+    # as it would show, with thousands separated by commas and at least as
+    # many digits before the point as asked for
+    def format_number(value, places, commas, digits, percent):
+        if not is_number(value):
+            raise error(TypeError, f"Type mismatch: only numbers can be written with decimal places or commas, got {nested(value)}")
+        for count, what in ((places, "decimal places"), (digits, "digits")):
+            if count is not None and not (is_number(count) and count == int(count) and count >= 0):
+                raise error(TypeError, f"Type mismatch: expected a Whole number of {what}, got {nested(count)}")
+        if places is not None:
+            # Halves round away from zero, as `round` has them
+            scale = 10 ** int(places)
+            written = f"{math.floor(abs(value) * (100 if percent else 1) * scale + 0.5) / scale:.{int(places)}f}"
+        elif percent:
+            # Moving the point two places along keeps 0.07 from showing as
+            # 7.000000000000001%
+            whole, _, fraction = number(abs(value)).partition(".")
+            fraction = fraction.ljust(2, "0")
+            before = (whole + fraction[:2]).lstrip("0") or "0"
+            written = f"{before}.{fraction[2:]}" if fraction[2:] else before
+        else:
+            written = number(abs(value))
+        whole, point, fraction = written.partition(".")
+        whole = whole.rjust(int(digits or 0), "0")
+        if commas:
+            head = len(whole) % 3 or 3
+            whole = ",".join([whole[:head]] + [whole[i:i + 3] for i in range(head, len(whole), 3)])
+        # No minus sign on a number that rounds to zero
+        sign = "-" if value < 0 and any(digit in "123456789" for digit in written) else ""
+        return f"{sign}{whole}{point}{fraction}{'%' if percent else ''}"
+
+    # `wrapping_add(a, b)` and the like: Whole arithmetic that wraps around
+    # past the largest or smallest Whole instead of failing, as a 54-bit
+    # integer would
+    def wrapping(operator, a, b):
+        if not (is_number(a) and is_number(b) and a == int(a) and b == int(b)):
+            raise error(TypeError, "Type mismatch: wrapping arithmetic requires two Whole numbers")
+        x, y = int(a), int(b)
+        result = x + y if operator == "+" else x - y if operator == "-" else x * y
+        return (result + 2 ** 53) % 2 ** 54 - 2 ** 53
+
+    # Whether a value is of a built-in type
+    def is_type(value, type):
+        match type:
+            case "Whole":
+                return is_number(value) and float(value).is_integer()
+            case "Decimal":
+                return is_number(value)
+            case "Text":
+                return isinstance(value, str)
+            case "Truth" | "Logic":
+                return isinstance(value, bool)
+            case "Nothing":
+                return value is None
+            case "Object":
+                return isinstance(value, Object) or is_kind(value)
+            case "List":
+                return isinstance(value, list)
+            case "Mapping":
+                return isinstance(value, Mapping)
+            case "Any":
+                return True
+            case "Promise":
+                return False
+        raise error(NameError, f"Unknown type: {type}")
+
+    # Whether a value is an Object of a kind, inherits from it or behaves like it
+    def is_a(value, kind):
+        return isinstance(value, Object) and kind in lineage(type(value))
+
+    def cast(value, type):
+        if type == "Whole" and is_number(value):
+            return math.floor(value)
+        if type == "Decimal" and is_number(value):
+            return value
+        if type == "Text":
+            return text(value)
+        if type == "Truth" and isinstance(value, bool):
+            return value
+        raise error(Error, f"Cannot cast {text(value)} to {type}")
+
+    def error(kind, message):
+        made = kind()
+        made.message = message
+        return made
+
+    def fail(kind, message):
+        raise error(kind, message)
+
+    # Sets a field and gives back its value, for assignments inside expressions
+    def put(target, field, value):
+        setattr(target, field, value)
+        return value
+
+    # What `raise` raises: an error, or an Error with the given message
+    def to_error(value):
+        if isinstance(value, str):
+            return error(Error, value)
+        if is_a(value, Error):
+            return value
+        return error(Error, f"Only Text or errors can be raised, not {text(value)}")
+
+    # What a fail clause receives, including Python's own errors
+    def caught(thrown):
+        if isinstance(thrown, Object):
+            return thrown
+        if isinstance(thrown, builtins.RecursionError):
+            return error(RecursionError, "Too much recursion")
+        if isinstance(thrown, builtins.NameError):
+            return error(NameError, f"Undefined variable: {thrown.name}")
+        if isinstance(thrown, builtins.AttributeError):
+            return error(PropertyError, str(thrown))
+        # Python reports a call with the wrong number of arguments as a
+        # TypeError; any other is a TypeError here too
+        if isinstance(thrown, builtins.TypeError):
+            arity = re.search(r"positional argument|keyword argument", str(thrown))
+            return error(ArgumentError if arity else TypeError, str(thrown))
+        return error(Error, str(thrown))
+
+    def expect(value, line, source):
+        if value is True:
+            return
+        failure = error(ExpectationFailed, f"expected that {source} (line {line}), but it was {text(value)}")
+        failure.actual, failure.expected, failure.line = value, True, line
+        raise failure
+
+    def expect_equal(actual, expected, line, source):
+        if equal(actual, expected):
+            return
+        message = f"expected {source} to equal {nested(expected)} (line {line}), but it was {nested(actual)}"
+        failure = error(ExpectationFailed, message)
+        failure.actual, failure.expected, failure.line = actual, expected, line
+        raise failure
+
+    # Runs the program's top-level statements, reporting an uncaught error
+    # as the interpreter does
+    def run(program):
+        try:
+            program()
+        except Exception as thrown:
+            failure = caught(thrown)
+            kind = name(type(failure))
+            sys.stdout.flush()
+            print(f"{kind}: {failure.message}" if failure.message else kind, file=sys.stderr)
+            sys.exit(1)
+
+    return SimpleNamespace(
+        Object=Object, Error=Error, TypeError=TypeError, NameError=NameError, PropertyError=PropertyError,
+        ArgumentError=ArgumentError, RecursionError=RecursionError, IndexError=IndexError, KeyError=KeyError, FrozenError=FrozenError,
+        SandboxViolation=SandboxViolation,
+        ExpectationFailed=ExpectationFailed, ArithmeticError=ArithmeticError, InputError=InputError, StackError=StackError, Mapping=Mapping, Jump=Jump,
+        behave=behave, text=text, show=show, equal=equal, same=same, item=item, set_item=set_item, from_end=from_end, slice=slice, get_if=get_if, items=items, times=times, clock=clock, timing=timing, timing_report=timing_report, copy=copy, freeze=freeze, arithmetic=arithmetic, compare=compare, divide=divide, remainder=remainder, type_of=type_of, properties_of=properties_of, has_task=has_task, get_property=get_property, set_property=set_property, round=round_number, format_number=format_number, read_number=read_number, compare_text=compare_text, normalize=normalize, wrapping=wrapping, is_type=is_type,
+        is_a=is_a, cast=cast, error=error, fail=fail, put=put, to_error=to_error, caught=caught, expect=expect,
+        expect_equal=expect_equal, run=run,
+    )
+
+
+V = _vernacular()
@@ -1,145 +1,966 @@
-use std::io::{self, Write};
-use crate::tokenizer::Tokenizer;
-use crate::parser::Parser;
-use crate::generator::{BytecodeGenerator, OpCode, Value};
-use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use crate::tokenizer::{Token, Tokenizer};
+use crate::parser::{Node, Parser};
+use crate::generator::{BytecodeGenerator, Class, Function, Instance, LineTable, Lock, Mapping, OpCode, TypedNames, Value};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use crate::analyzer::{Analyzer, Type};
+use crate::memory::{self, HeapMeter};
+use crate::numbers::Numbers;
+use crate::units::{Quantity, Unit};
+use crate::dates::Date;
+use crate::failure::Failure;
+use crate::collation::Collation;
+use crate::graphemes::graphemes;
+use crate::debugger::{Debugger, FrameView, PauseView};
+use crate::profiler::Profiler;
+use crate::coverage::Coverage;
+use crate::json::Json;
+use crate::formatter;
+use crate::pretty;
+use crate::cache::{BytecodeCache, Compiled};
+use crate::snapshot::Snapshot;
+use crate::config::Config;
+use crate::theme::Theme;
+use std::fmt;
+use std::time::Instant;
+
+// How a Runtime reads, checks and runs programs, and what they may do. An
+// isolated `evaluate` runs its code in a Runtime of its own with the same.
+#[derive(Clone)]
+struct Settings {
+    // Keywords must be written in their own case
+    strict_keywords: bool,
+    // Whether the analyzer insists on types, as `# strict` asks for one program
+    strict: bool,
+    number_words: bool,
+    // How Decimals are added, multiplied and so on
+    numbers: Numbers,
+    // The alphabetical order Text is sorted in unless a language is named
+    collation: Collation,
+    // How errors, warnings and shown values are colored
+    theme: Theme,
+    // Most Task calls that may be in progress at once
+    max_call_depth: usize,
+    // Most values that may wait on the operand stack at once
+    max_stack_depth: usize,
+    // Most bytes the program's values may hold
+    memory_limit: Option<usize>,
+    // Effects the program is allowed to have
+    capabilities: HashSet<Capability>,
+    // `today` and the clock `measure` reads give the same on every run
+    deterministic: bool,
+}
 
 pub struct Runtime {
     tokenizer: Tokenizer,
     variables: HashMap<String, Value>,
     variable_types: HashMap<String, Type>,
+    // Variables the host gave programs before they ran
+    given: HashMap<String, Value>,
+    functions: HashMap<String, Arc<Function>>,
+    classes: HashMap<String, Arc<Class>>,
+    shared: HashMap<String, HashMap<String, Value>>,
     stack: Vec<Value>,
+    frames: Vec<Frame>,
+    // The error object behind the error currently unwinding, if it was raised from code
+    raised: Option<Value>,
+    // Print the file banner and the tokens/AST/bytecode of each input
+    debug_output: bool,
+    // Echo each REPL input back as the canonical source it was read as
+    explain: bool,
+    settings: Settings,
+    // Where the first program compiled is looked for and saved
+    cache: Option<BytecodeCache>,
+    // Instructions left to run, when execution is bounded
+    fuel: Option<u64>,
+    // Set from another thread to stop the running program
+    interrupted: Arc<AtomicBool>,
+    // The running estimate of how many bytes the program's values hold
+    heap_estimate: usize,
+    // The file (or "<input>") being compiled, and the text of every one so far
+    source_name: Arc<str>,
+    sources: HashMap<Arc<str>, Vec<String>>,
+    debugger: Option<Debugger>,
+    // Where `show` writes, and where `ask` reads (standard input when None)
+    output: Box<dyn Write + Send>,
+    input: Option<Box<dyn BufRead + Send>>,
+    profiler: Option<Profiler>,
+    coverage: Option<Coverage>,
+    // Where each executed instruction is logged, and how many have been
+    trace: Option<Box<dyn Write + Send>>,
+    trace_steps: u64,
+    // Counts of what has run, for `stats`
+    stats: Stats,
+    // How many `evaluate` calls are in progress, and whether an error from
+    // one must end the program rather than be caught
+    evaluations: usize,
+    halting: bool,
+    // What `measure how long it takes to` times runs from
+    epoch: Instant,
+    // Object kinds declared again by the program just installed, whose
+    // existing Objects `begin` brings up to date
+    redeclared: Vec<String>,
+}
+
+// A Runtime can move to a worker thread, and its values can be handed
+// between threads; this stops compiling if either stops being true
+const _: fn() = || {
+    fn movable<T: Send>() {}
+    fn shareable<T: Send + Sync>() {}
+    movable::<Runtime>();
+    shareable::<Value>();
+};
+
+/// What the REPL says when it starts: its version and its commands.
+pub const REPL_BANNER: &str = "Vernacular Runtime v0.1.0\n\
+    '.exit' is quit, '.load file...' runs files, '.checkpoint [file]' and '.restore [file]' save and go back to the session, \
+    '.break file:line' sets a breakpoint, '.ast' explains each input, '.config [name value]' shows or changes a setting, \
+    '.history' lists past inputs, '.stats' shows what has run, '.tutorial' teaches the basics, or enter code directly.";
+
+/// Where a REPL session stands between lines: the input continued so far
+/// with `\`, the checkpoint `.restore` goes back to, its settings and the
+/// inputs `.history` lists. A remote session has no terminal for commands
+/// that would read one.
+#[derive(Default)]
+pub struct ReplSession {
+    input: String,
+    checkpoint: Option<Snapshot>,
+    config: Config,
+    history: VecDeque<String>,
+    remote: bool,
+}
+
+impl ReplSession {
+    pub fn remote() -> Self {
+        ReplSession { remote: true, ..ReplSession::default() }
+    }
+
+    /// What the REPL shows while it waits for the next line.
+    pub fn prompt(&self) -> &str {
+        match self.input.is_empty() {
+            true => &self.config.prompt,
+            false => "... ",
+        }
+    }
+
+    // Keeps a line for `.history`, forgetting the oldest past its size
+    fn remember(&mut self, line: &str) {
+        if !line.trim().is_empty() {
+            self.history.push_back(line.to_string());
+        }
+        while self.history.len() > self.config.history {
+            self.history.pop_front();
+        }
+    }
+}
+
+/// Where a started program stands after a call to `Runtime::step`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Step {
+    Paused,
+    Finished,
+}
+
+/// What a Runtime has done since it was made or last reset, from
+/// `Runtime::stats`, for seeing what a program costs to run.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Instructions run.
+    pub instructions: u64,
+    /// Text, Lists, Mappings and Objects made.
+    pub allocations: u64,
+    /// Objects the program can still reach.
+    pub live_objects: usize,
+    /// Times the whole heap was walked to measure it. Values are freed by
+    /// reference counting as soon as nothing holds them, so there is no
+    /// collector; these walks, made when the running estimate passes the
+    /// memory limit, are the nearest thing to its cycles.
+    pub gc_cycles: u64,
+    /// Most values waiting on the operand stack at once.
+    pub peak_stack_depth: usize,
+    /// Most calls in progress at once, counting the program itself.
+    pub peak_call_depth: usize,
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "instructions: {}", self.instructions)?;
+        writeln!(f, "allocations: {}", self.allocations)?;
+        writeln!(f, "live objects: {}", self.live_objects)?;
+        writeln!(f, "gc cycles: {}", self.gc_cycles)?;
+        writeln!(f, "peak stack depth: {}", self.peak_stack_depth)?;
+        write!(f, "peak call depth: {}", self.peak_call_depth)
+    }
+}
+
+/// An effect a program can have on the world outside the Runtime. A Runtime
+/// may be built without some of them, and using a missing one raises a
+/// SandboxViolation error.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Capability {
+    Console,    // printing with `show`
+    Files,      // reading files, e.g. `.load` in the REPL
+    Waiting,    // pausing with `wait`
+}
+
+impl Capability {
+    pub const ALL: [Capability; 3] = [Capability::Console, Capability::Files, Capability::Waiting];
+    /// What untrusted code gets: output, but nothing that reaches the host.
+    pub const SANDBOXED: [Capability; 1] = [Capability::Console];
+
+    fn describe(self) -> &'static str {
+        match self {
+            Capability::Console => "printing output",
+            Capability::Files => "file access",
+            Capability::Waiting => "waiting",
+        }
+    }
+}
+
+pub const DEFAULT_MAX_CALL_DEPTH: usize = 10_000;
+
+/// How many values may wait on the operand stack at once, across every call
+/// in progress, unless `set_max_stack_depth` says otherwise.
+pub const DEFAULT_MAX_STACK_DEPTH: usize = 100_000;
+
+// Most `evaluate` calls that may be in progress at once, since each runs its
+// code on the host's own stack
+const MAX_EVALUATIONS: usize = 64;
+
+// Most decimal places or digits `written with` will write a number with
+const MAX_WRITTEN: usize = 100;
+
+// Built-in error kinds, each inheriting from Error. Failures inside the
+// runtime are reported as one of these when a `do` block catches them;
+// a failed `expect` raises ExpectationFailed.
+const ERROR_KINDS: [&str; 13] = ["TypeError", "NameError", "PropertyError", "ArgumentError", "RecursionError",
+    "IndexError", "KeyError", "FrozenError", "SandboxViolation", "ExpectationFailed", "ArithmeticError", "InputError", "StackError"];
+
+// Where a value's Text, List, Mapping or Object is held, to tell a new one
+// from one already there
+fn address(value: &Value) -> Option<usize> {
+    match value {
+        Value::String(text) if text.capacity() > 0 => Some(text.as_ptr() as usize),
+        Value::List(items) => Some(Arc::as_ptr(items) as usize),
+        Value::Mapping(mapping) => Some(Arc::as_ptr(mapping) as usize),
+        Value::Object(instance) => Some(Arc::as_ptr(instance) as usize),
+        _ => None,
+    }
+}
+
+// An active Task invocation (or the top-level script)
+struct Frame {
+    function: Arc<Function>,
+    ip: usize,
+    locals: HashMap<String, Value>,
+    local_types: HashMap<String, Type>,
+    stack_base: usize,
+    is_script: bool,
+    discard_result: bool,
+    handlers: Vec<Handler>,
+    // Field initializers run again after their Object was declared again
+    // only set the fields that don't have a value yet
+    keeping: bool,
+}
+
+// An active `do` block: where to resume and how much stack to keep
+struct Handler {
+    target: usize,
+    stack_len: usize,
+}
+
+impl Frame {
+    fn new(function: Arc<Function>, receiver: Option<Value>, is_script: bool) -> Self {
+        let mut locals = HashMap::new();
+        if let Some(receiver) = receiver {
+            locals.insert("me".to_string(), receiver);
+        }
+        Frame {
+            function,
+            ip: 0,
+            locals,
+            local_types: HashMap::new(),
+            stack_base: 0,
+            is_script,
+            discard_result: false,
+            handlers: Vec::new(),
+            keeping: false,
+        }
+    }
+}
+
+impl Default for Runtime {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Runtime {
     pub fn new() -> Self {
-        Runtime {
+        Self::with_capabilities(&Capability::ALL)
+    }
+
+    /// A Runtime whose programs may only have the given effects.
+    pub fn with_capabilities(capabilities: &[Capability]) -> Self {
+        Self::with_settings(Settings {
+            strict_keywords: false,
+            strict: false,
+            number_words: true,
+            numbers: Numbers::Float,
+            collation: Collation::default(),
+            theme: Theme::default(),
+            max_call_depth: DEFAULT_MAX_CALL_DEPTH,
+            max_stack_depth: DEFAULT_MAX_STACK_DEPTH,
+            memory_limit: None,
+            capabilities: capabilities.iter().copied().collect(),
+            deterministic: false,
+        })
+    }
+
+    fn with_settings(settings: Settings) -> Self {
+        let mut runtime = Runtime {
             tokenizer: Tokenizer::new(""),
             variables: HashMap::new(),
             variable_types: HashMap::new(),
+            given: HashMap::new(),
+            functions: HashMap::new(),
+            classes: HashMap::new(),
+            shared: HashMap::new(),
             stack: Vec::new(),
-        }
+            frames: Vec::new(),
+            raised: None,
+            debug_output: true,
+            explain: false,
+            settings,
+            cache: None,
+            fuel: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
+            heap_estimate: 0,
+            source_name: Arc::from("<input>"),
+            sources: HashMap::new(),
+            debugger: None,
+            output: Box::new(io::stdout()),
+            input: None,
+            profiler: None,
+            coverage: None,
+            trace: None,
+            trace_steps: 0,
+            stats: Stats::default(),
+            evaluations: 0,
+            halting: false,
+            epoch: Instant::now(),
+            redeclared: Vec::new(),
+        };
+        runtime.define_error_kinds();
+        runtime
     }
 
-    pub fn run_repl(&mut self) -> Result<(), String> {
-        println!("Vernacular Runtime v0.1.0");
-        println!("'.exit' is quit, '.load' is load, or enter code directly.");
+    fn define_error_kinds(&mut self) {
+        let empty = |name: String, body: Vec<OpCode>| Arc::new(Function { name, params: Vec::new(), body, lines: LineTable::default() });
+        let returns_nothing = || vec![OpCode::Push(Value::Null), OpCode::Return];
+        let kind = |name: &str, base: Option<&str>, fields: TypedNames, initializer: Arc<Function>| Arc::new(Class {
+            name: name.to_string(),
+            base: base.map(str::to_string),
+            fields,
+            initializer,
+            methods: HashMap::new(),
+            getters: HashMap::new(),
+            setters: HashMap::new(),
+            constructor: None,
+            constructor_labels: Vec::new(),
+            shared_fields: Vec::new(),
+            shared_initializer: empty(format!("{}.<shared>", name), returns_nothing()),
+            shared_methods: HashMap::new(),
+            behaviors: Vec::new(),
+            is_behavior: false,
+        });
+
+        // Every error carries a message, empty unless set
+        let mut initializer = vec![
+            OpCode::LoadVar("me".to_string()),
+            OpCode::Push(Value::String(String::new())),
+            OpCode::SetProperty("message".to_string()),
+        ];
+        initializer.extend(returns_nothing());
+        let fields = vec![("message".to_string(), Some("Text".to_string()))];
+        let error = kind("Error", None, fields, empty("Error.<fields>".to_string(), initializer));
+        self.classes.insert("Error".to_string(), error);
 
-        let mut input = String::new();
-        let mut is_continuation = false;
+        for name in ERROR_KINDS {
+            let initializer = empty(format!("{}.<fields>", name), returns_nothing());
+            self.classes.insert(name.to_string(), kind(name, Some("Error"), Vec::new(), initializer));
+        }
+    }
 
+    /// Reads lines from standard input until `.exit`, with the settings in
+    /// `config`. `--quiet` and `--strict` on the command line win over it.
+    pub fn run_repl(&mut self, mut config: Config) -> Result<(), String> {
+        if !config.color {
+            self.set_theme(Theme::default());
+        }
+        println!("{}", self.settings.theme.banner(REPL_BANNER));
+        config.debug &= self.debug_output;
+        config.strict |= self.settings.strict;
+        self.set_debug_output(config.debug);
+        self.set_strict(config.strict);
+        let mut session = ReplSession { config, ..ReplSession::default() };
         loop {
-            if is_continuation {
-                print!("... ");
-            } else {
-                print!("> ");
-            }
-            io::stdout().flush().unwrap();
+            print!("{}", session.prompt());
+            io::stdout().flush().map_err(|e| format!("Failed to write prompt: {}", e))?;
 
             let mut line = String::new();
-            io::stdin().read_line(&mut line).expect("Failed to read line");
-            let line = line.trim_end();
-
-            match line {
-                ".exit" if !is_continuation => {
-                    println!("Goodbye!");
-                    break;
-                }
-                ".load" if !is_continuation => {
-                    println!("Enter file path:");
-                    let mut file_path = String::new();
-                    io::stdin().read_line(&mut file_path).expect("Failed to read line");
-                    let file_path = file_path.trim();
-                    
-                    self.run_file(file_path)?;
-                    input.clear();
-                    is_continuation = false;
-                }
-                _ => {
-                    input.push_str(line);
-                    input.push('\n');  // Add newline to maintain line structure
-                    
-                    if line.trim_end().ends_with('\\') {
-                        is_continuation = true;
-                    } else {
-                        if !input.trim().is_empty() {
-                            self.process_input(&input)?;
-                        }
-                        input.clear();
-                        is_continuation = false;
-                    }
+            let read = io::stdin().read_line(&mut line).map_err(|e| format!("Failed to read line: {}", e))?;
+            if read == 0 {
+                // End of input (Ctrl-D or a closed pipe)
+                println!();
+                break;
+            }
+            if !self.repl_line(line.trim_end(), &mut session)? {
+                break;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Handles one line typed at the REPL, writing what it says where `show`
+    /// writes. Gives false once the line asks to leave.
+    pub fn repl_line(&mut self, line: &str, session: &mut ReplSession) -> Result<bool, String> {
+        // Typed input isn't worth keeping compiled
+        self.cache = None;
+        let is_continuation = !session.input.is_empty();
+        if line != ".history" {
+            session.remember(line);
+        }
+        let said = match line {
+            ".exit" if !is_continuation => {
+                self.say("Goodbye!")?;
+                return Ok(false);
+            }
+            _ if !is_continuation && line.starts_with(".break") => {
+                let debugger = self.debugger.get_or_insert_with(|| Debugger::new(false));
+                let spec = line[".break".len()..].trim();
+                debugger.add_breakpoint(spec).map(|()| Some(format!("Breakpoint at {}", spec)))
+            }
+            ".tutorial" if !is_continuation && session.remote => Err("'.tutorial' needs a terminal of its own".to_string()),
+            ".tutorial" if !is_continuation => {
+                crate::tutorial::run()?;
+                Ok(None)
+            }
+            ".ast" if !is_continuation => {
+                self.explain = !self.explain;
+                Ok(Some(format!("Explaining input is {}", if self.explain { "on" } else { "off" })))
+            }
+            _ if !is_continuation && (line == ".config" || line.starts_with(".config ")) => {
+                self.config_command(line[".config".len()..].trim(), session).map(Some)
+            }
+            ".stats" if !is_continuation => Ok(Some(self.stats().to_string())),
+            ".history" if !is_continuation => {
+                let inputs: Vec<String> = session.history.iter().enumerate()
+                    .map(|(number, input)| format!("{:>4}  {}", number + 1, input))
+                    .collect();
+                Ok(Some(inputs.join("\n")).filter(|listed| !listed.is_empty()))
+            }
+            _ if !is_continuation && [".checkpoint", ".restore"].iter().any(|command| line.split_whitespace().next() == Some(command)) => {
+                self.checkpoint_command(line, &mut session.checkpoint).map(Some)
+            }
+            _ if !is_continuation && (line == ".load" || line.starts_with(".load ")) => {
+                // `.load a.vern "my notes.vern"` runs each file in turn;
+                // a bare `.load` asks for a single path
+                let file_paths = match line[".load".len()..].trim() {
+                    "" if session.remote => Err("'.load' needs the files to run".to_string()),
+                    "" => {
+                        self.say("Enter file path:")?;
+                        let mut file_path = String::new();
+                        io::stdin().read_line(&mut file_path).map_err(|e| format!("Failed to read line: {}", e))?;
+                        Ok(vec![file_path.trim().to_string()])
+                    },
+                    paths => shell_words(paths),
+                };
+                file_paths.and_then(|file_paths| match self.sandbox_message(Capability::Files) {
+                    Some(message) => Err(format!("SandboxViolation: {}", message)),
+                    // Later files may build on earlier ones, so a failure stops the rest
+                    None => file_paths.iter().try_for_each(|file_path| self.run_file(file_path)),
+                }).map(|()| None)
+            }
+            _ => {
+                session.input.push_str(line);
+                session.input.push('\n');  // Add newline to maintain line structure
+
+                if line.trim_end().ends_with('\\') {
+                    return Ok(true);
+                }
+                // A mistake in one input shouldn't end the session
+                let input = std::mem::take(&mut session.input);
+                match input.trim().is_empty() {
+                    true => Ok(None),
+                    false => self.process_input(&input).map(|()| None),
                 }
             }
+        };
+        match said {
+            Ok(Some(message)) => self.say(&message)?,
+            Ok(None) => {},
+            Err(error) => self.say(&self.settings.theme.error(&format!("Error: {}", error)))?,
+        }
+        Ok(true)
+    }
+
+    // `.config` lists the settings; `.config name value` changes one
+    fn config_command(&mut self, setting: &str, session: &mut ReplSession) -> Result<String, String> {
+        let config = &mut session.config;
+        if setting.is_empty() {
+            return Ok(config.to_string());
+        }
+        let (name, value) = setting.split_once(char::is_whitespace)
+            .ok_or_else(|| format!("'.config {}' needs a value", setting))?;
+        config.set(name, value)?;
+        // A remote client's terminal is out of sight, so it stays plain
+        if name == "color" && !session.remote {
+            self.set_theme(Theme::detect(config.color));
+        }
+        self.set_debug_output(config.debug);
+        self.set_strict(config.strict);
+        Ok(config.line(name).unwrap_or_default())
+    }
+
+    // Writes a line from the REPL itself, after anything the program showed
+    fn say(&mut self, text: &str) -> Result<(), String> {
+        self.output.write_all(format!("{}\n", text).as_bytes())
+            .and_then(|()| self.output.flush())
+            .map_err(|e| format!("Failed to write output: {}", e))
+    }
+
+    // `.checkpoint` keeps the session as it is for `.restore` to go back to,
+    // also saving it when given a file; `.restore file` goes back to a saved one
+    fn checkpoint_command(&mut self, line: &str, checkpoint: &mut Option<Snapshot>) -> Result<String, String> {
+        let words = shell_words(line)?;
+        let path = match &words[1..] {
+            [] => None,
+            [path] => match self.sandbox_message(Capability::Files) {
+                Some(message) => return Err(format!("SandboxViolation: {}", message)),
+                None => Some(path),
+            },
+            _ => return Err(format!("'{}' takes at most one file", words[0])),
+        };
+        match (words[0].as_str(), path) {
+            (".checkpoint", None) => {
+                *checkpoint = Some(self.snapshot());
+                Ok("Checkpoint taken; '.restore' goes back to it".to_string())
+            },
+            (".checkpoint", Some(path)) => {
+                let snapshot = self.snapshot();
+                std::fs::write(path, snapshot.to_json()?.to_string()).map_err(|e| format!("Error writing '{}': {}", path, e))?;
+                *checkpoint = Some(snapshot);
+                Ok(format!("Checkpoint saved to {}", path))
+            },
+            (_, None) => {
+                let snapshot = checkpoint.as_ref().ok_or("There is no checkpoint; '.checkpoint' takes one")?;
+                self.restore(snapshot)?;
+                Ok("Back at the checkpoint".to_string())
+            },
+            (_, Some(path)) => {
+                let text = std::fs::read_to_string(path).map_err(|e| format!("Error reading '{}': {}", path, e))?;
+                let snapshot = Snapshot::from_json(&Json::parse(&text)?)?;
+                self.restore(&snapshot)?;
+                *checkpoint = Some(snapshot);
+                Ok(format!("Back at the checkpoint in {}", path))
+            },
         }
+    }
+
+    pub fn set_debug_output(&mut self, enabled: bool) {
+        self.debug_output = enabled;
+    }
+
+    /// Requires keywords in their listed case, so `Show` is a name rather
+    /// than `show`.
+    pub fn set_strict_keywords(&mut self, strict: bool) {
+        self.settings.strict_keywords = strict;
+    }
+
+    /// Has the analyzer insist on types in every program, as a `# strict`
+    /// line asks for in one: variables declared with their types, values
+    /// that could be anything converted before they are stored, and no
+    /// Whole turning into a Decimal by itself.
+    pub fn set_strict(&mut self, strict: bool) {
+        self.settings.strict = strict;
+    }
+
+    /// Reads numbers spelled out, like `twenty-one`, as names instead, so
+    /// only digits make numbers.
+    pub fn set_number_words(&mut self, enabled: bool) {
+        self.settings.number_words = enabled;
+    }
+
+    /// Chooses how Decimals are added, multiplied and so on: quickly, or
+    /// exactly as they are written. See `Numbers`.
+    pub fn set_numbers(&mut self, numbers: Numbers) {
+        self.settings.numbers = numbers;
+    }
+
+    /// Chooses the language whose alphabetical order `sort` and `collate`
+    /// follow when a program doesn't name one, in place of the default order.
+    pub fn set_collation(&mut self, collation: Collation) {
+        self.settings.collation = collation;
+    }
+
+    /// Colors what this Runtime writes, which by default is left plain.
+    pub fn set_theme(&mut self, theme: Theme) {
+        self.settings.theme = theme;
+    }
+
+    pub fn theme(&self) -> Theme {
+        self.settings.theme
+    }
+
+    /// Looks for the first program this Runtime runs in an on-disk cache of
+    /// compiled programs before compiling it, and saves it there after.
+    pub fn set_bytecode_cache(&mut self, cache: BytecodeCache) {
+        self.cache = Some(cache);
+    }
+
+    /// Limits how many Task calls may be in progress at once; going deeper
+    /// raises a RecursionError.
+    pub fn set_max_call_depth(&mut self, depth: usize) {
+        self.settings.max_call_depth = depth;
+    }
+
+    /// Limits how many values may wait on the operand stack at once; more
+    /// raises a StackError, as a fault in the compiler or a pathological
+    /// program would otherwise grow it without bound.
+    pub fn set_max_stack_depth(&mut self, depth: usize) {
+        self.settings.max_stack_depth = depth;
+    }
+
+    /// Bounds how many more instructions this Runtime will run, across all
+    /// later inputs. Running out stops the program with an OutOfFuel error
+    /// that `do` blocks cannot catch.
+    pub fn set_fuel(&mut self, fuel: u64) {
+        self.fuel = Some(fuel);
+    }
+
+    /// A flag another thread can set to stop the running program before
+    /// its next instruction, with an Interrupted error that `do` blocks
+    /// cannot catch. It is cleared once the program has stopped.
+    pub fn interrupt_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.interrupted)
+    }
+
+    /// Caps the approximate bytes held by the program's text, lists, mappings
+    /// and objects. Going over stops the program with a MemoryLimitExceeded
+    /// error that `do` blocks cannot catch.
+    pub fn set_memory_limit(&mut self, bytes: usize) {
+        self.settings.memory_limit = Some(bytes);
+    }
+
+    /// Makes runs comparable: `today` is always January 1, 2000, and
+    /// `measure` counts a microsecond for each instruction run rather than
+    /// reading the clock.
+    pub fn set_deterministic(&mut self, deterministic: bool) {
+        self.settings.deterministic = deterministic;
+    }
+
+    /// Gives the programs run after this a top-level variable, so a host can
+    /// hand them data without writing it into their source.
+    pub fn set_global(&mut self, name: &str, value: Value) {
+        self.variable_types.remove(name);
+        self.variables.insert(name.to_string(), value.clone());
+        self.given.insert(name.to_string(), value);
+    }
+
+    /// Forgets the variables, Tasks and Objects that earlier programs
+    /// declared, so the next one starts afresh. Settings and the variables
+    /// given with `set_global` stay.
+    pub fn reset(&mut self) {
+        self.variables = self.given.clone();
+        self.variable_types.clear();
+        self.functions.clear();
+        self.classes.clear();
+        self.shared.clear();
+        self.stack.clear();
+        self.frames.clear();
+        self.raised = None;
+        self.heap_estimate = 0;
+        self.stats = Stats::default();
+        self.define_error_kinds();
+    }
+
+    /// What this Runtime has run and made so far; see `Stats`.
+    pub fn stats(&self) -> Stats {
+        Stats { live_objects: self.live_objects().len(), ..self.stats }
+    }
+
+    /// The top-level variables, their types, and the Tasks and Objects
+    /// defined so far, to come back to with `restore`.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::take(&self.variables, &self.variable_types, &self.functions, &self.classes, &self.shared)
+    }
 
+    /// Puts back what a snapshot kept, forgetting the variables and
+    /// definitions made since it was taken. A snapshot can be restored any
+    /// number of times.
+    pub fn restore(&mut self, snapshot: &Snapshot) -> Result<(), String> {
+        let restored = snapshot.restore()?;
+        self.variables = restored.variables;
+        self.variable_types = restored.types;
+        self.functions = restored.functions;
+        self.classes = restored.classes;
+        self.shared = restored.shared;
+        self.stack.clear();
+        self.frames.clear();
+        self.raised = None;
+        self.heap_estimate = self.live_bytes();
         Ok(())
     }
 
+    /// A top-level variable's value, such as a result left by a program
+    /// that has finished.
+    pub fn get_global(&self, name: &str) -> Option<Value> {
+        self.variables.get(name).cloned()
+    }
+
+    /// Pauses before the first statement of the next program and takes
+    /// debugger commands from standard input.
+    pub fn enable_debugger(&mut self) {
+        self.attach_debugger(Debugger::new(true));
+    }
+
+    pub fn attach_debugger(&mut self, debugger: Debugger) {
+        self.debugger = Some(debugger);
+    }
+
+    /// Counts instructions and times Tasks and lines from now on. Without
+    /// `show_times` the report leaves timings out.
+    pub fn enable_profiler(&mut self, show_times: bool) {
+        self.profiler = Some(Profiler::new(show_times));
+    }
+
+    /// The profiler's summary, if it is enabled.
+    pub fn profile_report(&self) -> Option<String> {
+        let sources = &self.sources;
+        self.profiler.as_ref().map(|profiler| profiler.report(|source, line| {
+            sources.get(source)?.get(line.checked_sub(1)?).map(|text| text.trim().to_string())
+        }))
+    }
+
+    /// Time spent in each chain of calls, in the folded format flamegraph tools read.
+    pub fn profile_folded_stacks(&self) -> Option<String> {
+        self.profiler.as_ref().map(Profiler::folded_stacks)
+    }
+
+    /// Records which statements run in code compiled from now on.
+    pub fn enable_coverage(&mut self) {
+        self.coverage = Some(Coverage::new());
+    }
+
+    pub fn take_coverage(&mut self) -> Option<Coverage> {
+        self.coverage.take()
+    }
+
+    /// Logs every instruction run from now on as a line of JSON: the step,
+    /// the Task and source line, the instruction, the call depth, and the
+    /// value on top of the stack afterwards.
+    pub fn set_trace(&mut self, output: Box<dyn Write + Send>) {
+        self.trace = Some(output);
+    }
+
+    /// Names the source that `run_nodes` compiles, for line tables and coverage.
+    pub fn set_source_name(&mut self, name: &str) {
+        self.source_name = Arc::from(name);
+    }
+
+    /// Sends what the program shows somewhere other than standard output.
+    pub fn set_output(&mut self, output: Box<dyn Write + Send>) {
+        self.output = output;
+    }
+
+    /// Answers `ask` from somewhere other than standard input.
+    pub fn set_input(&mut self, input: Box<dyn BufRead + Send>) {
+        self.input = Some(input);
+    }
+
     pub fn run_file(&mut self, file_path: &str) -> Result<(), String> {
         match std::fs::read_to_string(file_path) {
             Ok(content) => {
-                println!("Running file: {}", file_path);
-                self.process_input(&content)
+                if self.debug_output {
+                    println!("Running file: {}", file_path);
+                }
+                let previous = std::mem::replace(&mut self.source_name, Arc::from(file_path));
+                let result = self.process_input(&content);
+                self.source_name = previous;
+                result
             }
             Err(e) => Err(format!("Error reading file '{}': {}", file_path, e)),
         }
     }
 
-    fn process_input(&mut self, input: &str) -> Result<(), String> {
+    /// Compiles and runs a program given as text.
+    pub fn process_input(&mut self, input: &str) -> Result<(), String> {
+        self.start(input)?;
+        self.run()
+    }
+
+    /// Parses and compiles a program and gets it ready to run, without
+    /// running any of it. Drive it with `step`.
+    pub fn start(&mut self, input: &str) -> Result<(), String> {
+        self.sources.insert(Arc::clone(&self.source_name), input.lines().map(str::to_string).collect());
+
+        // Only the first program may come from the cache, since later ones
+        // are checked against what earlier ones declared, as are programs
+        // given variables by their host
+        let cache = self.cache.take().filter(|_| !self.debug_output && !self.explain && self.variables.is_empty());
+        let settings = format!("{}{}{}{} numbers", if self.settings.strict_keywords { "strict-keywords " } else { "" },
+            if self.settings.strict { "strict " } else { "" },
+            if self.settings.number_words { "" } else { "strict-numbers " }, self.settings.numbers.name());
+        if let Some(compiled) = cache.as_ref().and_then(|cache| cache.load(input, &settings, &self.source_name)) {
+            for warning in &compiled.warnings {
+                eprintln!("{}", self.settings.theme.warning(&format!("Warning: {}", warning)));
+            }
+            let script = self.install(&compiled);
+            self.begin(script);
+            return Ok(());
+        }
+
+        let (tokens, ast) = self.parse_source(input)?;
+        let compiled = self.compile(ast.clone())?;
+        if let Some(cache) = &cache {
+            cache.store(input, &settings, &compiled);
+        }
+        let script = self.install(&compiled);
+
+        if self.explain {
+            let explained: String = formatter::print(&ast).lines().map(|line| format!("\n  {}", line)).collect();
+            self.say(&format!("Understood as:{}", explained))?;
+        }
+
+        if self.debug_output {
+            println!("Tokens:");
+            for token in tokens {
+                println!("  {}", token);
+            }
+            
+            println!("\nAST:");
+            for node in &ast {
+                println!("  {:?}", node);
+            }
+            
+            println!("\nBytecode:");
+            for op in &script.body {
+                println!("  {:?}", op);
+            }
+        }
+
+        self.begin(script);
+        Ok(())
+    }
+
+    /// Tokenizes and parses source text without running it.
+    pub fn parse_source(&mut self, input: &str) -> Result<(Vec<Token>, Vec<Node>), String> {
         // First, preprocess the input to handle line continuations
         let processed_input = self.preprocess_input(input)?;
         
         self.tokenizer = Tokenizer::new(&processed_input);
+        self.tokenizer.set_strict_keywords(self.settings.strict_keywords);
+        self.tokenizer.set_number_words(self.settings.number_words);
         let tokens = self.tokenizer.tokenize()?;
         
         // Create and run parser
         let mut parser = Parser::new(tokens.clone());
         let ast = parser.parse()?;
-        
+        Ok((tokens, ast))
+    }
+
+    /// Checks and compiles a whole program without running it.
+    pub fn compile_source(&mut self, input: &str) -> Result<Compiled, String> {
+        let (_, ast) = self.parse_source(input)?;
+        self.compile(ast)
+    }
+
+    /// Runs a program compiled earlier from `input`, which is only used to
+    /// quote source lines in errors.
+    pub fn run_compiled(&mut self, input: &str, compiled: &Compiled) -> Result<(), String> {
+        self.sources.insert(Arc::clone(&self.source_name), input.lines().map(str::to_string).collect());
+        let script = self.install(compiled);
+        self.begin(script);
+        self.run()
+    }
+
+    /// Type checks and runs already-parsed statements.
+    pub fn run_nodes(&mut self, ast: Vec<Node>) -> Result<(), String> {
+        let compiled = self.compile(ast)?;
+        let script = self.install(&compiled);
+        self.begin(script);
+        self.run()
+    }
+
+    // Checks and compiles statements into top-level code and the Tasks and
+    // Objects they declare
+    fn compile(&mut self, ast: Vec<Node>) -> Result<Compiled, String> {
         // Run type checker with existing variables
         let mut analyzer = Analyzer::new();
+        analyzer.numbers = self.settings.numbers;
+        analyzer.strict = self.settings.strict || self.sources.get(&self.source_name)
+            .is_some_and(|lines| Analyzer::strict_directive(lines.iter().map(String::as_str)));
         
         // Only copy variables that have explicit types
-        for (name, _value) in &self.variables {
-            let var_type = if let Some(declared_type) = self.variable_types.get(name) {
-                match declared_type.as_str() {
-                    "Whole" => Type::Whole,
-                    "Decimal" => Type::Decimal,
-                    "Text" => Type::Text,
-                    "Truth" => Type::Truth,
-                    "Nothing" => Type::Nothing,
-                    _ => Type::Any,
-                }
-            } else {
-                Type::Any
-            };
+        for name in self.variables.keys() {
+            let var_type = self.variable_types.get(name).cloned().unwrap_or(Type::Any);
             analyzer.variables.insert(name.clone(), var_type);
         }
+        analyzer.classes.extend(self.classes.keys().cloned());
+        for class in self.classes.values().filter(|class| class.is_behavior) {
+            let members = class.fields.iter().map(|(name, _)| name)
+                .chain(class.methods.keys())
+                .chain(class.getters.keys())
+                .chain(class.setters.keys())
+                .cloned()
+                .collect();
+            analyzer.behaviors.insert(class.name.clone(), members);
+        }
         
         analyzer.analyze(&ast)?;
-        
-        // Generate and run bytecode
-        let mut generator = BytecodeGenerator::new();
-        let bytecode = generator.generate(ast.clone())?;
-        
-        // Debug output
-        println!("Tokens:");
-        for token in tokens {
-            println!("  {}", token);
+        for warning in &analyzer.warnings {
+            eprintln!("{}", self.settings.theme.warning(&format!("Warning: {}", warning)));
         }
         
-        println!("\nAST:");
-        for node in &ast {
-            println!("  {:?}", node);
+        // Generate bytecode
+        let mut generator = BytecodeGenerator::new(Arc::clone(&self.source_name));
+        let bytecode = generator.generate(ast)?;
+
+        let script = Arc::new(Function {
+            name: "<script>".to_string(),
+            params: Vec::new(),
+            body: bytecode,
+            lines: generator.lines().clone(),
+        });
+        Ok(Compiled { script, functions: generator.functions().to_vec(), classes: generator.classes().to_vec(), warnings: analyzer.warnings })
+    }
+
+    // Makes a compiled program's Tasks and Objects available, returning its
+    // top-level code
+    fn install(&mut self, compiled: &Compiled) -> Arc<Function> {
+        // Declarations are visible to the whole input, wherever they appear in it
+        for function in &compiled.functions {
+            self.functions.insert(function.name.clone(), Arc::clone(function));
         }
-        
-        println!("\nBytecode:");
-        for op in &bytecode {
-            println!("  {:?}", op);
+        // Objects already made from a kind declared again take on its new
+        // Tasks straight away, since they are looked up by the kind's name
+        for class in &compiled.classes {
+            if self.classes.insert(class.name.clone(), Arc::clone(class)).is_some() {
+                self.redeclared.push(class.name.clone());
+            }
         }
 
-        self.execute_bytecode(bytecode)
+        if let Some(coverage) = self.coverage.as_mut() {
+            let class_functions = compiled.classes.iter().flat_map(|class| class.functions());
+            for function in compiled.functions.iter().chain(class_functions).chain([&compiled.script]) {
+                coverage.add_function(function);
+            }
+        }
+        Arc::clone(&compiled.script)
     }
 
     fn preprocess_input(&self, input: &str) -> Result<String, String> {
@@ -148,9 +969,9 @@ impl Runtime {
         
         while let Some(line) = lines.next() {
             let trimmed = line.trim_end();
-            if trimmed.ends_with('\\') {
+            if let Some(joined) = trimmed.strip_suffix('\\') {
                 // Remove the \ and add a space
-                processed.push_str(&trimmed[..trimmed.len()-1]);
+                processed.push_str(joined);
                 processed.push(' ');
             } else {
                 // Add the line as-is
@@ -165,327 +986,1927 @@ impl Runtime {
         Ok(processed)
     }
 
-    fn execute_bytecode(&mut self, bytecode: Vec<OpCode>) -> Result<(), String> {
-        let mut stack: Vec<Value> = Vec::new();
-        let mut ip = 0;
-
-        while ip < bytecode.len() {
-            match &bytecode[ip] {
-                OpCode::StoreVar(name) => {
-                    let value = stack.pop().ok_or("Stack underflow")?;
-                    
-                    if let Some(declared_type) = self.variable_types.get(name) {
-                        // Skip type checking if we're storing null during declaration
-                        if !matches!(value, Value::Null) {
-                            let value_type = match &value {
-                                Value::Number(n) => {
-                                    if n.fract() == 0.0 { "Whole" } else { "Decimal" }
-                                },
-                                Value::String(_) => "Text",
-                                Value::Boolean(_) => "Truth",
-                                Value::Null => "Nothing",
-                                Value::Object(ref class_name) => class_name,
-                            };
-                            
-                            if declared_type != value_type {
-                                return Err(format!("Type mismatch: cannot assign {} to variable of type {}", 
-                                              value_type, declared_type));
-                            }
-                        }
-                    }
-                    
-                    self.variables.insert(name.clone(), value);
-                    Ok(())
-                },
-                OpCode::LoadVar(name) => {
-                    // Only try to load if the variable exists
-                    if let Some(value) = self.variables.get(name) {
-                        stack.push(value.clone());
-                        Ok(())
-                    } else {
-                        Err(format!("Undefined variable: {}", name))
-                    }
-                },
-                OpCode::Push(value) => {
-                    stack.push(value.clone());
-                    Ok(())
-                },
-                OpCode::Pop => {
-                    stack.pop();
-                    Ok(())
-                },
-                OpCode::Duplicate => {
-                    if let Some(value) = stack.last() {
-                        stack.push(value.clone());
-                    }
-                    Ok(())
-                },
-                OpCode::Add => {
-                    let b = stack.pop().ok_or("Stack underflow")?;
-                    let a = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(self.binary_op(a, b, |x, y| x + y)?);
-                    Ok(())
-                },
-                OpCode::Subtract => {
-                    let b = stack.pop().ok_or("Stack underflow")?;
-                    let a = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(self.binary_op(a, b, |x, y| x - y)?);
-                    Ok(())
-                },
-                OpCode::Multiply => {
-                    let b = stack.pop().ok_or("Stack underflow")?;
-                    let a = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(self.binary_op(a, b, |x, y| x * y)?);
-                    Ok(())
-                },
-                OpCode::Divide => {
-                    let b = stack.pop().ok_or("Stack underflow")?;
-                    let a = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(self.binary_op(a, b, |x, y| x / y)?);
-                    Ok(())
-                },
-                OpCode::Jump(target) => {
-                    ip = *target;
-                    Ok(())
-                },
-                OpCode::JumpIfFalse(target) => {
-                    if let Some(Value::Boolean(false)) = stack.last() {
-                        ip = *target;
-                        Ok(())
-                    } else {
-                        Ok(())
-                    }
-                },
-                OpCode::ConvertToString => {
-                    let value = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(Value::String(value.to_string()));
-                    Ok(())
-                },
-                OpCode::Call(name, arg_count) => {
-                    let mut args = Vec::new();
-                    // Pop arguments in reverse order
-                    for _ in 0..*arg_count {
-                        if let Some(arg) = stack.pop() {
-                            args.insert(0, arg);
-                        }
-                    }
+    // Makes compiled top-level code the next thing to run
+    fn begin(&mut self, script: Arc<Function>) {
+        self.stack.clear();
+        self.frames.clear();
+        self.frames.push(Frame::new(script, None, true));
+        self.update_redeclared();
+    }
 
-                    match name.as_str() {
-                        "show" => {
-                            // Built-in show function
-                            if let Some(value) = args.get(0) {
-                                println!("{}", value);
-                            }
-                            stack.push(Value::Null); // show returns null
-                        },
-                        _ => {
-                            return Err(format!("Unknown function: {}", name));
-                        }
-                    }
-                    Ok(())
-                },
-                OpCode::Return => {
-                    // TODO: Implement return
-                    break;
-                },
-                OpCode::NewObject(_class_name) => {
-                    // TODO: Implement object creation
-                    return Err("Object creation not implemented yet".to_string());
-                },
-                OpCode::GetProperty(_name) => {
-                    // TODO: Implement property access
-                    return Err("Property access not implemented yet".to_string());
-                },
-                OpCode::SetProperty(_name) => {
-                    // TODO: Implement property setting
-                    return Err("Property setting not implemented yet".to_string());
-                },
-                OpCode::CheckType(type_name) => {
-                    if let Some(var_name) = self.get_next_var_name(&bytecode[ip+1..]) {
-                        self.variable_types.insert(var_name.clone(), type_name.clone());
-                    }
-                    Ok(())
-                },
-                OpCode::Cast(type_name) => {
-                    if let Some(value) = stack.pop() {
-                        let new_value = match (value.clone(), type_name.as_str()) {
-                            (Value::Number(n), "Whole") => {
-                                Value::Number(n.floor())
-                            },
-                            (Value::Number(n), "Decimal") => {
-                                Value::Number(n)
-                            },
-                            (Value::String(s), "Text") => {
-                                Value::String(s)
-                            },
-                            (Value::Boolean(b), "Truth") => {
-                                Value::Boolean(b)
-                            },
-                            _ => return Err(format!("Cannot cast {:?} to {}", value, type_name)),
-                        };
-                        stack.push(new_value);
-                    }
-                    Ok(())
+    // Objects made before their kind was declared again get the fields the
+    // new declaration adds, before anything else runs: its field
+    // initializers run on them again, setting only the fields they lack.
+    // Frozen ones can't change and are left as they are.
+    fn update_redeclared(&mut self) {
+        let redeclared = std::mem::take(&mut self.redeclared);
+        if redeclared.is_empty() {
+            return;
+        }
+        for instance in self.live_objects() {
+            if instance.frozen || !redeclared.iter().any(|kind| self.is_kind_of(&instance.class_name, kind)) {
+                continue;
+            }
+            for class in self.lineage(&instance.class_name).unwrap_or_default() {
+                let mut frame = Frame::new(Arc::clone(&class.initializer), Some(Value::Object(Arc::clone(&instance))), false);
+                frame.discard_result = true;
+                frame.keeping = true;
+                frame.stack_base = self.stack.len();
+                self.frames.push(frame);
+            }
+        }
+    }
+
+    // Every Object the program can still reach, once each
+    fn live_objects(&self) -> Vec<Arc<Instance>> {
+        let locals = self.frames.iter().flat_map(|frame| frame.locals.values());
+        let shared = self.shared.values().flat_map(|fields| fields.values());
+        let mut pending: Vec<Value> = self.variables.values().chain(locals).chain(shared).chain(&self.stack).cloned().collect();
+        let mut seen: HashSet<*const ()> = HashSet::new();
+        let mut objects = Vec::new();
+        while let Some(value) = pending.pop() {
+            match value {
+                Value::List(items) if seen.insert(Arc::as_ptr(&items).cast()) => pending.extend(items.borrow().iter().cloned()),
+                Value::Mapping(mapping) if seen.insert(Arc::as_ptr(&mapping).cast()) => {
+                    pending.extend(mapping.borrow().iter().flat_map(|(key, entry)| [key.clone(), entry.clone()]));
                 },
-                OpCode::Concat => {
-                    let b = stack.pop().ok_or("Stack underflow")?;
-                    let a = stack.pop().ok_or("Stack underflow")?;
-                    stack.push(self.concat_values(a, b)?);
-                    Ok(())
+                Value::Object(instance) if seen.insert(Arc::as_ptr(&instance).cast()) => {
+                    pending.extend(instance.fields.borrow().values().cloned());
+                    objects.push(instance);
                 },
-                OpCode::Interpolate(part_count) => {
-                    let mut result = String::new();
-                    for _ in 0..*part_count {
-                        if let Some(value) = stack.pop() {
-                            result = value.to_string() + &result;
-                        }
-                    }
-                    stack.push(Value::String(result));
+                _ => {},
+            }
+        }
+        objects
+    }
+
+    // Runs until the outermost frame finishes
+    fn run(&mut self) -> Result<(), String> {
+        while self.step(usize::MAX)? == Step::Paused {}
+        Ok(())
+    }
+
+    /// Runs at most `limit` instructions of the started program, then hands
+    /// control back. An uncaught error ends the program.
+    pub fn step(&mut self, limit: usize) -> Result<Step, String> {
+        for _ in 0..limit {
+            if self.frames.is_empty() {
+                break;
+            }
+            if let Err(message) = self.execute_next() {
+                self.frames.clear();
+                self.stack.clear();
+                return Err(message);
+            }
+        }
+        match self.frames.is_empty() {
+            true => Ok(Step::Finished),
+            false => Ok(Step::Paused),
+        }
+    }
+
+    // Runs one instruction of the innermost frame
+    fn execute_next(&mut self) -> Result<(), String> {
+        let Some(frame) = self.frames.last_mut() else {
+            return Ok(());
+        };
+        let function = Arc::clone(&frame.function);
+        let ip = frame.ip;
+        let Some(opcode) = function.body.get(ip) else {
+            // Top-level code simply ends; Tasks always end in Return
+            self.frames.pop();
+            return Ok(());
+        };
+        frame.ip += 1;
+        if self.debugger.is_some() {
+            self.debug_hook(&function, ip)?;
+        }
+        if let Some(coverage) = self.coverage.as_mut() {
+            if let Some(line) = function.lines.starting_at(ip) {
+                coverage.hit(&function.lines.source, line);
+            }
+        }
+        if let Some(fuel) = self.fuel.as_mut() {
+            if *fuel == 0 {
+                return Err("OutOfFuel: the program ran out of its instruction budget".to_string());
+            }
+            *fuel -= 1;
+        }
+        if self.interrupted.load(Ordering::Relaxed) {
+            self.interrupted.store(false, Ordering::Relaxed);
+            return Err("Interrupted: the program was stopped before it finished".to_string());
+        }
+        let stack_len = self.stack.len();
+        let top = self.stack.last().and_then(address);
+        let started = self.profiler.is_some().then(Instant::now);
+        if let Some(profiler) = self.profiler.as_mut() {
+            let frames = &self.frames;
+            profiler.enter(frames.len(), |depth| frames[depth].function.name.as_str());
+        }
+        if let Err(message) = self.execute_opcode(opcode).and_then(|()| self.check_stacks(&function, ip)) {
+            self.recover(message)?;
+        }
+        self.count(top);
+        if self.trace.is_some() {
+            self.trace_instruction(&function, ip, opcode)?;
+        }
+        if let (Some(profiler), Some(started)) = (self.profiler.as_mut(), started) {
+            let line = function.lines.line_of(ip).map(|line| (Arc::clone(&function.lines.source), line));
+            profiler.record(opcode, line, started.elapsed());
+        }
+        if let Some(limit) = self.settings.memory_limit {
+            self.check_memory(limit, stack_len)?;
+        }
+        Ok(())
+    }
+
+    // Adds an instruction to the stats, and what it made: a value on top of
+    // the stack that wasn't there before and that nothing else holds
+    fn count(&mut self, top: Option<usize>) {
+        self.stats.instructions += 1;
+        let fresh = match self.stack.last() {
+            Some(Value::List(items)) => Arc::strong_count(items) == 1,
+            Some(Value::Mapping(mapping)) => Arc::strong_count(mapping) == 1,
+            Some(Value::Object(instance)) => Arc::strong_count(instance) == 1,
+            // Text is never shared, so each copy is its own
+            Some(Value::String(_)) => true,
+            _ => false,
+        };
+        if fresh && self.stack.last().and_then(address) != top {
+            self.stats.allocations += 1;
+        }
+        self.stats.peak_stack_depth = self.stats.peak_stack_depth.max(self.stack.len());
+        self.stats.peak_call_depth = self.stats.peak_call_depth.max(self.frames.len());
+    }
+
+    // Catches either stack growing past its limit after an instruction.
+    // Calls to Tasks are checked as they are made; this also covers frames
+    // pushed for initializers and `evaluate`.
+    fn check_stacks(&mut self, function: &Function, ip: usize) -> Result<(), Failure> {
+        // The script itself occupies the first frame
+        if self.frames.len() > self.settings.max_call_depth + 1 {
+            return Err(Failure::new("RecursionError", format!("Too much recursion: more than {} Tasks in progress\n{}",
+                self.settings.max_call_depth, self.call_trace())));
+        }
+        if self.stack.len() > self.settings.max_stack_depth {
+            let at = match function.lines.line_of(ip) {
+                Some(line) => format!(" at line {} of {}", line, function.lines.source),
+                None => String::new(),
+            };
+            let message = format!("more than {} values waiting on the stack after {:?} in {}{}\n{}",
+                self.settings.max_stack_depth, function.body[ip], function.name, at, self.call_trace());
+            return self.raise_error(Self::error_value("StackError", message));
+        }
+        Ok(())
+    }
+
+    // Pauses for the debugger when a statement it should stop at begins
+    fn debug_hook(&mut self, function: &Function, ip: usize) -> Result<(), String> {
+        let Some(line) = function.lines.starting_at(ip) else {
+            return Ok(());
+        };
+        let depth = self.frames.len();
+        let Some(reason) = self.debugger.as_ref()
+            .and_then(|debugger| debugger.pause_reason(&function.lines.source, line, depth)) else {
+            return Ok(());
+        };
+        let view = self.pause_view();
+        let Some(mut debugger) = self.debugger.take() else {
+            return Ok(());
+        };
+        let result = debugger.pause(reason, &view);
+        self.debugger = Some(debugger);
+        result
+    }
+
+    fn trace_instruction(&mut self, function: &Function, ip: usize, opcode: &OpCode) -> Result<(), String> {
+        self.trace_steps += 1;
+        let line = match function.lines.line_of(ip) {
+            Some(line) => line.into(),
+            None => Json::Null,
+        };
+        let top = match self.stack.last() {
+            Some(value) => Json::String(value.nested()),
+            None => Json::Null,
+        };
+        let entry = Json::object(vec![
+            ("step", Json::Number(self.trace_steps as f64)),
+            ("task", Json::text(&function.name)),
+            ("source", Json::text(&function.lines.source)),
+            ("line", line),
+            ("ip", ip.into()),
+            ("opcode", Json::String(format!("{:?}", opcode))),
+            ("depth", self.frames.len().into()),
+            ("top", top),
+        ]);
+        match self.trace.as_mut() {
+            Some(trace) => writeln!(trace, "{}", entry).map_err(|e| format!("Failed to write trace: {}", e)),
+            None => Ok(()),
+        }
+    }
+
+    // A snapshot of the calls in progress, innermost first, for the debugger
+    fn pause_view(&self) -> PauseView {
+        let frames = self.frames.iter().rev().map(|frame| {
+            let lines = &frame.function.lines;
+            let line = lines.line_of(frame.ip.saturating_sub(1));
+            let text = line.and_then(|line| self.sources.get(&lines.source)?.get(line - 1))
+                .map(|text| text.trim().to_string());
+            // Task locals, or the globals for top-level code
+            let scope = if frame.is_script { &self.variables } else { &frame.locals };
+            let mut variables: Vec<(String, String)> = scope.iter()
+                .map(|(name, value)| (name.clone(), value.nested()))
+                .collect();
+            variables.sort();
+            FrameView { task: frame.function.name.clone(), source: Arc::clone(&lines.source), line, text, variables }
+        }).collect();
+        PauseView { frames, stack: self.stack.iter().map(Value::nested).collect() }
+    }
+
+    // Every new value passes through the stack, so adding each one to the
+    // estimate bounds growth. The estimate only ever overcounts (values get
+    // dropped too), so it is measured afresh before giving up.
+    fn check_memory(&mut self, limit: usize, stack_len: usize) -> Result<(), String> {
+        if self.stack.len() >= stack_len {
+            self.heap_estimate += self.stack.last().map(memory::fresh_size).unwrap_or(0);
+        }
+        if self.heap_estimate <= limit {
+            return Ok(());
+        }
+
+        self.heap_estimate = self.live_bytes();
+        self.stats.gc_cycles += 1;
+        if self.heap_estimate > limit {
+            return Err(format!("MemoryLimitExceeded: the program holds about {} bytes, more than its limit of {}",
+                self.heap_estimate, limit));
+        }
+        Ok(())
+    }
+
+    // Approximate bytes reachable from variables, the stack and shared fields
+    fn live_bytes(&self) -> usize {
+        let mut meter = HeapMeter::new();
+        let locals = self.frames.iter().flat_map(|frame| frame.locals.iter());
+        let shared = self.shared.values().flat_map(|fields| fields.iter());
+        for (name, value) in self.variables.iter().chain(locals).chain(shared) {
+            meter.add_name(name);
+            meter.add(value);
+        }
+        for value in self.stack.iter().chain(self.raised.iter()) {
+            meter.add(value);
+        }
+        meter.bytes
+    }
+
+    // Unwinds to the innermost `do` block and resumes at its fail clauses,
+    // or gives the error back when nothing catches it
+    fn recover(&mut self, failure: Failure) -> Result<(), String> {
+        let message = failure.message.clone();
+        if std::mem::take(&mut self.halting) {
+            return Err(message);
+        }
+        let error = match self.raised.take() {
+            Some(error) => error,
+            None => Value::Object(Self::error_value(failure.kind, failure.message)),
+        };
+        while let Some(frame) = self.frames.last_mut() {
+            if let Some(handler) = frame.handlers.pop() {
+                frame.ip = handler.target;
+                self.stack.truncate(handler.stack_len);
+                self.stack.push(error);
+                return Ok(());
+            }
+            self.frames.pop();
+        }
+        Err(message)
+    }
+
+    // Starts unwinding with an error object, which is what gets caught in
+    // place of the failure's kind; uncaught, it reads "Kind: message"
+    fn raise_error(&mut self, instance: Arc<Instance>) -> Result<(), Failure> {
+        let message = match instance.fields.borrow().get("message") {
+            Some(Value::String(message)) if !message.is_empty() => format!("{}: {}", instance.class_name, message),
+            _ => instance.class_name.clone(),
+        };
+        self.raised = Some(Value::Object(instance));
+        Err(Failure::new("Error", message))
+    }
+
+    // Prints a value for `show`, wherever this Runtime's output goes
+    fn write_output(&mut self, value: &Value) -> Result<(), Failure> {
+        let text = self.settings.theme.value(value);
+        self.write_text(&text)
+    }
+
+    fn write_text(&mut self, text: &str) -> Result<(), Failure> {
+        if let Some(message) = self.sandbox_message(Capability::Console) {
+            return self.raise_error(Self::error_value("SandboxViolation", message));
+        }
+        // One write per value, so what it says reaches the output whole. A
+        // failed write ends the program, as writing on would only fail again.
+        let written = self.output.write_all(format!("{}\n", text).as_bytes());
+        if written.is_err() {
+            self.halting = true;
+        }
+        written.map_err(|e| Failure::new("Error", format!("Failed to write output: {}", e)))
+    }
+
+    // Why an effect can't happen here, if this Runtime was built without it
+    fn sandbox_message(&self, capability: Capability) -> Option<String> {
+        match self.settings.capabilities.contains(&capability) {
+            true => None,
+            false => Some(format!("{} is not allowed in this sandbox", capability.describe())),
+        }
+    }
+
+    // Both sides of a failed expectation, for whoever catches or reports it
+    fn record_expectation(error: &Instance, actual: Value, expected: Value, line: usize) {
+        let mut fields = error.fields.borrow_mut();
+        fields.insert("actual".to_string(), actual);
+        fields.insert("expected".to_string(), expected);
+        fields.insert("line".to_string(), Value::Number(line as f64));
+    }
+
+    fn error_value(kind: &str, message: String) -> Arc<Instance> {
+        let fields = HashMap::from([("message".to_string(), Value::String(message))]);
+        Arc::new(Instance {
+            class_name: kind.to_string(),
+            fields: Lock::new(fields),
+            frozen: false,
+        })
+    }
+
+    fn execute_opcode(&mut self, opcode: &OpCode) -> Result<(), Failure> {
+        match opcode {
+            OpCode::StoreVar(name) => self.store_var(name),
+            OpCode::DeclareVar(name, type_name) => {
+                let declared_type = Type::from_name(type_name).unwrap_or(Type::Object);
+                match self.frames.last_mut() {
+                    Some(frame) if !frame.is_script => {
+                        frame.local_types.insert(name.clone(), declared_type);
+                    },
+                    _ => {
+                        self.variable_types.insert(name.clone(), declared_type);
+                    },
+                }
+                self.store_var(name)
+            },
+            OpCode::LoadVar(name) => {
+                // Locals shadow globals
+                let local = self.frames.last().and_then(|frame| frame.locals.get(name));
+                if let Some(value) = local.or_else(|| self.variables.get(name)) {
+                    self.stack.push(value.clone());
                     Ok(())
-                },
-                OpCode::CheckAssignmentType => {
-                    let _var_value = stack.pop().ok_or("Stack underflow")?;
-                    let new_value = stack.last().ok_or("Stack underflow")?;
-                    
-                    if let Some(var_name) = self.get_next_var_name(&bytecode[ip+1..]) {
-                        // Only check type if the variable has an explicit type declaration
-                        if let Some(declared_type) = self.variable_types.get(&var_name) {
-                            let new_type = match new_value {
-                                Value::Number(n) => {
-                                    if n.fract() == 0.0 { Type::Whole } else { Type::Decimal }
-                                },
-                                Value::String(_) => Type::Text,
-                                Value::Boolean(_) => Type::Truth,
-                                Value::Null => Type::Nothing,
-                                Value::Object(ref class_name) => Type::Object,
-                                Value::Promise(ref class_name) => Type::Promise,
-                                Value::List(ref class_name) => Type::List,
-                                Value::Mapping(ref class_name) => Type::Mapping,
-                            };
-
-                            if declared_type != new_type {
-                                return Err(format!("Type mismatch: cannot assign {} to variable of type {}", 
-                                              new_type, declared_type));
+                } else if self.classes.contains_key(name) {
+                    // An object kind's name refers to the kind itself
+                    self.stack.push(Value::Class(name.clone()));
+                    Ok(())
+                } else {
+                    Err(Failure::new("NameError", format!("Undefined variable: {}", name)))
+                }
+            },
+            OpCode::Push(value) => {
+                self.stack.push(value.clone());
+                Ok(())
+            },
+            OpCode::Pop => {
+                self.stack.pop();
+                Ok(())
+            },
+            OpCode::Dup => {
+                let value = self.stack.last().cloned().ok_or_else(underflow)?;
+                self.stack.push(value);
+                Ok(())
+            },
+            OpCode::PushHandler(target) => {
+                let stack_len = self.stack.len();
+                let frame = self.frames.last_mut().ok_or_else(|| Failure::new("Error", "No active frame"))?;
+                frame.handlers.push(Handler { target: *target, stack_len });
+                Ok(())
+            },
+            OpCode::PopHandler => {
+                let frame = self.frames.last_mut().ok_or_else(|| Failure::new("Error", "No active frame"))?;
+                frame.handlers.pop();
+                Ok(())
+            },
+            OpCode::Raise => {
+                let value = self.pop()?;
+                let instance = match value {
+                    Value::String(message) => Self::error_value("Error", message),
+                    Value::Object(instance) if self.is_kind_of(&instance.class_name, "Error") => instance,
+                    other => return Err(Failure::new("TypeError", format!("Only Text or errors can be raised, not {}", other))),
+                };
+                self.raise_error(instance)
+            },
+            OpCode::Expect(line, source) => {
+                let value = self.pop()?;
+                if value == Value::Boolean(true) {
+                    return Ok(());
+                }
+                let message = format!("expected that {} (line {}), but it was {}", source, line, value);
+                let error = Self::error_value("ExpectationFailed", message);
+                Self::record_expectation(&error, value, Value::Boolean(true), *line);
+                self.raise_error(error)
+            },
+            OpCode::ExpectEqual(line, source) => {
+                let expected = self.pop()?;
+                let actual = self.pop()?;
+                if actual == expected {
+                    return Ok(());
+                }
+                let message = format!("expected {} to equal {} (line {}), but it was {}",
+                    source, expected.nested(), line, actual.nested());
+                let error = Self::error_value("ExpectationFailed", message);
+                Self::record_expectation(&error, actual, expected, *line);
+                self.raise_error(error)
+            },
+            OpCode::Add => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                if matches!(a, Value::String(_)) {
+                    self.stack.push(self.concat_values(a, b)?);
+                } else {
+                    self.stack.push(self.whole_op(a, b, "+", i64::checked_add, |x, y| x + y)?);
+                }
+                Ok(())
+            },
+            OpCode::Subtract => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(self.whole_op(a, b, "-", i64::checked_sub, |x, y| x - y)?);
+                Ok(())
+            },
+            OpCode::Multiply => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(self.whole_op(a, b, "*", i64::checked_mul, |x, y| x * y)?);
+                Ok(())
+            },
+            OpCode::Divide => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(self.divide(a, b, false)?);
+                Ok(())
+            },
+            OpCode::Modulo => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(self.divide(a, b, true)?);
+                Ok(())
+            },
+            OpCode::Power => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(self.whole_op(a, b, "^", |x, y| x.checked_pow(u32::try_from(y).ok()?), f64::powf)?);
+                Ok(())
+            },
+            OpCode::Equal | OpCode::NotEqual => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let equal = a == b;
+                self.stack.push(Value::Boolean(if matches!(opcode, OpCode::Equal) { equal } else { !equal }));
+                Ok(())
+            },
+            OpCode::SameAs => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                self.stack.push(Value::Boolean(a.same_as(&b)));
+                Ok(())
+            },
+            OpCode::Greater | OpCode::GreaterEqual | OpCode::Less | OpCode::LessEqual => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                let ordering = self.compare_values(&a, &b)?;
+                let result = match opcode {
+                    OpCode::Greater => ordering.is_gt(),
+                    OpCode::GreaterEqual => ordering.is_ge(),
+                    OpCode::Less => ordering.is_lt(),
+                    _ => ordering.is_le(),
+                };
+                self.stack.push(Value::Boolean(result));
+                Ok(())
+            },
+            OpCode::And | OpCode::Or => {
+                let b = self.pop()?;
+                let a = self.pop()?;
+                match (a, b) {
+                    (Value::Boolean(x), Value::Boolean(y)) => {
+                        let result = if matches!(opcode, OpCode::And) { x && y } else { x || y };
+                        self.stack.push(Value::Boolean(result));
+                        Ok(())
+                    },
+                    _ => Err(Failure::new("TypeError", "'and'/'or' require Truth operands")),
+                }
+            },
+            OpCode::Not => {
+                match self.pop()? {
+                    Value::Boolean(b) => self.stack.push(Value::Boolean(!b)),
+                    other => return Err(Failure::new("TypeError", format!("Type mismatch: a condition must be true or false, not {}", other.nested()))),
+                }
+                Ok(())
+            },
+            OpCode::Jump(target) => {
+                self.jump_to(*target);
+                Ok(())
+            },
+            OpCode::JumpIfFalse(target) => {
+                match self.pop()? {
+                    Value::Boolean(false) => self.jump_to(*target),
+                    Value::Boolean(true) => {},
+                    other => return Err(Failure::new("TypeError", format!("Type mismatch: a condition must be true or false, not {}", other.nested()))),
+                }
+                Ok(())
+            },
+            OpCode::Call(name, arg_count) => {
+                let args = self.pop_args(*arg_count)?;
+
+                match name.as_str() {
+                    "show" => {
+                        // Built-in show function
+                        if let Some(value) = args.first() {
+                            self.write_output(value)?;
+                        }
+                        self.stack.push(Value::Null); // show returns null
+                        Ok(())
+                    },
+                    "<describe>" => {
+                        let value = args.first().ok_or_else(|| Failure::new("ArgumentError", "'describe' needs a value"))?;
+                        self.write_text(&pretty::describe(value))?;
+                        self.stack.push(Value::Null);
+                        Ok(())
+                    },
+                    "copy" => {
+                        let value = args.first().ok_or_else(|| Failure::new("ArgumentError", "'a copy of' needs a value"))?;
+                        self.stack.push(value.copy());
+                        Ok(())
+                    },
+                    "freeze" => {
+                        let value = args.first().ok_or_else(|| Failure::new("ArgumentError", "'freeze' needs a value"))?;
+                        self.stack.push(value.freeze());
+                        Ok(())
+                    },
+                    "round" | "round_down" | "round_up" | "round_even" => {
+                        let result = self.round(name, &args)?;
+                        self.stack.push(result);
+                        Ok(())
+                    },
+                    "properties_of" | "has_task" | "get_property" | "set_property" => self.reflect(name, args),
+                    "<clock>" => {
+                        let milliseconds = match self.settings.deterministic {
+                            true => self.stats.instructions as f64 / 1000.0,
+                            false => self.epoch.elapsed().as_secs_f64() * 1000.0,
+                        };
+                        self.stack.push(Value::Number(milliseconds));
+                        Ok(())
+                    },
+                    "<timing>" | "<timing report>" => {
+                        let Some(Value::List(times)) = args.first() else {
+                            return Err(Failure::new("ArgumentError", "'measure' needs the list of times"));
+                        };
+                        let timing = Timing::of(&times.borrow())
+                            .ok_or_else(|| Failure::new("Error", "'measure how long it takes to' stopped before its block ran once"))?;
+                        self.stack.push(match name.as_str() {
+                            "<timing>" => timing.mapping(),
+                            _ => Value::String(timing.to_string()),
+                        });
+                        Ok(())
+                    },
+                    "evaluate" => {
+                        let result = self.evaluate(&args)?;
+                        self.stack.push(result);
+                        Ok(())
+                    },
+                    "number_in" | "whole_in" => {
+                        let number = match args.first() {
+                            Some(Value::String(text)) => read_number(text, name == "whole_in"),
+                            Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: only Text can be read as a number, got {}", other.nested()))),
+                            None => return Err(Failure::new("ArgumentError", format!("'{}' needs a value", name))),
+                        };
+                        self.stack.push(number);
+                        Ok(())
+                    },
+                    "ask" => {
+                        let answer = self.ask(&args)?;
+                        self.stack.push(answer);
+                        Ok(())
+                    },
+                    "length_of" => {
+                        let length = Self::length_of(&args)?;
+                        self.stack.push(Value::Number(length as f64));
+                        Ok(())
+                    },
+                    "sort" => {
+                        self.sort(&args)?;
+                        self.stack.push(Value::Null);
+                        Ok(())
+                    },
+                    "collate" => {
+                        let order = self.collate(&args)?;
+                        self.stack.push(Value::Number(order));
+                        Ok(())
+                    },
+                    "compare_text" => {
+                        let same = Self::compare_text(&args)?;
+                        self.stack.push(Value::Boolean(same));
+                        Ok(())
+                    },
+                    "normalize" => {
+                        let text = match args.first() {
+                            Some(Value::String(text)) => fold_case(text.trim()),
+                            Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: only Text can be normalized, got {}", other.nested()))),
+                            None => return Err(Failure::new("ArgumentError", "'normalize' needs a value")),
+                        };
+                        self.stack.push(Value::String(text));
+                        Ok(())
+                    },
+                    "format_number" => {
+                        let text = Self::format_number(&args)?;
+                        self.stack.push(Value::String(text));
+                        Ok(())
+                    },
+                    "wait" => {
+                        if let Some(message) = self.sandbox_message(Capability::Waiting) {
+                            return self.raise_error(Self::error_value("SandboxViolation", message));
+                        }
+                        self.wait(&args)?;
+                        self.stack.push(Value::Null);
+                        Ok(())
+                    },
+                    "today" => {
+                        let today = match self.settings.deterministic {
+                            true => Date::fixed(),
+                            false => Date::today(),
+                        };
+                        self.stack.push(Value::Date(today));
+                        Ok(())
+                    },
+                    "convert" => {
+                        let result = Self::convert(&args)?;
+                        self.stack.push(result);
+                        Ok(())
+                    },
+                    "wrapping_add" | "wrapping_subtract" | "wrapping_multiply" => {
+                        let result = self.wrapping_op(name, &args)?;
+                        self.stack.push(result);
+                        Ok(())
+                    },
+                    _ => {
+                        let function = self.functions.get(name).cloned()
+                            .ok_or_else(|| Failure::new("NameError", format!("Unknown function: {}", name)))?;
+                        self.call_function(function, args, None, false)
+                    }
+                }
+            },
+            OpCode::CallMethod(name, arg_count) => {
+                let args = self.pop_args(*arg_count)?;
+                let receiver = self.pop()?;
+                let method = match &receiver {
+                    Value::Object(instance) => self.find_member(&instance.class_name, |class| class.methods.get(name))
+                        .ok_or_else(|| Failure::new("PropertyError", format!("{} has no task '{}'", instance.class_name, name)))?,
+                    Value::Class(class_name) => self.find_member(class_name, |class| class.shared_methods.get(name))
+                        .ok_or_else(|| Failure::new("PropertyError", format!("{} has no shared task '{}'", class_name, name)))?,
+                    other => return Err(Failure::new("PropertyError", format!("Cannot call '{}' on {}", name, other))),
+                };
+                self.call_function(method, args, Some(receiver.clone()), false)
+            },
+            OpCode::Return => {
+                let value = self.pop()?;
+                let frame = self.frames.pop().ok_or_else(|| Failure::new("Error", "Return outside of a task"))?;
+                self.stack.truncate(frame.stack_base);
+                if !frame.discard_result {
+                    self.stack.push(value);
+                }
+                Ok(())
+            },
+            OpCode::NewObject(class_name, labels) => {
+                let args = self.pop_args(labels.len())?;
+                let class = self.classes.get(class_name).cloned()
+                    .ok_or_else(|| Failure::new("NameError", format!("Unknown object type: {}", class_name)))?;
+                if class.is_behavior {
+                    return Err(Failure::new("TypeError", format!("{} is a behavior; objects take it on with 'behaves like {}'", class_name, class_name)));
+                }
+                let instance = Value::Object(Arc::new(Instance {
+                    class_name: class_name.clone(),
+                    fields: Lock::new(HashMap::new()),
+                    frozen: false,
+                }));
+                self.stack.push(instance.clone());
+
+                // The nearest `build` runs after every field default has been set
+                match self.find_constructor(class_name) {
+                    Some(constructor_class) => {
+                        let args = Self::order_arguments(&constructor_class, args, labels)?;
+                        let constructor = constructor_class.constructor.clone().ok_or_else(|| Failure::new("Error", "Missing constructor"))?;
+                        self.call_function(constructor, args, Some(instance.clone()), true)?;
+                    },
+                    None if !args.is_empty() => {
+                        return Err(Failure::new("ArgumentError", format!("{} has no 'build' block, so it takes no arguments", class_name)));
+                    },
+                    None => {},
+                }
+
+                // Field defaults run most-derived last, so push the frames in reverse
+                for class in self.lineage(class_name)? {
+                    self.call_function(Arc::clone(&class.initializer), Vec::new(), Some(instance.clone()), true)?;
+                }
+                Ok(())
+            },
+            OpCode::InitClass(class_name) => {
+                let class = self.classes.get(class_name).cloned()
+                    .ok_or_else(|| Failure::new("NameError", format!("Unknown object type: {}", class_name)))?;
+                // Declared again, the kind keeps its shared fields' values
+                let declared = self.shared.contains_key(class_name);
+                self.shared.entry(class_name.clone()).or_default();
+                self.call_function(Arc::clone(&class.shared_initializer), Vec::new(), Some(Value::Class(class_name.clone())), true)?;
+                if let Some(frame) = self.frames.last_mut() {
+                    frame.keeping = declared;
+                }
+                Ok(())
+            },
+            OpCode::GetProperty(name) => {
+                let object = self.pop()?;
+                match &object {
+                    Value::Object(instance) => {
+                        // Computed properties take precedence over stored fields
+                        if let Some(getter) = self.find_member(&instance.class_name, |class| class.getters.get(name)) {
+                            return self.call_function(getter, Vec::new(), Some(object.clone()), false);
+                        }
+                        let value = instance.fields.borrow().get(name).cloned();
+                        match value {
+                            Some(value) => {
+                                self.stack.push(value);
+                                Ok(())
+                            },
+                            None => Err(Failure::new("PropertyError", format!("{} has no property '{}'", instance.class_name, name))),
+                        }
+                    },
+                    Value::Class(class_name) => {
+                        let (owner, _) = self.shared_field(class_name, name)
+                            .ok_or_else(|| Failure::new("PropertyError", format!("{} has no shared property '{}'", class_name, name)))?;
+                        let value = self.shared.get(&owner).and_then(|fields| fields.get(name)).cloned();
+                        self.stack.push(value.unwrap_or(Value::Null));
+                        Ok(())
+                    },
+                    other => Err(Failure::new("PropertyError", format!("Cannot read property '{}' of {}", name, other))),
+                }
+            },
+            OpCode::SetProperty(name) => {
+                let value = self.pop()?;
+                let object = self.pop()?;
+                if self.frames.last().is_some_and(|frame| frame.keeping) && self.has_value(&object, name) {
+                    return Ok(());
+                }
+                match &object {
+                    Value::Object(instance) => {
+                        if instance.frozen {
+                            return Err(Failure::new("FrozenError", format!("Cannot change '{}': this {} is frozen", name, instance.class_name)));
+                        }
+                        if let Some(setter) = self.find_member(&instance.class_name, |class| class.setters.get(name)) {
+                            return self.call_function(setter, vec![value], Some(object.clone()), true);
+                        }
+                        if self.find_member(&instance.class_name, |class| class.getters.get(name)).is_some() {
+                            return Err(Failure::new("PropertyError", format!("Property '{}' of {} is read-only", name, instance.class_name)));
+                        }
+                        if let Some(declared_type) = self.field_type(&instance.class_name, name) {
+                            let value_type = Analyzer::type_of_value(&value);
+                            if !matches!(value, Value::Null) && !declared_type.accepts(&value_type) {
+                                return Err(Failure::new("TypeError", format!("Type mismatch: cannot assign {} to {}.{} of type {}",
+                                    value_type, instance.class_name, name, declared_type)));
+                            }
+                        }
+                        instance.fields.borrow_mut().insert(name.clone(), value);
+                        Ok(())
+                    },
+                    Value::Class(class_name) => {
+                        let (owner, declared_type) = self.shared_field(class_name, name)
+                            .ok_or_else(|| Failure::new("PropertyError", format!("{} has no shared property '{}'", class_name, name)))?;
+                        if let Some(declared_type) = declared_type {
+                            let value_type = Analyzer::type_of_value(&value);
+                            if !matches!(value, Value::Null) && !declared_type.accepts(&value_type) {
+                                return Err(Failure::new("TypeError", format!("Type mismatch: cannot assign {} to {}.{} of type {}",
+                                    value_type, class_name, name, declared_type)));
                             }
                         }
-                        // If variable doesn't have a declared type, allow any assignment
+                        self.shared.entry(owner).or_default().insert(name.clone(), value);
+                        Ok(())
+                    },
+                    other => Err(Failure::new("PropertyError", format!("Cannot set property '{}' of {}", name, other))),
+                }
+            },
+            OpCode::BuildList(count) => {
+                if self.stack.len() < *count {
+                    return Err(underflow());
+                }
+                let elements = self.stack.split_off(self.stack.len() - count);
+                self.stack.push(Value::list(elements));
+                Ok(())
+            },
+            OpCode::JoinLists(count) => {
+                if self.stack.len() < *count {
+                    return Err(underflow());
+                }
+                let mut items = Vec::new();
+                for list in self.stack.split_off(self.stack.len() - count) {
+                    match list {
+                        Value::List(list) => items.extend(list.borrow().iter().cloned()),
+                        other => return Err(Failure::new("TypeError", format!("Type mismatch: expected a List, got {}", other))),
                     }
-                    Ok(())
-                },
-                OpCode::Show => {
-                    if let Some(value) = stack.pop() {
-                        println!("{}", value);
-                    } else {
-                        return Err("Stack underflow".to_string());
+                }
+                self.stack.push(Value::list(items));
+                Ok(())
+            },
+            OpCode::ForEach(end) => {
+                let Some(Value::Number(index)) = self.stack.pop() else {
+                    return Err(underflow());
+                };
+                // Text goes a character at a time, as a reader sees them,
+                // split once as the loop starts
+                if let Some(Value::String(text)) = self.stack.last() {
+                    let characters = graphemes(text).into_iter().map(|character| Value::String(character.to_string())).collect();
+                    *self.stack.last_mut().ok_or_else(underflow)? = Value::list(characters);
+                }
+                let item = match self.stack.last() {
+                    Some(Value::List(items)) => items.borrow().get(index as usize).cloned(),
+                    Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: expected a List or Text, got {}", other))),
+                    None => return Err(underflow()),
+                };
+                match item {
+                    Some(item) => {
+                        self.stack.push(Value::Number(index + 1.0));
+                        self.stack.push(item);
+                    },
+                    None => {
+                        self.stack.pop();
+                        self.jump_to(*end);
+                    },
+                }
+                Ok(())
+            },
+            OpCode::Append => {
+                let value = self.pop()?;
+                match self.pop()? {
+                    Value::List(items) => match items.change() {
+                        Some(mut items) => items.push(value),
+                        None => return Err(Failure::new("FrozenError", "Cannot add an item: this List is frozen")),
+                    },
+                    other => return Err(Failure::new("TypeError", format!("Type mismatch: only a List can be added to, not {}", other))),
+                }
+                Ok(())
+            },
+            OpCode::GetIndex => {
+                let key = self.pop()?;
+                let object = self.pop()?;
+                self.stack.push(object.item(&key)?);
+                Ok(())
+            },
+            OpCode::SetIndex => {
+                let value = self.pop()?;
+                let key = self.pop()?;
+                let object = self.pop()?;
+                object.set_item(key, value)
+            },
+            OpCode::FromEnd(depth) => {
+                let number = match self.stack.pop() {
+                    Some(Value::Number(number)) => number as usize,
+                    _ => return Err(underflow()),
+                };
+                let whole = self.stack.len().checked_sub(*depth).and_then(|at| self.stack.get(at)).ok_or_else(underflow)?;
+                let number = whole.counted_back(number)?;
+                self.stack.push(number);
+                Ok(())
+            },
+            OpCode::Slice => {
+                let last = self.pop()?;
+                let first = self.pop()?;
+                let object = self.pop()?;
+                self.stack.push(object.slice(&first, &last)?);
+                Ok(())
+            },
+            OpCode::BuildMapping(count) => {
+                if self.stack.len() < count * 2 {
+                    return Err(underflow());
+                }
+                let mut flat = self.stack.split_off(self.stack.len() - count * 2).into_iter();
+                let mut mapping = Mapping::new();
+                while let (Some(key), Some(value)) = (flat.next(), flat.next()) {
+                    mapping.insert(key, value);
+                }
+                self.stack.push(Value::mapping(mapping));
+                Ok(())
+            },
+            OpCode::IsInstance(type_name) => {
+                let value = self.pop()?;
+                // Object kinds come first so the built-in Error kinds are checked by inheritance
+                let result = if self.classes.contains_key(type_name) {
+                    match &value {
+                        Value::Object(instance) => self.is_kind_of(&instance.class_name, type_name),
+                        _ => false,
                     }
-                    Ok(())
-                },
-            }?;
-            ip += 1;
+                } else {
+                    match Type::from_name(type_name) {
+                        // Whole numbers also count as Decimal, as they do for assignment
+                        Some(typ) => typ.accepts(&Analyzer::type_of_value(&value)),
+                        None => return Err(Failure::new("NameError", format!("Unknown type: {}", type_name))),
+                    }
+                };
+                self.stack.push(Value::Boolean(result));
+                Ok(())
+            },
+            OpCode::Cast(type_name) => {
+                if let Some(value) = self.stack.pop() {
+                    let new_value = match (value.clone(), type_name.as_str()) {
+                        (Value::Number(n), "Whole") => {
+                            Value::Number(self.settings.numbers.settle(n).floor())
+                        },
+                        (Value::Number(n), "Decimal") => {
+                            Value::Number(self.settings.numbers.settle(n))
+                        },
+                        (Value::Money(money), "Decimal") => {
+                            Value::Number(money.to_number())
+                        },
+                        (Value::Money(money), "Whole") => {
+                            Value::Number(money.to_number().floor())
+                        },
+                        (Value::Money(money), "Money") => {
+                            Value::Money(money)
+                        },
+                        (Value::Quantity(quantity), "Decimal") => {
+                            Value::Number(quantity.amount)
+                        },
+                        (Value::Quantity(quantity), "Whole") => {
+                            Value::Number(quantity.amount.floor())
+                        },
+                        (value @ Value::Quantity(_), "Quantity") => {
+                            value
+                        },
+                        (value, "Text") => {
+                            Value::String(value.to_string())
+                        },
+                        (Value::Boolean(b), "Truth") => {
+                            Value::Boolean(b)
+                        },
+                        _ => return Err(Failure::new("TypeError", format!("Cannot cast {:?} to {}", value, type_name))),
+                    };
+                    self.stack.push(new_value);
+                }
+                Ok(())
+            },
+            OpCode::TypeOf => {
+                let value = self.pop()?;
+                self.stack.push(Value::String(value.type_name()));
+                Ok(())
+            },
+            OpCode::Interpolate(part_count) => {
+                let at = self.stack.len().checked_sub(*part_count).ok_or_else(underflow)?;
+                let result = self.stack.drain(at..).map(|value| value.to_string()).collect();
+                self.stack.push(Value::String(result));
+                Ok(())
+            },
+            OpCode::Concat(count) => {
+                let at = self.stack.len().checked_sub(*count).ok_or_else(underflow)?;
+                let parts = self.stack.split_off(at);
+                self.stack.push(self.concat_all(parts)?);
+                Ok(())
+            },
+            OpCode::Show => {
+                let value = self.pop()?;
+                self.write_output(&value)
+            },
+        }
+    }
+
+    fn jump_to(&mut self, target: usize) {
+        if let Some(frame) = self.frames.last_mut() {
+            frame.ip = target;
+        }
+    }
+
+    fn pop(&mut self) -> Result<Value, Failure> {
+        self.stack.pop().ok_or_else(underflow)
+    }
+
+    fn pop_args(&mut self, count: usize) -> Result<Vec<Value>, Failure> {
+        if self.stack.len() < count {
+            return Err(underflow());
+        }
+        Ok(self.stack.split_off(self.stack.len() - count))
+    }
+
+    // Pops a value into a variable of the current scope, which must be of
+    // the variable's declared type, if it has one
+    fn store_var(&mut self, name: &str) -> Result<(), Failure> {
+        let value = self.pop()?;
+
+        if let Some(declared_type) = self.declared_type(name) {
+            // Skip type checking if we're storing null during declaration
+            if !matches!(value, Value::Null) {
+                let value_type = Analyzer::type_of_value(&value);
+                if !declared_type.accepts(&value_type) {
+                    return Err(Failure::new("TypeError", format!("Type mismatch: cannot assign {} to variable of type {}",
+                                  value_type, declared_type)));
+                }
+            }
+        }
+
+        match self.frames.last_mut() {
+            Some(frame) if !frame.is_script => {
+                frame.locals.insert(name.to_string(), value);
+            },
+            _ => {
+                self.variables.insert(name.to_string(), value);
+            },
         }
         Ok(())
     }
 
-    fn get_next_var_name(&self, upcoming_ops: &[OpCode]) -> Option<String> {
-        for op in upcoming_ops {
-            if let OpCode::StoreVar(name) = op {
-                return Some(name.clone());
+    // The declared type of a variable in the current scope, if it has one
+    fn declared_type(&self, name: &str) -> Option<&Type> {
+        match self.frames.last() {
+            Some(frame) if !frame.is_script => frame.local_types.get(name),
+            _ => self.variable_types.get(name),
+        }
+    }
+
+    // A class followed by its behaviors, then the same for each base in turn.
+    // This is the order members are looked up in.
+    fn lineage(&self, class_name: &str) -> Result<Vec<Arc<Class>>, Failure> {
+        let mut lineage: Vec<Arc<Class>> = Vec::new();
+        let mut current = Some(class_name.to_string());
+        while let Some(name) = current {
+            if lineage.iter().any(|class| class.name == name) {
+                return Err(Failure::new("Error", format!("{} inherits from itself", name)));
+            }
+            let class = self.classes.get(&name).cloned()
+                .ok_or_else(|| Failure::new("NameError", format!("Unknown object type: {}", name)))?;
+            current = class.base.clone();
+            lineage.push(Arc::clone(&class));
+            for behavior in &class.behaviors {
+                let behavior = self.classes.get(behavior).cloned()
+                    .ok_or_else(|| Failure::new("NameError", format!("Unknown behavior: {}", behavior)))?;
+                lineage.push(behavior);
+            }
+        }
+        Ok(lineage)
+    }
+
+    // `properties_of(object)`, `has_task(object, name)`, `get_property(object,
+    // name)` and `set_property(object, name, value)`, which reach an
+    // Object's members by names held in Text. Properties are read and set
+    // as `object.name` would be, computed ones included.
+    fn reflect(&mut self, name: &str, mut args: Vec<Value>) -> Result<(), Failure> {
+        let expected = match name {
+            "properties_of" => 1,
+            "set_property" => 3,
+            _ => 2,
+        };
+        if args.len() != expected {
+            return Err(Failure::new("ArgumentError", format!("'{}' takes {} arguments, got {}", name, expected, args.len())));
+        }
+        let member = match args.get(1) {
+            Some(Value::String(member)) => member.clone(),
+            Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: '{}' requires a property or task name as Text, got {}", name, other.nested()))),
+            None => String::new(),
+        };
+        match (name, &args[0]) {
+            ("properties_of", Value::Object(instance)) => {
+                // Stored fields in the order they are declared, base objects
+                // first, then computed properties
+                let lineage = self.lineage(&instance.class_name)?;
+                let mut names: Vec<String> = lineage.iter().rev()
+                    .flat_map(|class| class.fields.iter().map(|(field, _)| field.clone()))
+                    .collect();
+                let mut others: Vec<String> = instance.fields.borrow().keys().filter(|field| !names.contains(field)).cloned().collect();
+                others.sort();
+                let mut computed: Vec<String> = lineage.iter().flat_map(|class| class.getters.keys().cloned()).collect();
+                computed.sort();
+                for field in others.into_iter().chain(computed) {
+                    if !names.contains(&field) {
+                        names.push(field);
+                    }
+                }
+                self.stack.push(Value::list(names.into_iter().map(Value::String).collect()));
+                Ok(())
+            },
+            ("has_task", Value::Object(instance)) => {
+                let found = self.find_member(&instance.class_name, |class| class.methods.get(&member)).is_some();
+                self.stack.push(Value::Boolean(found));
+                Ok(())
+            },
+            ("has_task", Value::Class(class_name)) => {
+                let found = self.find_member(class_name, |class| class.shared_methods.get(&member)).is_some();
+                self.stack.push(Value::Boolean(found));
+                Ok(())
+            },
+            ("get_property", _) => {
+                self.stack.push(args.swap_remove(0));
+                self.execute_opcode(&OpCode::GetProperty(member))
+            },
+            ("set_property", _) => {
+                // Setting gives nothing back, so that is what the call leaves
+                let value = args.pop().unwrap_or(Value::Null);
+                self.stack.push(Value::Null);
+                self.stack.push(args.swap_remove(0));
+                self.stack.push(value);
+                self.execute_opcode(&OpCode::SetProperty(member))
+            },
+            (_, other) => Err(Failure::new("TypeError", format!("Type mismatch: '{}' requires an Object, got {}", name, other.nested()))),
+        }
+    }
+
+    // `evaluate code` compiles and runs Text, giving back the value of its
+    // last line (when that is an expression) or the error that stopped it.
+    // It shares this program's top-level variables, Tasks and Objects;
+    // `in isolation` runs it in a fresh Runtime that has the same limits and
+    // allowed effects instead
+    fn evaluate(&mut self, args: &[Value]) -> Result<Value, Failure> {
+        let (code, isolated) = match args {
+            [Value::String(code)] => (code, false),
+            [Value::String(code), Value::Boolean(isolated)] => (code, *isolated),
+            [other, ..] => return Err(Failure::new("TypeError", format!("Type mismatch: 'evaluate' requires code as Text, got {}", other.nested()))),
+            [] => return Err(Failure::new("ArgumentError", "'evaluate' needs code to run")),
+        };
+        if self.evaluations >= MAX_EVALUATIONS {
+            return Err(Failure::new("RecursionError", format!("Too much recursion: more than {} 'evaluate' calls in progress", MAX_EVALUATIONS)));
+        }
+
+        self.evaluations += 1;
+        let result = match isolated {
+            true => {
+                let mut runtime = Runtime::with_settings(self.settings.clone());
+                runtime.debug_output = false;
+                runtime.fuel = self.fuel;
+                runtime.interrupted = Arc::clone(&self.interrupted);
+                runtime.evaluations = self.evaluations;
+                runtime.output = std::mem::replace(&mut self.output, Box::new(io::sink()));
+                let result = runtime.evaluate_code(code);
+                self.output = runtime.output;
+                self.fuel = runtime.fuel;
+                let inner = runtime.stats;
+                self.stats.instructions += inner.instructions;
+                self.stats.allocations += inner.allocations;
+                self.stats.gc_cycles += inner.gc_cycles;
+                result
+            },
+            false => self.evaluate_code(code),
+        };
+        self.evaluations -= 1;
+        // Running out of fuel or memory, or being interrupted, inside still
+        // ends the whole program
+        self.halting = result.is_err();
+        result.map_err(|message| Failure::new("Error", message))
+    }
+
+    // Compiles code and runs it above whatever is running now, catching
+    // anything it raises as the result
+    fn evaluate_code(&mut self, code: &str) -> Result<Value, String> {
+        let previous = std::mem::replace(&mut self.source_name, Arc::from("<evaluated>"));
+        self.sources.insert(Arc::clone(&self.source_name), code.lines().map(str::to_string).collect());
+        let compiled = self.parse_source(code).and_then(|(_, ast)| {
+            let gives_value = matches!(ast.last(), Some(Node::ExpressionStmt(_)));
+            Ok((self.compile(ast)?, gives_value))
+        });
+        self.source_name = previous;
+        let (compiled, gives_value) = match compiled {
+            Ok(compiled) => compiled,
+            // Code that doesn't compile gives a plain Error
+            Err(message) => return Ok(Value::Object(Self::error_value("Error", message))),
+        };
+        let mut script = self.install(&compiled);
+        if gives_value && matches!(script.body.last(), Some(OpCode::Pop)) {
+            // Keep the last expression's value instead of discarding it
+            let mut body = script.body.clone();
+            body.pop();
+            script = Arc::new(Function { name: script.name.clone(), params: Vec::new(), body, lines: script.lines.clone() });
+        }
+
+        let (depth, base) = (self.frames.len(), self.stack.len());
+        let mut frame = Frame::new(Arc::clone(&script), None, true);
+        frame.stack_base = base;
+        // Resuming past the end finishes the code with the error as its value
+        frame.handlers.push(Handler { target: script.body.len(), stack_len: base });
+        self.frames.push(frame);
+        self.update_redeclared();
+        while self.frames.len() > depth {
+            if let Err(message) = self.execute_next() {
+                self.frames.truncate(depth);
+                self.stack.truncate(base);
+                return Err(message);
+            }
+        }
+        let result = if self.stack.len() > base { self.stack.pop().unwrap_or(Value::Null) } else { Value::Null };
+        self.stack.truncate(base);
+        Ok(result)
+    }
+
+    // Whether an Object's field, or an Object kind's shared one, has been set
+    fn has_value(&self, object: &Value, name: &str) -> bool {
+        match object {
+            Value::Object(instance) => instance.fields.borrow().contains_key(name),
+            Value::Class(class_name) => self.shared_field(class_name, name)
+                .and_then(|(owner, _)| self.shared.get(&owner))
+                .is_some_and(|fields| fields.contains_key(name)),
+            _ => false,
+        }
+    }
+
+    // Looks up a method or computed property on a class, its behaviors or any of its bases
+    fn find_member<F>(&self, class_name: &str, lookup: F) -> Option<Arc<Function>>
+    where
+        F: Fn(&Class) -> Option<&Arc<Function>>,
+    {
+        self.lineage(class_name).ok()?.iter()
+            .find_map(|class| lookup(class).cloned())
+    }
+
+    // The declared type of a stored field, searching behaviors and base objects too
+    fn field_type(&self, class_name: &str, field: &str) -> Option<Type> {
+        self.lineage(class_name).ok()?.iter()
+            .find_map(|class| class.fields.iter().find(|(name, _)| name == field).cloned())
+            .and_then(|(_, type_name)| type_name)
+            .map(|name| Type::from_name(&name).unwrap_or(Type::Object))
+    }
+
+    // Whether class_name is target, inherits from it or behaves like it
+    fn is_kind_of(&self, class_name: &str, target: &str) -> bool {
+        self.lineage(class_name)
+            .map(|lineage| lineage.iter().any(|class| class.name == target))
+            .unwrap_or(false)
+    }
+
+    // The object kind whose `build` block runs for a new instance of class_name
+    fn find_constructor(&self, class_name: &str) -> Option<Arc<Class>> {
+        let mut current = self.classes.get(class_name);
+        while let Some(class) = current {
+            if class.constructor.is_some() {
+                return Some(Arc::clone(class));
             }
+            current = class.base.as_ref().and_then(|base| self.classes.get(base));
         }
         None
     }
 
+    // Puts labeled constructor arguments into parameter order
+    fn order_arguments(class: &Class, args: Vec<Value>, labels: &[Option<String>]) -> Result<Vec<Value>, Failure> {
+        if labels.iter().all(Option::is_none) {
+            return Ok(args);
+        }
+        if labels.iter().any(Option::is_none) {
+            return Err(Failure::new("ArgumentError", format!("new {} cannot mix labeled and unlabeled arguments", class.name)));
+        }
+
+        let mut ordered: Vec<Option<Value>> = vec![None; class.constructor_labels.len()];
+        for (label, arg) in labels.iter().flatten().zip(args) {
+            let position = class.constructor_labels.iter().position(|known| known == label)
+                .ok_or_else(|| Failure::new("ArgumentError", format!("new {} has no argument labeled '{}'", class.name, label)))?;
+            if ordered[position].replace(arg).is_some() {
+                return Err(Failure::new("ArgumentError", format!("new {} was given '{}' twice", class.name, label)));
+            }
+        }
+        ordered.into_iter().zip(&class.constructor_labels)
+            .map(|(arg, label)| arg.ok_or_else(|| Failure::new("ArgumentError", format!("new {} is missing '{}'", class.name, label))))
+            .collect()
+    }
+
+    // The object kind that declares a shared field, and the field's type
+    fn shared_field(&self, class_name: &str, field: &str) -> Option<(String, Option<Type>)> {
+        let mut current = self.classes.get(class_name);
+        while let Some(class) = current {
+            if let Some((_, type_name)) = class.shared_fields.iter().find(|(name, _)| name == field) {
+                let declared_type = type_name.as_deref().map(|name| Type::from_name(name).unwrap_or(Type::Object));
+                return Some((class.name.clone(), declared_type));
+            }
+            current = class.base.as_ref().and_then(|base| self.classes.get(base));
+        }
+        None
+    }
+
+    // Pushes a frame for a Task; its result lands on the stack when it returns
+    fn call_function(&mut self, function: Arc<Function>, args: Vec<Value>, receiver: Option<Value>, discard_result: bool) -> Result<(), Failure> {
+        if args.len() != function.params.len() {
+            return Err(Failure::new("ArgumentError", format!("{} expects {} argument(s), got {}",
+                function.name, function.params.len(), args.len())));
+        }
+
+        // The script itself occupies the first frame
+        if self.frames.len() > self.settings.max_call_depth {
+            return Err(Failure::new("RecursionError", format!("Too much recursion: more than {} Tasks in progress\n{}",
+                self.settings.max_call_depth, self.call_trace())));
+        }
+
+        let mut frame = Frame::new(Arc::clone(&function), receiver, false);
+        frame.discard_result = discard_result;
+        for ((param, type_name), arg) in function.params.iter().zip(args) {
+            if let Some(type_name) = type_name {
+                let declared_type = Type::from_name(type_name).unwrap_or(Type::Object);
+                let arg_type = Analyzer::type_of_value(&arg);
+                if !declared_type.accepts(&arg_type) {
+                    return Err(Failure::new("TypeError", format!("{} expects {} to be {}, got {}",
+                        function.name, param, declared_type, arg_type)));
+                }
+                frame.local_types.insert(param.clone(), declared_type);
+            }
+            frame.locals.insert(param.clone(), arg);
+        }
+        frame.stack_base = self.stack.len();
+        self.frames.push(frame);
+        Ok(())
+    }
+
+    // The active calls, innermost first, with runs of the same Task folded together
+    fn call_trace(&self) -> String {
+        let mut lines: Vec<(String, usize)> = Vec::new();
+        for frame in self.frames.iter().rev() {
+            match lines.last_mut() {
+                Some((name, count)) if *name == frame.function.name => *count += 1,
+                _ => lines.push((frame.function.name.clone(), 1)),
+            }
+        }
+        lines.iter()
+            .map(|(name, count)| match count {
+                1 => format!("  in {}", name),
+                _ => format!("  in {} ({} calls)", name, count),
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
     // Helper methods for the Runtime impl
-    fn binary_op<F>(&self, a: Value, b: Value, op: F) -> Result<Value, String>
+    fn binary_op<F>(&self, a: Value, b: Value, symbol: &str, op: F) -> Result<Value, Failure>
     where
         F: Fn(f64, f64) -> f64,
     {
         match (a, b) {
-            (Value::Number(x), Value::Number(y)) => Ok(Value::Number(op(x, y))),
-            _ => Err("Invalid operands for arithmetic operation".to_string()),
+            (Value::Number(x), Value::Number(y)) => Ok(Value::Number(self.settings.numbers.arithmetic(symbol, x, y, op))),
+            // Money adds to and divides into Money of the same currency, and
+            // is multiplied or divided by numbers, staying to the cent
+            (Value::Money(x), Value::Money(y)) => match symbol {
+                "+" | "-" => x.add(y, symbol == "-").map(Value::Money),
+                "/" => x.ratio(y).map(Value::Number),
+                _ => Err(Failure::new("TypeError", format!("Type mismatch: cannot use {} on two amounts of money", symbol))),
+            },
+            (Value::Money(x), Value::Number(y)) if symbol == "*" => x.times(y).map(Value::Money),
+            (Value::Money(x), Value::Number(y)) if symbol == "/" => x.divided_by(y).map(Value::Money),
+            (Value::Number(x), Value::Money(y)) if symbol == "*" => y.times(x).map(Value::Money),
+            // `-price` is read as 0 - price
+            (Value::Number(x), Value::Money(y)) if symbol == "-" && x == 0.0 => y.times(-1.0).map(Value::Money),
+            // Durations of whole days move dates along, and two dates are
+            // some days apart
+            (Value::Date(x), Value::Quantity(y)) if symbol == "+" || symbol == "-" => x.after(&y, symbol == "-").map(Value::Date),
+            (Value::Quantity(x), Value::Date(y)) if symbol == "+" => y.after(&x, false).map(Value::Date),
+            (Value::Date(x), Value::Date(y)) if symbol == "-" => Ok(Value::Quantity(x.since(y))),
+            (Value::Date(_), _) | (_, Value::Date(_)) => Err(Failure::new("TypeError", format!("Type mismatch: dates can only have durations added or taken away, not be used with {}", symbol))),
+            // Quantities add to and compare with those measuring the same
+            // thing, and multiply and divide into new units
+            (Value::Quantity(x), Value::Quantity(y)) => match symbol {
+                "+" | "-" => x.add(&y, symbol == "-").map(Value::Quantity),
+                "*" | "/" => Ok(match x.times(&y, symbol == "/") {
+                    (amount, Some(unit)) => Value::Quantity(Quantity { amount, unit }),
+                    (amount, None) => Value::Number(amount),
+                }),
+                _ => Err(Failure::new("TypeError", format!("Type mismatch: cannot use {} on two quantities", symbol))),
+            },
+            (Value::Quantity(x), Value::Number(y)) if symbol == "*" || symbol == "/" => {
+                Ok(Value::Quantity(Quantity { amount: op(x.amount, y), unit: x.unit }))
+            },
+            (Value::Number(x), Value::Quantity(y)) if symbol == "*" => Ok(Value::Quantity(Quantity { amount: x * y.amount, ..y })),
+            (Value::Number(x), Value::Quantity(y)) if symbol == "/" => Ok(Value::Quantity(Quantity { amount: x / y.amount, unit: y.unit.inverse() })),
+            (Value::Number(x), Value::Quantity(y)) if symbol == "-" && x == 0.0 => Ok(Value::Quantity(Quantity { amount: -y.amount, ..y })),
+            (Value::Quantity(x), other) | (other, Value::Quantity(x)) => {
+                Err(Failure::new("TypeError", format!("Type mismatch: cannot use {} on {} and {}, which has no units", symbol, x, other.nested())))
+            },
+            (Value::Money(_), _) | (_, Value::Money(_)) => Err(Failure::new("TypeError", format!("Type mismatch: Money can only be added to Money, and multiplied or divided by a number, not used with {}", symbol))),
+            _ => Err(Failure::new("TypeError", "Invalid operands for arithmetic operation")),
         }
     }
 
-    fn concat_values(&self, a: Value, b: Value) -> Result<Value, String> {
+    // Arithmetic on two Whole numbers gives a Whole, failing when the answer
+    // is past `MAX_WHOLE` either way rather than quietly losing precision; a
+    // negative power, or any Decimal, falls back to ordinary arithmetic
+    fn whole_op<C, F>(&self, a: Value, b: Value, symbol: &str, checked: C, op: F) -> Result<Value, Failure>
+    where
+        C: Fn(i64, i64) -> Option<i64>,
+        F: Fn(f64, f64) -> f64,
+    {
+        if let (Some(x), Some(y)) = (whole(&a), whole(&b)) {
+            if symbol != "^" || y >= 0 {
+                return checked(x, y).filter(|n| (MIN_WHOLE..=MAX_WHOLE).contains(n))
+                    .map(|n| Value::Number(n as f64))
+                    .ok_or_else(|| Failure::new("ArithmeticError", format!("{} {} {} does not fit in a Whole", x, symbol, y)));
+            }
+        }
+        self.binary_op(a, b, symbol, op)
+    }
+
+    // `ask "Name? "` reads a line, or nothing once input has run out. `ask
+    // "Age? " for a Whole between 1 and 120, up to 3 tries` asks again until
+    // the answer is such a Whole, and hands back an InputError (rather than
+    // raising it) when the tries or the input run out first.
+    fn ask(&mut self, args: &[Value]) -> Result<Value, Failure> {
+        if let Some(message) = self.sandbox_message(Capability::Console) {
+            self.raise_error(Self::error_value("SandboxViolation", message))?;
+        }
+        let prompt = match args.first() {
+            Some(Value::String(prompt)) => prompt.clone(),
+            Some(other) => other.to_string(),
+            None => String::new(),
+        };
+        let Some(Value::String(kind)) = args.get(1) else {
+            return Ok(self.read_answer(&prompt)?.map_or(Value::Null, Value::String));
+        };
+        let end = |value: Option<&Value>| match value {
+            None | Some(Value::Null) => Ok(None),
+            Some(Value::Number(n)) if kind == "Whole" || kind == "Decimal" => Ok(Some(*n)),
+            Some(Value::Number(_)) => Err(Failure::new("TypeError", format!("Only a Whole or a Decimal can be asked for between two numbers, not a {}", kind))),
+            Some(other) => Err(Failure::new("TypeError", format!("Type mismatch: 'ask' asks for a number between two numbers, not {}", other.nested()))),
+        };
+        let (low, high) = (end(args.get(2))?, end(args.get(3))?);
+        let tries = match args.get(4) {
+            None | Some(Value::Null) => None,
+            Some(Value::Number(n)) if n.fract() == 0.0 && *n >= 1.0 => Some(*n as usize),
+            Some(other) => return Err(Failure::new("ArgumentError", format!("'ask' needs a Whole number of tries of at least 1, got {}", other.nested()))),
+        };
+        let wanted = match (low, high) {
+            (Some(low), Some(high)) => format!("a {} between {} and {}", kind, Value::Number(low), Value::Number(high)),
+            (Some(low), None) => format!("a {} of at least {}", kind, Value::Number(low)),
+            (None, Some(high)) => format!("a {} of at most {}", kind, Value::Number(high)),
+            (None, None) if kind == "Truth" => "yes or no".to_string(),
+            (None, None) => format!("a {}", kind),
+        };
+        let mut tried = 0;
+        while tries.is_none_or(|tries| tried < tries) {
+            let Some(answer) = self.read_answer(&prompt)? else {
+                break;
+            };
+            tried += 1;
+            if let Some(value) = Self::read_answer_as(&answer, kind, low, high) {
+                return Ok(value);
+            }
+            if tries.is_none_or(|tries| tried < tries) {
+                self.output.write_all(format!("Please answer with {}.\n", wanted).as_bytes())
+                    .map_err(|e| Failure::new("Error", format!("Failed to write output: {}", e)))?;
+            }
+        }
+        let message = format!("No valid answer after {} {}: expected {}", tried, if tried == 1 { "try" } else { "tries" }, wanted);
+        Ok(Value::Object(Self::error_value("InputError", message)))
+    }
+
+    // Shows the prompt and reads a line of the answer, without its line
+    // ending, or nothing at the end of the input
+    fn read_answer(&mut self, prompt: &str) -> Result<Option<String>, Failure> {
+        self.output.write_all(prompt.as_bytes())
+            .and_then(|()| self.output.flush())
+            .map_err(|e| Failure::new("Error", format!("Failed to write output: {}", e)))?;
+        let mut line = String::new();
+        let read = match &mut self.input {
+            Some(input) => input.read_line(&mut line),
+            None => io::stdin().read_line(&mut line),
+        };
+        match read.map_err(|e| Failure::new("Error", format!("Failed to read input: {}", e)))? {
+            0 => Ok(None),
+            _ => Ok(Some(line.trim_end_matches(['\r', '\n']).to_string())),
+        }
+    }
+
+    // An answer as the kind of value asked for, if it is one and in range
+    fn read_answer_as(answer: &str, kind: &str, low: Option<f64>, high: Option<f64>) -> Option<Value> {
+        let value = match kind {
+            "Whole" | "Decimal" => read_number(answer, kind == "Whole"),
+            "Truth" => match fold_case(answer.trim()).as_str() {
+                "yes" | "true" => Value::Boolean(true),
+                "no" | "false" => Value::Boolean(false),
+                _ => Value::Null,
+            },
+            _ => Value::String(answer.to_string()),
+        };
+        let in_range = match value {
+            Value::Number(n) => low.is_none_or(|low| n >= low) && high.is_none_or(|high| n <= high),
+            Value::Null => false,
+            _ => true,
+        };
+        in_range.then_some(value)
+    }
+
+    // `the length of x`: how many items a List or Mapping holds, or how many
+    // characters, bytes or code points make up Text
+    fn length_of(args: &[Value]) -> Result<usize, Failure> {
+        let counting = match args.get(1) {
+            Some(Value::String(counting)) => counting.as_str(),
+            _ => "characters",
+        };
+        match (args.first(), counting) {
+            (Some(Value::String(text)), "characters") => Ok(graphemes(text).len()),
+            (Some(Value::String(text)), "bytes") => Ok(text.len()),
+            (Some(Value::String(text)), "code points") => Ok(text.chars().count()),
+            (Some(Value::String(_)), counting) => Err(Failure::new("ArgumentError", format!("Text has a length in characters, bytes or code points, not {}", counting))),
+            (Some(Value::List(items)), "characters") => Ok(items.borrow().len()),
+            (Some(Value::Mapping(mapping)), "characters") => Ok(mapping.borrow().len()),
+            (Some(other), "characters") => Err(Failure::new("TypeError", format!("Type mismatch: only Text, Lists and Mappings have a length, got {}", other.nested()))),
+            (Some(other), _) => Err(Failure::new("TypeError", format!("Type mismatch: only Text has a length in bytes or code points, got {}", other.nested()))),
+            (None, _) => Err(Failure::new("ArgumentError", "'length_of' needs a value")),
+        }
+    }
+
+    // The collation a language named in Text asks for, or the Runtime's own
+    // for nothing
+    fn collation_for(&self, language: Option<&Value>) -> Result<Collation, Failure> {
+        match language {
+            None | Some(Value::Null) => Ok(self.settings.collation),
+            Some(Value::String(name)) => Collation::named(name)
+                .ok_or_else(|| Failure::new("ArgumentError", format!("Unknown language for sorting: {}; try {}", name, Collation::names()))),
+            Some(other) => Err(Failure::new("TypeError", format!("Type mismatch: a language is named in Text, like \"Spanish\", not {}", other.nested()))),
+        }
+    }
+
+    // `sort names alphabetically in Spanish`: puts a List's items in order
+    // where it is. Numbers, Money, quantities and dates go smallest first,
+    // and Text in the language's alphabetical order.
+    fn sort(&self, args: &[Value]) -> Result<(), Failure> {
+        let collation = self.collation_for(args.get(1))?;
+        let items = match args.first() {
+            Some(Value::List(items)) => items,
+            Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: only a List can be sorted, not {}", other.nested()))),
+            None => return Err(Failure::new("ArgumentError", "'sort' needs a List")),
+        };
+        if items.frozen {
+            return Err(Failure::new("FrozenError", "Cannot sort: this List is frozen"));
+        }
+        // Sorts a copy, so that no lock is held while an item that holds the
+        // List itself is compared or written into the error
+        let mut sorted = items.borrow().clone();
+        let mut failure = None;
+        sorted.sort_by(|a, b| match (a, b) {
+            (Value::String(a), Value::String(b)) => collation.compare(a, b),
+            _ => self.compare_values(a, b).unwrap_or_else(|_| {
+                failure.get_or_insert((a.clone(), b.clone()));
+                std::cmp::Ordering::Equal
+            }),
+        });
+        if let Some((a, b)) = failure {
+            return Err(Failure::new("TypeError", format!("Type mismatch: cannot sort a List holding {} and {}", a.nested(), b.nested())));
+        }
+        *items.change().ok_or_else(|| Failure::new("FrozenError", "Cannot sort: this List is frozen"))? = sorted;
+        Ok(())
+    }
+
+    // `collate(a, b)` or `collate(a, b, "Spanish")`: -1 when a comes before
+    // b alphabetically, 1 when it comes after and 0 when they are the same
+    fn collate(&self, args: &[Value]) -> Result<f64, Failure> {
+        let (Some(Value::String(a)), Some(Value::String(b))) = (args.first(), args.get(1)) else {
+            let other = args.iter().take(2).find(|value| !matches!(value, Value::String(_)));
+            return Err(match other {
+                Some(other) => Failure::new("TypeError", format!("Type mismatch: only Text can be collated, got {}", other.nested())),
+                None => Failure::new("ArgumentError", "'collate' needs two Texts"),
+            });
+        };
+        let collation = self.collation_for(args.get(2))?;
+        Ok(collation.compare(a, b) as i8 as f64)
+    }
+
+    // `a is the same text as b`, ignoring case if asked, or the opposite
+    // with `is not`
+    fn compare_text(args: &[Value]) -> Result<bool, Failure> {
+        let [Value::String(left), Value::String(right), Value::Boolean(ignoring_case), Value::Boolean(negated)] = args else {
+            let other = args.iter().take(2).find(|value| !matches!(value, Value::String(_)));
+            return Err(match other {
+                Some(other) => Failure::new("TypeError", format!("Type mismatch: only Text can be compared as text, got {}", other.nested())),
+                None => Failure::new("ArgumentError", "'compare_text' needs two Texts and two Truths"),
+            });
+        };
+        let same = match ignoring_case {
+            true => fold_case(left) == fold_case(right),
+            false => left == right,
+        };
+        Ok(same != *negated)
+    }
+
+    // `x written with ...`: the number to the decimal places asked for, or
+    // as it would show, with thousands separated by commas and at least as
+    // many digits before the point as asked for
+    fn format_number(args: &[Value]) -> Result<String, Failure> {
+        let count = |value: &Value, what: &str| match value {
+            Value::Null => Ok(None),
+            Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 && *n <= MAX_WRITTEN as f64 => Ok(Some(*n as usize)),
+            Value::Number(n) if n.fract() == 0.0 && *n >= 0.0 => Err(Failure::new("ArgumentError",
+                format!("A number can be written with at most {} {}, not {}", MAX_WRITTEN, what, n))),
+            other => Err(Failure::new("TypeError", format!("Type mismatch: expected a Whole number of {}, got {}", what, other.nested()))),
+        };
+        let [number, places, Value::Boolean(commas), digits, Value::Boolean(percent)] = args else {
+            return Err(Failure::new("ArgumentError", format!("'format_number' takes 5 arguments, got {}", args.len())));
+        };
+        // Money always has its currency's decimal places
+        if let Value::Money(money) = number {
+            return match (places, digits, percent) {
+                (Value::Null, Value::Null, false) => Ok(money.written(*commas)),
+                _ => Err(Failure::new("TypeError", format!("Type mismatch: Money is written with its symbol and {} decimal places, so only commas can be added", money.currency.places()))),
+            };
+        }
+        // A quantity's amount is written as a number would be, then its units
+        if let Value::Quantity(quantity) = number {
+            let mut args = args.to_vec();
+            args[0] = Value::Number(quantity.amount);
+            let written = Self::format_number(&args)?;
+            return Ok(format!("{} {}", written, quantity.unit.phrase(written != "1" && written != "-1")));
+        }
+        let Value::Number(number) = number else {
+            return Err(Failure::new("TypeError", format!("Type mismatch: only numbers can be written with decimal places or commas, got {}", number.nested())));
+        };
+        let written = match (count(places, "decimal places")?, percent) {
+            // Halves round away from zero, as `round` has them. Past 16
+            // places, or once scaling up leaves no fraction, a Decimal has
+            // no digits left to round, and scaling back down would only
+            // lose some.
+            (Some(places), percent) => {
+                let scale = 10f64.powi(places as i32);
+                let shown = if *percent { number.abs() * 100.0 } else { number.abs() };
+                let rounded = match places < 17 && shown * scale <= MAX_WHOLE as f64 {
+                    true => (shown * scale).round() / scale,
+                    false => shown,
+                };
+                format!("{:.*}", places, rounded)
+            },
+            // Moving the point two places along keeps 0.07 from showing as
+            // 7.000000000000001%
+            (None, true) => {
+                let written = number.abs().to_string();
+                let (whole, fraction) = written.split_once('.').unwrap_or((&written, ""));
+                let fraction = format!("{:0<2}", fraction);
+                let whole = format!("{}{}", whole, &fraction[..2]).trim_start_matches('0').to_string();
+                match &fraction[2..] {
+                    "" => format!("{:0>1}", whole),
+                    rest => format!("{:0>1}.{}", whole, rest),
+                }
+            },
+            (None, false) => number.abs().to_string(),
+        };
+        let (whole, fraction) = written.split_at(written.find('.').unwrap_or(written.len()));
+        let mut whole = format!("{:0>1$}", whole, count(digits, "digits")?.unwrap_or(0));
+        if *commas {
+            let groups: Vec<&str> = whole.as_bytes().rchunks(3).rev().map(|group| std::str::from_utf8(group).unwrap()).collect();
+            whole = groups.join(",");
+        }
+        // No minus sign on a number that rounds to zero
+        let sign = if *number < 0.0 && written.bytes().any(|digit| matches!(digit, b'1'..=b'9')) { "-" } else { "" };
+        Ok(format!("{}{}{}{}", sign, whole, fraction, if *percent { "%" } else { "" }))
+    }
+
+    // `wait 2 seconds`: pauses the program for a duration
+    fn wait(&self, args: &[Value]) -> Result<(), Failure> {
+        let seconds = Unit::parse("seconds").ok_or_else(|| Failure::new("Error", "'wait' can't measure seconds"))?;
+        let duration = match args.first() {
+            Some(Value::Quantity(duration)) if duration.unit.compatible(&seconds) => duration.convert(&seconds)?.amount,
+            Some(other) => return Err(Failure::new("TypeError", format!("Type mismatch: 'wait' needs a duration, like 2 seconds, got {}", other.nested()))),
+            None => return Err(Failure::new("ArgumentError", "'wait' needs a duration")),
+        };
+        let duration = std::time::Duration::try_from_secs_f64(duration)
+            .map_err(|_| Failure::new("ArgumentError", format!("Cannot wait {} seconds", duration)))?;
+        // A little at a time, so an interrupt needn't wait for the rest
+        let started = Instant::now();
+        while !self.interrupted.load(Ordering::Relaxed) && started.elapsed() < duration {
+            std::thread::sleep(duration.saturating_sub(started.elapsed()).min(std::time::Duration::from_millis(50)));
+        }
+        Ok(())
+    }
+
+    // `x in kilometers`: a quantity in other units measuring the same thing
+    fn convert(args: &[Value]) -> Result<Value, Failure> {
+        match args {
+            [Value::Quantity(quantity), Value::String(units)] => {
+                let unit = Unit::parse(units).ok_or_else(|| Failure::new("ArgumentError", format!("Unknown units: {}", units)))?;
+                quantity.convert(&unit).map(Value::Quantity)
+            },
+            [other, _] => Err(Failure::new("TypeError", format!("Type mismatch: only quantities can be converted to other units, got {}", other.nested()))),
+            _ => Err(Failure::new("ArgumentError", format!("'convert' takes 2 arguments, got {}", args.len()))),
+        }
+    }
+
+    // `wrapping_add(a, b)` and the like: Whole arithmetic that wraps around
+    // past the largest or smallest Whole instead of failing, as a 54-bit
+    // integer would
+    fn wrapping_op(&self, name: &str, args: &[Value]) -> Result<Value, Failure> {
+        let (x, y) = match args {
+            [a, b] => whole(a).zip(whole(b))
+                .ok_or_else(|| Failure::new("TypeError", format!("Type mismatch: '{}' requires two Whole numbers", name)))?,
+            _ => return Err(Failure::new("ArgumentError", format!("'{}' takes 2 arguments, got {}", name, args.len()))),
+        };
+        let result = match name {
+            "wrapping_add" => x.wrapping_add(y),
+            "wrapping_subtract" => x.wrapping_sub(y),
+            _ => x.wrapping_mul(y),
+        };
+        // Keeps the low 54 bits, with the top one of them as the sign
+        Ok(Value::Number((result << 10 >> 10) as f64))
+    }
+
+    // `round x`, `round x down`, `round x up` or `round x to even`, to a
+    // whole number or to the decimal places given
+    fn round(&self, name: &str, args: &[Value]) -> Result<Value, Failure> {
+        let (value, places) = match args {
+            [Value::Number(n)] => (*n, 0),
+            [Value::Number(n), Value::Number(places)] if places.fract() == 0.0 && *places >= 0.0 => (*n, *places as i32),
+            _ => return Err(Failure::new("TypeError", "Type mismatch: rounding requires a number and a Whole number of decimal places")),
+        };
+        let scale = 10f64.powi(places);
+        let scaled = value * scale;
+        // Past 16 places, or when scaling up overflows, there are no digits
+        // left to round
+        if places >= 17 || !scaled.is_finite() {
+            return Ok(Value::Number(value));
+        }
+        let rounded = match name {
+            "round_down" => scaled.floor(),
+            "round_up" => scaled.ceil(),
+            "round_even" => scaled.round_ties_even(),
+            _ => scaled.round(),
+        };
+        Ok(Value::Number(rounded / scale))
+    }
+
+    // `a / b`, or `a % b` for the `remainder`, failing instead of giving
+    // infinity or NaN when b is zero
+    fn divide(&self, a: Value, b: Value, remainder: bool) -> Result<Value, Failure> {
+        match (&a, &b) {
+            (Value::Number(_) | Value::Quantity(_), Value::Number(0.0) | Value::Quantity(Quantity { amount: 0.0, .. })) => Err(Failure::new("ArithmeticError", match remainder {
+                true => format!("Cannot find the remainder of {} divided by zero", a),
+                false => format!("Cannot divide {} by zero", a),
+            })),
+            _ if remainder => self.binary_op(a, b, "%", |x, y| x % y),
+            _ => self.binary_op(a, b, "/", |x, y| x / y),
+        }
+    }
+
+    fn concat_values(&self, a: Value, b: Value) -> Result<Value, Failure> {
         match (a, b) {
             (Value::String(s1), Value::String(s2)) => Ok(Value::String(s1 + &s2)),
-            _ => Err("Can only concatenate strings".to_string()),
+            _ => Err(Failure::new("TypeError", "Can only concatenate strings")),
         }
     }
 
-    fn execute(&mut self, instructions: &[OpCode]) -> Result<(), String> {
-        for instruction in instructions {
-            match instruction {
-                OpCode::Show => {
-                    if let Some(value) = self.stack.pop() {
-                        println!("{}", value);
-                    }
-                },
-                OpCode::Push(value) => {
-                    self.stack.push(value.clone());
-                },
-                OpCode::LoadVar(name) => {
-                    if let Some(value) = self.variables.get(name) {
-                        self.stack.push(value.clone());
-                    } else {
-                        return Err(format!("Undefined variable: {}", name));
-                    }
-                },
-                OpCode::StoreVar(name) => {
-                    let value = self.stack.pop().ok_or("Stack underflow")?;
-                    
-                    // Check type if variable has a declared type
-                    if let Some(declared_type) = self.variable_types.get(name) {
-                        let value_type = match &value {
-                            Value::Number(n) => {
-                                if n.fract() == 0.0 { Type::Whole } else { Type::Decimal }
-                            },
-                            Value::String(_) => Type::Text,
-                            Value::Boolean(_) => Type::Truth,
-                            Value::Null => Type::Nothing,
-                            Value::Object(_) => Type::Object,
-                            Value::Promise(_) => Type::Promise(Box::new(Type::Any)),
-                            Value::List(_) => Type::List(Box::new(Type::Any)),
-                            Value::Mapping(_) => Type::Map { key: Box::new(Type::Text), value: Box::new(Type::Any) },
-                        };
-                        
-                        if declared_type != &value_type {
-                            return Err(format!("Type mismatch: cannot assign {:?} to variable of type {:?}", 
-                                value_type, declared_type));
-                        }
-                    }
-                    
-                    self.variables.insert(name.clone(), value);
-                },
-                OpCode::Add | OpCode::Subtract | OpCode::Multiply | OpCode::Divide => {
-                    let b = self.stack.pop().ok_or("Stack underflow")?;
-                    let a = self.stack.pop().ok_or("Stack underflow")?;
-                    let result = match instruction {
-                        OpCode::Add => self.binary_op(a, b, |x, y| x + y)?,
-                        OpCode::Subtract => self.binary_op(a, b, |x, y| x - y)?,
-                        OpCode::Multiply => self.binary_op(a, b, |x, y| x * y)?,
-                        OpCode::Divide => self.binary_op(a, b, |x, y| x / y)?,
-                        _ => unreachable!(),
-                    };
-                    self.stack.push(result);
-                },
-                OpCode::Pop => {
-                    self.stack.pop();
-                },
-                OpCode::Duplicate => {
-                    if let Some(value) = self.stack.last() {
-                        self.stack.push(value.clone());
-                    }
-                },
-                _ => return Err(format!("Unhandled opcode: {:?}", instruction)),
+    // `a + b + c ...`: Text joined into one buffer sized up front, or
+    // numbers added a pair at a time like `Add`, Wholes checked the same way
+    fn concat_all(&self, parts: Vec<Value>) -> Result<Value, Failure> {
+        if let Some(Value::String(_)) = parts.first() {
+            let size = parts.iter().map(|part| if let Value::String(text) = part { text.len() } else { 0 }).sum();
+            let mut result = String::with_capacity(size);
+            for part in parts {
+                match part {
+                    Value::String(text) => result.push_str(&text),
+                    _ => return Err(Failure::new("TypeError", "Can only concatenate strings")),
+                }
             }
+            return Ok(Value::String(result));
+        }
+        let mut parts = parts.into_iter();
+        let first = parts.next().ok_or_else(underflow)?;
+        parts.try_fold(first, |total, part| self.whole_op(total, part, "+", i64::checked_add, |x, y| x + y))
+    }
+
+    fn compare_values(&self, a: &Value, b: &Value) -> Result<std::cmp::Ordering, Failure> {
+        match (a, b) {
+            (Value::Number(x), Value::Number(y)) => {
+                x.partial_cmp(y).ok_or_else(|| Failure::new("ArithmeticError", "Cannot order NaN"))
+            },
+            (Value::String(x), Value::String(y)) => Ok(x.cmp(y)),
+            (Value::Money(x), Value::Money(y)) => x.compare(*y),
+            (Value::Quantity(x), Value::Quantity(y)) => x.compare(y),
+            (Value::Date(x), Value::Date(y)) => Ok(x.cmp(y)),
+            _ => Err(Failure::new("TypeError", format!("Cannot compare {} with {}", a, b))),
         }
-        Ok(())
     }
 }
 
 
-fn main() -> Result<(), String> {
-    let mut runtime = Runtime::new();
-    runtime.run_repl()
+// What an instruction fails with when the values it works on aren't there,
+// which compiled code never leaves it to do
+fn underflow() -> Failure {
+    Failure::new("Error", "Stack underflow")
+}
+
+// The largest and smallest Wholes: every whole number between them is held
+// exactly, where past them some are skipped
+const MAX_WHOLE: i64 = (1 << 53) - 1;
+const MIN_WHOLE: i64 = -(1 << 53);
+
+// The number as a Whole, when it is one that fits
+fn whole(value: &Value) -> Option<i64> {
+    match value {
+        Value::Number(n) if n.fract() == 0.0 && (MIN_WHOLE as f64..=MAX_WHOLE as f64).contains(n) => Some(*n as i64),
+        _ => None,
+    }
+}
+
+// The times, in milliseconds, that `measure how long it takes to` took over
+// each run of its block
+struct Timing {
+    runs: usize,
+    fastest: f64,
+    median: f64,
+    mean: f64,
+}
+
+impl Timing {
+    fn of(times: &[Value]) -> Option<Timing> {
+        let mut times: Vec<f64> = times.iter().filter_map(|time| match time {
+            Value::Number(n) => Some(*n),
+            _ => None,
+        }).collect();
+        if times.is_empty() {
+            return None;
+        }
+        times.sort_by(f64::total_cmp);
+        let middle = times.len() / 2;
+        let median = match times.len() % 2 {
+            0 => (times[middle - 1] + times[middle]) / 2.0,
+            _ => times[middle],
+        };
+        Some(Timing {
+            runs: times.len(),
+            fastest: times[0],
+            median,
+            mean: times.iter().sum::<f64>() / times.len() as f64,
+        })
+    }
+
+    // What a named measurement keeps: the runs and each time in milliseconds
+    fn mapping(&self) -> Value {
+        let mut mapping = Mapping::new();
+        mapping.insert(Value::String("runs".to_string()), Value::Number(self.runs as f64));
+        mapping.insert(Value::String("fastest".to_string()), Value::Number(self.fastest));
+        mapping.insert(Value::String("median".to_string()), Value::Number(self.median));
+        mapping.insert(Value::String("mean".to_string()), Value::Number(self.mean));
+        Value::mapping(mapping)
+    }
+}
+
+impl fmt::Display for Timing {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} run{}: fastest {:.3} ms, median {:.3} ms, mean {:.3} ms",
+            self.runs, if self.runs == 1 { "" } else { "s" }, self.fastest, self.median, self.mean)
+    }
+}
+
+// Text with the differences between cases taken away, so `Straße` and
+// `STRASSE` both become `strasse`: uppercasing first spells out letters
+// like `ß` that have no single capital
+fn fold_case(text: &str) -> String {
+    text.to_uppercase().to_lowercase()
+}
+
+// `the number in x` and `try to read x as a Whole`: the number the Text
+// holds, which may have commas between its thousands, or nothing when it
+// holds anything else
+fn read_number(text: &str, whole_only: bool) -> Value {
+    let text = text.trim();
+    let unsigned = text.strip_prefix(['-', '+']).unwrap_or(text);
+    let (before, after) = unsigned.split_once('.').unwrap_or((unsigned, "0"));
+    let digits = |part: &str| !part.is_empty() && part.bytes().all(|digit| digit.is_ascii_digit());
+    let grouped = match before.contains(',') {
+        true => before.split(',').enumerate().all(|(i, group)| digits(group) && (group.len() == 3 || (i == 0 && group.len() < 3))),
+        false => digits(before) || (before.is_empty() && unsigned.starts_with('.')),
+    };
+    if !grouped || !digits(after) {
+        return Value::Null;
+    }
+    if whole_only {
+        // Read exactly, so a number just past the Wholes isn't rounded into them
+        let sign = &text[..text.len() - unsigned.len()];
+        let before = if before.is_empty() { "0" } else { before };
+        return match format!("{}{}", sign, before.replace(',', "")).parse::<i64>() {
+            Ok(n) if after.bytes().all(|digit| digit == b'0') && (MIN_WHOLE..=MAX_WHOLE).contains(&n) => Value::Number(n as f64),
+            _ => Value::Null,
+        };
+    }
+    match text.replace(',', "").parse::<f64>() {
+        // `-0` is read as 0, as arithmetic would give it
+        Ok(number) => Value::Number(number + 0.0),
+        Err(_) => Value::Null,
+    }
+}
+
+// Splits a line into words the way a shell does: quotes keep spaces in a
+// word, and a backslash takes the next character as it is (except inside
+// single quotes)
+fn shell_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut chars = line.chars();
+    let mut word: Option<String> = None;
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => words.extend(word.take()),
+            '\\' => word.get_or_insert_with(String::new).extend(chars.next()),
+            '\'' | '"' => {
+                let word = word.get_or_insert_with(String::new);
+                loop {
+                    match chars.next() {
+                        Some(end) if end == c => break,
+                        Some('\\') if c == '"' => word.extend(chars.next()),
+                        Some(inner) => word.push(inner),
+                        None => return Err(format!("Missing closing {} in '{}'", c, line)),
+                    }
+                }
+            },
+            c => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+    words.extend(word);
+    Ok(words)
 }
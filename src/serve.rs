@@ -0,0 +1,219 @@
+use crate::cache::Compiled;
+use crate::generator::Lock;
+use crate::json::Json;
+use crate::parser;
+use crate::runtime::{Capability, Runtime};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+// Where `nair serve` listens unless told otherwise
+const DEFAULT_ADDRESS: &str = "127.0.0.1:8000";
+
+// What a request's program may use unless --fuel or --memory-limit say
+// otherwise: enough for any exercise, not enough to hold up the server
+const DEFAULT_FUEL: u64 = 1_000_000;
+const DEFAULT_MEMORY_LIMIT: usize = 16 * 1024 * 1024;
+
+// The stack each request's thread gets: as much as the main thread
+// usually has, which the parser's nesting limit is measured against
+const WORKER_STACK: usize = 8 * 1024 * 1024;
+
+// Larger request bodies are turned away unread
+const MAX_BODY: usize = 1024 * 1024;
+
+// Most bytes a program may show; writing more ends it with an error
+const MAX_OUTPUT: usize = 1024 * 1024;
+
+// Most requests answered at once; more are told the server is busy
+const MAX_WORKERS: usize = 64;
+
+// How long a client may take to send its request, or to take the reply,
+// before the connection is dropped
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Limits on each program `nair serve` runs.
+pub struct ServeOptions {
+    pub fuel: Option<u64>,
+    pub memory_limit: Option<usize>,
+}
+
+/// Serves a JSON API for running programs, as a playground backend needs:
+/// `POST /eval` with `{"source": "..."}` runs the source in a fresh
+/// sandboxed Runtime with limited fuel and memory, and answers with what it
+/// showed, the analyzer's diagnostics, the error it stopped with and how
+/// long compiling and running took. Each connection gets a thread of its
+/// own, so a slow program doesn't keep others waiting, up to a limit past
+/// which requests are answered `503 Service Unavailable`.
+pub fn serve(args: &[String], options: &ServeOptions) -> Result<(), String> {
+    let address = match args {
+        [] => DEFAULT_ADDRESS,
+        [flag, address] if flag == "--listen" => address,
+        _ => return Err("Usage: nair serve [--listen address] [--fuel N] [--memory-limit N]".to_string()),
+    };
+    let listener = TcpListener::bind(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    let bound = listener.local_addr().map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    eprintln!("Serving POST /eval on http://{} (Ctrl-C stops)", bound);
+
+    let fuel = options.fuel.unwrap_or(DEFAULT_FUEL);
+    let memory_limit = options.memory_limit.unwrap_or(DEFAULT_MEMORY_LIMIT);
+    let busy = Arc::new(AtomicUsize::new(0));
+    for mut stream in listener.incoming().flatten() {
+        if busy.load(Ordering::SeqCst) >= MAX_WORKERS {
+            let _ = stream.set_write_timeout(Some(TIMEOUT));
+            let _ = reply(&mut stream, "503 Service Unavailable", Some(problem("The server is busy; try again shortly")));
+            continue;
+        }
+        let working = Working::start(&busy);
+        let worker = std::thread::Builder::new().stack_size(WORKER_STACK).spawn(move || {
+            let _working = working;
+            // A client that went away needs no answer
+            let _ = answer(stream, fuel, memory_limit);
+        });
+        if let Err(e) = worker {
+            eprintln!("Failed to start a worker: {}", e);
+        }
+    }
+    Ok(())
+}
+
+// Reads one request off the connection and replies to it
+fn answer(stream: TcpStream, fuel: u64, memory_limit: usize) -> io::Result<()> {
+    stream.set_read_timeout(Some(TIMEOUT))?;
+    stream.set_write_timeout(Some(TIMEOUT))?;
+    let mut writer = stream.try_clone()?;
+    let mut reader = BufReader::new(stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 {
+            break;
+        }
+        let header = header.trim();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("Content-Length") {
+                length = value.trim().parse().unwrap_or(0);
+            }
+        }
+    }
+
+    let mut words = request_line.split_whitespace();
+    let (status, body) = match (words.next(), words.next()) {
+        // Browsers ask first whether a page from elsewhere may post here
+        (Some("OPTIONS"), _) => ("204 No Content", None),
+        (Some("POST"), Some("/eval")) if length > MAX_BODY => ("413 Payload Too Large", Some(problem("The source is too long"))),
+        (Some("POST"), Some("/eval")) => {
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body)?;
+            match Json::parse(&String::from_utf8_lossy(&body)).ok().and_then(|request| request.get("source").and_then(Json::as_str).map(str::to_string)) {
+                Some(source) => ("200 OK", Some(evaluate(&source, fuel, memory_limit))),
+                None => ("400 Bad Request", Some(problem("Expected a JSON object with the program as \"source\""))),
+            }
+        },
+        (Some(_), Some("/eval")) => ("405 Method Not Allowed", Some(problem("Programs are sent with POST"))),
+        _ => ("404 Not Found", Some(problem("The only endpoint is POST /eval"))),
+    };
+
+    reply(&mut writer, status, body)
+}
+
+fn reply(writer: &mut TcpStream, status: &str, body: Option<Json>) -> io::Result<()> {
+    let body = body.map(|json| json.to_string()).unwrap_or_default();
+    write!(
+        writer,
+        "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\
+        Access-Control-Allow-Origin: *\r\nAccess-Control-Allow-Methods: POST, OPTIONS\r\n\
+        Access-Control-Allow-Headers: Content-Type\r\nConnection: close\r\n\r\n{}",
+        status, body.len(), body,
+    )?;
+    writer.flush()
+}
+
+fn problem(message: &str) -> Json {
+    Json::object(vec![("error", Json::text(message))])
+}
+
+// Runs one program in a Runtime of its own
+fn evaluate(source: &str, fuel: u64, memory_limit: usize) -> Json {
+    let shown = Arc::new(Lock::new(Vec::new()));
+    let mut runtime = Runtime::with_capabilities(&Capability::SANDBOXED);
+    runtime.set_debug_output(false);
+    runtime.set_fuel(fuel);
+    runtime.set_memory_limit(memory_limit);
+    runtime.set_output(Box::new(Shown(Arc::clone(&shown))));
+    // Nobody is there to answer `ask`
+    runtime.set_input(Box::new(io::empty()));
+
+    let started = Instant::now();
+    let compiled = runtime.compile_source(source);
+    let compile_ms = started.elapsed().as_secs_f64() * 1000.0;
+    let started = Instant::now();
+    let (diagnostics, error) = match compiled {
+        Ok(compiled) => (warnings(&compiled), runtime.run_compiled(source, &compiled).err()),
+        Err(error) => (vec![diagnostic("error", &error)], None),
+    };
+    let run_ms = started.elapsed().as_secs_f64() * 1000.0;
+
+    let output = String::from_utf8_lossy(&shown.borrow()).into_owned();
+    Json::object(vec![
+        ("output", Json::text(&output)),
+        ("diagnostics", Json::Array(diagnostics)),
+        ("error", error.map(|error| Json::text(&error)).unwrap_or(Json::Null)),
+        ("timing", Json::object(vec![("compile_ms", Json::Number(compile_ms)), ("run_ms", Json::Number(run_ms))])),
+    ])
+}
+
+fn warnings(compiled: &Compiled) -> Vec<Json> {
+    compiled.warnings.iter().map(|warning| diagnostic("warning", warning)).collect()
+}
+
+// A problem found before the program ran, with the line it is on
+fn diagnostic(severity: &str, message: &str) -> Json {
+    Json::object(vec![
+        ("severity", Json::text(severity)),
+        ("line", parser::error_line(message).into()),
+        ("message", Json::text(message)),
+    ])
+}
+
+// Keeps what a program shows, to send back with the result, and refuses
+// what would take it past MAX_OUTPUT
+struct Shown(Arc<Lock<Vec<u8>>>);
+
+impl Write for Shown {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        let mut shown = self.0.borrow_mut();
+        if shown.len() + bytes.len() > MAX_OUTPUT {
+            return Err(io::Error::other(format!("the program showed more than {} bytes", MAX_OUTPUT)));
+        }
+        shown.extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// Counts a request being answered for as long as it is held
+struct Working(Arc<AtomicUsize>);
+
+impl Working {
+    fn start(busy: &Arc<AtomicUsize>) -> Working {
+        busy.fetch_add(1, Ordering::SeqCst);
+        Working(Arc::clone(busy))
+    }
+}
+
+impl Drop for Working {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
@@ -0,0 +1,270 @@
+use crate::analyzer::Type;
+use crate::cache;
+use crate::generator::{Class, Collection, Function, Instance, Lock, Mapping, Value};
+use crate::json::Json;
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// A Runtime's top-level variables, their types, Objects' shared fields and
+/// the Tasks and Objects defined so far, as they were when it was taken.
+/// Values are written out rather than shared, so changing a List afterwards
+/// doesn't change the snapshot, and restoring it gives fresh ones each time.
+pub struct Snapshot {
+    // The variables and shared fields, in the form `Values` writes
+    values: Json,
+    types: HashMap<String, Type>,
+    functions: HashMap<String, Arc<Function>>,
+    classes: HashMap<String, Arc<Class>>,
+}
+
+/// What a restored snapshot puts back into a Runtime.
+pub struct Restored {
+    pub variables: HashMap<String, Value>,
+    pub types: HashMap<String, Type>,
+    pub functions: HashMap<String, Arc<Function>>,
+    pub classes: HashMap<String, Arc<Class>>,
+    pub shared: HashMap<String, HashMap<String, Value>>,
+}
+
+impl Snapshot {
+    pub fn take(
+        variables: &HashMap<String, Value>,
+        types: &HashMap<String, Type>,
+        functions: &HashMap<String, Arc<Function>>,
+        classes: &HashMap<String, Arc<Class>>,
+        shared: &HashMap<String, HashMap<String, Value>>,
+    ) -> Snapshot {
+        let mut values = Values::default();
+        let variables = values.table(variables);
+        let shared = Json::Object(shared.iter().map(|(class, fields)| (class.clone(), values.table(fields))).collect());
+        Snapshot {
+            values: Json::object(vec![("variables", variables), ("shared", shared), ("heap", Json::Array(values.heap))]),
+            types: types.clone(),
+            functions: functions.clone(),
+            classes: classes.clone(),
+        }
+    }
+
+    /// Fresh copies of everything the snapshot kept.
+    pub fn restore(&self) -> Result<Restored, String> {
+        let unreadable = || "The snapshot is damaged".to_string();
+        let values = Rebuilt::new(self.values.get("heap").and_then(Json::as_array).ok_or_else(unreadable)?)
+            .ok_or_else(unreadable)?;
+        let variables = self.values.get("variables").and_then(|table| values.table(table)).ok_or_else(unreadable)?;
+        let shared = match self.values.get("shared") {
+            Some(Json::Object(classes)) => classes.iter()
+                .map(|(class, fields)| Some((class.clone(), values.table(fields)?)))
+                .collect::<Option<_>>()
+                .ok_or_else(unreadable)?,
+            _ => return Err(unreadable()),
+        };
+        Ok(Restored {
+            variables,
+            types: self.types.clone(),
+            functions: self.functions.clone(),
+            classes: self.classes.clone(),
+            shared,
+        })
+    }
+
+    /// The snapshot as JSON, to save to a file. Fails if a Task holds a
+    /// value that can't be written out.
+    pub fn to_json(&self) -> Result<Json, String> {
+        let unsaved = |name: &str| format!("'{}' holds a value that can't be saved", name);
+        let tasks = self.functions.values()
+            .map(|function| Ok(Json::object(vec![
+                ("source", Json::text(&function.lines.source)),
+                ("task", cache::function(function).ok_or_else(|| unsaved(&function.name))?),
+            ])))
+            .collect::<Result<_, String>>()?;
+        let objects = self.classes.values()
+            .map(|class| Ok(Json::object(vec![
+                ("source", Json::text(&class.initializer.lines.source)),
+                ("object", cache::class(class).ok_or_else(|| unsaved(&class.name))?),
+            ])))
+            .collect::<Result<_, String>>()?;
+        Ok(Json::object(vec![
+            ("values", self.values.clone()),
+            ("types", Json::Object(self.types.iter().map(|(name, typ)| (name.clone(), type_json(typ))).collect())),
+            ("tasks", Json::Array(tasks)),
+            ("objects", Json::Array(objects)),
+        ]))
+    }
+
+    /// Reads back what `to_json` wrote.
+    pub fn from_json(json: &Json) -> Result<Snapshot, String> {
+        let read = || -> Option<Snapshot> {
+            let types = match json.get("types")? {
+                Json::Object(types) => types.iter().map(|(name, typ)| Some((name.clone(), read_type(typ)?))).collect::<Option<_>>()?,
+                _ => return None,
+            };
+            let functions = json.get("tasks")?.as_array()?.iter().map(|task| {
+                let function = cache::read_function(task.get("task")?, &Arc::from(task.get("source")?.as_str()?))?;
+                Some((function.name.clone(), function))
+            }).collect::<Option<_>>()?;
+            let classes = json.get("objects")?.as_array()?.iter().map(|object| {
+                let class = cache::read_class(object.get("object")?, &Arc::from(object.get("source")?.as_str()?))?;
+                Some((class.name.clone(), class))
+            }).collect::<Option<_>>()?;
+            Some(Snapshot { values: json.get("values")?.clone(), types, functions, classes })
+        };
+        let snapshot = read().ok_or("The file doesn't hold a snapshot")?;
+        // Reading the values now means a damaged file is found before it is restored
+        snapshot.restore()?;
+        Ok(snapshot)
+    }
+}
+
+// Writes values out as JSON. Lists, Mappings and Objects go in a heap, once
+// each, and are referred to by their place in it, so ones reached in
+// several ways (or from themselves) come back shared as they were.
+#[derive(Default)]
+struct Values {
+    heap: Vec<Json>,
+    places: HashMap<*const (), usize>,
+}
+
+impl Values {
+    fn table(&mut self, values: &HashMap<String, Value>) -> Json {
+        Json::Object(values.iter().map(|(name, value)| (name.clone(), self.value(value))).collect())
+    }
+
+    fn value(&mut self, value: &Value) -> Json {
+        match value {
+            Value::Number(n) if n.is_finite() => Json::Number(*n),
+            Value::Number(n) => Json::object(vec![("decimal", Json::text(&n.to_string()))]),
+            Value::Money(money) => Json::from_money(*money, "money"),
+            Value::Quantity(quantity) => Json::from_quantity(quantity, "quantity"),
+            Value::Date(date) => Json::from_date(*date, "date"),
+            Value::String(text) => Json::text(text),
+            Value::Boolean(b) => (*b).into(),
+            Value::Null => Json::Null,
+            Value::Class(name) => Json::object(vec![("class", Json::text(name))]),
+            Value::List(items) => self.place(Arc::as_ptr(items).cast(), |values| Json::object(vec![
+                ("list", Json::Array(items.borrow().iter().map(|item| values.value(item)).collect())),
+                ("frozen", items.frozen.into()),
+            ])),
+            Value::Mapping(mapping) => self.place(Arc::as_ptr(mapping).cast(), |values| Json::object(vec![
+                ("mapping", Json::Array(mapping.borrow().iter()
+                    .map(|(key, entry)| Json::Array(vec![values.value(key), values.value(entry)]))
+                    .collect())),
+                ("frozen", mapping.frozen.into()),
+            ])),
+            Value::Object(instance) => self.place(Arc::as_ptr(instance).cast(), |values| Json::object(vec![
+                ("object", Json::text(&instance.class_name)),
+                ("fields", values.table(&instance.fields.borrow())),
+                ("frozen", instance.frozen.into()),
+            ])),
+        }
+    }
+
+    // A reference to the heap entry for this collection or object, writing
+    // the entry the first time it is reached
+    fn place(&mut self, address: *const (), write: impl FnOnce(&mut Values) -> Json) -> Json {
+        let place = match self.places.get(&address) {
+            Some(place) => *place,
+            None => {
+                let place = self.heap.len();
+                self.places.insert(address, place);
+                self.heap.push(Json::Null);
+                self.heap[place] = write(self);
+                place
+            },
+        };
+        Json::object(vec![("ref", place.into())])
+    }
+}
+
+// Reads values back. Every heap entry is made empty first, so a reference
+// to any of them can be followed while the entries are filled in. They are
+// filled from the last, since what an entry holds comes after it, and a
+// List used as a Mapping key must have its items before it is hashed.
+struct Rebuilt {
+    heap: Vec<Value>,
+}
+
+impl Rebuilt {
+    fn new(entries: &[Json]) -> Option<Rebuilt> {
+        let heap = entries.iter().map(|entry| {
+            let frozen = entry.get("frozen")?.as_bool()?;
+            Some(match (entry.get("list"), entry.get("mapping"), entry.get("object")) {
+                (Some(_), _, _) => Value::List(Collection::unfilled(frozen)),
+                (_, Some(_), _) => Value::Mapping(Collection::unfilled(frozen)),
+                (_, _, Some(class_name)) => Value::Object(Arc::new(Instance {
+                    class_name: class_name.as_str()?.to_string(),
+                    fields: Lock::new(HashMap::new()),
+                    frozen,
+                })),
+                _ => return None,
+            })
+        }).collect::<Option<_>>()?;
+        let rebuilt = Rebuilt { heap };
+        for (entry, shell) in entries.iter().zip(&rebuilt.heap).rev() {
+            match shell {
+                Value::List(items) => {
+                    items.fill(entry.get("list")?.as_array()?.iter().map(|item| rebuilt.value(item)).collect::<Option<_>>()?);
+                },
+                Value::Mapping(mapping) => {
+                    let mut entries = Mapping::new();
+                    for pair in entry.get("mapping")?.as_array()? {
+                        entries.insert(rebuilt.value(pair.as_array()?.first()?)?, rebuilt.value(pair.as_array()?.get(1)?)?);
+                    }
+                    mapping.fill(entries);
+                },
+                Value::Object(instance) => *instance.fields.borrow_mut() = rebuilt.table(entry.get("fields")?)?,
+                _ => return None,
+            }
+        }
+        Some(rebuilt)
+    }
+
+    fn table(&self, json: &Json) -> Option<HashMap<String, Value>> {
+        match json {
+            Json::Object(values) => values.iter().map(|(name, value)| Some((name.clone(), self.value(value)?))).collect(),
+            _ => None,
+        }
+    }
+
+    fn value(&self, json: &Json) -> Option<Value> {
+        Some(match json {
+            Json::Number(n) => Value::Number(*n),
+            Json::String(text) => Value::String(text.clone()),
+            Json::Bool(b) => Value::Boolean(*b),
+            Json::Null => Value::Null,
+            Json::Object(_) => match (json.get("ref"), json.get("class"), json.get("decimal")) {
+                (Some(place), _, _) => self.heap.get(place.as_f64()? as usize)?.clone(),
+                (_, Some(name), _) => Value::Class(name.as_str()?.to_string()),
+                (_, _, Some(number)) => Value::Number(number.as_str()?.parse().ok()?),
+                _ => json.to_money("money").map(Value::Money)
+                    .or_else(|| json.to_quantity("quantity").map(Value::Quantity))
+                    .or_else(|| json.to_date("date").map(Value::Date))?,
+            },
+            Json::Array(_) => return None,
+        })
+    }
+}
+
+// Types are their names, with those of what a List, Mapping or Promise holds
+fn type_json(typ: &Type) -> Json {
+    match typ {
+        Type::List(element) => Json::object(vec![("list", type_json(element))]),
+        Type::Map { key, value } => Json::object(vec![("mapping", Json::Array(vec![type_json(key), type_json(value)]))]),
+        Type::Promise(value) => Json::object(vec![("promise", type_json(value))]),
+        simple => Json::text(&simple.to_string()),
+    }
+}
+
+fn read_type(json: &Json) -> Option<Type> {
+    match json {
+        Json::String(name) => Type::from_name(name),
+        _ => match (json.get("list"), json.get("mapping"), json.get("promise")) {
+            (Some(element), _, _) => Some(Type::List(Box::new(read_type(element)?))),
+            (_, Some(pair), _) => Some(Type::Map {
+                key: Box::new(read_type(pair.as_array()?.first()?)?),
+                value: Box::new(read_type(pair.as_array()?.get(1)?)?),
+            }),
+            (_, _, Some(value)) => Some(Type::Promise(Box::new(read_type(value)?))),
+            _ => None,
+        },
+    }
+}
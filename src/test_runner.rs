@@ -0,0 +1,181 @@
+use crate::coverage::Coverage;
+use crate::parser::Node;
+use crate::runtime::Runtime;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+// Extensions of the source files searched for `to test` blocks
+const SOURCE_EXTENSIONS: [&str; 2] = ["v", "vern"];
+
+enum Outcome {
+    Passed,
+    Failed(String),
+    Errored(String),
+}
+
+/// How `nair test` runs and reports.
+#[derive(Default)]
+pub struct TestOptions {
+    /// Leave out timings so the output never varies
+    pub deterministic: bool,
+    /// Report which statements the tests ran
+    pub coverage: bool,
+    /// Also write that report in LCOV format to this file
+    pub lcov: Option<String>,
+}
+
+#[derive(Default)]
+struct Summary {
+    passed: usize,
+    failed: usize,
+    errored: usize,
+    deterministic: bool,
+    coverage: Option<Coverage>,
+}
+
+impl Summary {
+    fn timing(&self, elapsed: Duration) -> String {
+        match self.deterministic {
+            true => String::new(),
+            false => format!(" ({:.2} ms)", elapsed.as_secs_f64() * 1000.0),
+        }
+    }
+}
+
+/// Runs every `to test` block found in the given files or directories
+/// (the current directory when none are given). Each test runs in a fresh
+/// Runtime after the file's other top-level code. Fails if any test does.
+pub fn run_tests(paths: &[String], options: &TestOptions) -> Result<(), String> {
+    let roots: Vec<PathBuf> = if paths.is_empty() {
+        vec![PathBuf::from(".")]
+    } else {
+        paths.iter().map(PathBuf::from).collect()
+    };
+
+    let mut files = Vec::new();
+    for root in &roots {
+        collect_files(root, &mut files)?;
+    }
+    files.sort();
+
+    let started = Instant::now();
+    let mut summary = Summary {
+        deterministic: options.deterministic,
+        coverage: (options.coverage || options.lcov.is_some()).then(Coverage::new),
+        ..Summary::default()
+    };
+    for file in &files {
+        run_file(file, &mut summary);
+    }
+
+    println!();
+    println!("{} passed, {} failed, {} errors in {} file(s){}",
+        summary.passed, summary.failed, summary.errored, files.len(), summary.timing(started.elapsed()));
+
+    if let Some(coverage) = &summary.coverage {
+        println!();
+        print!("{}", coverage.summary());
+        if let Some(path) = &options.lcov {
+            std::fs::write(path, coverage.lcov()).map_err(|e| format!("Error writing '{}': {}", path, e))?;
+        }
+    }
+
+    if summary.failed + summary.errored > 0 {
+        Err(format!("{} test(s) did not pass", summary.failed + summary.errored))
+    } else {
+        Ok(())
+    }
+}
+
+/// Adds the source files at a path, searching directories recursively.
+pub fn collect_files(path: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    if path.is_file() {
+        files.push(path.to_path_buf());
+        return Ok(());
+    }
+
+    let entries = std::fs::read_dir(path)
+        .map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Error reading '{}': {}", path.display(), e))?;
+        let entry_path = entry.path();
+        let name = entry.file_name().to_string_lossy().to_string();
+        // Build output and hidden directories never hold tests
+        if name.starts_with('.') || name == "target" {
+            continue;
+        }
+        if entry_path.is_dir() {
+            collect_files(&entry_path, files)?;
+        } else if entry_path.extension().is_some_and(|ext| SOURCE_EXTENSIONS.iter().any(|known| ext == *known)) {
+            files.push(entry_path);
+        }
+    }
+    Ok(())
+}
+
+fn run_file(file: &Path, summary: &mut Summary) {
+    let source = match std::fs::read_to_string(file) {
+        Ok(source) => source,
+        Err(e) => {
+            report(file, "(loading)", &Outcome::Errored(e.to_string()), Duration::ZERO, summary);
+            return;
+        },
+    };
+    let ast = match Runtime::new().parse_source(&source) {
+        Ok((_, ast)) => ast,
+        Err(e) => {
+            report(file, "(parsing)", &Outcome::Errored(e), Duration::ZERO, summary);
+            return;
+        },
+    };
+
+    // Everything that isn't a test is shared setup
+    let (tests, setup): (Vec<Node>, Vec<Node>) = ast.into_iter()
+        .partition(|node| matches!(node, Node::TestDecl { .. }));
+
+    for test in tests {
+        let Node::TestDecl { name, body } = test else {
+            continue;
+        };
+        let mut program = setup.clone();
+        program.push(*body);
+
+        let mut runtime = Runtime::new();
+        runtime.set_source_name(&file.display().to_string());
+        runtime.set_deterministic(summary.deterministic);
+        if summary.coverage.is_some() {
+            runtime.enable_coverage();
+        }
+        let started = Instant::now();
+        let outcome = match runtime.run_nodes(program) {
+            Ok(()) => Outcome::Passed,
+            Err(message) if message.starts_with("ExpectationFailed") => Outcome::Failed(message),
+            Err(message) => Outcome::Errored(message),
+        };
+        if let (Some(total), Some(coverage)) = (summary.coverage.as_mut(), runtime.take_coverage()) {
+            total.merge(coverage);
+        }
+        report(file, &name, &outcome, started.elapsed(), summary);
+    }
+}
+
+fn report(file: &Path, name: &str, outcome: &Outcome, elapsed: Duration, summary: &mut Summary) {
+    let (status, detail) = match outcome {
+        Outcome::Passed => {
+            summary.passed += 1;
+            ("ok", None)
+        },
+        Outcome::Failed(message) => {
+            summary.failed += 1;
+            ("FAILED", Some(message))
+        },
+        Outcome::Errored(message) => {
+            summary.errored += 1;
+            ("ERROR", Some(message))
+        },
+    };
+    println!("test {}: {} ... {}{}", file.display(), name, status, summary.timing(elapsed));
+    if let Some(detail) = detail {
+        println!("    {}", detail);
+    }
+}
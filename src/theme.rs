@@ -0,0 +1,82 @@
+use crate::generator::Value;
+use crate::pretty;
+use std::io::IsTerminal;
+
+// The terminal's codes for the colors used
+const RED: &str = "31";
+const GREEN: &str = "32";
+const YELLOW: &str = "33";
+const BLUE: &str = "34";
+const MAGENTA: &str = "35";
+const CYAN: &str = "36";
+const GRAY: &str = "90";
+const BOLD: &str = "1";
+
+/// How `nair` colors what it writes to a terminal: errors red, warnings
+/// yellow, the values `show` writes by their type and the REPL's banner in
+/// bold. Output and errors are colored separately, each only when it goes
+/// to a terminal, and not at all with `--no-color` or `NO_COLOR` set. The
+/// default colors nothing, as for output going to a file or another program.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Theme {
+    output: bool,
+    errors: bool,
+}
+
+impl Theme {
+    /// Colors for standard output and standard error, where each is a
+    /// terminal, if `wanted` and `NO_COLOR` isn't set.
+    pub fn detect(wanted: bool) -> Theme {
+        let wanted = wanted && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty());
+        Theme {
+            output: wanted && std::io::stdout().is_terminal(),
+            errors: wanted && std::io::stderr().is_terminal(),
+        }
+    }
+
+    /// A value as `show` writes it (see `pretty::show`), with Text,
+    /// numbers, truths, dates and the like each in their own color.
+    pub fn value(self, value: &Value) -> String {
+        pretty::show(value, &|value, text| paint(self.output, color(value), text))
+    }
+
+    /// An error the REPL reports on standard output.
+    pub fn error(self, text: &str) -> String {
+        paint(self.output, RED, text)
+    }
+
+    /// An error that ends `nair`, on standard error.
+    pub fn failure(self, text: &str) -> String {
+        paint(self.errors, RED, text)
+    }
+
+    /// A warning from the analyzer or about the config file, on standard error.
+    pub fn warning(self, text: &str) -> String {
+        paint(self.errors, YELLOW, text)
+    }
+
+    /// The REPL's banner, with its first line in bold.
+    pub fn banner(self, text: &str) -> String {
+        let (title, rest) = text.split_once('\n').unwrap_or((text, ""));
+        format!("{}\n{}", paint(self.output, BOLD, title), rest)
+    }
+}
+
+// The color for a value of each type
+fn color(value: &Value) -> &'static str {
+    match value {
+        Value::String(_) => GREEN,
+        Value::Number(_) | Value::Money(_) | Value::Quantity(_) => CYAN,
+        Value::Boolean(_) => MAGENTA,
+        Value::Date(_) => BLUE,
+        Value::Null => GRAY,
+        Value::Object(_) | Value::Class(_) | Value::List(_) | Value::Mapping(_) => BOLD,
+    }
+}
+
+fn paint(on: bool, color: &str, text: &str) -> String {
+    match on {
+        true => format!("\x1b[{}m{}\x1b[0m", color, text),
+        false => text.to_string(),
+    }
+}
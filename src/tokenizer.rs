@@ -1,9 +1,19 @@
+use crate::language::{self, Language};
+use crate::money::{Currency, Money};
+use std::collections::VecDeque;
+use std::io::BufRead;
+
 #[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub token_type: TokenType,
     pub literal: String,
+    /// The line the token is on, counting from 1.
     pub line: usize,
+    /// The column just past the token's end, counting characters from 1.
     pub column: usize,
+    /// How many bytes into the source the token starts. Line breaks,
+    /// indentation changes and the end are placed where they were noticed.
+    pub offset: usize,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -32,6 +42,7 @@ pub enum TokenType {
     Output,
     Raise,
     Show,
+    Expect,
     Await,
     At,
     And,
@@ -40,10 +51,13 @@ pub enum TokenType {
     My,
     About,
     Me,
-    
+    Not,
+    Shared,
+
     // Declaration keywords
     Task,
     Object,
+    Behavior,
     Build,
     Defaults,
 
@@ -51,7 +65,7 @@ pub enum TokenType {
     TypeWhole,  // Whole number
     TypeDecimal, // Decimal number
     TypeText, // Text
-    TypeLogic, // Boolean 
+    TypeLogic, // Boolean
     TypeNothing, // Null
     TypeList, // List
     TypeMapping, // Mapping
@@ -62,6 +76,7 @@ pub enum TokenType {
 
     // Literals
     Number(f64),
+    Money(Money),
     String(String),
     Boolean(bool),
     Null,
@@ -80,67 +95,280 @@ pub enum TokenType {
     Divide,
     Modulo,
     Power,
-    Equals,
     NotEquals,
     GreaterThan,
     GreaterThanOrEqual,
     LessThan,
     LessThanOrEqual,
-    BackSlash,      // For line continuation
 
     // Identifiers
     Identifier(String),
 
-    // Comments
-    Comment(String),
-
-    EOF,
+    Eof,
     NewLine,
+    Indent,
+    Dedent,
 
-    Includes,  // Add this new token
+    Includes,
     LeftBrace,
     RightBrace,
-    Quote,
-    StringPart(String),
 }
 
+/// Every reserved word and the token it becomes.
+pub static KEYWORDS: [(&str, TokenType); 55] = [
+    // Keywords
+    ("as", TokenType::As),
+    ("is", TokenType::Is),
+    ("of", TokenType::Of),
+    ("to", TokenType::To),
+    ("when", TokenType::When),
+    ("or", TokenType::Or),
+    ("do", TokenType::Do),
+    ("fail", TokenType::Fail),
+    ("always", TokenType::Always),
+    ("inherits", TokenType::Extends),
+    ("returns", TokenType::Returns),
+    ("requires", TokenType::Requires),
+    ("returning", TokenType::Returning),
+    ("new", TokenType::New),
+    ("with", TokenType::With),
+    ("using", TokenType::Using),
+    ("loop", TokenType::Loop),
+    ("while", TokenType::While),
+    ("Emit", TokenType::Emit),
+    ("match", TokenType::Match),
+    ("output", TokenType::Output),
+    ("raise", TokenType::Raise),
+    ("show", TokenType::Show),
+    ("expect", TokenType::Expect),
+    ("await", TokenType::Await),
+    ("at", TokenType::At),
+    ("and", TokenType::And),
+    ("each", TokenType::Each),
+    ("becomes", TokenType::Becomes),
+    ("my", TokenType::My),
+    ("about", TokenType::About),
+    ("me", TokenType::Me),
+    ("not", TokenType::Not),
+    ("shared", TokenType::Shared),
+    ("includes", TokenType::Includes),
+
+    // Declaration keywords
+    ("Task", TokenType::Task),
+    ("Object", TokenType::Object),
+    ("Behavior", TokenType::Behavior),
+    ("build", TokenType::Build),
+    ("defaults", TokenType::Defaults),
+
+    // Types
+    ("Whole", TokenType::TypeWhole),
+    ("Decimal", TokenType::TypeDecimal),
+    ("Text", TokenType::TypeText),
+    ("Truth", TokenType::TypeLogic),
+    ("Logic", TokenType::TypeLogic),
+    ("Nothing", TokenType::TypeNothing),
+    ("List", TokenType::TypeList),
+    ("Mapping", TokenType::TypeMapping),
+    ("Promise", TokenType::TypePromise),
+    ("Any", TokenType::TypeAny),
+    ("Number", TokenType::TypeNumber),
+    ("Error", TokenType::TypeError),
+
+    // Boolean literals
+    ("true", TokenType::Boolean(true)),
+    ("false", TokenType::Boolean(false)),
+    ("null", TokenType::Null),
+];
+
+// Numbers spelled out: those below twenty, the tens, and the scales that
+// multiply what comes before them
+const SMALL_NUMBERS: [&str; 20] = [
+    "zero", "one", "two", "three", "four", "five", "six", "seven", "eight", "nine", "ten",
+    "eleven", "twelve", "thirteen", "fourteen", "fifteen", "sixteen", "seventeen", "eighteen", "nineteen",
+];
+const TENS: [&str; 8] = ["twenty", "thirty", "forty", "fifty", "sixty", "seventy", "eighty", "ninety"];
+const SCALES: [(&str, f64); 3] = [("thousand", 1e3), ("million", 1e6), ("billion", 1e9)];
+
+/// The token a reserved word becomes, if it is one.
+pub fn keyword(text: &str) -> Option<TokenType> {
+    KEYWORDS.iter()
+        .find(|(word, _)| *word == text)
+        .map(|(_, token_type)| token_type.clone())
+}
+
+/// Turns source text into tokens. It is an iterator of tokens, and can read
+/// its source from a reader a line at a time instead of all at once.
 pub struct Tokenizer {
     source: Vec<char>,
+    // Where the rest of the source comes from, when it isn't all in `source`
+    reader: Option<Box<dyn BufRead + Send>>,
     current: usize,
     start: usize,
     line: usize,
     column: usize,
+    // Bytes of source before `current`, counting lines already read
+    offset: usize,
+    indent_stack: Vec<usize>,
+    bracket_depth: usize,
+    // The keyword pack chosen by a `# language:` directive
+    language: Option<&'static Language>,
+    // Keywords only match when written exactly as listed
+    strict_keywords: bool,
+    // Whether `twenty-one` is a number rather than a name
+    number_words: bool,
+    // Tokens scanned but not handed out yet
+    pending: VecDeque<Token>,
+    // Whether any token has been made, and whether the last one left a
+    // logical line open
+    started: bool,
+    open_line: bool,
+    finished: bool,
+    // A read error, reported once the tokens before it are handed out
+    failure: Option<String>,
 }
 
 impl Tokenizer {
     pub fn new(source: &str) -> Self {
         Tokenizer {
             source: source.chars().collect(),
+            reader: None,
             current: 0,
             start: 0,
             line: 1,
             column: 1,
+            offset: 0,
+            indent_stack: vec![0],
+            bracket_depth: 0,
+            language: None,
+            strict_keywords: false,
+            number_words: true,
+            pending: VecDeque::new(),
+            started: false,
+            open_line: false,
+            finished: false,
+            failure: None,
         }
     }
 
+    /// Reads the source a line at a time, so only the line being scanned is
+    /// held in memory however large the file is.
+    pub fn from_reader(reader: impl BufRead + Send + 'static) -> Self {
+        let mut tokenizer = Tokenizer::new("");
+        tokenizer.reader = Some(Box::new(reader));
+        tokenizer.refill();
+        tokenizer
+    }
+
+    /// Numbers lines from `line` on, for text taken from the middle of a file.
+    pub fn set_first_line(&mut self, line: usize) {
+        self.line = line;
+    }
+
+    /// Uses a keyword pack without a `# language:` directive in the text.
+    pub fn set_language(&mut self, language: Option<&'static Language>) {
+        self.language = language;
+    }
+
+    /// Keywords match whatever their case (`Show`, `WHEN`) unless this is
+    /// set. Names are always case-sensitive.
+    pub fn set_strict_keywords(&mut self, strict: bool) {
+        self.strict_keywords = strict;
+    }
+
+    /// Numbers spelled out in words, like `twenty-one` or `three hundred
+    /// and five`, are read as Whole numbers unless this is turned off, when
+    /// they are names like any other word.
+    pub fn set_number_words(&mut self, enabled: bool) {
+        self.number_words = enabled;
+    }
+
     pub fn tokenize(&mut self) -> Result<Vec<Token>, String> {
-        let mut tokens = Vec::new();
+        self.by_ref().collect()
+    }
 
-        while !self.is_at_end() {
-            self.start = self.current;
-            if let Ok(token) = self.scan_token() {
-                tokens.push(token);
+    // Closes the last logical line and any open blocks, then ends the stream
+    fn close(&mut self) {
+        if self.open_line {
+            self.emit(self.make_token(TokenType::NewLine, String::new()));
+        }
+        while self.indent_stack.len() > 1 {
+            self.indent_stack.pop();
+            self.emit(self.make_token(TokenType::Dedent, String::new()));
+        }
+        self.emit(self.make_token(TokenType::Eof, String::new()));
+        self.finished = true;
+    }
+
+    fn emit(&mut self, token: Token) {
+        self.started = true;
+        self.open_line = !matches!(token.token_type, TokenType::NewLine | TokenType::Dedent);
+        self.pending.push_back(token);
+    }
+
+    // Tokenizes one physical line, emitting indentation changes first
+    fn scan_line(&mut self) -> Result<(), String> {
+        let mut indent = 0;
+        while !self.is_at_end() && matches!(self.peek(), ' ' | '\t') {
+            indent += if self.advance() == '\t' { 4 } else { 1 };
+        }
+
+        // Blank and comment-only lines don't affect indentation
+        if self.is_at_end() || matches!(self.peek(), '\n' | '\r' | '#') {
+            if !self.started {
+                self.directive()?;
             }
+            self.skip_comment();
+            self.newline();
+            return Ok(());
         }
 
-        tokens.push(Token {
-            token_type: TokenType::EOF,
-            literal: String::new(),
-            line: self.line,
-            column: self.column,
-        });
+        let current_indent = *self.indent_stack.last().unwrap_or(&0);
+        if indent > current_indent {
+            self.indent_stack.push(indent);
+            self.emit(self.make_token(TokenType::Indent, String::new()));
+        } else {
+            while indent < *self.indent_stack.last().unwrap_or(&0) {
+                self.indent_stack.pop();
+                self.emit(self.make_token(TokenType::Dedent, String::new()));
+            }
+            if indent != *self.indent_stack.last().unwrap_or(&0) {
+                return Err(format!("Inconsistent indentation on line {}", self.line));
+            }
+        }
+
+        loop {
+            self.skip_whitespace();
+            self.skip_comment();
+            if self.is_at_end() || self.peek() == '\n' {
+                break;
+            }
+            // Line continuation: join the next physical line onto this one
+            if self.peek() == '\\' {
+                self.advance();
+                self.skip_whitespace();
+                self.skip_comment();
+                self.newline();
+                continue;
+            }
+            self.start = self.current;
+            let token = self.scan_token()?;
+            for token in self.localize(token) {
+                self.emit(token);
+            }
+            // Inside brackets a line break doesn't end the statement
+            if self.bracket_depth > 0 && self.peek_past_whitespace() == '\n' {
+                self.skip_whitespace();
+                self.skip_comment();
+                self.newline();
+                while !self.is_at_end() && matches!(self.peek(), ' ' | '\t') {
+                    self.advance();
+                }
+            }
+        }
 
-        Ok(tokens)
+        self.emit(self.make_token(TokenType::NewLine, String::new()));
+        self.newline();
+        Ok(())
     }
 
     // Helper methods
@@ -149,9 +377,12 @@ impl Tokenizer {
     }
 
     fn advance(&mut self) -> char {
-        let current_char = self.source[self.current];
+        let Some(&current_char) = self.source.get(self.current) else {
+            return '\0';
+        };
         self.current += 1;
         self.column += 1;
+        self.offset += current_char.len_utf8();
         current_char
     }
 
@@ -171,6 +402,18 @@ impl Tokenizer {
         }
     }
 
+    fn peek_past_whitespace(&self) -> char {
+        let mut index = self.current;
+        while index < self.source.len() && matches!(self.source[index], ' ' | '\t' | '\r') {
+            index += 1;
+        }
+        match self.source.get(index) {
+            Some('#') => '\n',
+            Some(c) => *c,
+            None => '\0',
+        }
+    }
+
     fn skip_whitespace(&mut self) {
         while !self.is_at_end() {
             match self.peek() {
@@ -182,59 +425,114 @@ impl Tokenizer {
         }
     }
 
-    fn create_token(&mut self, token_type: TokenType) -> Token {
-        Token {
-            token_type,
-            literal: self.source[self.start..self.current].iter().collect::<String>(),
-            line: self.line,
-            column: self.column,
+    // `# language: es` before the first statement picks a keyword pack
+    fn directive(&mut self) -> Result<(), String> {
+        let text: String = self.source[self.current..].iter().take_while(|c| **c != '\n').collect();
+        if let Some(name) = language::directive(&text) {
+            self.language = Some(language::find(name).ok_or_else(|| format!("Unknown language '{}' on line {}", name, self.line))?);
         }
+        Ok(())
     }
 
-    fn number_token(&mut self) -> Result<Token, String> {
-        
-        while self.peek().is_ascii_digit() {
-            self.advance();
+    // A word from the keyword pack becomes the English keywords it stands for
+    fn localize(&self, token: Token) -> Vec<Token> {
+        let english = match (&token.token_type, self.language) {
+            // Pack words win over everyday ones, so Spanish `no` is `not`
+            (TokenType::Identifier(_) | TokenType::Boolean(_), Some(language)) => language.words.iter()
+                .find(|(local, _)| *local == token.literal)
+                .map(|(_, english)| *english),
+            _ => None,
+        };
+        match english {
+            Some(english) => english.split(' ')
+                .map(|word| Token { token_type: keyword(word).unwrap_or_else(|| TokenType::Identifier(word.to_string())), ..token.clone() })
+                .collect(),
+            None => vec![token],
         }
+    }
 
-        // Look for a decimal part
-        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
-            self.advance(); // Consume the "."
-
-            while self.peek().is_ascii_digit() {
+    fn skip_comment(&mut self) {
+        if self.peek() == '#' {
+            while !self.is_at_end() && self.peek() != '\n' {
                 self.advance();
             }
         }
+        while self.peek() == '\r' {
+            self.advance();
+        }
+    }
 
-        let number_str: String = self.source[self.start..self.current].iter().collect();
-        match number_str.parse::<f64>() {
-            Ok(number) => Ok(Token {
-                token_type: TokenType::Number(number),
-                literal: number_str,
-                line: self.line,
-                column: self.column,
-            }),
-            Err(_) => Err("Invalid number".to_string()),
+    // Consumes a line break (if any) and moves to the next line
+    fn newline(&mut self) {
+        if self.peek() == '\n' {
+            self.current += 1;
+            self.offset += 1;
         }
+        self.line += 1;
+        self.column = 1;
+        self.refill();
     }
 
-    fn scan_token(&mut self) -> Result<Token, String> {
-        self.skip_whitespace();
-        self.start = self.current;
+    // Replaces the scanned text with the next line from the reader
+    fn refill(&mut self) {
+        let Some(reader) = &mut self.reader else {
+            return;
+        };
+        if self.current < self.source.len() {
+            return;
+        }
+        let mut line = String::new();
+        if let Err(error) = reader.read_line(&mut line) {
+            self.failure = Some(format!("Failed to read source on line {}: {}", self.line, error));
+            self.reader = None;
+        }
+        self.source.clear();
+        self.source.extend(line.chars());
+        self.current = 0;
+        self.start = 0;
+    }
 
-        if self.is_at_end() {
-            return Ok(self.create_token(TokenType::EOF));
+    fn make_token(&self, token_type: TokenType, literal: String) -> Token {
+        Token {
+            token_type,
+            literal,
+            line: self.line,
+            column: self.column,
+            offset: self.offset,
         }
+    }
 
+    fn create_token(&mut self, token_type: TokenType) -> Token {
+        Token {
+            token_type,
+            literal: self.source[self.start..self.current].iter().collect::<String>(),
+            line: self.line,
+            column: self.column,
+            offset: self.start_offset(),
+        }
+    }
+
+    // Where the token being scanned starts, in bytes
+    fn start_offset(&self) -> usize {
+        self.offset - self.source[self.start..self.current].iter().map(|c| c.len_utf8()).sum::<usize>()
+    }
+
+    fn scan_token(&mut self) -> Result<Token, String> {
         let c = self.advance();
         match c {
             '"' => self.string(),
             '{' => Ok(self.create_token(TokenType::LeftBrace)),
             '}' => Ok(self.create_token(TokenType::RightBrace)),
-            '(' => Ok(self.create_token(TokenType::OpenParen)),
-            ')' => Ok(self.create_token(TokenType::CloseParen)),
-            '[' => Ok(self.create_token(TokenType::OpenBracket)),
-            ']' => Ok(self.create_token(TokenType::CloseBracket)),
+            '(' | '[' => {
+                self.bracket_depth += 1;
+                let token_type = if c == '(' { TokenType::OpenParen } else { TokenType::OpenBracket };
+                Ok(self.create_token(token_type))
+            },
+            ')' | ']' => {
+                self.bracket_depth = self.bracket_depth.saturating_sub(1);
+                let token_type = if c == ')' { TokenType::CloseParen } else { TokenType::CloseBracket };
+                Ok(self.create_token(token_type))
+            },
             ':' => Ok(self.create_token(TokenType::Colon)),
             ',' => Ok(self.create_token(TokenType::Comma)),
             '.' => Ok(self.create_token(TokenType::Dot)),
@@ -242,153 +540,75 @@ impl Tokenizer {
             '-' => Ok(self.create_token(TokenType::Minus)),
             '*' => Ok(self.create_token(TokenType::Multiply)),
             '/' => Ok(self.create_token(TokenType::Divide)),
-            '>' => Ok(self.create_token(TokenType::GreaterThan)),
+            '%' => Ok(self.create_token(TokenType::Modulo)),
+            '^' => Ok(self.create_token(TokenType::Power)),
+            '>' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Ok(self.create_token(TokenType::GreaterThanOrEqual))
+                } else {
+                    Ok(self.create_token(TokenType::GreaterThan))
+                }
+            },
+            '<' => {
+                if self.peek() == '=' {
+                    self.advance();
+                    Ok(self.create_token(TokenType::LessThanOrEqual))
+                } else {
+                    Ok(self.create_token(TokenType::LessThan))
+                }
+            },
+            '!' if self.peek() == '=' => {
+                self.advance();
+                Ok(self.create_token(TokenType::NotEquals))
+            },
             '0'..='9' => self.number(),
+            // `$19.99`, `€5`, `£3.50`, `¥500`
+            _ if self.peek().is_ascii_digit() && Currency::from_symbol(c).is_some() => self.money(c),
+            _ if self.number_words && c.is_lowercase() => match number_words(&self.source[self.start..]) {
+                Some((number, length)) => {
+                    for _ in 1..length {
+                        self.advance();
+                    }
+                    Ok(self.create_token(TokenType::Number(number)))
+                },
+                None => {
+                    let ident = self.read_identifier();
+                    Ok(self.create_identifier_token(ident))
+                },
+            },
             _ => {
                 if c.is_alphabetic() || c == '_' {
                     let ident = self.read_identifier();
                     Ok(self.create_identifier_token(ident))
                 } else {
-                    Err(format!("Unexpected character: {}", c))
+                    Err(format!("Unexpected character '{}' on line {}", c, self.line))
                 }
             },
         }
     }
 
+    // Reads a string literal; interpolated `{...}` parts are kept verbatim for the parser
     fn string(&mut self) -> Result<Token, String> {
         let mut string = String::new();
-        
-        while !self.is_at_end() && self.peek() != '"' {
-            if self.peek() == '{' {
-                if !string.is_empty() {
-                    return Ok(Token {
-                        token_type: TokenType::StringPart(string.clone()),
-                        literal: string,
-                        line: self.line,
-                        column: self.column,
-                    });
-                }
-                return Ok(self.create_token(TokenType::LeftBrace));
-            }
+
+        while !self.is_at_end() && self.peek() != '"' && self.peek() != '\n' {
             string.push(self.advance());
         }
 
-        if self.is_at_end() {
-            return Err("Unterminated string".to_string());
+        if self.is_at_end() || self.peek() == '\n' {
+            return Err(format!("Unterminated string on line {}", self.line));
         }
 
         // Consume the closing quote
         self.advance();
-        
+
         Ok(Token {
             token_type: TokenType::String(string.clone()),
             literal: string,
             line: self.line,
             column: self.column,
-        })
-    }
-
-    fn identifier_token(&mut self) -> Result<Token, String> {
-        while !self.is_at_end() && (self.peek().is_alphanumeric() || self.peek() == '_') {
-            self.advance();
-        }
-
-        let text: String = self.source[self.start..self.current].iter().collect();
-        let token_type = match text.as_str() {
-            // Keywords
-            "as" => TokenType::As,
-            "is" => TokenType::Is,
-            "of" => TokenType::Of,
-            "to" => TokenType::To,
-            "when" => TokenType::When,
-            "or" => TokenType::Or,
-            "do" => TokenType::Do,
-            "fail" => TokenType::Fail,
-            "always" => TokenType::Always,
-            "inherits" => TokenType::Extends,
-            "returns" => TokenType::Returns,
-            "requires" => TokenType::Requires,
-            "returning" => TokenType::Returning,
-            "new" => TokenType::New,
-            "with" => TokenType::With,
-            "using" => TokenType::Using,
-            "loop" => TokenType::Loop,
-            "while" => TokenType::While,
-            "Emit" => TokenType::Emit,
-            "match" => TokenType::Match,
-            "output" => TokenType::Output,
-            "raise" => TokenType::Raise,
-            "show" => TokenType::Show,
-            "await" => TokenType::Await,
-            "at" => TokenType::At,
-            "and" => TokenType::And,
-            "each" => TokenType::Each,
-            "becomes" => TokenType::Becomes,
-            "my" => TokenType::My,
-            "about" => TokenType::About,
-            "me" => TokenType::Me,
-
-            // Declaration keywords
-            "Task" => TokenType::Task,
-            "Object" => TokenType::Object,
-            "build" => TokenType::Build,
-            "defaults" => TokenType::Defaults,
-
-            // Types
-            "Whole" => TokenType::TypeWhole,
-            "Decimal" => TokenType::TypeDecimal,
-            "Text" => TokenType::TypeText,
-            "Logic" => TokenType::TypeLogic,
-            "Nothing" => TokenType::TypeNothing,
-            "List" => TokenType::TypeList,
-            "Mapping" => TokenType::TypeMapping,
-            "Promise" => TokenType::TypePromise,
-            "Any" => TokenType::TypeAny,
-            "Number" => TokenType::TypeNumber,
-            "Error" => TokenType::TypeError,
-
-            // Boolean literals
-            "true" => TokenType::Boolean(true),
-            "false" => TokenType::Boolean(false),
-            "null" => TokenType::Null,
-
-            // Default to identifier
-            _ => TokenType::Identifier(text.clone()),
-        };
-
-        Ok(Token {
-            token_type,
-            literal: text,
-            line: self.line,
-            column: self.column,
-        })
-    }
-
-    fn identifier_type(&self, text: String) -> Result<Token, String> {
-        println!("Processing identifier: {}", text);
-        let token_type = match text.as_str() {
-            "Mapping" => {
-                println!("Found Mapping keyword");
-                TokenType::TypeMapping
-            },
-            "Text" => {
-                println!("Found Text keyword");
-                TokenType::TypeText
-            },
-            "of" => TokenType::Of,
-            "to" => TokenType::To,
-            "includes" => TokenType::Includes,
-            _ => {
-                println!("Unknown identifier: {}", text);
-                TokenType::Identifier(text.clone())
-            },
-        };
-
-        Ok(Token {
-            token_type,
-            literal: text,
-            line: self.line,
-            column: self.column,
+            offset: self.start_offset(),
         })
     }
 
@@ -401,64 +621,37 @@ impl Tokenizer {
     }
 
     fn create_identifier_token(&self, text: String) -> Token {
-        let token_type = match text.as_str() {
-            "is" => TokenType::Is,
-            "as" => TokenType::As,
-            "Mapping" => TokenType::TypeMapping,
-            "Text" => TokenType::TypeText,
-            "includes" => TokenType::Includes,
-            "Object" => TokenType::Object,
-            "Task" => TokenType::Task,
-            "build" => TokenType::Build,
-            "defaults" => TokenType::Defaults,
-            "of" => TokenType::Of,
-            "to" => TokenType::To,
-            // "includes" => TokenType::Includes,
-            "show" => TokenType::Show,
-            "raise" => TokenType::Raise,
-            "await" => TokenType::Await,
-            "at" => TokenType::At,
-            "and" => TokenType::And,
-            "each" => TokenType::Each,
-            "becomes" => TokenType::Becomes,
-            "my" => TokenType::My,
-            "about" => TokenType::About,
-            "me" => TokenType::Me,
-            "loop" => TokenType::Loop,
-            "while" => TokenType::While,
-            "Emit" => TokenType::Emit,
-            "match" => TokenType::Match,
-            "output" => TokenType::Output,
-            "returns" => TokenType::Returns,
-            "requires" => TokenType::Requires,
-            "returning" => TokenType::Returning,
-            "new" => TokenType::New,
-            "with" => TokenType::With,
-            "using" => TokenType::Using,
-            _ => TokenType::Identifier(text.clone()),
-        };
+        let token_type = keyword(&text)
+            .or_else(|| KEYWORDS.iter().find(|(word, _)| self.reads_as(word, &text)).map(|(_, token_type)| token_type.clone()))
+            .or_else(|| language::SYNONYMS.iter().find(|(word, _)| self.reads_as(word, &text)).and_then(|(_, meaning)| keyword(meaning)))
+            .unwrap_or_else(|| TokenType::Identifier(text.clone()));
 
         Token {
             token_type,
             literal: text,
             line: self.line,
             column: self.column,
+            offset: self.start_offset(),
         }
     }
 
+    // Whether `text` is `word` in another case. Capitalized keywords (Task,
+    // Whole, Error) never match all-lowercase text, so `error` stays a name.
+    fn reads_as(&self, word: &str, text: &str) -> bool {
+        let lowercase = |text: &str| !text.chars().any(char::is_uppercase);
+        word == text || (!self.strict_keywords && word.eq_ignore_ascii_case(text) && (lowercase(word) || !lowercase(text)))
+    }
+
     fn number(&mut self) -> Result<Token, String> {
-        let mut is_decimal = false;
-        
-        while !self.is_at_end() && self.peek().is_digit(10) {
+        while !self.is_at_end() && self.peek().is_ascii_digit() {
             self.advance();
         }
 
-        // Look for a decimal point
-        if !self.is_at_end() && self.peek() == '.' {
-            is_decimal = true;
+        // Look for a decimal part
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
             self.advance();  // Consume the dot
 
-            while !self.is_at_end() && self.peek().is_digit(10) {
+            while !self.is_at_end() && self.peek().is_ascii_digit() {
                 self.advance();
             }
         }
@@ -470,20 +663,125 @@ impl Tokenizer {
                 literal: number_str,
                 line: self.line,
                 column: self.column,
+                offset: self.start_offset(),
             }),
             Err(_) => Err("Invalid number".to_string()),
         }
     }
+
+    // An amount of money after its currency symbol
+    fn money(&mut self, symbol: char) -> Result<Token, String> {
+        let amount_start = self.current;
+        while !self.is_at_end() && self.peek().is_ascii_digit() {
+            self.advance();
+        }
+        if self.peek() == '.' && self.peek_next().is_ascii_digit() {
+            self.advance();
+            while !self.is_at_end() && self.peek().is_ascii_digit() {
+                self.advance();
+            }
+        }
+        let digits: String = self.source[amount_start..self.current].iter().collect();
+        let currency = Currency::from_symbol(symbol).ok_or("Unknown currency")?;
+        let money = Money::parse(&digits, currency).map_err(|error| format!("{} on line {}", error, self.line))?;
+        Ok(self.create_token(TokenType::Money(money)))
+    }
+}
+
+// A number spelled out at the start of the text, and how many characters it
+// takes: `twenty-one`, `three hundred and five`, `two million five hundred
+// thousand`. Words must come in the order they are said, so `one two` is
+// only the `one`.
+fn number_words(text: &[char]) -> Option<(f64, usize)> {
+    let word_at = |at: usize| {
+        let at = at.min(text.len());
+        let length = text[at..].iter().take_while(|c| c.is_alphanumeric() || **c == '_').count();
+        (text[at..at + length].iter().collect::<String>(), at + length)
+    };
+    // Below a hundred: `seven`, `forty`, `forty-two`
+    let small = |at: usize| {
+        let (word, end) = word_at(at);
+        if let Some(number) = SMALL_NUMBERS.iter().position(|small| *small == word) {
+            return Some((number as f64, end));
+        }
+        let tens = (TENS.iter().position(|tens| *tens == word)? as f64 + 2.0) * 10.0;
+        let (unit, after) = word_at(end + 1);
+        match SMALL_NUMBERS[1..10].iter().position(|small| *small == unit) {
+            Some(unit) if text.get(end) == Some(&'-') => Some((tens + unit as f64 + 1.0, after)),
+            _ => Some((tens, end)),
+        }
+    };
+    let next_word = |end: usize| end + text[end..].iter().take_while(|c| matches!(c, ' ' | '\t')).count();
+
+    let (first, mut end) = small(0)?;
+    // `zero` stands alone
+    if first == 0.0 {
+        return Some((0.0, end));
+    }
+    let (mut total, mut group, mut scale) = (0.0, first, f64::INFINITY);
+    // Whether a hundred or a scale came last, so a smaller number may follow
+    let mut multiplied = false;
+    loop {
+        let at = next_word(end);
+        let (word, after) = word_at(at);
+        if word == "hundred" && !multiplied && group < 100.0 {
+            group *= 100.0;
+        } else if let Some((_, size)) = SCALES.iter().find(|(name, size)| *name == word && *size < scale && group > 0.0) {
+            total += group * size;
+            (group, scale) = (0.0, *size);
+        } else if multiplied && (word == "and" || small(at).is_some()) {
+            let at = if word == "and" { next_word(after) } else { at };
+            let Some((number, after)) = small(at).filter(|(number, _)| *number > 0.0) else {
+                break;
+            };
+            group += number;
+            (end, multiplied) = (after, false);
+            continue;
+        } else {
+            break;
+        }
+        (end, multiplied) = (after, true);
+    }
+    Some((total + group, end))
+}
+
+impl Iterator for Tokenizer {
+    type Item = Result<Token, String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() && !self.finished {
+            let scanned = if self.is_at_end() {
+                self.close();
+                Ok(())
+            } else {
+                self.scan_line()
+            };
+            if let Err(error) = scanned.and(self.failure.take().map_or(Ok(()), Err)) {
+                self.pending.clear();
+                self.finished = true;
+                return Some(Err(error));
+            }
+        }
+        self.pending.pop_front().map(Ok)
+    }
+}
+
+impl Token {
+    /// The column the token starts at, counting characters from 1; Text
+    /// starts at its opening quote.
+    pub fn start_column(&self) -> usize {
+        let quotes = if matches!(self.token_type, TokenType::String(_)) { 2 } else { 0 };
+        self.column.saturating_sub(self.literal.chars().count() + quotes).max(1)
+    }
 }
 
-// Add Display implementation for Token if not already present
 impl std::fmt::Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{:?} '{}' (line: {}, col: {})", 
+        write!(f, "{:?} '{}' (line: {}, col: {})",
             self.token_type,
             self.literal,
             self.line,
             self.column
         )
     }
-}
\ No newline at end of file
+}
@@ -0,0 +1,203 @@
+use crate::generator::Lock;
+use crate::runtime::{Capability, Runtime};
+use std::io::{self, Write};
+use std::sync::Arc;
+
+// Instructions an answer may run before it is taken to be stuck
+const ANSWER_FUEL: u64 = 100_000;
+
+/// One step of the tutorial: what it teaches, what to try, and the runs
+/// that decide whether an answer does it.
+struct Lesson {
+    title: &'static str,
+    teaching: &'static str,
+    task: &'static str,
+    // An answer that passes, shown by `.hint`
+    hint: &'static str,
+    trials: &'static [Trial],
+}
+
+/// A run of an answer in a fresh Runtime. `given` runs first, the answer
+/// must show `shows` (unless it's None), and then each follow-up program
+/// must show what is paired with it, or fail with that "Error: ...".
+struct Trial {
+    given: &'static str,
+    shows: Option<&'static str>,
+    then: &'static [(&'static str, &'static str)],
+}
+
+const LESSONS: &[Lesson] = &[
+    Lesson {
+        title: "Showing things",
+        teaching: "`show` prints a value. Text goes between double quotes.",
+        task: "Show the text Hello, World!",
+        hint: "show \"Hello, World!\"",
+        trials: &[Trial { given: "", shows: Some("Hello, World!"), then: &[] }],
+    },
+    Lesson {
+        title: "Variables",
+        teaching: "`is` gives a name to a value, so `city is \"Paris\"` makes a variable called city.",
+        task: "Make a variable called age that holds 12.",
+        hint: "age is 12",
+        trials: &[Trial { given: "", shows: None, then: &[("show age", "12")] }],
+    },
+    Lesson {
+        title: "Types",
+        teaching: "`as` says what type a variable holds: Whole, Decimal, Text or Truth. \
+            Putting anything else in it is then an error.",
+        task: "Make a variable called price, as a Decimal, that holds 2.5.",
+        hint: "price as Decimal is 2.5",
+        trials: &[Trial { given: "", shows: None, then: &[("show price", "2.5"), ("price is \"free\"", "Error: Type mismatch: cannot assign Text to variable of type Decimal")] }],
+    },
+    Lesson {
+        title: "Text with values in it",
+        teaching: "Braces inside Text put a value there: with name holding \"Ada\", \
+            \"Hi {name}\" is \"Hi Ada\".",
+        task: "A variable called name holds someone's name. Show Hello, then their name and an exclamation mark.",
+        hint: "show \"Hello, {name}!\"",
+        trials: &[
+            Trial { given: "name is \"Ada\"", shows: Some("Hello, Ada!"), then: &[] },
+            Trial { given: "name is \"Grace\"", shows: Some("Hello, Grace!"), then: &[] },
+        ],
+    },
+    Lesson {
+        title: "Making decisions",
+        teaching: "`when condition:` runs the indented lines under it only when the condition holds, \
+            and `or:` runs its lines when it doesn't. End a block with an empty line.",
+        task: "A variable called temperature holds a number. Show Hot when it is above 25, and Cool otherwise.",
+        hint: "when temperature > 25:\n    show \"Hot\"\nor:\n    show \"Cool\"",
+        trials: &[
+            Trial { given: "temperature is 30", shows: Some("Hot"), then: &[] },
+            Trial { given: "temperature is 12", shows: Some("Cool"), then: &[] },
+        ],
+    },
+    Lesson {
+        title: "Repeating",
+        teaching: "`repeat 3 times:` runs the lines indented under it three times.",
+        task: "A variable called count holds a Whole number. Show the text hello that many times.",
+        hint: "repeat count times:\n    show \"hello\"",
+        trials: &[
+            Trial { given: "count is 3", shows: Some("hello\nhello\nhello"), then: &[] },
+            Trial { given: "count is 1", shows: Some("hello"), then: &[] },
+        ],
+    },
+    Lesson {
+        title: "Tasks",
+        teaching: "A Task is a named piece of work. `Task name requires x as Whole returning Whole:` \
+            declares one, and `output` gives back its result.",
+        task: "Write a Task called double that requires n as a Whole and outputs twice n.",
+        hint: "Task double requires n as Whole returning Whole:\n    output n * 2",
+        trials: &[Trial { given: "", shows: None, then: &[("show double(4)", "8"), ("show double(-3)", "-6")] }],
+    },
+];
+
+/// Walks through the lessons at the REPL, running each answer to check it
+/// and moving on once one passes. `.hint`, `.skip` and `.quit` do what they
+/// say.
+pub fn run() -> Result<(), String> {
+    println!("Welcome to the Vernacular tutorial! Type '.hint' for a hint, '.skip' to move on or '.quit' to stop.");
+    for (number, lesson) in LESSONS.iter().enumerate() {
+        println!();
+        println!("Lesson {} of {}: {}", number + 1, LESSONS.len(), lesson.title);
+        println!("{}", lesson.teaching);
+        println!("Try it: {}", lesson.task);
+        loop {
+            let Some(answer) = read_answer()? else {
+                return Ok(());
+            };
+            match answer.trim() {
+                "" => continue,
+                ".quit" => return Ok(()),
+                ".skip" => break,
+                ".hint" => {
+                    println!("One answer is:");
+                    for line in lesson.hint.lines() {
+                        println!("    {}", line);
+                    }
+                },
+                answer => match lesson.trials.iter().try_for_each(|trial| trial.check(answer)) {
+                    Ok(()) => {
+                        println!("Well done!");
+                        break;
+                    },
+                    Err(problem) => println!("Not quite: {}", problem),
+                },
+            }
+        }
+    }
+    println!();
+    println!("That's the end of the tutorial. Have fun!");
+    Ok(())
+}
+
+// One answer: a line, or a block and the lines indented under it up to an
+// empty line. None at the end of input.
+fn read_answer() -> Result<Option<String>, String> {
+    let mut answer = String::new();
+    loop {
+        print!("{}", if answer.is_empty() { "tutorial> " } else { "... " });
+        io::stdout().flush().map_err(|e| format!("Failed to write prompt: {}", e))?;
+        let mut line = String::new();
+        if io::stdin().read_line(&mut line).map_err(|e| format!("Failed to read line: {}", e))? == 0 {
+            println!();
+            return Ok(None);
+        }
+        let line = line.trim_end();
+        if answer.is_empty() && !line.ends_with(':') {
+            return Ok(Some(line.to_string()));
+        }
+        if line.is_empty() {
+            return Ok(Some(answer));
+        }
+        answer.push_str(line);
+        answer.push('\n');
+    }
+}
+
+impl Trial {
+    // Why the answer doesn't pass this trial, if it doesn't
+    fn check(&self, answer: &str) -> Result<(), String> {
+        let shown = Arc::new(Lock::new(Vec::new()));
+        let mut runtime = Runtime::with_capabilities(&Capability::SANDBOXED);
+        runtime.set_debug_output(false);
+        runtime.set_fuel(ANSWER_FUEL);
+        runtime.set_output(Box::new(Shown(Arc::clone(&shown))));
+        // Standard input holds the learner's answers, not the program's
+        runtime.set_input(Box::new(io::empty()));
+        let mut run = |code: &str| {
+            shown.borrow_mut().clear();
+            runtime.process_input(code)?;
+            Ok::<_, String>(String::from_utf8_lossy(&shown.borrow()).trim_end().to_string())
+        };
+
+        run(self.given).map_err(|error| format!("the lesson's own code failed ({})", error))?;
+        let output = run(answer).map_err(|error| format!("that didn't run: {}", error))?;
+        if let Some(expected) = self.shows.filter(|expected| output != *expected) {
+            let given = if self.given.is_empty() { String::new() } else { format!("with {}, ", self.given) };
+            return Err(format!("{}it should show\n    {}\nbut it showed\n    {}",
+                given, expected.replace('\n', "\n    "), output.replace('\n', "\n    ")));
+        }
+        for (code, expected) in self.then {
+            let outcome = run(code).unwrap_or_else(|error| format!("Error: {}", error));
+            if outcome != *expected {
+                let outcome = if outcome.is_empty() { "nothing" } else { &outcome };
+                return Err(format!("afterwards, `{}` gave\n    {}\ninstead of\n    {}", code, outcome, expected));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Keeps what an answer shows, to compare with what it should
+struct Shown(Arc<Lock<Vec<u8>>>);
+
+impl Write for Shown {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
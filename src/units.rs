@@ -0,0 +1,264 @@
+use crate::failure::Failure;
+use crate::numbers::Numbers;
+use std::hash::{Hash, Hasher};
+
+// What a unit measures: length, mass or time
+const LENGTH: usize = 0;
+const MASS: usize = 1;
+const TIME: usize = 2;
+const MEASURES: [&str; 3] = ["length", "mass", "time"];
+
+// Each unit's names, what it measures and how many of the measure's base
+// unit (meters, kilograms, seconds) one of it is
+const UNITS: [(&str, &str, usize, f64); 17] = [
+    ("meter", "meters", LENGTH, 1.0),
+    ("kilometer", "kilometers", LENGTH, 1000.0),
+    ("centimeter", "centimeters", LENGTH, 0.01),
+    ("millimeter", "millimeters", LENGTH, 0.001),
+    ("inch", "inches", LENGTH, 0.0254),
+    ("foot", "feet", LENGTH, 0.3048),
+    ("yard", "yards", LENGTH, 0.9144),
+    ("mile", "miles", LENGTH, 1609.344),
+    ("gram", "grams", MASS, 0.001),
+    ("kilogram", "kilograms", MASS, 1.0),
+    ("ounce", "ounces", MASS, 0.028349523125),
+    ("millisecond", "milliseconds", TIME, 0.001),
+    ("second", "seconds", TIME, 1.0),
+    ("minute", "minutes", TIME, 60.0),
+    ("hour", "hours", TIME, 3600.0),
+    ("day", "days", TIME, 86400.0),
+    ("week", "weeks", TIME, 604800.0),
+];
+
+/// The units a quantity is measured in: named units, each raised to a
+/// power, like meters per second squared (meter¹ second⁻²). Never empty,
+/// since a quantity whose units cancel out is a plain number.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Unit(Vec<(usize, i32)>);
+
+impl Unit {
+    /// Reads units from the words after a number, like `meters per second`
+    /// in `5 meters per second`, giving them and how many words they took.
+    /// `square` and `cubic` go before a unit, `squared` and `cubed` after.
+    pub fn read(words: &[&str]) -> Option<(Unit, usize)> {
+        let mut powers = Vec::new();
+        let mut at = 0;
+        let mut sign = 1;
+        loop {
+            // Everything after `per` divides
+            let per = usize::from(words.get(at) == Some(&"per"));
+            let Some((unit, power, length)) = factor(&words[at + per..]) else {
+                break;
+            };
+            if per == 1 {
+                sign = -1;
+            }
+            powers.push((unit, power * sign));
+            at += per + length;
+        }
+        let unit = Unit::from_powers(powers)?;
+        Some((unit, at))
+    }
+
+    /// Units written as `Unit::phrase` (or as a program would write them).
+    pub fn parse(text: &str) -> Option<Unit> {
+        let words: Vec<&str> = text.split_whitespace().collect();
+        match Unit::read(&words) {
+            Some((unit, length)) if length == words.len() => Some(unit),
+            _ => None,
+        }
+    }
+
+    // Combines repeated units, dropping those that cancel out
+    fn from_powers(powers: Vec<(usize, i32)>) -> Option<Unit> {
+        let mut combined: Vec<(usize, i32)> = Vec::new();
+        for (unit, power) in powers {
+            match combined.iter_mut().find(|(existing, _)| *existing == unit) {
+                Some(existing) => existing.1 += power,
+                None => combined.push((unit, power)),
+            }
+        }
+        combined.retain(|(_, power)| *power != 0);
+        (!combined.is_empty()).then_some(Unit(combined))
+    }
+
+    // The power of length, mass and time the units measure
+    fn measures(&self) -> [i32; 3] {
+        let mut measures = [0; 3];
+        for (unit, power) in &self.0 {
+            measures[UNITS[*unit].2] += power;
+        }
+        measures
+    }
+
+    /// Whether the units measure the same thing, like feet and meters.
+    pub fn compatible(&self, other: &Unit) -> bool {
+        self.measures() == other.measures()
+    }
+
+    // How many of the base units one of these is
+    fn size(&self) -> f64 {
+        self.0.iter().map(|(unit, power)| UNITS[*unit].3.powi(*power)).product()
+    }
+
+    /// The units of a product (or, with `per`, of a quotient) of quantities
+    /// in these units and the other ones, and the number to multiply the
+    /// product by to put it in them: a unit measuring the same thing as one
+    /// of these is changed into it, so meters times feet are square meters.
+    /// Gives None for units that cancel out.
+    pub fn times(&self, other: &Unit, per: bool) -> (Option<Unit>, f64) {
+        let mut scale = 1.0;
+        let mut powers = self.0.clone();
+        for (unit, power) in &other.0 {
+            let power = if per { -power } else { *power };
+            let measure = UNITS[*unit].2;
+            let into = self.0.iter().map(|(own, _)| *own).find(|own| UNITS[*own].2 == measure).unwrap_or(*unit);
+            scale *= (UNITS[*unit].3 / UNITS[into].3).powi(power);
+            powers.push((into, power));
+        }
+        (Unit::from_powers(powers), scale)
+    }
+
+    /// These units the other way up: seconds per meter for meters per
+    /// second.
+    pub fn inverse(&self) -> Unit {
+        Unit(self.0.iter().map(|(unit, power)| (*unit, -power)).collect())
+    }
+
+    /// The units in words, like `meters per second squared`, with the last
+    /// unit before `per` in the plural if asked.
+    pub fn phrase(&self, plural: bool) -> String {
+        let above: Vec<&(usize, i32)> = self.0.iter().filter(|(_, power)| *power > 0).collect();
+        let mut words = Vec::new();
+        for (position, (unit, power)) in above.iter().enumerate() {
+            let (singular, plurals, ..) = UNITS[*unit];
+            let name = if plural && position == above.len() - 1 { plurals } else { singular };
+            words.push(match power {
+                1 => name.to_string(),
+                2 => format!("square {}", name),
+                3 => format!("cubic {}", name),
+                _ => format!("{}^{}", name, power),
+            });
+        }
+        for (unit, power) in self.0.iter().filter(|(_, power)| *power < 0) {
+            let name = UNITS[*unit].0;
+            words.push(match -power {
+                1 => format!("per {}", name),
+                2 => format!("per {} squared", name),
+                3 => format!("per {} cubed", name),
+                power => format!("per {}^{}", name, power),
+            });
+        }
+        words.join(" ")
+    }
+
+    /// What the units measure, like `length per time`.
+    pub fn measure(&self) -> String {
+        let measures = self.measures();
+        let named = |power: i32| MEASURES.iter().zip(measures).filter(|(_, own)| own.signum() == power.signum())
+            .map(|(name, own)| if own.abs() == 1 { name.to_string() } else { format!("{}^{}", name, own.abs()) })
+            .collect::<Vec<_>>().join(" ");
+        match (named(1), named(-1)) {
+            (above, below) if below.is_empty() => above,
+            (above, below) if above.is_empty() => format!("per {}", below),
+            (above, below) => format!("{} per {}", above, below),
+        }
+    }
+}
+
+// One unit, maybe raised to a power, from the start of the words
+fn factor(words: &[&str]) -> Option<(usize, i32, usize)> {
+    let (prefix, at) = match words.first() {
+        Some(&"square") => (2, 1),
+        Some(&"cubic") => (3, 1),
+        _ => (1, 0),
+    };
+    let word = words.get(at)?;
+    let unit = UNITS.iter().position(|(singular, plural, ..)| word == singular || word == plural)?;
+    match words.get(at + 1) {
+        Some(&"squared") if prefix == 1 => Some((unit, 2, at + 2)),
+        Some(&"cubed") if prefix == 1 => Some((unit, 3, at + 2)),
+        _ => Some((unit, prefix, at + 1)),
+    }
+}
+
+/// A number of some units, like 5 meters or 9.8 meters per second squared.
+/// Quantities measuring the same thing can be added and compared whatever
+/// their units, and are equal when they are the same amount.
+#[derive(Debug, Clone)]
+pub struct Quantity {
+    pub amount: f64,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    /// The quantity in other units measuring the same thing.
+    pub fn convert(&self, unit: &Unit) -> Result<Quantity, Failure> {
+        if !self.unit.compatible(unit) {
+            return Err(Failure::new("TypeError", format!("Type mismatch: cannot convert {} to {}, which measure {} and {}",
+                self.unit.phrase(true), unit.phrase(true), self.unit.measure(), unit.measure())));
+        }
+        let amount = Numbers::Exact.settle(self.amount * self.unit.size() / unit.size());
+        Ok(Quantity { amount, unit: unit.clone() })
+    }
+
+    /// `a + b` or `a - b`, in a's units.
+    pub fn add(&self, other: &Quantity, subtract: bool) -> Result<Quantity, Failure> {
+        let other = self.same_measure(other, if subtract { "subtract" } else { "add" })?;
+        let amount = if subtract { self.amount - other.amount } else { self.amount + other.amount };
+        Ok(Quantity { amount, unit: self.unit.clone() })
+    }
+
+    pub fn compare(&self, other: &Quantity) -> Result<std::cmp::Ordering, Failure> {
+        let other = self.same_measure(other, "compare")?;
+        self.amount.partial_cmp(&other.amount).ok_or_else(|| Failure::new("ArithmeticError", "Cannot order NaN"))
+    }
+
+    /// `a * b`, or `a / b` with `per`: the amount and its units, or None
+    /// for units that cancel out, leaving a plain number.
+    pub fn times(&self, other: &Quantity, per: bool) -> (f64, Option<Unit>) {
+        let (unit, scale) = self.unit.times(&other.unit, per);
+        let amount = if per { self.amount / other.amount } else { self.amount * other.amount };
+        match scale {
+            1.0 => (amount, unit),
+            scale => (Numbers::Exact.settle(amount * scale), unit),
+        }
+    }
+
+    // The other quantity in these units, if it measures the same thing
+    fn same_measure(&self, other: &Quantity, doing: &str) -> Result<Quantity, Failure> {
+        match self.unit.compatible(&other.unit) {
+            true => other.convert(&self.unit),
+            false => Err(Failure::new("TypeError", format!("Type mismatch: cannot {} {} and {}, which measure {} and {}",
+                doing, self, other, self.unit.measure(), other.unit.measure()))),
+        }
+    }
+
+    // The amount in base units, which quantities are compared by, without
+    // the noise converting leaves in the last digits
+    fn base(&self) -> f64 {
+        Numbers::Exact.settle(self.amount * self.unit.size())
+    }
+}
+
+impl PartialEq for Quantity {
+    fn eq(&self, other: &Quantity) -> bool {
+        self.unit.compatible(&other.unit) && self.base() == other.base()
+    }
+}
+
+impl Eq for Quantity {}
+
+impl Hash for Quantity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.unit.measures().hash(state);
+        let base = self.base();
+        (if base == 0.0 { 0.0f64 } else { base }).to_bits().hash(state);
+    }
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.amount, self.unit.phrase(self.amount.abs() != 1.0))
+    }
+}
@@ -0,0 +1,42 @@
+use crate::runtime::Runtime;
+use std::time::Duration;
+
+// How often the script is read to see whether it has changed
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Runs a script, then runs it again each time it is saved with different
+/// contents, until interrupted. Each run starts afresh unless `--keep-state`
+/// is given, when it also sees what earlier runs declared. Errors are
+/// reported and watching carries on.
+pub fn watch(runtime: &mut Runtime, args: &[String]) -> Result<(), String> {
+    let (script, keep_state) = match args {
+        [script] => (script, false),
+        [flag, script] | [script, flag] if flag == "--keep-state" => (script, true),
+        _ => return Err("Usage: nair watch [--keep-state] script".to_string()),
+    };
+    let mut source = std::fs::read_to_string(script).map_err(|e| format!("Error reading file '{}': {}", script, e))?;
+    loop {
+        if let Err(error) = runtime.run_file(script) {
+            eprintln!("{}", runtime.theme().failure(&format!("Error: {}", error)));
+        }
+        eprintln!("Watching {} for changes (Ctrl-C stops)", script);
+        source = changed(script, &source);
+        eprintln!("\n{} changed, running it again", script);
+        if !keep_state {
+            runtime.reset();
+        }
+    }
+}
+
+// Waits for the file to hold something other than `source`. While an editor
+// saves it the file may briefly be missing, which isn't a change.
+fn changed(path: &str, source: &str) -> String {
+    loop {
+        std::thread::sleep(POLL_INTERVAL);
+        if let Ok(current) = std::fs::read_to_string(path) {
+            if current != source {
+                return current;
+            }
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::Arc;
+
+// Frame flags: more frames follow in this message, the size takes eight
+// bytes, and the frame is a command rather than part of a message
+const MORE: u8 = 0x01;
+const LONG: u8 = 0x02;
+const COMMAND: u8 = 0x04;
+
+/// One peer of a bound ZeroMQ socket, spoken to in ZMTP 3.0 with the NULL
+/// security mechanism: just enough of ZeroMQ for the ROUTER, PUB and REP
+/// sockets a Jupyter kernel binds.
+pub struct Connection {
+    stream: TcpStream,
+}
+
+impl Connection {
+    /// Greets a peer that has just connected, introducing this end as a
+    /// socket of the given type.
+    pub fn accept(mut stream: TcpStream, socket_type: &str) -> Result<Connection, String> {
+        let mut greeting = [0u8; 64];
+        greeting[0] = 0xFF;
+        greeting[8] = 1;
+        greeting[9] = 0x7F;
+        greeting[10] = 3;
+        greeting[12..16].copy_from_slice(b"NULL");
+        stream.write_all(&greeting).map_err(|e| format!("Failed to greet a peer: {}", e))?;
+        let mut theirs = [0u8; 64];
+        stream.read_exact(&mut theirs).map_err(|e| format!("Failed to read a peer's greeting: {}", e))?;
+        if theirs[0] != 0xFF || theirs[9] != 0x7F || theirs[10] < 3 {
+            return Err("The peer doesn't speak ZMTP 3".to_string());
+        }
+        if theirs[12..32] != greeting[12..32] {
+            return Err("The peer wants a security mechanism other than NULL".to_string());
+        }
+
+        let mut connection = Connection { stream };
+        let mut ready = b"\x05READY\x0bSocket-Type".to_vec();
+        ready.extend_from_slice(&(socket_type.len() as u32).to_be_bytes());
+        ready.extend_from_slice(socket_type.as_bytes());
+        connection.write_frame(&ready, COMMAND)?;
+        match connection.read_frame()? {
+            (flags, body) if flags & COMMAND != 0 && body.starts_with(b"\x05READY") => Ok(connection),
+            _ => Err("The peer didn't say it was ready".to_string()),
+        }
+    }
+
+    /// Another handle on the same peer, so one thread can read while
+    /// another writes.
+    pub fn try_clone(&self) -> Result<Connection, String> {
+        let stream = self.stream.try_clone().map_err(|e| format!("Failed to share a connection: {}", e))?;
+        Ok(Connection { stream })
+    }
+
+    /// The frames of the next message, passing over any commands.
+    pub fn receive(&mut self) -> Result<Vec<Vec<u8>>, String> {
+        let mut frames = Vec::new();
+        loop {
+            let (flags, body) = self.read_frame()?;
+            if flags & COMMAND != 0 {
+                continue;
+            }
+            frames.push(body);
+            if flags & MORE == 0 {
+                return Ok(frames);
+            }
+        }
+    }
+
+    pub fn send(&mut self, frames: &[Vec<u8>]) -> Result<(), String> {
+        for (i, frame) in frames.iter().enumerate() {
+            let more = if i + 1 < frames.len() { MORE } else { 0 };
+            self.write_frame(frame, more)?;
+        }
+        self.stream.flush().map_err(|e| format!("Failed to send a message: {}", e))
+    }
+
+    fn read_frame(&mut self) -> Result<(u8, Vec<u8>), String> {
+        let failed = |e: std::io::Error| format!("Failed to read a message: {}", e);
+        let mut flags = [0u8; 1];
+        self.stream.read_exact(&mut flags).map_err(failed)?;
+        let size = match flags[0] & LONG {
+            0 => {
+                let mut size = [0u8; 1];
+                self.stream.read_exact(&mut size).map_err(failed)?;
+                size[0] as usize
+            },
+            _ => {
+                let mut size = [0u8; 8];
+                self.stream.read_exact(&mut size).map_err(failed)?;
+                u64::from_be_bytes(size) as usize
+            },
+        };
+        let mut body = vec![0; size];
+        self.stream.read_exact(&mut body).map_err(failed)?;
+        Ok((flags[0], body))
+    }
+
+    fn write_frame(&mut self, body: &[u8], flags: u8) -> Result<(), String> {
+        let mut frame = Vec::with_capacity(body.len() + 9);
+        match u8::try_from(body.len()) {
+            Ok(size) => frame.extend_from_slice(&[flags, size]),
+            Err(_) => {
+                frame.push(flags | LONG);
+                frame.extend_from_slice(&(body.len() as u64).to_be_bytes());
+            },
+        }
+        frame.extend_from_slice(body);
+        self.stream.write_all(&frame).map_err(|e| format!("Failed to send a message: {}", e))
+    }
+}
+
+/// Binds a socket of the given type and, in the background, greets each
+/// peer that connects and hands it to `serve` on a thread of its own.
+pub fn bind(address: &str, socket_type: &'static str, serve: impl Fn(Connection) + Send + Sync + 'static) -> Result<(), String> {
+    let listener = TcpListener::bind(address).map_err(|e| format!("Failed to listen on {}: {}", address, e))?;
+    let serve = Arc::new(serve);
+    std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+            let serve = Arc::clone(&serve);
+            std::thread::spawn(move || {
+                if let Ok(connection) = Connection::accept(stream, socket_type) {
+                    serve(connection);
+                }
+            });
+        }
+    });
+    Ok(())
+}
@@ -0,0 +1,457 @@
+//! Runs every `tests/fixtures/*.vern` program through the interpreter and
+//! compares what it prints with the matching `.out` file. Run with
+//! `UPDATE_GOLDEN=1` to rewrite the expected files from the current output.
+//! A fixture with a `.args` file beside it is run with those extra flags, and
+//! one with a `.in` file gets its contents as standard input.
+
+use std::fs;
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpStream;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+fn fixtures() -> Vec<PathBuf> {
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures");
+    let mut fixtures: Vec<PathBuf> = fs::read_dir(&dir)
+        .expect("tests/fixtures should exist")
+        .map(|entry| entry.expect("fixture directory entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "vern"))
+        .collect();
+    fixtures.sort();
+    fixtures
+}
+
+// The interpreter, with a home of its own so that the REPL doesn't pick up
+// the settings in the user's config file, and a bytecode cache under the
+// build directory rather than the user's
+fn nair() -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_nair"));
+    command.env("HOME", Path::new(env!("CARGO_TARGET_TMPDIR")).join("home"))
+        .env_remove("XDG_CONFIG_HOME")
+        .env("NAIR_CACHE_DIR", cache_dir());
+    command
+}
+
+fn cache_dir() -> PathBuf {
+    Path::new(env!("CARGO_TARGET_TMPDIR")).join("cache")
+}
+
+// Program output, followed by any diagnostics under a marker line. Fixtures
+// run from their own directory so file names in the output don't vary.
+fn run_fixture(path: &Path) -> String {
+    let flags = fs::read_to_string(path.with_extension("args")).unwrap_or_default();
+    let input = fs::read_to_string(path.with_extension("in")).unwrap_or_default();
+    let mut child = nair()
+        .current_dir(path.parent().expect("fixture directory"))
+        .arg("--quiet")
+        .args(flags.split_whitespace())
+        .arg(path.file_name().expect("fixture file name"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes())
+        .expect("fixture input should be writable");
+    printed(child.wait_with_output().expect("interpreter should finish"))
+}
+
+// What a finished process printed, in the layout of the `.out` files
+fn printed(output: Output) -> String {
+    let mut actual = String::from_utf8_lossy(&output.stdout).to_string();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    if !stderr.is_empty() {
+        actual.push_str("--- stderr ---\n");
+        actual.push_str(&stderr);
+    }
+    actual
+}
+
+#[test]
+fn golden_fixtures() {
+    let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+    let fixtures = fixtures();
+    assert!(!fixtures.is_empty(), "no fixtures found");
+
+    let mut mismatches = Vec::new();
+    for fixture in &fixtures {
+        let actual = run_fixture(fixture);
+        let expected_path = fixture.with_extension("out");
+        if update {
+            fs::write(&expected_path, &actual).expect("expected output should be writable");
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_default();
+        if actual != expected {
+            mismatches.push(format!("{}\n--- expected ---\n{}--- actual ---\n{}",
+                fixture.display(), expected, actual));
+        }
+    }
+
+    assert!(mismatches.is_empty(), "{} of {} fixtures differ (UPDATE_GOLDEN=1 to accept):\n\n{}",
+        mismatches.len(), fixtures.len(), mismatches.join("\n"));
+}
+
+// A program built with `nair build` prints what running its script does
+#[test]
+fn built_program() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("objects.vern");
+    let app = Path::new(env!("CARGO_TARGET_TMPDIR")).join("objects-app");
+    let built = nair()
+        .arg("build")
+        .arg(&fixture)
+        .arg("-o")
+        .arg(&app)
+        .output()
+        .expect("interpreter should start");
+    assert!(built.status.success(), "{}", String::from_utf8_lossy(&built.stderr));
+
+    let ran = Command::new(&app)
+        .current_dir(fixture.parent().expect("fixture directory"))
+        .env("NAIR_CACHE_DIR", cache_dir())
+        .output()
+        .expect("built program should start");
+    let expected = fs::read_to_string(fixture.with_extension("out")).expect("fixture output");
+    assert_eq!(printed(ran), expected);
+}
+
+// `nair emit-wasm` compiles the Tasks that only work with numbers
+#[test]
+fn wasm_module() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("recursion.vern");
+    let module = Path::new(env!("CARGO_TARGET_TMPDIR")).join("recursion.wasm");
+    let compiled = nair()
+        .arg("emit-wasm")
+        .arg(&fixture)
+        .arg("-o")
+        .arg(&module)
+        .output()
+        .expect("interpreter should start");
+    assert!(compiled.status.success(), "{}", String::from_utf8_lossy(&compiled.stderr));
+    assert_eq!(printed(compiled), format!("Compiled factorial, fib to {}\n", module.display()));
+
+    let bytes = fs::read(&module).expect("module should be written");
+    assert_eq!(&bytes[..8], b"\0asm\x01\0\0\0");
+}
+
+// `opcodes.vern` runs every instruction the runtime has, so a new one needs
+// a line there, and prints the same when the REPL loads it as when it is run
+// as a file, since both go through the one machine
+#[test]
+fn every_opcode() {
+    let fixture = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("fixtures").join("opcodes.vern");
+    let dir = fixture.parent().expect("fixture directory");
+    let trace = Path::new(env!("CARGO_TARGET_TMPDIR")).join("opcodes.trace");
+    let ran = nair()
+        .current_dir(dir)
+        .arg("--quiet")
+        .arg("--trace")
+        .arg(&trace)
+        .arg("opcodes.vern")
+        .output()
+        .expect("interpreter should start");
+    let expected = fs::read_to_string(fixture.with_extension("out")).expect("fixture output");
+    assert_eq!(printed(ran), expected);
+
+    let generator = fs::read_to_string(Path::new(env!("CARGO_MANIFEST_DIR")).join("src").join("generator.rs")).expect("generator source");
+    let declared = &generator[generator.find("pub enum OpCode {").expect("OpCode declaration")..];
+    let declared = &declared[..declared.find("\n}").expect("end of OpCode")];
+    let traced = fs::read_to_string(&trace).expect("trace should be written");
+    let missing: Vec<&str> = declared.lines().skip(1)
+        .filter_map(|line| line.trim().split(|c: char| !c.is_alphanumeric()).next())
+        .filter(|name| name.starts_with(|c: char| c.is_ascii_uppercase()))
+        .filter(|name| !traced.contains(&format!("\"opcode\":\"{}\"", name)) && !traced.contains(&format!("\"opcode\":\"{}(", name)))
+        .collect();
+    assert!(missing.is_empty(), "opcodes.vern never runs {:?}", missing);
+
+    let mut child = nair()
+        .current_dir(dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    child.stdin.take().expect("piped stdin").write_all(b".load opcodes.vern\n.exit\n").expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains(&format!("> {}> ", expected)), "{}", output);
+}
+
+// The REPL's `.load` runs each file it is given, in order
+#[test]
+fn repl_load() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl load");
+    fs::create_dir_all(&dir).expect("scratch directory should be writable");
+    fs::write(dir.join("first.vern"), "greeting is \"hi\"\n").expect("first file should be writable");
+    fs::write(dir.join("second file.vern"), "show \"{greeting} from the second\"\n").expect("second file should be writable");
+
+    let mut child = nair()
+        .current_dir(&dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    child.stdin.take().expect("piped stdin").write_all(b".load first.vern 'second file.vern'\n.load \"first.vern\n.exit\n")
+        .expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains("> hi from the second\n"), "{}", output);
+    assert!(output.contains("> Error: Missing closing \" in '\"first.vern'\n"), "{}", output);
+}
+
+// Loading a file that declares its Tasks and Objects again redeclares them
+// in place: Objects already made take on the new Tasks and the fields
+// added, keeping their values and those of shared fields
+#[test]
+fn repl_reload() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl reload");
+    fs::create_dir_all(&dir).expect("scratch directory should be writable");
+    let point = |extra: &str, describe: &str| format!(
+        "Object Point:\n    x as Whole is 0\n{}    shared made as Whole is 0\n    Task describe:\n        show {}\n",
+        extra, describe);
+    fs::write(dir.join("before.vern"), point("", "\"at {my x}\"")).expect("first file should be writable");
+    fs::write(dir.join("after.vern"), point("    y as Whole is 7\n", "\"at {my x}, {my y}\"")).expect("second file should be writable");
+
+    let mut child = nair()
+        .current_dir(&dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    let input = ".load before.vern\np is new Point\np.x is 5\nPoint.made is 1\np.describe()\n\
+        .load after.vern\np.describe()\nshow Point.made\n.exit\n";
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains("> at 5\n"), "{}", output);
+    assert!(output.contains("> at 5, 7\n> 1\n"), "{}", output);
+}
+
+// `.restore` goes back to the last `.checkpoint`, or to one saved in a file,
+// keeping Lists shared as they were
+#[test]
+fn repl_checkpoint() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl checkpoint");
+    fs::create_dir_all(&dir).expect("scratch directory should be writable");
+
+    let mut child = nair()
+        .current_dir(&dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    let input = "items is [1, 2]\nboth is [items, items]\ncount as Whole is 3\n.checkpoint saved.json\n\
+        item 1 of items is 9\ncount is 4\n.restore\nshow both\nshow count\n\
+        item 1 of items is 5\nshow both\n.restore saved.json\nshow both\ncount is \"three\"\n.exit\n";
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains("> Back at the checkpoint\n> [[1, 2], [1, 2]]\n> 3\n"), "{}", output);
+    assert!(output.contains("> [[5, 2], [5, 2]]\n"), "{}", output);
+    assert!(output.contains("> Back at the checkpoint in saved.json\n> [[1, 2], [1, 2]]\n"), "{}", output);
+    assert!(output.contains("> Error: Type mismatch: cannot assign Text to variable of type Whole\n"), "{}", output);
+}
+
+// The REPL takes its settings from `~/.vernacularrc`, warning about lines
+// it can't use, and `.config` shows and changes them while it runs
+#[test]
+fn repl_config() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl config");
+    fs::create_dir_all(&dir).expect("scratch directory should be writable");
+    fs::write(dir.join(".vernacularrc"), "# settings\nprompt = \"vern> \"\nhistory = 2\ncolor = maybe\n")
+        .expect("config file should be writable");
+
+    let mut child = nair()
+        .current_dir(&dir)
+        .env("HOME", &dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    let input = "show 1\n.config\n.config prompt \"? \"\n.history\ncount is 2\n.config strict on\nother is 3\n.exit\n";
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains(".vernacularrc line 4: color is on or off, not 'maybe'\n"), "{}", output);
+    assert!(output.contains("vern> 1\nvern> prompt = \"vern> \"\ndebug = off\ncolor = on\nhistory = 2\nstrict = off\n"), "{}", output);
+    assert!(output.contains("vern> prompt = \"? \"\n?    1  .config\n   2  .config prompt \"? \"\n? "), "{}", output);
+    assert!(output.contains("? ? strict = on\n? Error: Strict mode: other needs a type"), "{}", output);
+}
+
+// `.stats` reports what the session has run and made so far
+#[test]
+fn repl_stats() {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("repl stats");
+    fs::create_dir_all(&dir).expect("scratch directory should be writable");
+    fs::write(dir.join("points.vern"), "Object Point:\n    x as Whole is 0\n\nfirst is new Point\nsecond is new Point\n\
+        Task count requires n as Whole returning Whole:\n    when n is 0:\n        output 0\n    output 1 + count(n - 1)\n\nshow count(4)\n")
+        .expect("program should be writable");
+
+    let mut child = nair()
+        .current_dir(&dir)
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    let input = ".stats\n.load points.vern\nsecond is null\nnames is [\"a\", \"b\"]\n.stats\n.exit\n";
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert!(output.contains("> instructions: 0\nallocations: 0\nlive objects: 0\ngc cycles: 0\npeak stack depth: 0\npeak call depth: 0\n"), "{}", output);
+    // Two Points, two Texts and their List are made and one Point is left;
+    // count(4) runs five calls deep under the program itself
+    assert!(output.contains("\nallocations: 5\nlive objects: 1\ngc cycles: 0\npeak stack depth: 6\npeak call depth: 6\n"), "{}", output);
+}
+
+// `.tutorial` checks each answer by running it, and moves on when it passes
+#[test]
+fn repl_tutorial() {
+    let answers = [
+        "show \"Hello\"",
+        "show \"Hello, World!\"",
+        "age is 12",
+        "price is 2.5",
+        "price as Decimal is 2.5",
+        "show \"Hello, {name}!\"",
+        "when temperature > 25:\n    show \"Hot\"\nor:\n    show \"Cool\"\n",
+        "repeat count times:\n    show \"hello\"\n",
+        "Task double requires n as Whole returning Whole:\n    output n * 2\n",
+    ];
+    let mut child = nair()
+        .arg("--quiet")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("interpreter should start");
+    let input = format!(".tutorial\n{}\n.exit\n", answers.join("\n"));
+    child.stdin.take().expect("piped stdin").write_all(input.as_bytes()).expect("REPL input should be writable");
+    let output = printed(child.wait_with_output().expect("interpreter should finish"));
+    assert_eq!(output.matches("Not quite").count(), 2, "{}", output);
+    assert_eq!(output.matches("Well done!").count(), 7, "{}", output);
+    assert!(output.contains("That's the end of the tutorial."), "{}", output);
+}
+
+// `nair repl --listen` serves one session to every client that has the token
+#[test]
+fn remote_repl() {
+    let mut server = nair()
+        .args(["--quiet", "repl", "--listen", "127.0.0.1:0", "--token", "secret"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("server should start");
+    let mut announced = String::new();
+    BufReader::new(server.stderr.take().expect("piped stderr")).read_line(&mut announced).expect("server should say where it is");
+    let address = announced.split_whitespace().nth(4).expect("address in announcement").to_string();
+
+    // Sends a line and reads what comes back, up to the next prompt or the end
+    let talk = |client: &mut TcpStream, line: &str| {
+        client.write_all(format!("{}\n", line).as_bytes()).expect("client should be able to send");
+        let mut reply = Vec::new();
+        let mut byte = [0u8];
+        while !reply.ends_with(b"> ") && !reply.ends_with(b"... ") && client.read(&mut byte).expect("client should be able to read") == 1 {
+            reply.push(byte[0]);
+        }
+        String::from_utf8_lossy(&reply).into_owned()
+    };
+    let mut stranger = TcpStream::connect(&address).expect("server should accept");
+    assert_eq!(talk(&mut stranger, "guess"), "Token: Error: wrong token\n");
+
+    let mut first = TcpStream::connect(&address).expect("server should accept");
+    let mut second = TcpStream::connect(&address).expect("server should accept");
+    assert!(talk(&mut first, "secret").ends_with("enter code directly.\n> "));
+    assert!(talk(&mut second, "secret").ends_with("enter code directly.\n> "));
+    assert_eq!(talk(&mut first, "answer is 41 \\"), "... ");
+    assert_eq!(talk(&mut second, "show 1"), "1\n> ");
+    assert_eq!(talk(&mut first, "+ 1"), "> ");
+    assert_eq!(talk(&mut second, "show answer"), "42\n> ");
+    assert_eq!(talk(&mut second, ".tutorial"), "Error: '.tutorial' needs a terminal of its own\n> ");
+    assert_eq!(talk(&mut first, ".exit"), "Goodbye!\n");
+    server.kill().expect("server should stop");
+    server.wait().expect("server should stop");
+}
+
+// `nair serve` runs each posted program in a sandbox of its own, out of fuel
+// when it runs too long and stopped when it shows too much
+#[test]
+fn eval_server() {
+    let mut server = nair()
+        .args(["serve", "--listen", "127.0.0.1:0", "--fuel", "1000"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("server should start");
+    let mut announced = String::new();
+    BufReader::new(server.stderr.take().expect("piped stderr")).read_line(&mut announced).expect("server should say where it is");
+    let address = announced.split("http://").nth(1).and_then(|rest| rest.split_whitespace().next()).expect("address in announcement").to_string();
+
+    let post = |path: &str, body: &str| {
+        let mut client = TcpStream::connect(&address).expect("server should accept");
+        write!(client, "POST {} HTTP/1.1\r\nContent-Length: {}\r\n\r\n{}", path, body.len(), body).expect("client should be able to send");
+        let mut reply = String::new();
+        client.read_to_string(&mut reply).expect("client should be able to read");
+        reply
+    };
+    let ran = post("/eval", r#"{"source": "total is 40\nshow total + 2"}"#);
+    assert!(ran.starts_with("HTTP/1.1 200 OK\r\n"), "{}", ran);
+    assert!(ran.contains(r#"{"output":"42\n","diagnostics":[],"error":null,"timing":{"compile_ms":"#), "{}", ran);
+    let unseen = post("/eval", r#"{"source": "show total"}"#);
+    assert!(unseen.contains(r#""error":"Undefined variable: total""#), "{}", unseen);
+    let unparsed = post("/eval", r#"{"source": "show 1\nx is"}"#);
+    assert!(unparsed.contains(r#""output":"","diagnostics":[{"severity":"error","line":2,"#), "{}", unparsed);
+    let endless = post("/eval", r#"{"source": "loop while true:\n    x is 1"}"#);
+    assert!(endless.contains(r#""error":"OutOfFuel: the program ran out of its instruction budget""#), "{}", endless);
+    let flood = post("/eval", r#"{"source": "text is \"x\"\nrepeat 20 times:\n    text is text + text\ndo:\n    show text\nfail problem:\n    show \"caught\""}"#);
+    assert!(flood.contains(r#"{"output":"","diagnostics":[],"error":"Failed to write output: the program showed more than 1048576 bytes""#), "{}", flood);
+    assert!(post("/eval", "show 1").starts_with("HTTP/1.1 400 Bad Request\r\n"));
+    assert!(post("/run", "{}").starts_with("HTTP/1.1 404 Not Found\r\n"));
+    server.kill().expect("server should stop");
+    server.wait().expect("server should stop");
+}
+
+// `nair lsp` keeps what it parsed of each statement, and moves it along when
+// lines are added above it. Positions count UTF-16 code units, as editors do.
+#[test]
+fn language_server() {
+    let frame = |body: String| format!("Content-Length: {}\r\n\r\n{}", body.len(), body);
+    let document = |method: &str, text: &str| frame(format!(
+        r#"{{"jsonrpc":"2.0","method":"textDocument/{}","params":{{"textDocument":{{"uri":"file:///a.vern","text":{:?}}},"contentChanges":[{{"text":{:?}}}]}}}}"#,
+        method, text, text,
+    ));
+    let program = "total is 1\nshow total\nrepeat \"😀\" times:\n    show \"😀😀\" + total\n";
+    let messages = [
+        frame(r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{}}"#.to_string()),
+        document("didOpen", program),
+        document("didChange", &format!("# Counting\n\n{}", program)),
+        frame(r#"{"jsonrpc":"2.0","id":2,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.vern"},"position":{"line":3,"character":6}}}"#.to_string()),
+        frame(r#"{"jsonrpc":"2.0","id":3,"method":"textDocument/definition","params":{"textDocument":{"uri":"file:///a.vern"},"position":{"line":5,"character":22}}}"#.to_string()),
+        frame(r#"{"jsonrpc":"2.0","id":4,"method":"shutdown"}"#.to_string()),
+        frame(r#"{"jsonrpc":"2.0","method":"exit"}"#.to_string()),
+    ];
+    let mut server = nair()
+        .arg("lsp")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("language server should start");
+    server.stdin.take().expect("piped stdin").write_all(messages.concat().as_bytes())
+        .expect("messages should be writable");
+    let replies = printed(server.wait_with_output().expect("language server should finish"));
+
+    let diagnostics: Vec<&str> = replies.split("publishDiagnostics").skip(1).collect();
+    assert_eq!(diagnostics.len(), 2, "{}", replies);
+    assert!(diagnostics[0].contains(r#""range":{"start":{"line":2,"character":0},"end":{"line":2,"character":18}}"#), "{}", replies);
+    assert!(diagnostics[1].contains(r#""range":{"start":{"line":4,"character":0},"end":{"line":4,"character":18}}"#), "{}", replies);
+    let total = r#""result":{"uri":"file:///a.vern","range":{"start":{"line":2,"character":0},"end":{"line":2,"character":5}}}"#;
+    assert!(replies.contains(&format!(r#""id":2,{}"#, total)), "{}", replies);
+    assert!(replies.contains(&format!(r#""id":3,{}"#, total)), "{}", replies);
+    assert!(!replies.contains("--- stderr ---"), "{}", replies);
+}
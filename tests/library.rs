@@ -0,0 +1,65 @@
+//! Drives the interpreter through its library, as a program embedding it
+//! would: giving it a variable, running a program a few instructions at a
+//! time, and reading back its output, variables, stats and a snapshot, or
+//! stopping it from another thread.
+
+use nair::{Runtime, Step, Value};
+use std::io::{self, Write};
+use std::sync::atomic::Ordering;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+// Keeps what the program shows, for the test to read
+struct Shown(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Shown {
+    fn write(&mut self, bytes: &[u8]) -> io::Result<usize> {
+        self.0.lock().expect("output lock").extend_from_slice(bytes);
+        Ok(bytes.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn embedded_runtime() {
+    let shown = Arc::new(Mutex::new(Vec::new()));
+    let mut runtime = Runtime::new();
+    runtime.set_debug_output(false);
+    runtime.set_output(Box::new(Shown(Arc::clone(&shown))));
+    runtime.set_global("start", Value::Number(40.0));
+
+    runtime.start("total is start\nrepeat 100 times:\n    increase total by 1\nshow total")
+        .expect("program should compile");
+    let mut pauses = 0;
+    while runtime.step(10).expect("program should run") == Step::Paused {
+        pauses += 1;
+    }
+    assert!(pauses > 1, "paused {} times", pauses);
+    assert_eq!(String::from_utf8_lossy(&shown.lock().expect("output lock")), "140\n");
+    assert_eq!(runtime.get_global("total"), Some(Value::Number(140.0)));
+    assert!(runtime.stats().instructions > 100, "{}", runtime.stats());
+
+    let snapshot = runtime.snapshot();
+    runtime.process_input("total is 0").expect("program should run");
+    assert_eq!(runtime.get_global("total"), Some(Value::Number(0.0)));
+    runtime.restore(&snapshot).expect("snapshot should restore");
+    assert_eq!(runtime.get_global("total"), Some(Value::Number(140.0)));
+}
+
+#[test]
+fn interrupted_runtime() {
+    let mut runtime = Runtime::new();
+    runtime.set_debug_output(false);
+    let interrupt = runtime.interrupt_flag();
+    let interrupter = std::thread::spawn(move || {
+        std::thread::sleep(Duration::from_millis(50));
+        interrupt.store(true, Ordering::Relaxed);
+    });
+    let result = runtime.process_input("do:\n    loop while true:\n        wait 1 second\nfail problem:\n    show \"caught\"");
+    interrupter.join().expect("interrupting thread should finish");
+    assert_eq!(result, Err("Interrupted: the program was stopped before it finished".to_string()));
+    assert_eq!(runtime.process_input("total is 2"), Ok(()));
+}